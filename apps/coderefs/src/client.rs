@@ -0,0 +1,56 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::scanner::CodeReference;
+
+#[derive(Debug, Serialize)]
+struct UploadRequest<'a> {
+    repository: &'a str,
+    branch: Option<&'a str>,
+    references: &'a [CodeReference],
+}
+
+/// Server response to an upload.
+#[derive(Debug, Deserialize)]
+pub struct UploadResponse {
+    pub reference_count: u64,
+    #[serde(default)]
+    pub unknown_flag_keys: Vec<String>,
+}
+
+/// Upload a scan to `PUT /api/v1/projects/{project_id}/code-refs`, replacing
+/// the references previously stored for the repository.
+pub async fn upload(config: &Config, references: &[CodeReference]) -> Result<UploadResponse> {
+    let url = format!(
+        "{}/api/v1/projects/{}/code-refs",
+        config.api_url.trim_end_matches('/'),
+        config.project_id
+    );
+
+    // SDK keys are sent bare; anything else is treated as a bearer token.
+    let authorization = if config.api_key.starts_with("srv_") {
+        config.api_key.clone()
+    } else {
+        format!("Bearer {}", config.api_key)
+    };
+
+    let response = reqwest::Client::new()
+        .put(&url)
+        .header(reqwest::header::AUTHORIZATION, authorization)
+        .json(&UploadRequest {
+            repository: &config.repository,
+            branch: config.branch.as_deref(),
+            references,
+        })
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Upload failed with {status}: {body}"));
+    }
+
+    Ok(response.json().await?)
+}
@@ -0,0 +1,75 @@
+use std::env;
+use std::path::PathBuf;
+
+/// SDK call patterns matched by default. Capture group 1 must be the flag key.
+pub(crate) const DEFAULT_PATTERNS: &[&str] = &[
+    r#"(?:get(?:Boolean|String|Number|Json)Value|evaluate)\s*(?:<[^>]*>)?\(\s*["'`]([A-Za-z0-9_.:-]+)["'`]"#,
+    r#"use(?:Boolean|String|Number|Json)?Flag\s*(?:<[^>]*>)?\(\s*["'`]([A-Za-z0-9_.:-]+)["'`]"#,
+];
+
+const DEFAULT_EXCLUDES: &str = ".git,node_modules,target,dist,build,.next,.turbo";
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub api_url: String,
+    pub api_key: String,
+    pub project_id: String,
+    pub root: PathBuf,
+    pub repository: String,
+    pub branch: Option<String>,
+    pub patterns: Vec<String>,
+    pub exclude_dirs: Vec<String>,
+    pub dry_run: bool,
+}
+
+impl Config {
+    /// Read configuration from the environment. The first CLI argument, if
+    /// present, overrides `CODEREFS_DIR`.
+    pub fn from_env() -> Self {
+        let root = env::args()
+            .nth(1)
+            .or_else(|| env::var("CODEREFS_DIR").ok())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("."));
+
+        let repository = env::var("CODEREFS_REPOSITORY").unwrap_or_else(|_| {
+            root.canonicalize()
+                .ok()
+                .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| "default".into())
+        });
+
+        // One regex per line so patterns may contain commas and semicolons.
+        let patterns = match env::var("CODEREFS_PATTERNS") {
+            Ok(raw) => raw
+                .lines()
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(String::from)
+                .collect(),
+            Err(_) => DEFAULT_PATTERNS.iter().map(|p| p.to_string()).collect(),
+        };
+
+        Self {
+            api_url: env::var("FLAGFORGE_API_URL")
+                .unwrap_or_else(|_| "http://localhost:8080".into()),
+            api_key: env::var("FLAGFORGE_API_KEY")
+                .expect("FLAGFORGE_API_KEY must be set (server SDK key or JWT)"),
+            project_id: env::var("FLAGFORGE_PROJECT_ID")
+                .expect("FLAGFORGE_PROJECT_ID must be set"),
+            root,
+            repository,
+            branch: env::var("CODEREFS_BRANCH").ok(),
+            patterns,
+            exclude_dirs: env::var("CODEREFS_EXCLUDE")
+                .unwrap_or_else(|_| DEFAULT_EXCLUDES.into())
+                .split(',')
+                .map(|d| d.trim().to_string())
+                .filter(|d| !d.is_empty())
+                .collect(),
+            dry_run: env::var("CODEREFS_DRY_RUN")
+                .map(|v| v == "1" || v == "true")
+                .unwrap_or(false),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+mod client;
+mod config;
+mod scanner;
+
+use tracing_subscriber::{fmt, EnvFilter};
+
+use crate::config::Config;
+use crate::scanner::Scanner;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenvy::dotenv().ok();
+
+    fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+
+    let config = Config::from_env();
+    let scanner = Scanner::new(&config.patterns, &config.exclude_dirs)?;
+
+    tracing::info!(
+        "Scanning {} for flag references ({} patterns)",
+        config.root.display(),
+        config.patterns.len()
+    );
+    let references = scanner.scan_dir(&config.root)?;
+    tracing::info!("Found {} references", references.len());
+
+    if config.dry_run {
+        for r in &references {
+            println!("{}:{}\t{}", r.file_path, r.line_number, r.flag_key);
+        }
+        return Ok(());
+    }
+
+    let response = client::upload(&config, &references).await?;
+    tracing::info!(
+        "Uploaded {} references for repository {}",
+        response.reference_count,
+        config.repository
+    );
+
+    if !response.unknown_flag_keys.is_empty() {
+        tracing::warn!(
+            "Keys not found in project (ignored): {}",
+            response.unknown_flag_keys.join(", ")
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// Files larger than this are skipped (generated bundles, fixtures, etc.).
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Snippets are truncated to keep uploads small.
+const MAX_LINE_CONTENT: usize = 200;
+
+/// A single flag key usage found in the source tree.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct CodeReference {
+    pub flag_key: String,
+    pub file_path: String,
+    pub line_number: i32,
+    pub line_content: Option<String>,
+}
+
+/// Walks a source tree and matches every line against the configured patterns.
+pub struct Scanner {
+    patterns: Vec<Regex>,
+    exclude_dirs: Vec<String>,
+}
+
+impl Scanner {
+    pub fn new(patterns: &[String], exclude_dirs: &[String]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|p| {
+                let re = Regex::new(p).map_err(|e| anyhow!("Invalid pattern {p:?}: {e}"))?;
+                if re.captures_len() < 2 {
+                    return Err(anyhow!("Pattern {p:?} has no capture group for the flag key"));
+                }
+                Ok(re)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            patterns,
+            exclude_dirs: exclude_dirs.to_vec(),
+        })
+    }
+
+    /// Scan every file under `root`. Paths in the result are relative to `root`.
+    pub fn scan_dir(&self, root: &Path) -> Result<Vec<CodeReference>> {
+        let mut refs = Vec::new();
+        let mut stack = vec![root.to_path_buf()];
+
+        while let Some(dir) = stack.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                let path = entry.path();
+                let file_type = entry.file_type()?;
+
+                if file_type.is_dir() {
+                    let name = entry.file_name();
+                    if !self.exclude_dirs.iter().any(|d| *d == *name.to_string_lossy()) {
+                        stack.push(path);
+                    }
+                    continue;
+                }
+
+                if !file_type.is_file() || entry.metadata()?.len() > MAX_FILE_SIZE {
+                    continue;
+                }
+
+                // Non-UTF-8 files are almost always binaries — skip them.
+                let Ok(contents) = fs::read_to_string(&path) else {
+                    continue;
+                };
+
+                let relative = path
+                    .strip_prefix(root)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                refs.extend(self.scan_source(&relative, &contents));
+            }
+        }
+
+        refs.sort_by(|a, b| {
+            (&a.file_path, a.line_number, &a.flag_key).cmp(&(&b.file_path, b.line_number, &b.flag_key))
+        });
+        Ok(refs)
+    }
+
+    /// Scan a single file's contents.
+    pub fn scan_source(&self, file_path: &str, contents: &str) -> Vec<CodeReference> {
+        let mut refs = Vec::new();
+
+        for (i, line) in contents.lines().enumerate() {
+            let mut keys: Vec<&str> = Vec::new();
+            for re in &self.patterns {
+                for caps in re.captures_iter(line) {
+                    if let Some(m) = caps.get(1) {
+                        if !keys.contains(&m.as_str()) {
+                            keys.push(m.as_str());
+                        }
+                    }
+                }
+            }
+
+            for key in keys {
+                refs.push(CodeReference {
+                    flag_key: key.to_string(),
+                    file_path: file_path.to_string(),
+                    line_number: (i + 1) as i32,
+                    line_content: Some(truncate(line.trim(), MAX_LINE_CONTENT)),
+                });
+            }
+        }
+
+        refs
+    }
+}
+
+fn truncate(s: &str, max: usize) -> String {
+    match s.char_indices().nth(max) {
+        Some((idx, _)) => s[..idx].to_string(),
+        None => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scanner(patterns: &[&str]) -> Scanner {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        Scanner::new(&patterns, &[]).unwrap()
+    }
+
+    fn default_scanner() -> Scanner {
+        scanner(crate::config::DEFAULT_PATTERNS)
+    }
+
+    #[test]
+    fn test_matches_sdk_calls() {
+        let src = r#"
+const a = await client.getBooleanValue("new-checkout", false);
+const b = useStringFlag('banner-text', "hi");
+const c = client.getJsonValue<Config>(`pricing-config`, {});
+"#;
+        let refs = default_scanner().scan_source("app.ts", src);
+        let keys: Vec<_> = refs.iter().map(|r| (r.flag_key.as_str(), r.line_number)).collect();
+        assert_eq!(
+            keys,
+            vec![("new-checkout", 2), ("banner-text", 3), ("pricing-config", 4)]
+        );
+    }
+
+    #[test]
+    fn test_ignores_non_sdk_strings() {
+        let src = r#"console.log("new-checkout"); getFoo("new-checkout");"#;
+        assert!(default_scanner().scan_source("a.ts", src).is_empty());
+    }
+
+    #[test]
+    fn test_dedupes_same_key_on_line() {
+        let src = r#"evaluate("x") || getBooleanValue("x", false)"#;
+        assert_eq!(default_scanner().scan_source("a.ts", src).len(), 1);
+    }
+
+    #[test]
+    fn test_custom_pattern() {
+        let s = scanner(&[r#"flags\.is_enabled\("([a-z-]+)"\)"#]);
+        let refs = s.scan_source("main.rs", "if flags.is_enabled(\"dark-mode\") {}");
+        assert_eq!(refs.len(), 1);
+        assert_eq!(refs[0].flag_key, "dark-mode");
+        assert_eq!(refs[0].file_path, "main.rs");
+    }
+
+    #[test]
+    fn test_rejects_pattern_without_capture_group() {
+        let patterns = vec!["evaluate\\(".to_string()];
+        assert!(Scanner::new(&patterns, &[]).is_err());
+    }
+
+    #[test]
+    fn test_truncate_is_char_safe() {
+        assert_eq!(truncate("héllo", 2), "hé");
+        assert_eq!(truncate("hi", 10), "hi");
+    }
+}
@@ -0,0 +1,17 @@
+-- ============================================================
+-- Code References (flag key usages uploaded by flagforge-coderefs)
+-- ============================================================
+CREATE TABLE code_references (
+    id              UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    project_id      UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    flag_id         UUID NOT NULL REFERENCES flags(id) ON DELETE CASCADE,
+    repository      VARCHAR(255) NOT NULL,
+    branch          VARCHAR(255),
+    file_path       TEXT NOT NULL,
+    line_number     INTEGER NOT NULL,
+    line_content    TEXT,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_code_references_flag ON code_references(flag_id);
+CREATE INDEX idx_code_references_repo ON code_references(project_id, repository, branch);
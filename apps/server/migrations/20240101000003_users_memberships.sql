@@ -0,0 +1,28 @@
+-- ============================================================
+-- Users (one row per Clerk user, keyed by the JWT `sub` claim)
+-- ============================================================
+CREATE TABLE users (
+    id          UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    external_id VARCHAR(255) NOT NULL UNIQUE,
+    email       VARCHAR(255),
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER trg_users_updated_at BEFORE UPDATE ON users FOR EACH ROW EXECUTE FUNCTION update_updated_at();
+
+-- Clerk organization linked to this organization (the JWT `org_id` claim).
+ALTER TABLE organizations ADD COLUMN external_id VARCHAR(255) UNIQUE;
+
+-- ============================================================
+-- Organization Memberships
+-- ============================================================
+CREATE TABLE organization_members (
+    id              UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    user_id         UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE(organization_id, user_id)
+);
+
+CREATE INDEX idx_organization_members_user ON organization_members(user_id);
@@ -0,0 +1,40 @@
+-- ============================================================
+-- Project Roles
+-- ============================================================
+CREATE TYPE project_role AS ENUM ('viewer', 'editor', 'admin', 'owner');
+
+CREATE TABLE project_members (
+    id          UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    project_id  UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    user_id     UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role        project_role NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE(project_id, user_id)
+);
+
+CREATE INDEX idx_project_members_user ON project_members(user_id);
+
+CREATE TRIGGER trg_project_members_updated_at BEFORE UPDATE ON project_members FOR EACH ROW EXECUTE FUNCTION update_updated_at();
+
+-- Organization members already had full access to every project; keep it.
+INSERT INTO project_members (project_id, user_id, role)
+SELECT p.id, m.user_id, 'owner'
+FROM projects p
+JOIN organization_members m ON m.organization_id = p.organization_id;
+
+-- ============================================================
+-- Environment Permissions
+-- ============================================================
+
+-- Changes to a restricted environment need an admin or an explicit grant.
+ALTER TABLE environments ADD COLUMN restricted BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE environment_permissions (
+    id              UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    environment_id  UUID NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+    user_id         UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role            project_role NOT NULL,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE(environment_id, user_id)
+);
@@ -0,0 +1,30 @@
+-- ============================================================
+-- Change Requests (four-eyes review for protected environments)
+-- ============================================================
+ALTER TABLE environments ADD COLUMN requires_approval BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TYPE change_request_status AS ENUM ('pending', 'applied', 'rejected', 'cancelled');
+
+CREATE TABLE change_requests (
+    id                  UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    project_id          UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    flag_environment_id UUID NOT NULL REFERENCES flag_environments(id) ON DELETE CASCADE,
+    action              VARCHAR(100) NOT NULL,
+    -- Key-based snapshots of the flag environment when the request was made
+    -- and as it would be after applying it.
+    base_state          JSONB NOT NULL,
+    proposed_state      JSONB NOT NULL,
+    status              change_request_status NOT NULL DEFAULT 'pending',
+    requested_by        UUID REFERENCES users(id) ON DELETE SET NULL,
+    requested_by_email  VARCHAR(255),
+    reviewed_by         UUID REFERENCES users(id) ON DELETE SET NULL,
+    reviewed_by_email   VARCHAR(255),
+    review_comment      TEXT,
+    reviewed_at         TIMESTAMPTZ,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at          TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_change_requests_project ON change_requests(project_id, status);
+
+CREATE TRIGGER trg_change_requests_updated_at BEFORE UPDATE ON change_requests FOR EACH ROW EXECUTE FUNCTION update_updated_at();
@@ -0,0 +1,26 @@
+-- ============================================================
+-- Scheduled Changes (run by the server's background worker)
+-- ============================================================
+CREATE TYPE scheduled_change_status AS ENUM ('pending', 'succeeded', 'failed', 'cancelled');
+
+CREATE TABLE scheduled_changes (
+    id                  UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    project_id          UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    flag_environment_id UUID NOT NULL REFERENCES flag_environments(id) ON DELETE CASCADE,
+    action              VARCHAR(50) NOT NULL,
+    -- The change itself, tagged by `type` (see `ScheduledAction`).
+    payload             JSONB NOT NULL,
+    run_at              TIMESTAMPTZ NOT NULL,
+    status              scheduled_change_status NOT NULL DEFAULT 'pending',
+    error               TEXT,
+    created_by          UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_by_email    VARCHAR(255),
+    executed_at         TIMESTAMPTZ,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at          TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_scheduled_changes_project ON scheduled_changes(project_id, status);
+CREATE INDEX idx_scheduled_changes_due ON scheduled_changes(run_at) WHERE status = 'pending';
+
+CREATE TRIGGER trg_scheduled_changes_updated_at BEFORE UPDATE ON scheduled_changes FOR EACH ROW EXECUTE FUNCTION update_updated_at();
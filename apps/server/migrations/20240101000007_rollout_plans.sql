@@ -0,0 +1,31 @@
+-- ============================================================
+-- Rollout Plans (progressive rollouts advanced by the worker)
+-- ============================================================
+CREATE TYPE rollout_status AS ENUM ('active', 'paused', 'completed', 'rolled_back');
+
+CREATE TABLE rollout_plans (
+    id                     UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    project_id             UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    flag_environment_id    UUID NOT NULL REFERENCES flag_environments(id) ON DELETE CASCADE,
+    rule_id                UUID NOT NULL REFERENCES targeting_rules(id) ON DELETE CASCADE,
+    variant_id             UUID NOT NULL REFERENCES flag_variants(id) ON DELETE CASCADE,
+    control_variant_id     UUID REFERENCES flag_variants(id) ON DELETE CASCADE,
+    -- [{ "rollout_pct": 100, "dwell_secs": 3600 }, ...]
+    steps                  JSONB NOT NULL,
+    current_step           INT NOT NULL DEFAULT 0,
+    status                 rollout_status NOT NULL DEFAULT 'active',
+    next_step_at           TIMESTAMPTZ,
+    -- The rule as it was before the plan started, restored on rollback.
+    original_variant_id    UUID REFERENCES flag_variants(id) ON DELETE SET NULL,
+    original_distributions JSONB NOT NULL DEFAULT '[]',
+    created_by             UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_by_email       VARCHAR(255),
+    created_at             TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at             TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- At most one running plan per flag environment.
+CREATE UNIQUE INDEX idx_rollout_plans_running ON rollout_plans(flag_environment_id) WHERE status IN ('active', 'paused');
+CREATE INDEX idx_rollout_plans_due ON rollout_plans(next_step_at) WHERE status = 'active';
+
+CREATE TRIGGER trg_rollout_plans_updated_at BEFORE UPDATE ON rollout_plans FOR EACH ROW EXECUTE FUNCTION update_updated_at();
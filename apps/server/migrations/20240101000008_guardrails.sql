@@ -0,0 +1,20 @@
+-- ============================================================
+-- Guarded Rollouts
+-- ============================================================
+-- [{ "event_key": "request", "max_increase_pct": 10, "min_samples": 100, "action": "rollback" }, ...]
+ALTER TABLE rollout_plans ADD COLUMN guardrails JSONB NOT NULL DEFAULT '[]';
+-- Why the guardrail evaluator stopped the plan, if it did.
+ALTER TABLE rollout_plans ADD COLUMN halted_reason JSONB;
+
+-- Metric events reported by services, attributed to the variant they were served.
+CREATE TABLE metric_events (
+    id             BIGSERIAL PRIMARY KEY,
+    environment_id UUID NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+    flag_key       VARCHAR(255) NOT NULL,
+    variant_key    VARCHAR(255) NOT NULL,
+    event_key      VARCHAR(255) NOT NULL,
+    value          DOUBLE PRECISION NOT NULL DEFAULT 1,
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_metric_events_lookup ON metric_events(environment_id, flag_key, event_key, created_at);
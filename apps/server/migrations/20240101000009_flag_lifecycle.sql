@@ -0,0 +1,16 @@
+-- ============================================================
+-- Flag Lifecycle
+-- ============================================================
+CREATE TYPE flag_kind AS ENUM ('release', 'experiment', 'ops', 'permission');
+
+-- Who to ask before changing or deleting the flag: a user email or a team handle.
+ALTER TABLE flags ADD COLUMN owner VARCHAR(255);
+ALTER TABLE flags ADD COLUMN kind flag_kind NOT NULL DEFAULT 'release';
+-- Temporary flags are expected to be removed from code once they have served their purpose.
+-- Flags that already exist were never declared temporary, so they start out permanent;
+-- new flags are temporary unless created otherwise.
+ALTER TABLE flags ADD COLUMN temporary BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE flags ALTER COLUMN temporary SET DEFAULT TRUE;
+ALTER TABLE flags ADD COLUMN expires_at TIMESTAMPTZ;
+
+CREATE INDEX idx_flags_expires_at ON flags(project_id, expires_at) WHERE expires_at IS NOT NULL;
@@ -0,0 +1,8 @@
+-- ============================================================
+-- Audit Trail
+-- ============================================================
+-- Field-level changes between before_state and after_state, computed on write:
+-- { "enabled": { "before": false, "after": true }, "rules[0].variant": { ... } }
+ALTER TABLE audit_log ADD COLUMN diff JSONB;
+
+CREATE INDEX idx_audit_log_actor ON audit_log(project_id, actor_id);
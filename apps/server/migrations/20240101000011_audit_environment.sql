@@ -0,0 +1,9 @@
+-- ============================================================
+-- Audit Log Querying
+-- ============================================================
+-- Environment the change applied to, for entries scoped to one. No foreign key,
+-- so entries outlive the environment.
+ALTER TABLE audit_log ADD COLUMN environment_id UUID;
+
+CREATE INDEX idx_audit_log_project_created ON audit_log(project_id, created_at DESC, id DESC);
+CREATE INDEX idx_audit_log_environment ON audit_log(environment_id, created_at DESC);
@@ -0,0 +1,22 @@
+-- ============================================================
+-- Tamper-Evident Audit Log
+-- ============================================================
+-- Each project's entries form a chain: `seq` numbers them without gaps and
+-- `hash` covers the entry's content together with the previous entry's hash,
+-- so editing or deleting a row breaks every link after it.
+ALTER TABLE audit_log ADD COLUMN seq BIGINT;
+
+UPDATE audit_log a SET seq = n.seq
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY project_id ORDER BY created_at, id) AS seq
+    FROM audit_log
+) n
+WHERE a.id = n.id;
+
+ALTER TABLE audit_log ALTER COLUMN seq SET NOT NULL;
+
+-- Entries written before this migration keep a NULL hash and are not verified.
+ALTER TABLE audit_log ADD COLUMN prev_hash VARCHAR(64);
+ALTER TABLE audit_log ADD COLUMN hash VARCHAR(64);
+
+CREATE UNIQUE INDEX idx_audit_log_seq ON audit_log(project_id, seq);
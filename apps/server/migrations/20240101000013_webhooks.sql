@@ -0,0 +1,42 @@
+-- ============================================================
+-- Webhooks (signed outbound notifications, retried by the worker)
+-- ============================================================
+CREATE TABLE webhooks (
+    id          UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    project_id  UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    url         TEXT NOT NULL,
+    description TEXT,
+    -- Key for the HMAC-SHA256 signature sent with every delivery.
+    secret      VARCHAR(128) NOT NULL,
+    -- Event types to deliver: audit log actions or `config_changed`. Empty means all.
+    events      TEXT[] NOT NULL DEFAULT '{}',
+    enabled     BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by  UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_webhooks_project ON webhooks(project_id);
+
+CREATE TRIGGER trg_webhooks_updated_at BEFORE UPDATE ON webhooks FOR EACH ROW EXECUTE FUNCTION update_updated_at();
+
+CREATE TYPE webhook_delivery_status AS ENUM ('pending', 'succeeded', 'failed');
+
+-- The delivery queue, kept afterwards as the delivery log.
+CREATE TABLE webhook_deliveries (
+    id               UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    webhook_id       UUID NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+    project_id       UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    event            VARCHAR(100) NOT NULL,
+    payload          JSONB NOT NULL,
+    status           webhook_delivery_status NOT NULL DEFAULT 'pending',
+    attempts         INT NOT NULL DEFAULT 0,
+    next_attempt_at  TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_status_code INT,
+    last_error       TEXT,
+    delivered_at     TIMESTAMPTZ,
+    created_at       TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_webhook_deliveries_due ON webhook_deliveries(next_attempt_at) WHERE status = 'pending';
+CREATE INDEX idx_webhook_deliveries_webhook ON webhook_deliveries(webhook_id, created_at DESC);
@@ -0,0 +1,12 @@
+-- ============================================================
+-- Config Snapshots (full evaluation config for every version)
+-- ============================================================
+CREATE TABLE config_snapshots (
+    environment_id UUID NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+    version        BIGINT NOT NULL,
+    project_id     UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    -- The serialised eval-core `FlagsConfig` as of this version.
+    config         JSONB NOT NULL,
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (environment_id, version)
+);
@@ -0,0 +1,22 @@
+-- ============================================================
+-- Entity Revisions (optimistic concurrency via ETag / If-Match)
+-- ============================================================
+ALTER TABLE flags ADD COLUMN revision BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE flag_environments ADD COLUMN revision BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE segments ADD COLUMN revision BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE targeting_rules ADD COLUMN revision BIGINT NOT NULL DEFAULT 1;
+
+-- Every UPDATE bumps the revision, whichever code path issued it, so a
+-- client holding an older revision can never overwrite a newer write.
+CREATE OR REPLACE FUNCTION bump_revision()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.revision = OLD.revision + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trg_flags_revision BEFORE UPDATE ON flags FOR EACH ROW EXECUTE FUNCTION bump_revision();
+CREATE TRIGGER trg_flag_environments_revision BEFORE UPDATE ON flag_environments FOR EACH ROW EXECUTE FUNCTION bump_revision();
+CREATE TRIGGER trg_segments_revision BEFORE UPDATE ON segments FOR EACH ROW EXECUTE FUNCTION bump_revision();
+CREATE TRIGGER trg_targeting_rules_revision BEFORE UPDATE ON targeting_rules FOR EACH ROW EXECUTE FUNCTION bump_revision();
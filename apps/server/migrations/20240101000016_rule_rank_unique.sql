@@ -0,0 +1,17 @@
+-- ============================================================
+-- Unique Rule Ranks
+-- ============================================================
+-- Concurrent rule creation could hand two rules the same rank. Renumber each
+-- flag environment's rules in their current evaluation order first.
+UPDATE targeting_rules tr
+SET rank = ranked.new_rank
+FROM (
+    SELECT id, (ROW_NUMBER() OVER (PARTITION BY flag_environment_id ORDER BY rank, created_at, id) - 1)::INTEGER AS new_rank
+    FROM targeting_rules
+) ranked
+WHERE tr.id = ranked.id AND tr.rank <> ranked.new_rank;
+
+-- Deferred so a reorder can swap ranks within one transaction.
+ALTER TABLE targeting_rules
+    ADD CONSTRAINT targeting_rules_rank_unique UNIQUE (flag_environment_id, rank)
+    DEFERRABLE INITIALLY DEFERRED;
@@ -0,0 +1,9 @@
+-- ============================================================
+-- Organization Roles
+-- ============================================================
+-- Managing an organization (its settings, members and new projects) needs
+-- the admin role in it; plain members are viewers.
+ALTER TABLE organization_members ADD COLUMN role project_role NOT NULL DEFAULT 'viewer';
+
+-- Existing members could already manage their organization; keep it.
+UPDATE organization_members SET role = 'owner';
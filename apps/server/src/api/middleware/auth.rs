@@ -36,8 +36,7 @@ pub async fn require_auth(
         .and_then(|v| v.to_str().ok())
         .ok_or(StatusCode::UNAUTHORIZED)?;
 
-    let auth_info = if auth_header.starts_with("Bearer ") {
-        let token = &auth_header[7..];
+    let auth_info = if let Some(token) = auth_header.strip_prefix("Bearer ") {
         let claims = state
             .jwks
             .verify_token(token)
@@ -77,6 +76,35 @@ pub async fn require_auth(
     Ok(next.run(req).await)
 }
 
+/// Middleware: require JWT (dashboard user) authentication only.
+pub async fn require_jwt(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let claims = state
+        .jwks
+        .verify_token(token)
+        .await
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let auth_info = AuthInfo::Jwt {
+        user_id: claims.sub,
+        email: claims.email,
+        org_id: claims.org_id,
+    };
+
+    req.extensions_mut().insert(auth_info);
+    Ok(next.run(req).await)
+}
+
 /// Middleware: require SDK key authentication only.
 pub async fn require_sdk_key(
     State(state): State<AppState>,
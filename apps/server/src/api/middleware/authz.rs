@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::middleware::auth::AuthInfo;
+use crate::api::middleware::request_context::RequestContext;
+use crate::state::AppState;
+use crate::store::models::AuditContext;
+
+/// The dashboard user behind a JWT and the organizations they belong to.
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+    pub id: Uuid,
+    /// Clerk user ID (the JWT `sub` claim).
+    pub external_id: String,
+    pub email: Option<String>,
+    /// Clerk `org_id` claim of the current session, if any.
+    pub clerk_org_id: Option<String>,
+    pub organization_ids: Vec<Uuid>,
+}
+
+impl CurrentUser {
+    pub fn is_member(&self, org_id: Uuid) -> bool {
+        self.organization_ids.contains(&org_id)
+    }
+
+    /// Attribute audit entries for `request` to this user.
+    pub fn audit_context(&self, request: &RequestContext) -> AuditContext {
+        AuditContext {
+            actor_id: Some(self.id),
+            actor_email: self.email.clone(),
+            ..request.audit_context()
+        }
+    }
+}
+
+/// Project roles, in increasing order of privilege.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Read-only access.
+    Viewer,
+    /// Change flags, segments, rules and overrides.
+    Editor,
+    /// Also manage environments, SDK keys, members and project settings.
+    Admin,
+    /// Also delete the project and appoint other owners.
+    Owner,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+            Role::Owner => "owner",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Role> {
+        match s {
+            "viewer" => Some(Role::Viewer),
+            "editor" => Some(Role::Editor),
+            "admin" => Some(Role::Admin),
+            "owner" => Some(Role::Owner),
+            _ => None,
+        }
+    }
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+/// The caller's role in the project addressed by `{project_id}`.
+///
+/// Organization members without an explicit role are viewers. Server SDK
+/// keys hold no role: they only pass [`ProjectAccess::require_machine`], on
+/// the few routes meant for CI jobs and services.
+#[derive(Debug, Clone)]
+pub struct ProjectAccess {
+    pub project_id: Uuid,
+    pub role: Role,
+    /// Set for JWT users, `None` for SDK keys.
+    pub user_id: Option<Uuid>,
+    pub actor_email: Option<String>,
+    /// Set for SDK keys.
+    pub sdk_key_id: Option<Uuid>,
+    /// For SDK keys, the key's own environment.
+    pub sdk_environment_id: Option<Uuid>,
+    pub request: RequestContext,
+}
+
+impl ProjectAccess {
+    /// Look up a user's role in a project.
+    pub async fn for_user(
+        state: &AppState,
+        project_id: Uuid,
+        user: &CurrentUser,
+        request: RequestContext,
+    ) -> anyhow::Result<Self> {
+        let role = state
+            .store
+            .get_project_role(project_id, user.id)
+            .await?
+            .as_deref()
+            .and_then(Role::parse)
+            .unwrap_or(Role::Viewer);
+
+        Ok(Self {
+            project_id,
+            role,
+            user_id: Some(user.id),
+            actor_email: user.email.clone(),
+            sdk_key_id: None,
+            sdk_environment_id: None,
+            request,
+        })
+    }
+
+    /// The caller and request to attribute audit entries to.
+    pub fn audit_context(&self) -> AuditContext {
+        AuditContext {
+            actor_id: self.user_id,
+            actor_email: self.actor_email.clone(),
+            sdk_key_id: self.sdk_key_id,
+            environment_id: self.sdk_environment_id,
+            ..self.request.audit_context()
+        }
+    }
+
+    /// Require at least `required` on the project.
+    pub async fn require(
+        &self,
+        state: &AppState,
+        action: &str,
+        required: Role,
+    ) -> Result<(), ApiError> {
+        if self.role >= required {
+            return Ok(());
+        }
+        Err(self.deny(state, action, required, self.role, None).await)
+    }
+
+    /// Accept a server SDK key of the project, or a user with at least
+    /// `required`. This is the only check SDK keys can pass.
+    pub async fn require_machine(
+        &self,
+        state: &AppState,
+        action: &str,
+        required: Role,
+    ) -> Result<(), ApiError> {
+        if self.sdk_key_id.is_some() {
+            return Ok(());
+        }
+        self.require(state, action, required).await
+    }
+
+    /// Require at least `required` on one environment of the project.
+    ///
+    /// In a restricted environment only admins keep their project role;
+    /// everyone else is a viewer there unless granted a role for it. Grants
+    /// never lower a role.
+    pub async fn require_env(
+        &self,
+        state: &AppState,
+        action: &str,
+        environment_id: Uuid,
+        required: Role,
+    ) -> Result<(), ApiError> {
+        let role = self.environment_role(state, environment_id).await?;
+        if role >= required {
+            return Ok(());
+        }
+        Err(self
+            .deny(state, action, required, role, Some(environment_id))
+            .await)
+    }
+
+    /// Require at least `required` on each of `environment_ids`, for changes
+    /// that reach every environment using a shared object.
+    pub async fn require_envs(
+        &self,
+        state: &AppState,
+        action: &str,
+        environment_ids: &[Uuid],
+        required: Role,
+    ) -> Result<(), ApiError> {
+        for &environment_id in environment_ids {
+            self.require_env(state, action, environment_id, required)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn environment_role(
+        &self,
+        state: &AppState,
+        environment_id: Uuid,
+    ) -> Result<Role, ApiError> {
+        let internal = |e: anyhow::Error| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        };
+
+        let restricted = state
+            .store
+            .get_environment(environment_id)
+            .await
+            .map_err(internal)?
+            .filter(|env| env.project_id == self.project_id)
+            .is_some_and(|env| env.restricted);
+
+        let base = if restricted && self.role < Role::Admin {
+            Role::Viewer
+        } else {
+            self.role
+        };
+
+        let granted = match self.user_id {
+            Some(user_id) => state
+                .store
+                .get_environment_role(environment_id, user_id)
+                .await
+                .map_err(internal)?
+                .as_deref()
+                .and_then(Role::parse),
+            None => None,
+        };
+
+        Ok(granted.map_or(base, |g| g.max(base)))
+    }
+
+    /// Build the structured 403 and record the denial in the audit log.
+    async fn deny(
+        &self,
+        state: &AppState,
+        action: &str,
+        required: Role,
+        role: Role,
+        environment_id: Option<Uuid>,
+    ) -> ApiError {
+        let details = serde_json::json!({
+            "action": action,
+            "required_role": required,
+            "role": role,
+            "environment_id": environment_id,
+        });
+
+        let (entity_type, entity_id) = match environment_id {
+            Some(id) => ("environment", id),
+            None => ("project", self.project_id),
+        };
+        let ctx = AuditContext {
+            environment_id,
+            ..self.audit_context()
+        };
+        let _ = state
+            .store
+            .create_audit_log(
+                self.project_id,
+                &ctx,
+                "permission_denied",
+                entity_type,
+                Some(entity_id),
+                None,
+                Some(&details),
+            )
+            .await;
+
+        (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({
+                "error": format!("{action} requires the {} role", required.as_str()),
+                "code": "forbidden",
+                "details": details,
+            })),
+        )
+    }
+}
+
+fn uuid_param(params: &HashMap<String, String>, name: &str) -> Result<Option<Uuid>, StatusCode> {
+    params
+        .get(name)
+        .map(|v| v.parse::<Uuid>().map_err(|_| StatusCode::BAD_REQUEST))
+        .transpose()
+}
+
+/// Middleware: enforce organization and project scoping.
+///
+/// Runs after `require_jwt`/`require_auth` and must be added with
+/// `route_layer` so the `{org_id}` and `{project_id}` path parameters are
+/// available. JWT users must be members of the organization that owns the
+/// addressed resource; SDK keys may only reach the project of their own
+/// environment, and only server keys are accepted. Only the machine routes
+/// take SDK keys at all; the rest of the management API requires a JWT. Out-of-scope resources
+/// are reported as not found so their existence is not leaked.
+///
+/// For project routes it also resolves the caller's [`ProjectAccess`]; the
+/// handlers check it against the role each action needs.
+pub async fn authorize(
+    State(state): State<AppState>,
+    params: Option<Path<HashMap<String, String>>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let auth = req
+        .extensions()
+        .get::<AuthInfo>()
+        .cloned()
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let params = params.map(|Path(p)| p).unwrap_or_default();
+    let request = req
+        .extensions()
+        .get::<RequestContext>()
+        .cloned()
+        .unwrap_or_default();
+    let org_id = uuid_param(&params, "org_id")?;
+    let project_id = uuid_param(&params, "project_id")?;
+
+    match auth {
+        AuthInfo::Jwt {
+            user_id,
+            email,
+            org_id: clerk_org_id,
+        } => {
+            let (user, organization_ids) = state
+                .store
+                .resolve_user(&user_id, email.as_deref(), clerk_org_id.as_deref())
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            let current = CurrentUser {
+                id: user.id,
+                external_id: user.external_id,
+                email,
+                clerk_org_id,
+                organization_ids,
+            };
+
+            if let Some(org_id) = org_id {
+                if !current.is_member(org_id) {
+                    return Err(StatusCode::NOT_FOUND);
+                }
+            }
+
+            if let Some(project_id) = project_id {
+                let project = state
+                    .store
+                    .get_project(project_id)
+                    .await
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                    .ok_or(StatusCode::NOT_FOUND)?;
+                if !current.is_member(project.organization_id) {
+                    return Err(StatusCode::NOT_FOUND);
+                }
+
+                let access = ProjectAccess::for_user(&state, project_id, &current, request)
+                    .await
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                req.extensions_mut().insert(access);
+            }
+
+            req.extensions_mut().insert(current);
+        }
+        AuthInfo::SdkKey {
+            key_id,
+            environment_id,
+            key_type,
+        } => {
+            if key_type != "server" || org_id.is_some() {
+                return Err(StatusCode::FORBIDDEN);
+            }
+            let project_id = project_id.ok_or(StatusCode::FORBIDDEN)?;
+
+            let env = state
+                .store
+                .get_environment(environment_id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .ok_or(StatusCode::UNAUTHORIZED)?;
+            if env.project_id != project_id {
+                return Err(StatusCode::NOT_FOUND);
+            }
+
+            req.extensions_mut().insert(ProjectAccess {
+                project_id,
+                role: Role::Viewer,
+                user_id: None,
+                actor_email: None,
+                sdk_key_id: Some(key_id),
+                sdk_environment_id: Some(environment_id),
+                request,
+            });
+        }
+    }
+
+    Ok(next.run(req).await)
+}
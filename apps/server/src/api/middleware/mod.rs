@@ -1 +1,4 @@
 pub mod auth;
+pub mod authz;
+pub mod precondition;
+pub mod request_context;
@@ -0,0 +1,163 @@
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+/// The strong `ETag` for an entity revision, e.g. `"7"`.
+pub fn etag(revision: i64) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{revision}\"")).expect("revision is a valid header value")
+}
+
+/// Attach the `ETag` for `revision` to a response.
+pub fn with_etag(revision: i64, response: impl IntoResponse) -> Response {
+    let mut response = response.into_response();
+    response.headers_mut().insert(header::ETAG, etag(revision));
+    response
+}
+
+/// 412: the entity changed since the caller read it.
+pub fn precondition_failed() -> ApiError {
+    (
+        StatusCode::PRECONDITION_FAILED,
+        Json(serde_json::json!({
+            "error": "Resource has been modified since it was read; fetch it again and retry",
+        })),
+    )
+}
+
+/// The revisions a write is conditional on, from the `If-Match` header.
+///
+/// `None` when the header is absent or `*`, i.e. the write is unconditional.
+/// Otherwise the header is a comma-separated list of ETags, any of which may
+/// match. Weak tags never match (RFC 9110 uses strong comparison for
+/// `If-Match`), so a list of only weak tags fails with 412 rather than being
+/// ignored.
+pub fn if_match(headers: &HeaderMap) -> Result<Option<Vec<i64>>, ApiError> {
+    let mut values = headers.get_all(header::IF_MATCH).iter().peekable();
+    if values.peek().is_none() {
+        return Ok(None);
+    }
+    let mut tags = Vec::new();
+    for value in values {
+        let value = value
+            .to_str()
+            .map_err(|_| bad_request("If-Match must be ASCII"))?;
+        tags.extend(value.split(',').map(str::trim));
+    }
+    if tags == ["*"] {
+        return Ok(None);
+    }
+
+    let mut revisions = Vec::new();
+    for tag in tags {
+        if tag.starts_with("W/") {
+            continue;
+        }
+        let revision = tag
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .and_then(|v| v.parse::<i64>().ok())
+            .ok_or_else(|| bad_request("If-Match must list ETags such as \"3\""))?;
+        revisions.push(revision);
+    }
+    Ok(Some(revisions))
+}
+
+/// Fail with 412 unless `current` satisfies the `If-Match` revisions. Returns
+/// the revision the write should be conditional on, if any.
+pub fn check_revision(expected: Option<&[i64]>, current: i64) -> Result<Option<i64>, ApiError> {
+    match expected {
+        Some(expected) if !expected.contains(&current) => Err(precondition_failed()),
+        Some(_) => Ok(Some(current)),
+        None => Ok(None),
+    }
+}
+
+fn bad_request(msg: &str) -> ApiError {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({ "error": msg })),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(values: &[&str]) -> Result<Option<Vec<i64>>, StatusCode> {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(header::IF_MATCH, HeaderValue::from_str(value).unwrap());
+        }
+        if_match(&headers).map_err(|(status, _)| status)
+    }
+
+    #[test]
+    fn test_if_match_absent_or_star_is_unconditional() {
+        assert_eq!(parse(&[]), Ok(None));
+        assert_eq!(parse(&["*"]), Ok(None));
+        assert_eq!(parse(&[" * "]), Ok(None));
+    }
+
+    #[test]
+    fn test_if_match_single_tag() {
+        assert_eq!(parse(&["\"7\""]), Ok(Some(vec![7])));
+        assert_eq!(parse(&[" \"7\" "]), Ok(Some(vec![7])));
+    }
+
+    #[test]
+    fn test_if_match_lists() {
+        assert_eq!(parse(&["\"3\", \"4\""]), Ok(Some(vec![3, 4])));
+        assert_eq!(parse(&["\"3\"", "\"4\""]), Ok(Some(vec![3, 4])));
+    }
+
+    #[test]
+    fn test_if_match_weak_tags_never_match() {
+        assert_eq!(parse(&["W/\"3\""]), Ok(Some(vec![])));
+        assert_eq!(parse(&["W/\"3\", \"4\""]), Ok(Some(vec![4])));
+        assert_eq!(
+            check_revision(Some(&[]), 3).map_err(|(status, _)| status),
+            Err(StatusCode::PRECONDITION_FAILED)
+        );
+    }
+
+    #[test]
+    fn test_if_match_rejects_malformed_values() {
+        for value in [
+            "3",
+            "\"abc\"",
+            "\"3",
+            "3\"",
+            "\"\"",
+            "",
+            "\"3\", *",
+            "\"3\",,\"4\"",
+        ] {
+            assert_eq!(parse(&[value]), Err(StatusCode::BAD_REQUEST), "{value:?}");
+        }
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_MATCH,
+            HeaderValue::from_bytes(b"\"\xff\"").unwrap(),
+        );
+        assert_eq!(
+            if_match(&headers).map_err(|(status, _)| status),
+            Err(StatusCode::BAD_REQUEST)
+        );
+    }
+
+    #[test]
+    fn test_check_revision() {
+        let status = |r: Result<Option<i64>, ApiError>| r.map_err(|(status, _)| status);
+        assert_eq!(status(check_revision(None, 5)), Ok(None));
+        assert_eq!(status(check_revision(Some(&[5]), 5)), Ok(Some(5)));
+        assert_eq!(status(check_revision(Some(&[4, 5]), 5)), Ok(Some(5)));
+        assert_eq!(
+            status(check_revision(Some(&[4]), 5)),
+            Err(StatusCode::PRECONDITION_FAILED)
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use ipnet::IpNet;
+use uuid::Uuid;
+
+use crate::store::models::AuditContext;
+
+static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+static X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// Where a request came from, recorded with the audit entries it causes.
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+    /// Client address: the peer address, or, when the peer is a trusted
+    /// proxy, the right-most `X-Forwarded-For` hop that is not one.
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    /// The caller's `X-Request-Id`, or one generated for this request.
+    pub request_id: String,
+}
+
+impl RequestContext {
+    /// Audit attribution for this request, without an actor.
+    pub fn audit_context(&self) -> AuditContext {
+        AuditContext {
+            ip: self.ip.clone(),
+            user_agent: self.user_agent.clone(),
+            request_id: Some(self.request_id.clone()),
+            ..AuditContext::default()
+        }
+    }
+}
+
+/// Reverse proxies allowed to report the client address in
+/// `X-Forwarded-For`. Anyone else can put anything in that header.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(Arc<Vec<IpNet>>);
+
+impl TrustedProxies {
+    pub fn new(networks: Vec<IpNet>) -> Self {
+        Self(Arc::new(networks))
+    }
+
+    fn contains(&self, ip: &IpAddr) -> bool {
+        self.0.iter().any(|net| net.contains(ip))
+    }
+
+    /// The client behind `peer`. Each trusted proxy appends the address it
+    /// received from, so walk `X-Forwarded-For` from the right and stop at
+    /// the first hop that is not a trusted proxy; hops left of it are
+    /// whatever the client claimed.
+    fn client_ip(&self, peer: IpAddr, forwarded_for: &str) -> IpAddr {
+        let mut client = peer;
+        for hop in forwarded_for.rsplit(',') {
+            if !self.contains(&client) {
+                break;
+            }
+            match hop.trim().parse::<IpAddr>() {
+                Ok(ip) => client = ip,
+                Err(_) => break,
+            }
+        }
+        client
+    }
+}
+
+/// Every `X-Forwarded-For` header as one list: repeated headers are joined
+/// in order, and a value that is not text stands in as a hop that can't be
+/// parsed.
+fn forwarded_for(headers: &HeaderMap) -> String {
+    headers
+        .get_all(&X_FORWARDED_FOR)
+        .iter()
+        .map(|v| v.to_str().unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Middleware: attach a [`RequestContext`] to every request and echo its
+/// request ID in the `X-Request-Id` response header.
+pub async fn request_context(
+    State(proxies): State<TrustedProxies>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let headers = req.headers();
+    let header_str = |name: &HeaderName| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    };
+
+    let request_id = header_str(&X_REQUEST_ID)
+        .filter(|v| v.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let ip = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| {
+            proxies
+                .client_ip(addr.ip(), &forwarded_for(headers))
+                .to_string()
+        });
+    let user_agent = header_str(&header::USER_AGENT).map(str::to_string);
+
+    let ctx = RequestContext {
+        ip,
+        user_agent,
+        request_id: request_id.clone(),
+    };
+    req.extensions_mut().insert(ctx);
+
+    let mut response = next.run(req).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(X_REQUEST_ID.clone(), value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxies(networks: &[&str]) -> TrustedProxies {
+        TrustedProxies::new(networks.iter().map(|n| n.parse().unwrap()).collect())
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_client_ip_ignores_header_from_untrusted_peer() {
+        let proxies = proxies(&["10.0.0.0/8"]);
+        assert_eq!(
+            proxies.client_ip(ip("203.0.113.7"), "198.51.100.1"),
+            ip("203.0.113.7")
+        );
+        assert_eq!(proxies.client_ip(ip("203.0.113.7"), ""), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn test_client_ip_without_header_is_peer() {
+        let proxies = proxies(&["10.0.0.0/8"]);
+        assert_eq!(proxies.client_ip(ip("10.0.0.1"), ""), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn test_client_ip_walks_chain_of_trusted_proxies() {
+        let proxies = proxies(&["10.0.0.0/8", "192.168.0.0/16"]);
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), "198.51.100.1, 192.168.1.1, 10.0.0.2"),
+            ip("198.51.100.1")
+        );
+    }
+
+    #[test]
+    fn test_client_ip_stops_before_spoofed_leftmost_hop() {
+        let proxies = proxies(&["10.0.0.0/8"]);
+        // The client sent "1.2.3.4" itself; the proxy appended its real address.
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), "1.2.3.4, 198.51.100.1"),
+            ip("198.51.100.1")
+        );
+        // A spoofed trusted address on the left doesn't extend the walk past
+        // the first untrusted hop.
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), "1.2.3.4, 10.9.9.9, 198.51.100.1"),
+            ip("198.51.100.1")
+        );
+    }
+
+    #[test]
+    fn test_client_ip_stops_at_garbage_hop() {
+        let proxies = proxies(&["10.0.0.0/8"]);
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), "1.2.3.4, unknown"),
+            ip("10.0.0.1")
+        );
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), "1.2.3.4, not-an-ip, 10.0.0.2"),
+            ip("10.0.0.2")
+        );
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), "198.51.100.1:443"),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn test_forwarded_for_joins_repeated_headers() {
+        let mut headers = HeaderMap::new();
+        headers.append(&X_FORWARDED_FOR, HeaderValue::from_static("1.2.3.4"));
+        headers.append(
+            &X_FORWARDED_FOR,
+            HeaderValue::from_static("198.51.100.1, 10.0.0.2"),
+        );
+        let forwarded_for = forwarded_for(&headers);
+        assert_eq!(forwarded_for, "1.2.3.4,198.51.100.1, 10.0.0.2");
+
+        // Reading only the first header would have returned 1.2.3.4.
+        let proxies = proxies(&["10.0.0.0/8"]);
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), &forwarded_for),
+            ip("198.51.100.1")
+        );
+    }
+
+    #[test]
+    fn test_forwarded_for_non_text_value_is_garbage_hop() {
+        let mut headers = HeaderMap::new();
+        headers.append(&X_FORWARDED_FOR, HeaderValue::from_static("1.2.3.4"));
+        headers.append(&X_FORWARDED_FOR, HeaderValue::from_bytes(b"\xff").unwrap());
+        headers.append(&X_FORWARDED_FOR, HeaderValue::from_static("10.0.0.2"));
+
+        let proxies = proxies(&["10.0.0.0/8"]);
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), &forwarded_for(&headers)),
+            ip("10.0.0.2")
+        );
+    }
+
+    #[test]
+    fn test_client_ip_supports_ipv6() {
+        let proxies = proxies(&["fd00::/8"]);
+        assert_eq!(
+            proxies.client_ip(ip("fd00::1"), "2001:db8::5, fd00::2"),
+            ip("2001:db8::5")
+        );
+    }
+}
@@ -1,26 +1,79 @@
 use axum::{
+    body::Body,
     extract::{Extension, Path, Query, State},
-    http::StatusCode,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::api::middleware::auth::AuthInfo;
+use crate::api::middleware::authz::{ProjectAccess, Role};
 use crate::state::AppState;
+use crate::store::models::{AuditChainHead, AuditChainReport, AuditLogFilter, AuditLogRow};
+
+/// Upper bound on `limit` for one page.
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// Rows fetched per query while streaming an export.
+const EXPORT_BATCH_SIZE: i64 = 500;
 
 #[derive(Debug, Deserialize)]
 pub struct AuditLogQuery {
     #[serde(default = "default_limit")]
     pub limit: i64,
-    #[serde(default)]
-    pub offset: i64,
+    /// `next_cursor` from the previous page.
+    pub cursor: Option<i64>,
+    #[serde(flatten)]
+    pub filter: AuditLogFilterQuery,
 }
 
 fn default_limit() -> i64 {
     50
 }
 
+#[derive(Debug, Deserialize)]
+pub struct AuditLogFilterQuery {
+    pub entity_type: Option<String>,
+    pub entity_id: Option<Uuid>,
+    pub action: Option<String>,
+    /// Actor email or user ID.
+    pub actor: Option<String>,
+    pub environment_id: Option<Uuid>,
+    /// Inclusive start of the time range.
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive end of the time range.
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl From<AuditLogFilterQuery> for AuditLogFilter {
+    fn from(q: AuditLogFilterQuery) -> Self {
+        AuditLogFilter {
+            entity_type: q.entity_type,
+            entity_id: q.entity_id,
+            action: q.action,
+            actor: q.actor,
+            environment_id: q.environment_id,
+            from: q.from,
+            to: q.to,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogExportQuery {
+    /// `ndjson` (default) or `csv`.
+    #[serde(default = "default_format")]
+    pub format: String,
+    #[serde(flatten)]
+    pub filter: AuditLogFilterQuery,
+}
+
+fn default_format() -> String {
+    "ndjson".to_string()
+}
+
 #[derive(Debug, Serialize)]
 pub struct AuditLogResponse {
     pub id: String,
@@ -30,46 +83,285 @@ pub struct AuditLogResponse {
     pub action: String,
     pub entity_type: String,
     pub entity_id: Option<String>,
+    pub environment_id: Option<String>,
     pub before_state: Option<serde_json::Value>,
     pub after_state: Option<serde_json::Value>,
+    /// Changed fields by path, each with its `before` and `after` value.
+    pub diff: Option<serde_json::Value>,
+    /// Request details: IP, user agent, request ID and SDK key, if any.
     pub metadata: Option<serde_json::Value>,
+    /// Position in the project's hash chain.
+    pub seq: i64,
+    pub prev_hash: Option<String>,
+    pub hash: Option<String>,
     pub created_at: String,
 }
 
+#[derive(Debug, Serialize)]
+pub struct AuditLogPage {
+    pub entries: Vec<AuditLogResponse>,
+    /// Pass as `cursor` to fetch the next page; absent on the last page.
+    pub next_cursor: Option<String>,
+}
+
 type ApiError = (StatusCode, Json<serde_json::Value>);
 
 fn err(status: StatusCode, msg: &str) -> ApiError {
     (status, Json(serde_json::json!({ "error": msg })))
 }
 
+fn to_response(log: AuditLogRow) -> AuditLogResponse {
+    AuditLogResponse {
+        id: log.id.to_string(),
+        project_id: log.project_id.to_string(),
+        actor_id: log.actor_id.map(|id| id.to_string()),
+        actor_email: log.actor_email,
+        action: log.action,
+        entity_type: log.entity_type,
+        entity_id: log.entity_id.map(|id| id.to_string()),
+        environment_id: log.environment_id.map(|id| id.to_string()),
+        before_state: log.before_state,
+        after_state: log.after_state,
+        diff: log.diff,
+        metadata: log.metadata,
+        seq: log.seq,
+        prev_hash: log.prev_hash,
+        hash: log.hash,
+        created_at: log.created_at.to_rfc3339(),
+    }
+}
+
+const CSV_HEADER: &str = "id,created_at,actor_id,actor_email,action,entity_type,entity_id,\
+environment_id,request_id,ip,user_agent,diff\n";
+
+/// Quote a CSV field if it contains a delimiter, quote or line break.
+///
+/// Fields a spreadsheet would read as a formula (emails, user agents and
+/// diffs are caller-controlled) get a leading `'` so they stay text.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn csv_row(log: &AuditLogRow) -> String {
+    let meta = |key: &str| {
+        log.metadata
+            .as_ref()
+            .and_then(|m| m.get(key))
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let fields = [
+        log.id.to_string(),
+        log.created_at.to_rfc3339(),
+        log.actor_id.map(|id| id.to_string()).unwrap_or_default(),
+        log.actor_email.clone().unwrap_or_default(),
+        log.action.clone(),
+        log.entity_type.clone(),
+        log.entity_id.map(|id| id.to_string()).unwrap_or_default(),
+        log.environment_id.map(|id| id.to_string()).unwrap_or_default(),
+        meta("request_id"),
+        meta("ip"),
+        meta("user_agent"),
+        log.diff.as_ref().map(|d| d.to_string()).unwrap_or_default(),
+    ];
+    let mut row = fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",");
+    row.push('\n');
+    row
+}
+
 pub async fn list_audit_log(
     State(state): State<AppState>,
     Path(project_id): Path<Uuid>,
-    Extension(_auth): Extension<AuthInfo>,
+    Extension(access): Extension<ProjectAccess>,
     Query(query): Query<AuditLogQuery>,
-) -> Result<Json<Vec<AuditLogResponse>>, ApiError> {
-    let logs = state
+) -> Result<Json<AuditLogPage>, ApiError> {
+    access.require(&state, "audit_log.read", Role::Viewer).await?;
+
+    if !(1..=MAX_PAGE_SIZE).contains(&query.limit) {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!("limit must be between 1 and {MAX_PAGE_SIZE}"),
+        ));
+    }
+
+    // One extra row tells us whether there is another page.
+    let mut logs = state
         .store
-        .list_audit_log(project_id, query.limit, query.offset)
+        .list_audit_log(
+            project_id,
+            &query.filter.into(),
+            query.cursor,
+            query.limit + 1,
+        )
         .await
         .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
-    let responses: Vec<AuditLogResponse> = logs
-        .into_iter()
-        .map(|log| AuditLogResponse {
-            id: log.id.to_string(),
-            project_id: log.project_id.to_string(),
-            actor_id: log.actor_id.map(|id| id.to_string()),
-            actor_email: log.actor_email,
-            action: log.action,
-            entity_type: log.entity_type,
-            entity_id: log.entity_id.map(|id| id.to_string()),
-            before_state: log.before_state,
-            after_state: log.after_state,
-            metadata: log.metadata,
-            created_at: log.created_at.to_rfc3339(),
-        })
-        .collect();
-
-    Ok(Json(responses))
+    let next_cursor = if logs.len() as i64 > query.limit {
+        logs.truncate(query.limit as usize);
+        logs.last().map(|log| log.seq.to_string())
+    } else {
+        None
+    };
+
+    Ok(Json(AuditLogPage {
+        entries: logs.into_iter().map(to_response).collect(),
+        next_cursor,
+    }))
+}
+
+/// Stream every entry matching the filters as NDJSON or CSV, newest first.
+///
+/// Entries are read in batches, so exports of any size run in constant
+/// memory. Restricted to admins, as exports are meant for compliance review.
+pub async fn export_audit_log(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+    Query(query): Query<AuditLogExportQuery>,
+) -> Result<Response, ApiError> {
+    access.require(&state, "audit_log.export", Role::Admin).await?;
+
+    let csv = match query.format.as_str() {
+        "ndjson" => false,
+        "csv" => true,
+        _ => {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                "format must be 'ndjson' or 'csv'",
+            ))
+        }
+    };
+    let filter: AuditLogFilter = query.filter.into();
+
+    let stream = async_stream::try_stream! {
+        if csv {
+            yield CSV_HEADER.to_string();
+        }
+        let mut cursor = None;
+        loop {
+            let batch = state
+                .store
+                .list_audit_log(project_id, &filter, cursor, EXPORT_BATCH_SIZE)
+                .await?;
+            for log in &batch {
+                if csv {
+                    yield csv_row(log);
+                } else {
+                    let mut line = serde_json::to_string(log)?;
+                    line.push('\n');
+                    yield line;
+                }
+            }
+            if (batch.len() as i64) < EXPORT_BATCH_SIZE {
+                break;
+            }
+            cursor = batch.last().map(|log| log.seq);
+        }
+    };
+    let stream: std::pin::Pin<
+        Box<dyn tokio_stream::Stream<Item = anyhow::Result<String>> + Send>,
+    > = Box::pin(stream);
+
+    let (content_type, extension) = if csv {
+        ("text/csv", "csv")
+    } else {
+        ("application/x-ndjson", "ndjson")
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"audit-log-{project_id}.{extension}\""),
+            ),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response())
+}
+
+/// Walk the project's audit hash chain and report the first entry that was
+/// deleted, edited or reordered. Entries written before chaining was
+/// introduced are counted as `unchained` rather than verified.
+pub async fn verify_audit_log(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<AuditChainReport>, ApiError> {
+    access.require(&state, "audit_log.verify", Role::Admin).await?;
+
+    let report = state
+        .store
+        .verify_audit_chain(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    Ok(Json(report))
+}
+
+/// The latest entry of the project's audit chain. Record it somewhere the
+/// database's operators cannot edit; any later rewrite of the chain up to
+/// that point changes the hash at that `seq`.
+pub async fn get_audit_chain_head(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<AuditChainHead>, ApiError> {
+    access.require(&state, "audit_log.verify", Role::Admin).await?;
+
+    let head = state
+        .store
+        .get_audit_chain_head(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "The audit log has no hashed entries yet"))?;
+    Ok(Json(head))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field_plain_values_unchanged() {
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("flag_updated"), "flag_updated");
+        assert_eq!(csv_field("ops@example.com"), "ops@example.com");
+        assert_eq!(csv_field("a-b"), "a-b");
+    }
+
+    #[test]
+    fn test_csv_field_escapes_formula_prefixes() {
+        assert_eq!(csv_field("=1+1"), "'=1+1");
+        assert_eq!(csv_field("+1"), "'+1");
+        assert_eq!(csv_field("-1"), "'-1");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("\t=1"), "'\t=1");
+    }
+
+    #[test]
+    fn test_csv_field_escaped_value_still_quoted() {
+        assert_eq!(csv_field("\r=1"), "\"'\r=1\"");
+        assert_eq!(
+            csv_field("=HYPERLINK(\"http://x\",\"y\")"),
+            "\"'=HYPERLINK(\"\"http://x\"\",\"\"y\"\")\""
+        );
+    }
+
+    #[test]
+    fn test_csv_field_quotes_separators() {
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("a\"b,c"), "\"a\"\"b,c\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+    }
 }
@@ -0,0 +1,699 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::api::routes::flags::notify_environment_changes;
+use crate::api::routes::promote::{
+    build_snapshot, diff_snapshots, snapshot_flag_environment, snapshot_flag_environment_in,
+    DistributionSnapshot, FlagEnvironmentSnapshot, FlagPromotionDiff, RuleSegmentSnapshot,
+    RuleSnapshot,
+};
+use crate::state::AppState;
+use crate::store::postgres::UnitOfWork;
+use crate::store::models::{
+    ChangeRequestRow, FlagEnvironmentRow, FlagEnvironmentSpec, FlagRow, TargetingRuleSpec,
+};
+
+// ============================================================
+// Request/Response types
+// ============================================================
+
+#[derive(Debug, Deserialize)]
+pub struct ListChangeRequestsQuery {
+    /// `pending`, `applied`, `rejected` or `cancelled`.
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReviewRequest {
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangeRequestResponse {
+    pub id: String,
+    pub flag_key: String,
+    pub environment_id: String,
+    pub action: String,
+    pub status: String,
+    /// What approving the request changes, relative to `base_state`.
+    pub diff: FlagPromotionDiff,
+    pub base_state: serde_json::Value,
+    pub proposed_state: serde_json::Value,
+    pub requested_by: Option<String>,
+    pub requested_by_email: Option<String>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_by_email: Option<String>,
+    pub review_comment: Option<String>,
+    pub reviewed_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+/// Lookups a handler needs to express its change as an edit of a
+/// [`FlagEnvironmentSnapshot`].
+pub(crate) struct ProposalContext {
+    pub variant_keys: HashMap<Uuid, String>,
+    pub segment_keys: HashMap<Uuid, String>,
+    /// Current rule IDs in rank order, parallel to the snapshot's rules.
+    pub rule_ids: Vec<Uuid>,
+}
+
+impl ProposalContext {
+    pub fn variant(&self, id: Uuid) -> String {
+        self.variant_keys
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    pub fn segment(&self, id: Uuid) -> String {
+        self.segment_keys
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    /// Position of a rule in the snapshot.
+    pub fn rule_index(&self, rule_id: Uuid) -> Option<usize> {
+        self.rule_ids.iter().position(|id| *id == rule_id)
+    }
+
+    pub fn rule(
+        &self,
+        description: Option<String>,
+        variant_id: Option<Uuid>,
+        segments: &[(Uuid, bool)],
+        distributions: &[(Uuid, i32)],
+    ) -> RuleSnapshot {
+        RuleSnapshot {
+            description,
+            variant: variant_id.map(|id| self.variant(id)),
+            segments: segments
+                .iter()
+                .map(|(id, negate)| RuleSegmentSnapshot {
+                    segment: self.segment(*id),
+                    negate: *negate,
+                })
+                .collect(),
+            distributions: distributions
+                .iter()
+                .map(|(id, pct)| DistributionSnapshot {
+                    variant: self.variant(*id),
+                    rollout_pct: *pct,
+                })
+                .collect(),
+        }
+    }
+}
+
+// ============================================================
+// Helpers
+// ============================================================
+
+pub(crate) async fn proposal_context(
+    state: &AppState,
+    project_id: Uuid,
+    flag_id: Uuid,
+    fe: &FlagEnvironmentRow,
+) -> Result<ProposalContext, ApiError> {
+    let variant_keys = state
+        .store
+        .get_flag_variants(flag_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .map(|v| (v.id, v.key))
+        .collect();
+
+    let segment_keys = state
+        .store
+        .list_segments(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .map(|s| (s.id, s.key))
+        .collect();
+
+    let rule_ids = state
+        .store
+        .get_targeting_rules(fe.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .map(|r| r.id)
+        .collect();
+
+    Ok(ProposalContext {
+        variant_keys,
+        segment_keys,
+        rule_ids,
+    })
+}
+
+/// [`proposal_context`] as seen inside `uow`.
+pub(crate) async fn proposal_context_in(
+    uow: &mut UnitOfWork,
+    project_id: Uuid,
+    flag_id: Uuid,
+    fe: &FlagEnvironmentRow,
+) -> Result<ProposalContext, ApiError> {
+    let variant_keys = uow
+        .get_flag_variants(flag_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .map(|v| (v.id, v.key))
+        .collect();
+
+    let segment_keys = uow
+        .list_segments(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .map(|s| (s.id, s.key))
+        .collect();
+
+    let rule_ids = uow
+        .get_flag_environment_contents(fe.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .rules
+        .into_iter()
+        .map(|r| r.rule.id)
+        .collect();
+
+    Ok(ProposalContext {
+        variant_keys,
+        segment_keys,
+        rule_ids,
+    })
+}
+
+/// Key-based snapshot of one flag environment for the audit log, or `None`
+/// if it cannot be loaded.
+pub(crate) async fn audit_snapshot(
+    state: &AppState,
+    project_id: Uuid,
+    flag_id: Uuid,
+    environment_id: Uuid,
+) -> Option<serde_json::Value> {
+    let fe = state
+        .store
+        .get_flag_environment(flag_id, environment_id)
+        .await
+        .ok()??;
+    let ctx = proposal_context(state, project_id, flag_id, &fe).await.ok()?;
+    let snapshot = snapshot_flag_environment(state, &fe, &ctx.variant_keys, &ctx.segment_keys)
+        .await
+        .ok()?;
+    serde_json::to_value(snapshot).ok()
+}
+
+/// [`audit_snapshot`] as seen inside `uow`, for audit entries written in the
+/// same transaction as the change they record.
+pub(crate) async fn audit_snapshot_in(
+    uow: &mut UnitOfWork,
+    project_id: Uuid,
+    flag_id: Uuid,
+    environment_id: Uuid,
+) -> Option<serde_json::Value> {
+    let fe = uow.get_flag_environment(flag_id, environment_id).await.ok()??;
+    let variant_keys: HashMap<Uuid, String> = uow
+        .get_flag_variants(flag_id)
+        .await
+        .ok()?
+        .into_iter()
+        .map(|v| (v.id, v.key))
+        .collect();
+    let segment_keys: HashMap<Uuid, String> = uow
+        .list_segments(project_id)
+        .await
+        .ok()?
+        .into_iter()
+        .map(|s| (s.id, s.key))
+        .collect();
+    let contents = uow.get_flag_environment_contents(fe.id).await.ok()?;
+    serde_json::to_value(build_snapshot(&fe, contents, &variant_keys, &segment_keys)).ok()
+}
+
+/// Turn a key-based snapshot back into IDs. Fails if a variant or segment
+/// it refers to has been deleted since the request was made.
+pub(crate) fn resolve_spec(
+    snapshot: &FlagEnvironmentSnapshot,
+    ctx: &ProposalContext,
+) -> Result<FlagEnvironmentSpec, ApiError> {
+    let variant = |key: &str| {
+        ctx.variant_keys
+            .iter()
+            .find(|(_, k)| k.as_str() == key)
+            .map(|(id, _)| *id)
+            .ok_or_else(|| {
+                err(
+                    StatusCode::CONFLICT,
+                    &format!("Variant {key} no longer exists"),
+                )
+            })
+    };
+    let segment = |key: &str| {
+        ctx.segment_keys
+            .iter()
+            .find(|(_, k)| k.as_str() == key)
+            .map(|(id, _)| *id)
+            .ok_or_else(|| {
+                err(
+                    StatusCode::CONFLICT,
+                    &format!("Segment {key} no longer exists"),
+                )
+            })
+    };
+
+    let mut rules = Vec::with_capacity(snapshot.rules.len());
+    for rule in &snapshot.rules {
+        rules.push(TargetingRuleSpec {
+            description: rule.description.clone(),
+            variant_id: rule.variant.as_deref().map(variant).transpose()?,
+            segments: rule
+                .segments
+                .iter()
+                .map(|s| Ok((segment(&s.segment)?, s.negate)))
+                .collect::<Result<_, ApiError>>()?,
+            distributions: rule
+                .distributions
+                .iter()
+                .map(|d| Ok((variant(&d.variant)?, d.rollout_pct)))
+                .collect::<Result<_, ApiError>>()?,
+        });
+    }
+
+    let overrides = snapshot
+        .overrides
+        .iter()
+        .map(|(key, v)| Ok((key.clone(), variant(v)?)))
+        .collect::<Result<_, ApiError>>()?;
+
+    Ok(FlagEnvironmentSpec {
+        enabled: snapshot.enabled,
+        default_variant_id: snapshot
+            .default_variant
+            .as_deref()
+            .map(variant)
+            .transpose()?,
+        rules,
+        overrides,
+    })
+}
+
+fn parse_snapshot(value: &serde_json::Value) -> Result<FlagEnvironmentSnapshot, ApiError> {
+    serde_json::from_value(value.clone())
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))
+}
+
+fn to_response(cr: ChangeRequestRow) -> Result<ChangeRequestResponse, ApiError> {
+    let base = parse_snapshot(&cr.base_state)?;
+    let proposed = parse_snapshot(&cr.proposed_state)?;
+
+    Ok(ChangeRequestResponse {
+        id: cr.id.to_string(),
+        diff: diff_snapshots(&cr.flag_key, &proposed, &base),
+        flag_key: cr.flag_key,
+        environment_id: cr.environment_id.to_string(),
+        action: cr.action,
+        status: cr.status,
+        base_state: cr.base_state,
+        proposed_state: cr.proposed_state,
+        requested_by: cr.requested_by.map(|id| id.to_string()),
+        requested_by_email: cr.requested_by_email,
+        reviewed_by: cr.reviewed_by.map(|id| id.to_string()),
+        reviewed_by_email: cr.reviewed_by_email,
+        review_comment: cr.review_comment,
+        reviewed_at: cr.reviewed_at.map(|t| t.to_rfc3339()),
+        created_at: cr.created_at.to_rfc3339(),
+        updated_at: cr.updated_at.to_rfc3339(),
+    })
+}
+
+async fn load_pending(
+    state: &AppState,
+    project_id: Uuid,
+    change_request_id: Uuid,
+) -> Result<ChangeRequestRow, ApiError> {
+    let cr = state
+        .store
+        .get_change_request(change_request_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .filter(|cr| cr.project_id == project_id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Change request not found"))?;
+
+    if cr.status != "pending" {
+        return Err(err(
+            StatusCode::CONFLICT,
+            &format!("Change request is already {}", cr.status),
+        ));
+    }
+    Ok(cr)
+}
+
+/// Four-eyes rule: a change is reviewed by a signed-in user other than the
+/// one who requested it.
+fn check_reviewer(access: &ProjectAccess, cr: &ChangeRequestRow) -> Result<Uuid, ApiError> {
+    let Some(user_id) = access.user_id else {
+        return Err(err(
+            StatusCode::FORBIDDEN,
+            "Change requests must be reviewed by a signed-in user",
+        ));
+    };
+    if cr.requested_by == Some(user_id) {
+        return Err(err(
+            StatusCode::FORBIDDEN,
+            "Change requests cannot be reviewed by their requester",
+        ));
+    }
+    Ok(user_id)
+}
+
+/// Divert a change to a flag environment into a change request when its
+/// environment requires approval.
+///
+/// `edit` applies the change to a snapshot of the current configuration.
+/// Returns the `202 Accepted` response to send instead of applying, or
+/// `None` if the caller should apply the change itself.
+pub(crate) async fn propose_if_required<F>(
+    state: &AppState,
+    access: &ProjectAccess,
+    flag: &FlagRow,
+    fe: &FlagEnvironmentRow,
+    action: &str,
+    edit: F,
+) -> Result<Option<Response>, ApiError>
+where
+    F: FnOnce(&mut FlagEnvironmentSnapshot, &ProposalContext) -> Result<(), ApiError>,
+{
+    let requires_approval = state
+        .store
+        .get_environment(fe.environment_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .is_some_and(|env| env.requires_approval);
+    if !requires_approval {
+        return Ok(None);
+    }
+
+    let ctx = proposal_context(state, access.project_id, flag.id, fe).await?;
+    let base = snapshot_flag_environment(state, fe, &ctx.variant_keys, &ctx.segment_keys).await?;
+    let mut proposed = base.clone();
+    edit(&mut proposed, &ctx)?;
+    if proposed == base {
+        return Err(err(StatusCode::BAD_REQUEST, "The change has no effect"));
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let cr = uow
+        .create_change_request(
+            access.project_id,
+            fe.id,
+            action,
+            &serde_json::to_value(&base).unwrap_or_default(),
+            &serde_json::to_value(&proposed).unwrap_or_default(),
+            access.user_id,
+            access.actor_email.as_deref(),
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.create_audit_log(
+        access.project_id,
+        &access.audit_context().in_environment(fe.environment_id),
+        "change_request_created",
+        "change_request",
+        Some(cr.id),
+        None,
+        serde_json::to_value(&cr).ok().as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Some(
+        (StatusCode::ACCEPTED, Json(to_response(cr)?)).into_response(),
+    ))
+}
+
+// ============================================================
+// Handlers
+// ============================================================
+
+pub async fn list_change_requests(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+    Query(query): Query<ListChangeRequestsQuery>,
+) -> Result<Json<Vec<ChangeRequestResponse>>, ApiError> {
+    access
+        .require(&state, "change_request.read", Role::Viewer)
+        .await?;
+
+    let rows = state
+        .store
+        .list_change_requests(project_id, query.status.as_deref())
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let responses = rows
+        .into_iter()
+        .map(to_response)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Json(responses))
+}
+
+pub async fn get_change_request(
+    State(state): State<AppState>,
+    Path((project_id, change_request_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<ChangeRequestResponse>, ApiError> {
+    access
+        .require(&state, "change_request.read", Role::Viewer)
+        .await?;
+
+    let cr = state
+        .store
+        .get_change_request(change_request_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .filter(|cr| cr.project_id == project_id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Change request not found"))?;
+
+    Ok(Json(to_response(cr)?))
+}
+
+/// Approve a pending request and apply its proposed configuration.
+///
+/// Refused with 409 if the flag environment changed since the request was
+/// made; the requester has to propose the change again.
+pub async fn approve_change_request(
+    State(state): State<AppState>,
+    Path((project_id, change_request_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    body: Option<Json<ReviewRequest>>,
+) -> Result<Json<ChangeRequestResponse>, ApiError> {
+    let review = body.map(|Json(r)| r).unwrap_or_default();
+    let cr = load_pending(&state, project_id, change_request_id).await?;
+    access
+        .require_env(
+            &state,
+            "change_request.approve",
+            cr.environment_id,
+            Role::Editor,
+        )
+        .await?;
+    let reviewer = check_reviewer(&access, &cr)?;
+
+    // The flag environment stays locked from the base-state check until the
+    // proposed state is written, so no concurrent write can slip between.
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let fe = uow
+        .lock_flag_environment(cr.flag_id, cr.environment_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| {
+            err(
+                StatusCode::NOT_FOUND,
+                "Flag is not configured in this environment",
+            )
+        })?;
+
+    let ctx = proposal_context_in(&mut uow, project_id, cr.flag_id, &fe).await?;
+    let current =
+        snapshot_flag_environment_in(&mut uow, &fe, &ctx.variant_keys, &ctx.segment_keys).await?;
+    let base = parse_snapshot(&cr.base_state)?;
+    if current != base {
+        return Err(err(
+            StatusCode::CONFLICT,
+            "The flag has changed since this request was made",
+        ));
+    }
+    let proposed = parse_snapshot(&cr.proposed_state)?;
+    let spec = resolve_spec(&proposed, &ctx)?;
+
+    let applied = uow
+        .apply_change_request(
+            cr.id,
+            &spec,
+            Some(reviewer),
+            access.actor_email.as_deref(),
+            review.comment.as_deref(),
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::CONFLICT, "Change request is no longer pending"))?;
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(cr.environment_id),
+        "change_request_approved",
+        "flag",
+        Some(cr.flag_id),
+        Some(&cr.base_state),
+        Some(&cr.proposed_state),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    Ok(Json(to_response(applied)?))
+}
+
+pub async fn reject_change_request(
+    State(state): State<AppState>,
+    Path((project_id, change_request_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    body: Option<Json<ReviewRequest>>,
+) -> Result<Json<ChangeRequestResponse>, ApiError> {
+    let review = body.map(|Json(r)| r).unwrap_or_default();
+    let cr = load_pending(&state, project_id, change_request_id).await?;
+    access
+        .require_env(
+            &state,
+            "change_request.reject",
+            cr.environment_id,
+            Role::Editor,
+        )
+        .await?;
+    let reviewer = check_reviewer(&access, &cr)?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let rejected = uow
+        .close_change_request(
+            cr.id,
+            "rejected",
+            Some(reviewer),
+            access.actor_email.as_deref(),
+            review.comment.as_deref(),
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::CONFLICT, "Change request is no longer pending"))?;
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(cr.environment_id),
+        "change_request_rejected",
+        "change_request",
+        Some(cr.id),
+        serde_json::to_value(&cr).ok().as_ref(),
+        serde_json::to_value(&rejected).ok().as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(to_response(rejected)?))
+}
+
+/// Withdraw a pending request. Open to its requester and to admins.
+pub async fn cancel_change_request(
+    State(state): State<AppState>,
+    Path((project_id, change_request_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<ChangeRequestResponse>, ApiError> {
+    let cr = load_pending(&state, project_id, change_request_id).await?;
+    let is_requester = access.user_id.is_some() && access.user_id == cr.requested_by;
+    if !is_requester {
+        access
+            .require(&state, "change_request.cancel", Role::Admin)
+            .await?;
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let cancelled = uow
+        .close_change_request(
+            cr.id,
+            "cancelled",
+            access.user_id,
+            access.actor_email.as_deref(),
+            None,
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::CONFLICT, "Change request is no longer pending"))?;
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(cr.environment_id),
+        "change_request_cancelled",
+        "change_request",
+        Some(cr.id),
+        serde_json::to_value(&cr).ok().as_ref(),
+        serde_json::to_value(&cancelled).ok().as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(to_response(cancelled)?))
+}
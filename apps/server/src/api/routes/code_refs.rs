@@ -0,0 +1,125 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::state::AppState;
+
+/// Upload payload sent by `flagforge-coderefs` after scanning a repository.
+#[derive(Debug, Deserialize)]
+pub struct UploadCodeReferencesRequest {
+    pub repository: String,
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub references: Vec<CodeReferenceInput>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CodeReferenceInput {
+    pub flag_key: String,
+    pub file_path: String,
+    pub line_number: i32,
+    pub line_content: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UploadCodeReferencesResponse {
+    pub repository: String,
+    pub branch: Option<String>,
+    pub reference_count: u64,
+    pub unknown_flag_keys: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CodeReferenceResponse {
+    pub repository: String,
+    pub branch: Option<String>,
+    pub file_path: String,
+    pub line_number: i32,
+    pub line_content: Option<String>,
+    pub uploaded_at: String,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+/// Replace all code references for a repository with the uploaded scan.
+/// Accepts a server SDK key, so CI can upload without a user's token.
+pub async fn upload_code_references(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<UploadCodeReferencesRequest>,
+) -> Result<Json<UploadCodeReferencesResponse>, ApiError> {
+    access
+        .require_machine(&state, "code_refs.upload", Role::Editor)
+        .await?;
+
+    if req.repository.trim().is_empty() {
+        return Err(err(StatusCode::BAD_REQUEST, "repository is required"));
+    }
+
+    let flag_ids = state
+        .store
+        .list_flag_ids_by_key(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let mut references = Vec::new();
+    let mut unknown_flag_keys = Vec::new();
+    for r in req.references {
+        match flag_ids.get(&r.flag_key) {
+            Some(flag_id) => {
+                references.push((*flag_id, r.file_path, r.line_number, r.line_content));
+            }
+            None => {
+                if !unknown_flag_keys.contains(&r.flag_key) {
+                    unknown_flag_keys.push(r.flag_key);
+                }
+            }
+        }
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let reference_count = uow
+        .replace_code_references(project_id, &req.repository, req.branch.as_deref(), &references)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "code_references_uploaded",
+        "code_reference",
+        None,
+        None,
+        Some(&serde_json::json!({
+            "repository": req.repository,
+            "branch": req.branch,
+            "reference_count": reference_count,
+        })),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(UploadCodeReferencesResponse {
+        repository: req.repository,
+        branch: req.branch,
+        reference_count,
+        unknown_flag_keys,
+    }))
+}
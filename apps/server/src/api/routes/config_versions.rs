@@ -0,0 +1,576 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use eval_core::{EvaluationContext, EvaluationResult, EvaluationTrace, Evaluator};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::api::routes::change_requests::{proposal_context, resolve_spec};
+use crate::api::routes::environments::load_environment;
+use crate::api::routes::flags::notify_environment_changes;
+use crate::api::routes::promote::{
+    diff_snapshots, snapshot_flag_environment, DistributionSnapshot, FlagEnvironmentSnapshot,
+    FlagPromotionDiff, RuleSegmentSnapshot, RuleSnapshot,
+};
+use crate::state::AppState;
+use crate::store::models::AuditLogRow;
+
+/// Upper bound on `limit` for one page of versions.
+const MAX_PAGE_SIZE: i64 = 100;
+
+// ============================================================
+// Request/Response types
+// ============================================================
+
+#[derive(Debug, Deserialize)]
+pub struct ListConfigVersionsQuery {
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    /// `next_cursor` from the previous page.
+    pub before: Option<i64>,
+}
+
+fn default_limit() -> i64 {
+    20
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiffConfigVersionsQuery {
+    pub from: i64,
+    pub to: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RollbackRequest {
+    /// Without this the response is a preview and nothing is written.
+    #[serde(default)]
+    pub apply: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EvaluateAtRequest {
+    pub flag_key: String,
+    #[serde(default)]
+    pub context: EvaluationContext,
+    #[serde(default)]
+    pub default_value: serde_json::Value,
+    /// Evaluate against the config that was live at this time...
+    pub at: Option<DateTime<Utc>>,
+    /// ...or against this config version. Exactly one must be given.
+    pub version: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EvaluateAtResponse {
+    pub environment_id: String,
+    /// The config version the flag was evaluated against.
+    pub version: i64,
+    /// When that version went live.
+    pub version_created_at: String,
+    pub result: EvaluationResult,
+    pub trace: EvaluationTrace,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigVersionResponse {
+    pub version: i64,
+    pub created_at: String,
+    /// Audit entries recorded between this version and the next one, which
+    /// are the changes that produced it.
+    pub changes: Vec<ConfigChangeResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigChangeResponse {
+    pub audit_log_id: String,
+    pub action: String,
+    pub entity_type: String,
+    pub entity_id: Option<String>,
+    pub actor_id: Option<String>,
+    pub actor_email: Option<String>,
+    pub request_id: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigVersionPage {
+    pub versions: Vec<ConfigVersionResponse>,
+    /// Pass as `before` to fetch older versions; absent on the last page.
+    pub next_cursor: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigSnapshotResponse {
+    pub environment_id: String,
+    pub version: i64,
+    pub created_at: String,
+    /// The full evaluation config as served to SDKs at this version.
+    pub config: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigDiffResponse {
+    pub from: i64,
+    pub to: i64,
+    /// Changed fields by path, each with its `before` and `after` value.
+    pub changes: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RollbackResponse {
+    pub environment_id: String,
+    pub version: i64,
+    pub applied: bool,
+    pub flags: Vec<FlagPromotionDiff>,
+    /// Flags that cannot be restored, with the reason.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+    /// Set when the environment requires approval: one pending change
+    /// request per changed flag, created instead of applying.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub change_request_ids: Vec<String>,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+// ============================================================
+// Helpers
+// ============================================================
+
+fn to_change_response(log: AuditLogRow) -> ConfigChangeResponse {
+    let request_id = log
+        .metadata
+        .as_ref()
+        .and_then(|m| m.get("request_id"))
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    ConfigChangeResponse {
+        audit_log_id: log.id.to_string(),
+        action: log.action,
+        entity_type: log.entity_type,
+        entity_id: log.entity_id.map(|id| id.to_string()),
+        actor_id: log.actor_id.map(|id| id.to_string()),
+        actor_email: log.actor_email,
+        request_id,
+        created_at: log.created_at.to_rfc3339(),
+    }
+}
+
+/// Key-based snapshot of one flag environment as stored in a config
+/// version, comparable with [`snapshot_flag_environment`].
+fn historical_snapshot(
+    fe: &eval_core::FlagEnvironment,
+    variant_keys: &HashMap<Uuid, String>,
+    segment_keys: &HashMap<Uuid, String>,
+) -> FlagEnvironmentSnapshot {
+    let key = |keys: &HashMap<Uuid, String>, id: Uuid| {
+        keys.get(&id).cloned().unwrap_or_else(|| id.to_string())
+    };
+
+    let mut rules = fe.rules.clone();
+    rules.sort_by_key(|r| r.rank);
+
+    FlagEnvironmentSnapshot {
+        enabled: fe.enabled,
+        default_variant: Some(key(variant_keys, fe.default_variant_id)),
+        rules: rules
+            .into_iter()
+            .map(|r| RuleSnapshot {
+                description: r.description,
+                variant: r.variant_id.map(|id| key(variant_keys, id)),
+                segments: r
+                    .segments
+                    .iter()
+                    .map(|s| RuleSegmentSnapshot {
+                        segment: key(segment_keys, s.segment_id),
+                        negate: s.negate,
+                    })
+                    .collect(),
+                distributions: r
+                    .distributions
+                    .iter()
+                    .map(|d| DistributionSnapshot {
+                        variant: key(variant_keys, d.variant_id),
+                        rollout_pct: d.rollout_pct,
+                    })
+                    .collect(),
+            })
+            .collect(),
+        overrides: fe
+            .overrides
+            .iter()
+            .map(|o| (o.targeting_key.clone(), key(variant_keys, o.variant_id)))
+            .collect(),
+    }
+}
+
+// ============================================================
+// Handlers
+// ============================================================
+
+/// Versions of an environment's config, newest first, each with the audit
+/// entries that produced it.
+pub async fn list_config_versions(
+    State(state): State<AppState>,
+    Path((project_id, environment_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Query(query): Query<ListConfigVersionsQuery>,
+) -> Result<Json<ConfigVersionPage>, ApiError> {
+    access
+        .require(&state, "config_version.read", Role::Viewer)
+        .await?;
+    load_environment(&state, project_id, environment_id).await?;
+
+    if !(1..=MAX_PAGE_SIZE).contains(&query.limit) {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!("limit must be between 1 and {MAX_PAGE_SIZE}"),
+        ));
+    }
+
+    // One extra row tells us whether there is another page.
+    let mut versions = state
+        .store
+        .list_config_versions(environment_id, query.before, query.limit + 1)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let next_cursor = if versions.len() as i64 > query.limit {
+        versions.truncate(query.limit as usize);
+        versions.last().map(|(version, _)| *version)
+    } else {
+        None
+    };
+    let Some(&(_, oldest)) = versions.last() else {
+        return Ok(Json(ConfigVersionPage {
+            versions: Vec::new(),
+            next_cursor,
+        }));
+    };
+
+    // Changes after the newest version on this page belong to the version
+    // that follows it, which is the cursor we were given.
+    let until: Option<DateTime<Utc>> = match query.before {
+        Some(before) => state
+            .store
+            .get_config_snapshot(environment_id, before)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+            .map(|s| s.created_at),
+        None => None,
+    };
+    let mut logs = state
+        .store
+        .list_config_audit_log(project_id, environment_id, oldest, until)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .peekable();
+
+    // Walk versions oldest first, handing each the entries up to the next.
+    let mut responses = Vec::with_capacity(versions.len());
+    for (i, &(version, created_at)) in versions.iter().enumerate().rev() {
+        let next = i.checked_sub(1).map(|j| versions[j].1);
+        let mut changes = Vec::new();
+        while let Some(log) = logs.next_if(|log| next.is_none_or(|next| log.created_at < next)) {
+            changes.push(to_change_response(log));
+        }
+        responses.push(ConfigVersionResponse {
+            version,
+            created_at: created_at.to_rfc3339(),
+            changes,
+        });
+    }
+    responses.reverse();
+
+    Ok(Json(ConfigVersionPage {
+        versions: responses,
+        next_cursor,
+    }))
+}
+
+pub async fn get_config_version(
+    State(state): State<AppState>,
+    Path((project_id, environment_id, version)): Path<(Uuid, Uuid, i64)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<ConfigSnapshotResponse>, ApiError> {
+    access
+        .require(&state, "config_version.read", Role::Viewer)
+        .await?;
+    load_environment(&state, project_id, environment_id).await?;
+
+    let snapshot = state
+        .store
+        .get_config_snapshot(environment_id, version)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Config version not found"))?;
+
+    Ok(Json(ConfigSnapshotResponse {
+        environment_id: snapshot.environment_id.to_string(),
+        version: snapshot.version,
+        created_at: snapshot.created_at.to_rfc3339(),
+        config: snapshot.config,
+    }))
+}
+
+pub async fn diff_config_versions(
+    State(state): State<AppState>,
+    Path((project_id, environment_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Query(query): Query<DiffConfigVersionsQuery>,
+) -> Result<Json<ConfigDiffResponse>, ApiError> {
+    access
+        .require(&state, "config_version.read", Role::Viewer)
+        .await?;
+    load_environment(&state, project_id, environment_id).await?;
+
+    let changes = state
+        .store
+        .diff_config_versions(environment_id, query.from, query.to)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Config version not found"))?;
+
+    Ok(Json(ConfigDiffResponse {
+        from: query.from,
+        to: query.to,
+        changes,
+    }))
+}
+
+/// Preview, and optionally apply, restoring every flag in an environment to
+/// how it was configured at `version`.
+///
+/// Only per-environment settings are restored: enabled state, default
+/// variant, rules and overrides. Flags, variants and segments are shared by
+/// all environments and are left as they are; a flag whose old settings
+/// refer to one that no longer exists is skipped.
+pub async fn rollback_config_version(
+    State(state): State<AppState>,
+    Path((project_id, environment_id, version)): Path<(Uuid, Uuid, i64)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<RollbackRequest>,
+) -> Result<Json<RollbackResponse>, ApiError> {
+    access
+        .require_env(
+            &state,
+            "config_version.rollback",
+            environment_id,
+            Role::Editor,
+        )
+        .await?;
+    let env = load_environment(&state, project_id, environment_id).await?;
+
+    let snapshot = state
+        .store
+        .get_config_snapshot(environment_id, version)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Config version not found"))?;
+    let config: eval_core::FlagsConfig = serde_json::from_value(snapshot.config)
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let segment_keys: HashMap<Uuid, String> = config
+        .segments
+        .values()
+        .map(|s| (s.id, s.key.clone()))
+        .collect();
+
+    let flags = state
+        .store
+        .list_flags(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let mut skipped = Vec::new();
+    let mut gone: Vec<&String> = config
+        .flags
+        .keys()
+        .filter(|key| !flags.iter().any(|f| &f.key == *key))
+        .collect();
+    gone.sort();
+    for key in gone {
+        skipped.push(format!(
+            "{key}: deleted or archived since version {version}"
+        ));
+    }
+
+    let mut diffs = Vec::new();
+    let mut specs = Vec::new();
+    let mut snapshots = Vec::new();
+    for flag in &flags {
+        let Some(old) = config.flags.get(&flag.key) else {
+            skipped.push(format!("{}: created after version {version}", flag.key));
+            continue;
+        };
+        let Some(fe) = state
+            .store
+            .get_flag_environment(flag.id, environment_id)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        else {
+            continue;
+        };
+
+        let variant_keys: HashMap<Uuid, String> =
+            old.variants.iter().map(|v| (v.id, v.key.clone())).collect();
+        let target = historical_snapshot(&old.environment, &variant_keys, &segment_keys);
+        let ctx = proposal_context(&state, project_id, flag.id, &fe).await?;
+        let current =
+            snapshot_flag_environment(&state, &fe, &ctx.variant_keys, &ctx.segment_keys).await?;
+
+        let diff = diff_snapshots(&flag.key, &target, &current);
+        if diff.is_empty() {
+            continue;
+        }
+        let spec = match resolve_spec(&target, &ctx) {
+            Ok(spec) => spec,
+            Err((_, Json(body))) => {
+                let reason = body["error"].as_str().unwrap_or_default();
+                skipped.push(format!("{}: {reason}", flag.key));
+                continue;
+            }
+        };
+
+        diffs.push(diff);
+        specs.push((fe.id, spec));
+        snapshots.push((flag.id, fe.id, current, target));
+    }
+
+    // The changes or change requests and their audit entries commit together.
+    let mut change_request_ids = Vec::new();
+    if req.apply && !specs.is_empty() {
+        let ctx = access.audit_context().in_environment(environment_id);
+        let mut uow = state
+            .store
+            .begin()
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+        if env.requires_approval {
+            for (_, fe_id, before, after) in &snapshots {
+                let row = uow
+                    .create_change_request(
+                        project_id,
+                        *fe_id,
+                        "config.rollback",
+                        &serde_json::to_value(before).unwrap_or_default(),
+                        &serde_json::to_value(after).unwrap_or_default(),
+                        access.user_id,
+                        access.actor_email.as_deref(),
+                    )
+                    .await
+                    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+                uow.create_audit_log(
+                    project_id,
+                    &ctx,
+                    "change_request_created",
+                    "change_request",
+                    Some(row.id),
+                    None,
+                    serde_json::to_value(&row).ok().as_ref(),
+                )
+                .await
+                .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+                change_request_ids.push(row.id.to_string());
+            }
+        } else {
+            uow.write_flag_environments(&specs)
+                .await
+                .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+            for (flag_id, _, before, after) in &snapshots {
+                uow.create_audit_log(
+                    project_id,
+                    &ctx,
+                    "flag_rolled_back",
+                    "flag",
+                    Some(*flag_id),
+                    serde_json::to_value(before).ok().as_ref(),
+                    serde_json::to_value(after).ok().as_ref(),
+                )
+                .await
+                .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+            }
+        }
+
+        let changed = uow
+            .commit()
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+        notify_environment_changes(&state, &changed).await;
+    }
+
+    Ok(Json(RollbackResponse {
+        environment_id: environment_id.to_string(),
+        version,
+        applied: req.apply && !env.requires_approval && !specs.is_empty(),
+        flags: diffs,
+        skipped,
+        change_request_ids,
+    }))
+}
+
+/// Evaluate a flag for a context as it would have been evaluated at a past
+/// time or config version, with a trace of how the result was reached.
+pub async fn evaluate_at(
+    State(state): State<AppState>,
+    Path((project_id, environment_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<EvaluateAtRequest>,
+) -> Result<Json<EvaluateAtResponse>, ApiError> {
+    access
+        .require(&state, "config_version.evaluate", Role::Viewer)
+        .await?;
+    load_environment(&state, project_id, environment_id).await?;
+
+    let snapshot = match (req.at, req.version) {
+        (Some(at), None) => state
+            .store
+            .get_config_snapshot_at(environment_id, at)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+            .ok_or_else(|| {
+                err(
+                    StatusCode::NOT_FOUND,
+                    "No config version was recorded at or before that time",
+                )
+            })?,
+        (None, Some(version)) => state
+            .store
+            .get_config_snapshot(environment_id, version)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+            .ok_or_else(|| err(StatusCode::NOT_FOUND, "Config version not found"))?,
+        _ => {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                "Provide exactly one of at or version",
+            ))
+        }
+    };
+
+    let config: eval_core::FlagsConfig = serde_json::from_value(snapshot.config)
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let evaluator = Evaluator::new(config);
+    let (result, trace) =
+        evaluator.evaluate_with_trace(&req.flag_key, &req.context, &req.default_value);
+
+    Ok(Json(EvaluateAtResponse {
+        environment_id: environment_id.to_string(),
+        version: snapshot.version,
+        version_created_at: snapshot.created_at.to_rfc3339(),
+        result,
+        trace,
+    }))
+}
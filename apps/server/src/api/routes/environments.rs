@@ -1,13 +1,15 @@
 use axum::{
-    extract::{Extension, Path, State},
+    extract::{Extension, Path, Query, State},
     http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::api::middleware::auth::AuthInfo;
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::api::routes::flags::notify_environment_changes;
 use crate::state::AppState;
+use crate::store::models::EnvironmentRow;
 
 #[derive(Debug, Deserialize)]
 pub struct CreateEnvironmentRequest {
@@ -16,6 +18,22 @@ pub struct CreateEnvironmentRequest {
     pub color: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct UpdateEnvironmentRequest {
+    pub name: Option<String>,
+    pub color: Option<String>,
+    pub sort_order: Option<i32>,
+    /// Flag changes in this environment go through change requests.
+    pub requires_approval: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteEnvironmentQuery {
+    /// Delete even if the environment still has active SDK keys.
+    #[serde(default)]
+    pub force: bool,
+}
+
 #[derive(Debug, Serialize)]
 pub struct EnvironmentResponse {
     pub id: String,
@@ -23,6 +41,8 @@ pub struct EnvironmentResponse {
     pub slug: String,
     pub color: Option<String>,
     pub sort_order: i32,
+    pub restricted: bool,
+    pub requires_approval: bool,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -33,60 +53,273 @@ fn err(status: StatusCode, msg: &str) -> ApiError {
     (status, Json(serde_json::json!({ "error": msg })))
 }
 
+/// Load an environment, treating environments of other projects as missing.
+pub(crate) async fn load_environment(
+    state: &AppState,
+    project_id: Uuid,
+    environment_id: Uuid,
+) -> Result<EnvironmentRow, ApiError> {
+    state
+        .store
+        .get_environment(environment_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .filter(|env| env.project_id == project_id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Environment not found"))
+}
+
+fn to_response(env: EnvironmentRow) -> EnvironmentResponse {
+    EnvironmentResponse {
+        id: env.id.to_string(),
+        name: env.name,
+        slug: env.slug,
+        color: env.color,
+        sort_order: env.sort_order,
+        restricted: env.restricted,
+        requires_approval: env.requires_approval,
+        created_at: env.created_at.to_rfc3339(),
+        updated_at: env.updated_at.to_rfc3339(),
+    }
+}
+
 pub async fn create_environment(
     State(state): State<AppState>,
     Path(project_id): Path<Uuid>,
-    Extension(_auth): Extension<AuthInfo>,
+    Extension(access): Extension<ProjectAccess>,
     Json(req): Json<CreateEnvironmentRequest>,
 ) -> Result<(StatusCode, Json<EnvironmentResponse>), ApiError> {
-    let env = state
+    access.require(&state, "environment.create", Role::Admin).await?;
+
+    let mut uow = state
         .store
-        .create_environment(project_id, &req.name, &req.slug, req.color.as_deref())
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let env = uow
+        .create_environment(
+            project_id,
+            &req.name,
+            &req.slug,
+            req.color.as_deref(),
+            false,
+        )
         .await
         .map_err(|e| err(StatusCode::CONFLICT, &e.to_string()))?;
 
-    let _ = state
-        .store
-        .create_audit_log(project_id, None, "environment_created", "environment", Some(env.id), None, None)
-        .await;
-
-    Ok((
-        StatusCode::CREATED,
-        Json(EnvironmentResponse {
-            id: env.id.to_string(),
-            name: env.name,
-            slug: env.slug,
-            color: env.color,
-            sort_order: env.sort_order,
-            created_at: env.created_at.to_rfc3339(),
-            updated_at: env.updated_at.to_rfc3339(),
-        }),
-    ))
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(env.id),
+        "environment_created",
+        "environment",
+        Some(env.id),
+        None,
+        serde_json::to_value(&env).ok().as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    Ok((StatusCode::CREATED, Json(to_response(env))))
 }
 
 pub async fn list_environments(
     State(state): State<AppState>,
     Path(project_id): Path<Uuid>,
-    Extension(_auth): Extension<AuthInfo>,
+    Extension(access): Extension<ProjectAccess>,
 ) -> Result<Json<Vec<EnvironmentResponse>>, ApiError> {
+    access.require(&state, "environment.read", Role::Viewer).await?;
+
     let environments = state
         .store
         .list_environments(project_id)
         .await
         .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
-    let responses: Vec<EnvironmentResponse> = environments
-        .into_iter()
-        .map(|env| EnvironmentResponse {
-            id: env.id.to_string(),
-            name: env.name,
-            slug: env.slug,
-            color: env.color,
-            sort_order: env.sort_order,
-            created_at: env.created_at.to_rfc3339(),
-            updated_at: env.updated_at.to_rfc3339(),
-        })
-        .collect();
-
-    Ok(Json(responses))
+    Ok(Json(environments.into_iter().map(to_response).collect()))
+}
+
+/// Update an environment. Turning `requires_approval` off lets flag changes
+/// there skip review, so it needs the owner role.
+pub async fn update_environment(
+    State(state): State<AppState>,
+    Path((project_id, environment_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<UpdateEnvironmentRequest>,
+) -> Result<Json<EnvironmentResponse>, ApiError> {
+    access
+        .require_env(&state, "environment.update", environment_id, Role::Admin)
+        .await?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let before = uow
+        .lock_environment(environment_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .filter(|env| env.project_id == project_id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Environment not found"))?;
+
+    if before.requires_approval && req.requires_approval == Some(false) {
+        access
+            .require_env(
+                &state,
+                "environment.disable_approval",
+                environment_id,
+                Role::Owner,
+            )
+            .await?;
+    }
+
+    let env = uow
+        .update_environment(
+            environment_id,
+            req.name.as_deref(),
+            req.color.as_deref(),
+            req.sort_order,
+            req.requires_approval,
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(env.id),
+        "environment_updated",
+        "environment",
+        Some(env.id),
+        serde_json::to_value(&before).ok().as_ref(),
+        serde_json::to_value(&env).ok().as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(to_response(env)))
+}
+
+/// Delete an environment. Refuses to delete the last environment of a
+/// project, and one with active SDK keys unless `?force=true`.
+pub async fn delete_environment(
+    State(state): State<AppState>,
+    Path((project_id, environment_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Query(query): Query<DeleteEnvironmentQuery>,
+) -> Result<StatusCode, ApiError> {
+    access
+        .require_env(&state, "environment.delete", environment_id, Role::Admin)
+        .await?;
+
+    let env = load_environment(&state, project_id, environment_id).await?;
+
+    let environments = state
+        .store
+        .list_environments(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    if environments.len() <= 1 {
+        return Err(err(
+            StatusCode::CONFLICT,
+            "Cannot delete the last environment of a project",
+        ));
+    }
+
+    let active_keys = state
+        .store
+        .count_active_sdk_keys(env.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    if active_keys > 0 && !query.force {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": "Environment has active SDK keys; revoke them or pass ?force=true",
+                "active_sdk_keys": active_keys,
+            })),
+        ));
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.delete_environment(env.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(env.id),
+        "environment_deleted",
+        "environment",
+        Some(env.id),
+        serde_json::to_value(&env).ok().as_ref(),
+        None,
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    if let Some(ref redis) = state.redis {
+        let _ = redis.invalidate_config(env.id).await;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Create a new environment that copies every flag's enabled state, default
+/// variant, rules and overrides from the source environment.
+pub async fn clone_environment(
+    State(state): State<AppState>,
+    Path((project_id, source_environment_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<CreateEnvironmentRequest>,
+) -> Result<(StatusCode, Json<EnvironmentResponse>), ApiError> {
+    access.require(&state, "environment.clone", Role::Admin).await?;
+
+    let source = load_environment(&state, project_id, source_environment_id).await?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let env = uow
+        .clone_environment(project_id, source.id, &req.name, &req.slug, req.color.as_deref())
+        .await
+        .map_err(|e| err(StatusCode::CONFLICT, &e.to_string()))?;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(env.id),
+        "environment_cloned",
+        "environment",
+        Some(env.id),
+        None,
+        Some(&serde_json::json!({
+            "source_environment_id": source.id,
+            "environment": env,
+        })),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    notify_environment_changes(&state, &changed).await;
+
+    Ok((StatusCode::CREATED, Json(to_response(env))))
 }
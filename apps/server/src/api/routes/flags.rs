@@ -1,13 +1,24 @@
 use axum::{
-    extract::{Extension, Path, State},
-    http::StatusCode,
+    extract::{Extension, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
 
-use crate::api::middleware::auth::AuthInfo;
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::api::middleware::precondition::{
+    check_revision, if_match, precondition_failed, with_etag,
+};
+use crate::api::routes::change_requests::{audit_snapshot_in, propose_if_required};
+use crate::api::routes::code_refs::CodeReferenceResponse;
+use crate::api::routes::environments::load_environment;
+use crate::api::routes::rules::load_flag_environment;
 use crate::state::AppState;
+use crate::store::models::{EnvironmentRow, FlagFilter, FlagRow};
+use crate::store::postgres::UnitOfWork;
 
 // ============================================================
 // Request/Response types
@@ -24,12 +35,30 @@ pub struct CreateFlagRequest {
     pub tags: Vec<String>,
     pub variants: Vec<CreateVariantInput>,
     pub default_variant_key: String,
+    /// User email or team handle responsible for the flag.
+    pub owner: Option<String>,
+    #[serde(default = "default_flag_kind")]
+    pub kind: String,
+    #[serde(default = "default_temporary")]
+    pub temporary: bool,
+    /// Only allowed on temporary flags.
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
-fn default_flag_type() -> String {
+pub(crate) fn default_flag_type() -> String {
     "boolean".to_string()
 }
 
+pub(crate) fn default_flag_kind() -> String {
+    "release".to_string()
+}
+
+pub(crate) fn default_temporary() -> bool {
+    true
+}
+
+const FLAG_KINDS: [&str; 4] = ["release", "experiment", "ops", "permission"];
+
 #[derive(Debug, Deserialize)]
 pub struct CreateVariantInput {
     pub key: String,
@@ -43,6 +72,45 @@ pub struct UpdateFlagRequest {
     pub description: Option<String>,
     pub tags: Option<Vec<String>>,
     pub archived: Option<bool>,
+    /// `null` clears the owner.
+    #[serde(default, deserialize_with = "nullable")]
+    pub owner: Option<Option<String>>,
+    pub kind: Option<String>,
+    /// Setting this to `false` also clears `expires_at`.
+    pub temporary: Option<bool>,
+    /// `null` clears the expiry date.
+    #[serde(default, deserialize_with = "nullable")]
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+}
+
+/// Tell an explicit `null` (`Some(None)`, clear the field) apart from a
+/// missing field (`None`, leave it as it is).
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListFlagsQuery {
+    pub owner: Option<String>,
+    pub kind: Option<String>,
+    pub temporary: Option<bool>,
+    /// `true` for flags past their expiry date, `false` for the rest.
+    pub expired: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExpiringFlagsQuery {
+    /// Look-ahead window; already expired flags are always included.
+    #[serde(default = "default_expiring_within_days")]
+    pub within_days: i64,
+}
+
+fn default_expiring_within_days() -> i64 {
+    14
 }
 
 #[derive(Debug, Deserialize)]
@@ -51,6 +119,60 @@ pub struct ToggleFlagRequest {
     pub environment_id: Uuid,
 }
 
+/// The variant served when no rule matches. Given by ID or key.
+#[derive(Debug, Deserialize)]
+pub struct SetDefaultVariantRequest {
+    pub variant_id: Option<Uuid>,
+    pub variant_key: Option<String>,
+}
+
+/// Most flags a single bulk request may change.
+const MAX_BULK_FLAGS: usize = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct BulkFlagRequest {
+    pub select: BulkFlagSelector,
+    pub operation: BulkFlagOperation,
+}
+
+/// Which flags a bulk operation applies to. Criteria combine with AND; at
+/// least one must be given.
+#[derive(Debug, Deserialize)]
+pub struct BulkFlagSelector {
+    /// Flags with any of these keys.
+    pub keys: Option<Vec<String>>,
+    /// Flags carrying any of these tags.
+    pub tags: Option<Vec<String>>,
+    pub owner: Option<String>,
+    pub kind: Option<String>,
+    pub temporary: Option<bool>,
+    pub expired: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BulkFlagOperation {
+    Toggle { environment_id: Uuid, enabled: bool },
+    Archive,
+    /// Selects among archived flags only.
+    Unarchive,
+    AddTags { tags: Vec<String> },
+    RemoveTags { tags: Vec<String> },
+    /// `null` clears the owner.
+    SetOwner { owner: Option<String> },
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkFlagResponse {
+    /// Keys of every flag the selector matched.
+    pub matched: Vec<String>,
+    /// Keys of the flags the operation changed; the rest already matched it.
+    pub updated: Vec<String>,
+    /// Change requests opened instead of toggling, when the environment
+    /// requires approval.
+    pub change_request_ids: Vec<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct FlagResponse {
     pub id: String,
@@ -60,10 +182,30 @@ pub struct FlagResponse {
     pub flag_type: String,
     pub tags: Vec<String>,
     pub archived: bool,
+    pub owner: Option<String>,
+    pub kind: String,
+    pub temporary: bool,
+    pub expires_at: Option<String>,
     pub variants: Vec<VariantResponse>,
     pub environments: Vec<FlagEnvironmentState>,
+    /// Where the flag is used in code; only populated by `get_flag`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_references: Option<Vec<CodeReferenceResponse>>,
     pub created_at: String,
     pub updated_at: String,
+    /// Also sent as the `ETag`; pass it back in `If-Match` to update safely.
+    pub revision: i64,
+}
+
+/// A temporary flag that is past or close to its expiry date.
+#[derive(Debug, Serialize)]
+pub struct ExpiringFlagResponse {
+    pub key: String,
+    pub name: String,
+    pub owner: Option<String>,
+    pub kind: String,
+    pub expires_at: String,
+    pub expired: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -72,6 +214,9 @@ pub struct FlagEnvironmentState {
     pub environment_name: String,
     pub environment_slug: String,
     pub enabled: bool,
+    /// Revision of the flag's state in this environment; the `If-Match`
+    /// value for `toggle_flag`.
+    pub revision: Option<i64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -88,6 +233,49 @@ fn err(status: StatusCode, msg: &str) -> ApiError {
     (status, Json(serde_json::json!({ "error": msg })))
 }
 
+pub(crate) fn validate_kind(kind: &str) -> Result<(), ApiError> {
+    if FLAG_KINDS.contains(&kind) {
+        Ok(())
+    } else {
+        Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!("kind must be one of: {}", FLAG_KINDS.join(", ")),
+        ))
+    }
+}
+
+/// Refuse a change that reaches `environments` directly, with no change
+/// request form, while any of them requires approval.
+pub(crate) fn refuse_approval_environments(
+    environments: &[EnvironmentRow],
+    change: &str,
+) -> Result<(), ApiError> {
+    let names: Vec<&str> = environments
+        .iter()
+        .filter(|env| env.requires_approval)
+        .map(|env| env.name.as_str())
+        .collect();
+    if names.is_empty() {
+        return Ok(());
+    }
+    Err(err(
+        StatusCode::CONFLICT,
+        &format!(
+            "{change} changes environments that require approval: {}",
+            names.join(", ")
+        ),
+    ))
+}
+
+/// A flag and its variants as recorded in the audit log, as seen inside
+/// `uow`, or `None` if the variants cannot be loaded.
+async fn flag_audit_snapshot_in(uow: &mut UnitOfWork, flag: &FlagRow) -> Option<serde_json::Value> {
+    let variants = uow.get_flag_variants(flag.id).await.ok()?;
+    let mut snapshot = serde_json::to_value(flag).ok()?;
+    snapshot["variants"] = serde_json::to_value(variants).ok()?;
+    Some(snapshot)
+}
+
 /// Build environment states for a flag by joining flag_environments with environments.
 async fn build_env_states(
     state: &AppState,
@@ -108,24 +296,21 @@ async fn build_env_states(
 
     let mut states = Vec::new();
     for env in &environments {
-        let enabled = flag_envs
-            .iter()
-            .find(|fe| fe.environment_id == env.id)
-            .map(|fe| fe.enabled)
-            .unwrap_or(false);
+        let fe = flag_envs.iter().find(|fe| fe.environment_id == env.id);
 
         states.push(FlagEnvironmentState {
             environment_id: env.id.to_string(),
             environment_name: env.name.clone(),
             environment_slug: env.slug.clone(),
-            enabled,
+            enabled: fe.is_some_and(|fe| fe.enabled),
+            revision: fe.map(|fe| fe.revision),
         });
     }
     Ok(states)
 }
 
 /// Publish config change events for all environments in a project.
-async fn notify_config_change(state: &AppState, project_id: Uuid) {
+pub(crate) async fn notify_config_change(state: &AppState, project_id: Uuid) {
     let environments = match state.store.list_environments(project_id).await {
         Ok(envs) => envs,
         Err(e) => {
@@ -135,16 +320,55 @@ async fn notify_config_change(state: &AppState, project_id: Uuid) {
     };
 
     for env in environments {
-        let version = state
-            .store
-            .increment_config_version(env.id)
-            .await
-            .unwrap_or(0);
+        notify_environment_change(state, env.id).await;
+    }
+}
 
-        if let Some(ref redis) = state.redis {
-            let _ = redis.invalidate_config(env.id).await;
-            let _ = redis.publish_config_change(env.id, version).await;
+/// Publish config changes for the environments a committed unit of work
+/// touched.
+pub(crate) async fn notify_environment_changes(state: &AppState, environment_ids: &[Uuid]) {
+    for &environment_id in environment_ids {
+        notify_environment_change(state, environment_id).await;
+    }
+}
+
+/// Bump the config version of a single environment, publish the change,
+/// record a snapshot of the new version and queue a `config_changed` webhook
+/// event.
+pub(crate) async fn notify_environment_change(state: &AppState, environment_id: Uuid) {
+    let env = match state.store.get_environment(environment_id).await {
+        Ok(Some(env)) => env,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("Failed to load environment {environment_id}: {e}");
+            return;
+        }
+    };
+
+    let version = match state.store.publish_config_version(env.project_id, env.id).await {
+        Ok(version) => version,
+        Err(e) => {
+            tracing::error!("Failed to publish config version: {e}");
+            0
         }
+    };
+
+    if let Some(ref redis) = state.redis {
+        let _ = redis.invalidate_config(environment_id).await;
+        let _ = redis.publish_config_change(environment_id, version).await;
+    }
+
+    let data = serde_json::json!({
+        "environment_id": env.id,
+        "environment_slug": env.slug,
+        "version": version,
+    });
+    if let Err(e) = state
+        .store
+        .enqueue_webhook_event(env.project_id, "config_changed", &data)
+        .await
+    {
+        tracing::error!("Failed to queue config_changed webhooks: {e}");
     }
 }
 
@@ -155,12 +379,40 @@ async fn notify_config_change(state: &AppState, project_id: Uuid) {
 pub async fn create_flag(
     State(state): State<AppState>,
     Path(project_id): Path<Uuid>,
-    Extension(_auth): Extension<AuthInfo>,
+    Extension(access): Extension<ProjectAccess>,
     Json(req): Json<CreateFlagRequest>,
 ) -> Result<(StatusCode, Json<FlagResponse>), ApiError> {
-    // Create the flag
-    let flag = state
+    access.require(&state, "flag.create", Role::Editor).await?;
+
+    validate_kind(&req.kind)?;
+    if !req.temporary && req.expires_at.is_some() {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "Permanent flags cannot have an expiry date",
+        ));
+    }
+    if !req.variants.iter().any(|v| v.key == req.default_variant_key) {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "default_variant_key must match one of the variants",
+        ));
+    }
+
+    let environments = state
+        .store
+        .list_environments(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    // The flag, its variants and its per-environment rows are written in one
+    // transaction, so a failure part-way leaves nothing behind.
+    let mut uow = state
         .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let flag = uow
         .create_flag(
             project_id,
             &req.key,
@@ -168,6 +420,10 @@ pub async fn create_flag(
             req.description.as_deref(),
             &req.flag_type,
             &req.tags,
+            req.owner.as_deref(),
+            &req.kind,
+            req.temporary,
+            req.expires_at,
         )
         .await
         .map_err(|e| err(StatusCode::CONFLICT, &e.to_string()))?;
@@ -177,8 +433,7 @@ pub async fn create_flag(
     let mut default_variant_id = None;
 
     for (i, v) in req.variants.iter().enumerate() {
-        let variant = state
-            .store
+        let variant = uow
             .create_flag_variant(flag.id, &v.key, &v.value, v.description.as_deref(), i as i32)
             .await
             .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
@@ -195,35 +450,30 @@ pub async fn create_flag(
         });
     }
 
-    // Create flag_environment entries for all environments
-    let environments = state
-        .store
-        .list_environments(project_id)
-        .await
-        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
-
     for env in &environments {
-        state
-            .store
-            .create_flag_environment(flag.id, env.id, false, default_variant_id)
+        uow.create_flag_environment(flag.id, env.id, false, default_variant_id)
             .await
             .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
     }
 
-    notify_config_change(&state, project_id).await;
+    let after = flag_audit_snapshot_in(&mut uow, &flag).await;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "flag_created",
+        "flag",
+        Some(flag.id),
+        None,
+        after.as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
-    let _ = state
-        .store
-        .create_audit_log(
-            project_id,
-            None,
-            "flag_created",
-            "flag",
-            Some(flag.id),
-            None,
-            None,
-        )
-        .await;
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
 
     let env_states = build_env_states(&state, flag.id, project_id).await?;
 
@@ -237,10 +487,16 @@ pub async fn create_flag(
             flag_type: flag.flag_type,
             tags: flag.tags,
             archived: flag.archived,
+            owner: flag.owner,
+            kind: flag.kind,
+            temporary: flag.temporary,
+            expires_at: flag.expires_at.map(|t| t.to_rfc3339()),
             variants: variant_responses,
             environments: env_states,
+            code_references: None,
             created_at: flag.created_at.to_rfc3339(),
             updated_at: flag.updated_at.to_rfc3339(),
+            revision: flag.revision,
         }),
     ))
 }
@@ -248,11 +504,23 @@ pub async fn create_flag(
 pub async fn list_flags(
     State(state): State<AppState>,
     Path(project_id): Path<Uuid>,
-    Extension(_auth): Extension<AuthInfo>,
+    Extension(access): Extension<ProjectAccess>,
+    Query(query): Query<ListFlagsQuery>,
 ) -> Result<Json<Vec<FlagResponse>>, ApiError> {
+    access.require(&state, "flag.read", Role::Viewer).await?;
+
+    if let Some(kind) = &query.kind {
+        validate_kind(kind)?;
+    }
+    let filter = FlagFilter {
+        owner: query.owner,
+        kind: query.kind,
+        temporary: query.temporary,
+        expired: query.expired,
+    };
     let flags = state
         .store
-        .list_flags(project_id)
+        .list_flags_filtered(project_id, &filter)
         .await
         .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
@@ -274,6 +542,10 @@ pub async fn list_flags(
             flag_type: flag.flag_type,
             tags: flag.tags,
             archived: flag.archived,
+            owner: flag.owner,
+            kind: flag.kind,
+            temporary: flag.temporary,
+            expires_at: flag.expires_at.map(|t| t.to_rfc3339()),
             variants: variants
                 .into_iter()
                 .map(|v| VariantResponse {
@@ -284,19 +556,65 @@ pub async fn list_flags(
                 })
                 .collect(),
             environments: env_states,
+            code_references: None,
             created_at: flag.created_at.to_rfc3339(),
             updated_at: flag.updated_at.to_rfc3339(),
+            revision: flag.revision,
         });
     }
 
     Ok(Json(responses))
 }
 
+/// Temporary flags past their expiry date or expiring within the window,
+/// soonest first, so owners can be chased to clean them up.
+pub async fn list_expiring_flags(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+    Query(query): Query<ExpiringFlagsQuery>,
+) -> Result<Json<Vec<ExpiringFlagResponse>>, ApiError> {
+    access.require(&state, "flag.read", Role::Viewer).await?;
+
+    if !(0..=3650).contains(&query.within_days) {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "within_days must be between 0 and 3650",
+        ));
+    }
+
+    let now = Utc::now();
+    let flags = state
+        .store
+        .list_expiring_flags(project_id, now + Duration::days(query.within_days))
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(
+        flags
+            .into_iter()
+            .filter_map(|f| {
+                let expires_at = f.expires_at?;
+                Some(ExpiringFlagResponse {
+                    key: f.key,
+                    name: f.name,
+                    owner: f.owner,
+                    kind: f.kind,
+                    expires_at: expires_at.to_rfc3339(),
+                    expired: expires_at <= now,
+                })
+            })
+            .collect(),
+    ))
+}
+
 pub async fn get_flag(
     State(state): State<AppState>,
     Path((project_id, flag_key)): Path<(Uuid, String)>,
-    Extension(_auth): Extension<AuthInfo>,
-) -> Result<Json<FlagResponse>, ApiError> {
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Response, ApiError> {
+    access.require(&state, "flag.read", Role::Viewer).await?;
+
     let flag = state
         .store
         .get_flag_by_key(project_id, &flag_key)
@@ -312,7 +630,23 @@ pub async fn get_flag(
 
     let env_states = build_env_states(&state, flag.id, project_id).await?;
 
-    Ok(Json(FlagResponse {
+    let code_references = state
+        .store
+        .list_code_references_for_flag(flag.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .map(|r| CodeReferenceResponse {
+            repository: r.repository,
+            branch: r.branch,
+            file_path: r.file_path,
+            line_number: r.line_number,
+            line_content: r.line_content,
+            uploaded_at: r.created_at.to_rfc3339(),
+        })
+        .collect();
+
+    let response = FlagResponse {
         id: flag.id.to_string(),
         key: flag.key,
         name: flag.name,
@@ -320,6 +654,10 @@ pub async fn get_flag(
         flag_type: flag.flag_type,
         tags: flag.tags,
         archived: flag.archived,
+        owner: flag.owner,
+        kind: flag.kind,
+        temporary: flag.temporary,
+        expires_at: flag.expires_at.map(|t| t.to_rfc3339()),
         variants: variants
             .into_iter()
             .map(|v| VariantResponse {
@@ -330,50 +668,93 @@ pub async fn get_flag(
             })
             .collect(),
         environments: env_states,
+        code_references: Some(code_references),
         created_at: flag.created_at.to_rfc3339(),
         updated_at: flag.updated_at.to_rfc3339(),
-    }))
+        revision: flag.revision,
+    };
+    Ok(with_etag(response.revision, Json(response)))
 }
 
 pub async fn update_flag(
     State(state): State<AppState>,
     Path((project_id, flag_key)): Path<(Uuid, String)>,
-    Extension(_auth): Extension<AuthInfo>,
+    Extension(access): Extension<ProjectAccess>,
+    headers: HeaderMap,
     Json(req): Json<UpdateFlagRequest>,
-) -> Result<Json<FlagResponse>, ApiError> {
-    let flag = state
+) -> Result<Response, ApiError> {
+    access.require(&state, "flag.update", Role::Editor).await?;
+    let precondition = if_match(&headers)?;
+
+    if let Some(kind) = &req.kind {
+        validate_kind(kind)?;
+    }
+
+    let mut uow = state
         .store
-        .get_flag_by_key(project_id, &flag_key)
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let flag = uow
+        .lock_flag(project_id, &flag_key)
         .await
         .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
         .ok_or_else(|| err(StatusCode::NOT_FOUND, "Flag not found"))?;
+    let expected_revision = check_revision(precondition.as_deref(), flag.revision)?;
 
-    let updated = state
-        .store
+    if matches!(req.expires_at, Some(Some(_))) && !req.temporary.unwrap_or(flag.temporary) {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "Permanent flags cannot have an expiry date",
+        ));
+    }
+
+    // Archiving takes the flag out of every environment's config.
+    if req.archived.is_some_and(|archived| archived != flag.archived) {
+        let environments = uow
+            .list_environments(project_id)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+        refuse_approval_environments(&environments, "Archiving or unarchiving a flag")?;
+    }
+
+    let before = flag_audit_snapshot_in(&mut uow, &flag).await;
+
+    let updated = uow
         .update_flag(
             flag.id,
+            expected_revision,
             req.name.as_deref(),
             req.description.as_deref(),
             req.tags.as_deref(),
             req.archived,
+            req.owner.as_ref().map(Option::as_deref),
+            req.kind.as_deref(),
+            req.temporary,
+            req.expires_at,
         )
         .await
-        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(precondition_failed)?;
 
-    notify_config_change(&state, project_id).await;
+    let after = flag_audit_snapshot_in(&mut uow, &updated).await;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "flag_updated",
+        "flag",
+        Some(updated.id),
+        before.as_ref(),
+        after.as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
-    let _ = state
-        .store
-        .create_audit_log(
-            project_id,
-            None,
-            "flag_updated",
-            "flag",
-            Some(updated.id),
-            None,
-            None,
-        )
-        .await;
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
 
     let variants = state
         .store
@@ -383,7 +764,7 @@ pub async fn update_flag(
 
     let env_states = build_env_states(&state, updated.id, project_id).await?;
 
-    Ok(Json(FlagResponse {
+    let response = FlagResponse {
         id: updated.id.to_string(),
         key: updated.key,
         name: updated.name,
@@ -391,6 +772,10 @@ pub async fn update_flag(
         flag_type: updated.flag_type,
         tags: updated.tags,
         archived: updated.archived,
+        owner: updated.owner,
+        kind: updated.kind,
+        temporary: updated.temporary,
+        expires_at: updated.expires_at.map(|t| t.to_rfc3339()),
         variants: variants
             .into_iter()
             .map(|v| VariantResponse {
@@ -401,43 +786,59 @@ pub async fn update_flag(
             })
             .collect(),
         environments: env_states,
+        code_references: None,
         created_at: updated.created_at.to_rfc3339(),
         updated_at: updated.updated_at.to_rfc3339(),
-    }))
+        revision: updated.revision,
+    };
+    Ok(with_etag(response.revision, Json(response)))
 }
 
 pub async fn delete_flag(
     State(state): State<AppState>,
     Path((project_id, flag_key)): Path<(Uuid, String)>,
-    Extension(_auth): Extension<AuthInfo>,
+    Extension(access): Extension<ProjectAccess>,
 ) -> Result<StatusCode, ApiError> {
-    let flag = state
+    access.require(&state, "flag.delete", Role::Admin).await?;
+
+    let mut uow = state
         .store
-        .get_flag_by_key(project_id, &flag_key)
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let flag = uow
+        .lock_flag(project_id, &flag_key)
         .await
         .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
         .ok_or_else(|| err(StatusCode::NOT_FOUND, "Flag not found"))?;
+    let environments = uow
+        .list_environments(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    refuse_approval_environments(&environments, "Deleting a flag")?;
+    let before = flag_audit_snapshot_in(&mut uow, &flag).await;
 
-    state
-        .store
-        .delete_flag(flag.id)
+    uow.delete_flag(project_id, flag.id)
         .await
         .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
-    notify_config_change(&state, project_id).await;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "flag_deleted",
+        "flag",
+        Some(flag.id),
+        before.as_ref(),
+        None,
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
-    let _ = state
-        .store
-        .create_audit_log(
-            project_id,
-            None,
-            "flag_deleted",
-            "flag",
-            Some(flag.id),
-            None,
-            None,
-        )
-        .await;
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -445,52 +846,370 @@ pub async fn delete_flag(
 pub async fn toggle_flag(
     State(state): State<AppState>,
     Path((project_id, flag_key)): Path<(Uuid, String)>,
-    Extension(_auth): Extension<AuthInfo>,
+    Extension(access): Extension<ProjectAccess>,
+    headers: HeaderMap,
     Json(req): Json<ToggleFlagRequest>,
-) -> Result<Json<serde_json::Value>, ApiError> {
+) -> Result<Response, ApiError> {
+    access
+        .require_env(&state, "flag.toggle", req.environment_id, Role::Editor)
+        .await?;
+    let precondition = if_match(&headers)?;
+
     let flag = state
         .store
         .get_flag_by_key(project_id, &flag_key)
         .await
         .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
         .ok_or_else(|| err(StatusCode::NOT_FOUND, "Flag not found"))?;
+    load_environment(&state, project_id, req.environment_id).await?;
 
-    let fe = state
+    let current = state
         .store
-        .toggle_flag(flag.id, req.environment_id, req.enabled)
+        .get_flag_environment(flag.id, req.environment_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Flag is not configured in this environment"))?;
+    let expected_revision = check_revision(precondition.as_deref(), current.revision)?;
+    let proposal = propose_if_required(&state, &access, &flag, &current, "flag.toggle", |s, _| {
+        s.enabled = req.enabled;
+        Ok(())
+    })
+    .await?;
+    if let Some(response) = proposal {
+        return Ok(response);
+    }
+
+    let mut uow = state
+        .store
+        .begin()
         .await
         .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let before = audit_snapshot_in(&mut uow, project_id, flag.id, req.environment_id).await;
+
+    let fe = uow
+        .toggle_flag(flag.id, req.environment_id, req.enabled, expected_revision)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(precondition_failed)?;
+
+    let after = audit_snapshot_in(&mut uow, project_id, flag.id, req.environment_id).await;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(req.environment_id),
+        "flag_toggled",
+        "flag",
+        Some(flag.id),
+        before.as_ref(),
+        after.as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
     // Invalidate cache + bump version + publish change
-    let version = state
-        .store
-        .increment_config_version(req.environment_id)
+    let changed = uow
+        .commit()
         .await
-        .unwrap_or(0);
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
 
-    if let Some(ref redis) = state.redis {
-        let _ = redis.invalidate_config(req.environment_id).await;
-        let _ = redis
-            .publish_config_change(req.environment_id, version)
-            .await;
+    Ok(with_etag(
+        fe.revision,
+        Json(serde_json::json!({
+            "flag_key": flag_key,
+            "environment_id": req.environment_id,
+            "enabled": fe.enabled,
+            "revision": fe.revision,
+        })),
+    ))
+}
+
+/// Apply one operation to every flag matching a selector, e.g. "turn off
+/// every flag tagged `new-search` in prod".
+///
+/// All changes and their audit entries (one per changed flag) commit in one
+/// transaction, and each affected environment's config version is bumped
+/// once. Toggles in an environment that requires approval become change
+/// requests instead, and archiving is refused while any environment does.
+pub async fn bulk_update_flags(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<BulkFlagRequest>,
+) -> Result<Json<BulkFlagResponse>, ApiError> {
+    match req.operation {
+        BulkFlagOperation::Toggle { environment_id, .. } => {
+            access
+                .require_env(&state, "flag.toggle", environment_id, Role::Editor)
+                .await?;
+            load_environment(&state, project_id, environment_id).await?;
+        }
+        _ => access.require(&state, "flag.update", Role::Editor).await?,
     }
 
-    let _ = state
+    let select = req.select;
+    if select.keys.is_none()
+        && select.tags.is_none()
+        && select.owner.is_none()
+        && select.kind.is_none()
+        && select.temporary.is_none()
+        && select.expired.is_none()
+    {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "select must set at least one of keys, tags, owner, kind, temporary or expired",
+        ));
+    }
+    if let Some(kind) = &select.kind {
+        validate_kind(kind)?;
+    }
+    if let BulkFlagOperation::AddTags { tags } | BulkFlagOperation::RemoveTags { tags } =
+        &req.operation
+    {
+        if tags.is_empty() {
+            return Err(err(StatusCode::BAD_REQUEST, "tags must not be empty"));
+        }
+    }
+    let filter = FlagFilter {
+        owner: select.owner,
+        kind: select.kind,
+        temporary: select.temporary,
+        expired: select.expired,
+    };
+
+    let mut uow = state
         .store
-        .create_audit_log(
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let flags = uow
+        .lock_flags(
+            project_id,
+            matches!(req.operation, BulkFlagOperation::Unarchive),
+            select.keys.as_deref(),
+            select.tags.as_deref(),
+            &filter,
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    if flags.len() > MAX_BULK_FLAGS {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!(
+                "The selection matches {} flags; at most {MAX_BULK_FLAGS} can be changed at once",
+                flags.len()
+            ),
+        ));
+    }
+
+    let approval_environments: Vec<_> = uow
+        .list_environments(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .filter(|env| env.requires_approval)
+        .collect();
+    // Archiving takes a flag out of every environment's config and has no
+    // change request form, so it is refused where any of them needs review.
+    if matches!(req.operation, BulkFlagOperation::Archive | BulkFlagOperation::Unarchive)
+        && !flags.is_empty()
+    {
+        refuse_approval_environments(&approval_environments, "Archiving or unarchiving flags")?;
+    }
+
+    let mut updated = Vec::new();
+    let mut change_request_ids = Vec::new();
+    for flag in &flags {
+        let (ctx, action, before, after) = match &req.operation {
+            BulkFlagOperation::Toggle {
+                environment_id,
+                enabled,
+            } => {
+                let fe = uow
+                    .get_flag_environment(flag.id, *environment_id)
+                    .await
+                    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+                let Some(fe) = fe.filter(|fe| fe.enabled != *enabled) else {
+                    continue;
+                };
+                let ctx = access.audit_context().in_environment(*environment_id);
+                let before =
+                    audit_snapshot_in(&mut uow, project_id, flag.id, *environment_id).await;
+                let after = before.clone().map(|mut snapshot| {
+                    snapshot["enabled"] = serde_json::json!(enabled);
+                    snapshot
+                });
+
+                if approval_environments.iter().any(|env| env.id == *environment_id) {
+                    let (Some(base), Some(proposed)) = (&before, &after) else {
+                        return Err(err(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            &format!("Could not snapshot flag {}", flag.key),
+                        ));
+                    };
+                    let row = uow
+                        .create_change_request(
+                            project_id,
+                            fe.id,
+                            "flag.toggle",
+                            base,
+                            proposed,
+                            access.user_id,
+                            access.actor_email.as_deref(),
+                        )
+                        .await
+                        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+                    uow.create_audit_log(
+                        project_id,
+                        &ctx,
+                        "change_request_created",
+                        "change_request",
+                        Some(row.id),
+                        None,
+                        serde_json::to_value(&row).ok().as_ref(),
+                    )
+                    .await
+                    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+                    change_request_ids.push(row.id.to_string());
+                    continue;
+                }
+
+                let toggled = uow
+                    .set_flag_enabled(flag.id, *environment_id, *enabled)
+                    .await
+                    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+                if toggled.is_none() {
+                    continue;
+                }
+                (ctx, "flag_toggled", before, after)
+            }
+            op => {
+                let result = match op {
+                    BulkFlagOperation::Archive => uow.set_flag_archived(flag.id, true).await,
+                    BulkFlagOperation::Unarchive => uow.set_flag_archived(flag.id, false).await,
+                    BulkFlagOperation::AddTags { tags } => uow.add_flag_tags(flag.id, tags).await,
+                    BulkFlagOperation::RemoveTags { tags } => {
+                        uow.remove_flag_tags(flag.id, tags).await
+                    }
+                    BulkFlagOperation::SetOwner { owner } => {
+                        uow.set_flag_owner(flag.id, owner.as_deref()).await
+                    }
+                    BulkFlagOperation::Toggle { .. } => unreachable!("handled above"),
+                };
+                let Some(changed) =
+                    result.map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+                else {
+                    continue;
+                };
+                (
+                    access.audit_context(),
+                    "flag_updated",
+                    flag_audit_snapshot_in(&mut uow, flag).await,
+                    flag_audit_snapshot_in(&mut uow, &changed).await,
+                )
+            }
+        };
+
+        uow.create_audit_log(
             project_id,
-            None,
-            "flag_toggled",
+            &ctx,
+            action,
             "flag",
             Some(flag.id),
-            None,
-            None,
+            before.as_ref(),
+            after.as_ref(),
         )
-        .await;
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+        updated.push(flag.key.clone());
+    }
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    Ok(Json(BulkFlagResponse {
+        matched: flags.into_iter().map(|f| f.key).collect(),
+        updated,
+        change_request_ids,
+    }))
+}
+
+pub async fn set_default_variant(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, environment_id)): Path<(Uuid, String, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<SetDefaultVariantRequest>,
+) -> Result<Response, ApiError> {
+    access
+        .require_env(&state, "flag.default_variant", environment_id, Role::Editor)
+        .await?;
+
+    let (flag, fe) = load_flag_environment(&state, project_id, &flag_key, environment_id).await?;
+
+    let variants = state
+        .store
+        .get_flag_variants(flag.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let variant = match (&req.variant_id, &req.variant_key) {
+        (Some(id), _) => variants.iter().find(|v| v.id == *id),
+        (None, Some(key)) => variants.iter().find(|v| v.key == *key),
+        (None, None) => {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                "variant_id or variant_key is required",
+            ))
+        }
+    }
+    .ok_or_else(|| err(StatusCode::BAD_REQUEST, "Unknown variant"))?;
+
+    let proposal = propose_if_required(&state, &access, &flag, &fe, "flag.default_variant", |s, _| {
+        s.default_variant = Some(variant.key.clone());
+        Ok(())
+    })
+    .await?;
+    if let Some(response) = proposal {
+        return Ok(response);
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let before = audit_snapshot_in(&mut uow, project_id, flag.id, environment_id).await;
+
+    let updated = uow
+        .set_default_variant(fe.id, variant.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let after = audit_snapshot_in(&mut uow, project_id, flag.id, environment_id).await;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(environment_id),
+        "flag_default_variant_set",
+        "flag",
+        Some(flag.id),
+        before.as_ref(),
+        after.as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
 
     Ok(Json(serde_json::json!({
         "flag_key": flag_key,
-        "environment_id": req.environment_id,
-        "enabled": fe.enabled,
-    })))
+        "environment_id": environment_id,
+        "default_variant_id": updated.default_variant_id,
+        "default_variant_key": variant.key,
+    }))
+    .into_response())
 }
@@ -0,0 +1,903 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use axum::{
+    body::Bytes,
+    extract::{Extension, Path, Query, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::api::routes::change_requests::{resolve_spec, ProposalContext};
+use crate::api::routes::flags::{
+    default_flag_kind, default_flag_type, default_temporary, notify_environment_changes,
+    validate_kind,
+};
+use crate::api::routes::promote::{snapshot_flag_environment_in, FlagEnvironmentSnapshot};
+use crate::api::routes::segments::default_match_type;
+use crate::state::AppState;
+use crate::store::models::{FlagRow, SegmentRow};
+use crate::store::postgres::{json_diff, UnitOfWork};
+
+/// Version of the document layout written by [`export_project`]. Imports of
+/// any other version are rejected.
+const FORMAT_VERSION: u32 = 1;
+
+// ============================================================
+// Document types
+// ============================================================
+
+/// A whole project without database IDs: environments are referred to by
+/// slug and variants and segments by key, so a document can be imported
+/// into another project or another instance.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectDocument {
+    pub format_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exported_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub environments: Vec<EnvironmentDocument>,
+    #[serde(default)]
+    pub segments: Vec<SegmentDocument>,
+    #[serde(default)]
+    pub flags: Vec<FlagDocument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnvironmentDocument {
+    pub slug: String,
+    pub name: String,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub requires_approval: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SegmentDocument {
+    pub key: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default = "default_match_type")]
+    pub match_type: String,
+    #[serde(default)]
+    pub constraints: Vec<ConstraintDocument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConstraintDocument {
+    pub attribute: String,
+    pub operator: String,
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlagDocument {
+    pub key: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default = "default_flag_type")]
+    pub flag_type: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default = "default_flag_kind")]
+    pub kind: String,
+    #[serde(default = "default_temporary")]
+    pub temporary: bool,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub archived: bool,
+    pub variants: Vec<VariantDocument>,
+    /// Configuration per environment slug. Environments left out keep their
+    /// current state; a new flag starts disabled there.
+    #[serde(default)]
+    pub environments: BTreeMap<String, FlagEnvironmentSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariantDocument {
+    pub key: String,
+    pub value: serde_json::Value,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+// ============================================================
+// Request/Response types
+// ============================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Only add; fails if any flag or segment key already exists.
+    Create,
+    /// Add what is missing and overwrite what differs.
+    Merge,
+    /// Report what `merge` would do without writing anything.
+    #[default]
+    DryRun,
+}
+
+/// Serialization of a [`ProjectDocument`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentFormat {
+    Json,
+    Yaml,
+}
+
+impl DocumentFormat {
+    /// `explicit` if given, otherwise YAML when the `name` header (`Accept`
+    /// or `Content-Type`) mentions it, and JSON by default.
+    fn negotiate(explicit: Option<Self>, headers: &HeaderMap, name: HeaderName) -> Self {
+        explicit.unwrap_or_else(|| {
+            let yaml = headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.contains("yaml"));
+            if yaml {
+                Self::Yaml
+            } else {
+                Self::Json
+            }
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    /// `json` or `yaml`; defaults to what the `Accept` header asks for.
+    pub format: Option<DocumentFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub mode: ImportMode,
+    /// `json` or `yaml`; defaults to what the `Content-Type` header says.
+    pub format: Option<DocumentFormat>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub mode: ImportMode,
+    pub applied: bool,
+    pub environments: Vec<ImportChange>,
+    pub segments: Vec<ImportChange>,
+    pub flags: Vec<ImportChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportChange {
+    pub key: String,
+    pub action: ImportAction,
+    /// Changed fields by path, each `{before, after}`.
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    pub changes: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportAction {
+    Create,
+    Update,
+    Unchanged,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+// ============================================================
+// Export
+// ============================================================
+
+/// The project as `uow` sees it.
+async fn build_document(
+    uow: &mut UnitOfWork,
+    project_id: Uuid,
+) -> Result<ProjectDocument, ApiError> {
+    let environments = uow
+        .list_environments(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let env_slugs: HashMap<Uuid, String> = environments
+        .iter()
+        .map(|env| (env.id, env.slug.clone()))
+        .collect();
+
+    let segment_rows = uow
+        .list_segments(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let segment_keys: HashMap<Uuid, String> =
+        segment_rows.iter().map(|s| (s.id, s.key.clone())).collect();
+
+    let mut segments = Vec::with_capacity(segment_rows.len());
+    for segment in segment_rows {
+        let constraints = uow
+            .get_segment_constraints(segment.id)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+        segments.push(SegmentDocument {
+            key: segment.key,
+            name: segment.name,
+            description: segment.description,
+            match_type: segment.match_type,
+            constraints: constraints
+                .into_iter()
+                .map(|c| ConstraintDocument {
+                    attribute: c.attribute,
+                    operator: c.operator,
+                    values: c.values,
+                })
+                .collect(),
+        });
+    }
+    segments.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let flag_rows = uow
+        .list_all_flags(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let mut flags = Vec::with_capacity(flag_rows.len());
+    for flag in flag_rows {
+        let variants = uow
+            .get_flag_variants(flag.id)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+        let variant_keys: HashMap<Uuid, String> =
+            variants.iter().map(|v| (v.id, v.key.clone())).collect();
+
+        let flag_environments = uow
+            .list_flag_environments(flag.id)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+        let mut environments = BTreeMap::new();
+        for fe in flag_environments {
+            if let Some(slug) = env_slugs.get(&fe.environment_id) {
+                let snapshot =
+                    snapshot_flag_environment_in(uow, &fe, &variant_keys, &segment_keys).await?;
+                environments.insert(slug.clone(), snapshot);
+            }
+        }
+
+        flags.push(FlagDocument {
+            key: flag.key,
+            name: flag.name,
+            description: flag.description,
+            flag_type: flag.flag_type,
+            tags: flag.tags,
+            owner: flag.owner,
+            kind: flag.kind,
+            temporary: flag.temporary,
+            expires_at: flag.expires_at,
+            archived: flag.archived,
+            variants: variants
+                .into_iter()
+                .map(|v| VariantDocument {
+                    key: v.key,
+                    value: v.value,
+                    description: v.description,
+                })
+                .collect(),
+            environments,
+        });
+    }
+
+    Ok(ProjectDocument {
+        format_version: FORMAT_VERSION,
+        exported_at: Some(Utc::now()),
+        environments: environments
+            .into_iter()
+            .map(|env| EnvironmentDocument {
+                slug: env.slug,
+                name: env.name,
+                color: env.color,
+                requires_approval: env.requires_approval,
+            })
+            .collect(),
+        segments,
+        flags,
+    })
+}
+
+// ============================================================
+// Import planning
+// ============================================================
+
+/// What an import changes, with the flags as they will look afterwards.
+struct ImportPlan {
+    report: ImportReport,
+    /// Parallel to the document's flags.
+    targets: Vec<FlagDocument>,
+}
+
+/// `doc` laid over `current`: variants are upserted by key and never
+/// removed, and environments left out of `doc` keep their configuration.
+fn merge_flag(current: &FlagDocument, doc: &FlagDocument) -> FlagDocument {
+    let mut merged = doc.clone();
+    merged.variants = current.variants.clone();
+    for variant in &doc.variants {
+        match merged.variants.iter_mut().find(|v| v.key == variant.key) {
+            Some(existing) => *existing = variant.clone(),
+            None => merged.variants.push(variant.clone()),
+        }
+    }
+    merged.environments = current.environments.clone();
+    merged.environments.extend(doc.environments.clone());
+    merged
+}
+
+fn change<T: Serialize>(key: &str, before: Option<&T>, after: &T) -> ImportChange {
+    let mut changes = serde_json::Map::new();
+    let action = match before {
+        None => ImportAction::Create,
+        Some(before) => {
+            json_diff(
+                String::new(),
+                &serde_json::to_value(before).unwrap_or_default(),
+                &serde_json::to_value(after).unwrap_or_default(),
+                &mut changes,
+            );
+            if changes.is_empty() {
+                ImportAction::Unchanged
+            } else {
+                ImportAction::Update
+            }
+        }
+    };
+    ImportChange {
+        key: key.to_string(),
+        action,
+        changes,
+    }
+}
+
+/// Fail with 400 if `keys` repeats an entry.
+fn check_unique<'a>(what: &str, keys: impl Iterator<Item = &'a str>) -> Result<(), ApiError> {
+    let mut seen = HashSet::new();
+    for key in keys {
+        if !seen.insert(key) {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                &format!("Duplicate {what} '{key}'"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Every variant and segment a snapshot refers to.
+fn snapshot_refs(snapshot: &FlagEnvironmentSnapshot) -> (Vec<&str>, Vec<&str>) {
+    let mut variants: Vec<&str> = snapshot
+        .default_variant
+        .iter()
+        .map(String::as_str)
+        .collect();
+    let mut segments = Vec::new();
+    for rule in &snapshot.rules {
+        variants.extend(rule.variant.as_deref());
+        variants.extend(rule.distributions.iter().map(|d| d.variant.as_str()));
+        segments.extend(rule.segments.iter().map(|s| s.segment.as_str()));
+    }
+    variants.extend(snapshot.overrides.values().map(String::as_str));
+    (variants, segments)
+}
+
+/// Validate `doc` against the project's current state and work out what
+/// importing it changes.
+fn plan_import(
+    current: &ProjectDocument,
+    doc: &ProjectDocument,
+    mode: ImportMode,
+) -> Result<ImportPlan, ApiError> {
+    if doc.format_version != FORMAT_VERSION {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!("Unsupported format_version; expected {FORMAT_VERSION}"),
+        ));
+    }
+    check_unique(
+        "environment slug",
+        doc.environments.iter().map(|e| e.slug.as_str()),
+    )?;
+    check_unique("segment key", doc.segments.iter().map(|s| s.key.as_str()))?;
+    check_unique("flag key", doc.flags.iter().map(|f| f.key.as_str()))?;
+
+    let env_slugs: HashSet<&str> = current
+        .environments
+        .iter()
+        .chain(&doc.environments)
+        .map(|e| e.slug.as_str())
+        .collect();
+    let segment_keys: HashSet<&str> = current
+        .segments
+        .iter()
+        .chain(&doc.segments)
+        .map(|s| s.key.as_str())
+        .collect();
+
+    let environments = doc
+        .environments
+        .iter()
+        .map(|env| {
+            // Existing environments are matched by slug and keep their settings.
+            let action = if current.environments.iter().any(|e| e.slug == env.slug) {
+                ImportAction::Unchanged
+            } else {
+                ImportAction::Create
+            };
+            ImportChange {
+                key: env.slug.clone(),
+                action,
+                changes: serde_json::Map::new(),
+            }
+        })
+        .collect();
+
+    let mut segments = Vec::with_capacity(doc.segments.len());
+    for segment in &doc.segments {
+        if segment.match_type != "all" && segment.match_type != "any" {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                &format!(
+                    "Segment '{}': match_type must be 'all' or 'any'",
+                    segment.key
+                ),
+            ));
+        }
+        let before = current.segments.iter().find(|s| s.key == segment.key);
+        segments.push(change(&segment.key, before, segment));
+    }
+
+    let mut flags = Vec::with_capacity(doc.flags.len());
+    let mut targets = Vec::with_capacity(doc.flags.len());
+    for flag in &doc.flags {
+        validate_kind(&flag.kind)?;
+        if flag.variants.is_empty() {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                &format!("Flag '{}' must have at least one variant", flag.key),
+            ));
+        }
+        check_unique(
+            &format!("variant key in flag '{}':", flag.key),
+            flag.variants.iter().map(|v| v.key.as_str()),
+        )?;
+
+        let before = current.flags.iter().find(|f| f.key == flag.key);
+        let target = match before {
+            Some(before) if before.flag_type != flag.flag_type => {
+                return Err(err(
+                    StatusCode::BAD_REQUEST,
+                    &format!("Flag '{}': flag_type cannot be changed", flag.key),
+                ));
+            }
+            Some(before) => merge_flag(before, flag),
+            None => flag.clone(),
+        };
+
+        for (slug, snapshot) in &flag.environments {
+            if !env_slugs.contains(slug.as_str()) {
+                return Err(err(
+                    StatusCode::BAD_REQUEST,
+                    &format!("Flag '{}': unknown environment '{slug}'", flag.key),
+                ));
+            }
+            let (variants, segments) = snapshot_refs(snapshot);
+            if let Some(missing) = variants
+                .into_iter()
+                .find(|key| !target.variants.iter().any(|v| v.key == *key))
+            {
+                return Err(err(
+                    StatusCode::BAD_REQUEST,
+                    &format!(
+                        "Flag '{}' in '{slug}': unknown variant '{missing}'",
+                        flag.key
+                    ),
+                ));
+            }
+            if let Some(missing) = segments.into_iter().find(|key| !segment_keys.contains(key)) {
+                return Err(err(
+                    StatusCode::BAD_REQUEST,
+                    &format!(
+                        "Flag '{}' in '{slug}': unknown segment '{missing}'",
+                        flag.key
+                    ),
+                ));
+            }
+        }
+
+        flags.push(change(&flag.key, before, &target));
+        targets.push(target);
+    }
+
+    if mode == ImportMode::Create {
+        let existing: Vec<&str> = segments
+            .iter()
+            .chain(&flags)
+            .filter(|c| c.action != ImportAction::Create)
+            .map(|c| c.key.as_str())
+            .collect();
+        if !existing.is_empty() {
+            return Err(err(
+                StatusCode::CONFLICT,
+                &format!(
+                    "Already exists: {}; use mode=merge to update",
+                    existing.join(", ")
+                ),
+            ));
+        }
+    }
+
+    Ok(ImportPlan {
+        report: ImportReport {
+            mode,
+            applied: false,
+            environments,
+            segments,
+            flags,
+        },
+        targets,
+    })
+}
+
+// ============================================================
+// Handlers
+// ============================================================
+
+/// Export the whole project as a [`ProjectDocument`], in JSON or YAML.
+pub async fn export_project(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+    Query(query): Query<ExportQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    access
+        .require(&state, "project.export", Role::Viewer)
+        .await?;
+
+    // Read through one transaction, which is rolled back when dropped.
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let doc = build_document(&mut uow, project_id).await?;
+
+    match DocumentFormat::negotiate(query.format, &headers, header::ACCEPT) {
+        DocumentFormat::Json => Ok(Json(doc).into_response()),
+        DocumentFormat::Yaml => {
+            let yaml = serde_yaml::to_string(&doc)
+                .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+            Ok(([(header::CONTENT_TYPE, "application/yaml")], yaml).into_response())
+        }
+    }
+}
+
+/// Import a [`ProjectDocument`] given in JSON or YAML. `dry_run` (the
+/// default) only reports the changes; `create` and `merge` apply them in a
+/// single transaction, with the project locked from planning to commit.
+pub async fn import_project(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+    Query(query): Query<ImportQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<ImportReport>, ApiError> {
+    let role = match query.mode {
+        ImportMode::DryRun => Role::Viewer,
+        ImportMode::Create | ImportMode::Merge => Role::Admin,
+    };
+    access.require(&state, "project.import", role).await?;
+
+    let doc: ProjectDocument =
+        match DocumentFormat::negotiate(query.format, &headers, header::CONTENT_TYPE) {
+            DocumentFormat::Json => serde_json::from_slice(&body).map_err(|e| e.to_string()),
+            DocumentFormat::Yaml => serde_yaml::from_slice(&body).map_err(|e| e.to_string()),
+        }
+        .map_err(|e| err(StatusCode::BAD_REQUEST, &format!("Invalid document: {e}")))?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    if query.mode != ImportMode::DryRun {
+        uow.lock_project(project_id)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    }
+    let current = build_document(&mut uow, project_id).await?;
+    let ImportPlan {
+        mut report,
+        targets,
+    } = plan_import(&current, &doc, query.mode)?;
+    if query.mode == ImportMode::DryRun {
+        return Ok(Json(report));
+    }
+
+    // Flag changes in protected environments go through change requests.
+    let gated: Vec<String> = doc
+        .flags
+        .iter()
+        .filter_map(|flag| Some((flag, current.flags.iter().find(|f| f.key == flag.key)?)))
+        .flat_map(|(flag, before)| {
+            flag.environments
+                .iter()
+                .filter(move |(slug, snapshot)| before.environments.get(*slug) != Some(snapshot))
+                .map(move |(slug, _)| (flag.key.as_str(), slug.as_str()))
+        })
+        .filter(|(_, slug)| {
+            current
+                .environments
+                .iter()
+                .any(|e| e.slug == *slug && e.requires_approval)
+        })
+        .map(|(key, slug)| format!("{key}@{slug}"))
+        .collect();
+    if !gated.is_empty() {
+        return Err(err(
+            StatusCode::CONFLICT,
+            &format!(
+                "Environments require approval for these changes: {}",
+                gated.join(", ")
+            ),
+        ));
+    }
+
+    let failed = |e: anyhow::Error| err(StatusCode::BAD_REQUEST, &format!("Import failed: {e}"));
+    let mut env_ids: HashMap<String, Uuid> = uow
+        .list_environments(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .map(|env| (env.slug, env.id))
+        .collect();
+    for (env, change) in doc.environments.iter().zip(&report.environments) {
+        if change.action == ImportAction::Create {
+            let row = uow
+                .create_environment(
+                    project_id,
+                    &env.name,
+                    &env.slug,
+                    env.color.as_deref(),
+                    env.requires_approval,
+                )
+                .await
+                .map_err(failed)?;
+            env_ids.insert(row.slug, row.id);
+        }
+    }
+
+    let existing_segments: HashMap<String, SegmentRow> = uow
+        .list_segments(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .map(|s| (s.key.clone(), s))
+        .collect();
+    let mut segment_keys: HashMap<Uuid, String> = existing_segments
+        .values()
+        .map(|s| (s.id, s.key.clone()))
+        .collect();
+    for (segment, change) in doc.segments.iter().zip(&report.segments) {
+        let constraints: Vec<(String, String, Vec<String>)> = segment
+            .constraints
+            .iter()
+            .map(|c| (c.attribute.clone(), c.operator.clone(), c.values.clone()))
+            .collect();
+        match (change.action, existing_segments.get(&segment.key)) {
+            (ImportAction::Update, Some(row)) => {
+                uow.replace_segment(
+                    row,
+                    &segment.name,
+                    segment.description.as_deref(),
+                    &segment.match_type,
+                    &constraints,
+                )
+                .await
+                .map_err(failed)?;
+            }
+            (ImportAction::Create, _) => {
+                let (row, _) = uow
+                    .create_segment(
+                        project_id,
+                        &segment.key,
+                        &segment.name,
+                        segment.description.as_deref(),
+                        &segment.match_type,
+                        &constraints,
+                    )
+                    .await
+                    .map_err(failed)?;
+                segment_keys.insert(row.id, row.key);
+            }
+            _ => {}
+        }
+    }
+
+    let existing_flags: HashMap<String, FlagRow> = uow
+        .list_all_flags(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .map(|f| (f.key.clone(), f))
+        .collect();
+    let mut specs = Vec::new();
+    for ((flag, target), change) in doc.flags.iter().zip(&targets).zip(&report.flags) {
+        if change.action == ImportAction::Unchanged {
+            continue;
+        }
+        let before = current.flags.iter().find(|f| f.key == flag.key);
+
+        let (flag_id, variant_keys) = match existing_flags.get(&flag.key) {
+            Some(row) => {
+                let metadata = |f: &FlagDocument| FlagDocument {
+                    variants: Vec::new(),
+                    environments: BTreeMap::new(),
+                    ..f.clone()
+                };
+                if before.map(metadata) != Some(metadata(target)) {
+                    uow.replace_flag(
+                        row,
+                        &target.name,
+                        target.description.as_deref(),
+                        &target.tags,
+                        target.archived,
+                        target.owner.as_deref(),
+                        &target.kind,
+                        target.temporary,
+                        target.expires_at,
+                    )
+                    .await
+                    .map_err(failed)?;
+                }
+
+                let variants = uow
+                    .get_flag_variants(row.id)
+                    .await
+                    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+                let mut variant_keys: HashMap<Uuid, String> =
+                    variants.iter().map(|v| (v.id, v.key.clone())).collect();
+                let mut sort_order = variants.len() as i32;
+                for variant in &flag.variants {
+                    match variants.iter().find(|v| v.key == variant.key) {
+                        Some(existing)
+                            if existing.value != variant.value
+                                || existing.description != variant.description =>
+                        {
+                            uow.replace_flag_variant(
+                                existing.id,
+                                &variant.value,
+                                variant.description.as_deref(),
+                            )
+                            .await
+                            .map_err(failed)?;
+                        }
+                        Some(_) => {}
+                        None => {
+                            let created = uow
+                                .create_flag_variant(
+                                    row.id,
+                                    &variant.key,
+                                    &variant.value,
+                                    variant.description.as_deref(),
+                                    sort_order,
+                                )
+                                .await
+                                .map_err(failed)?;
+                            sort_order += 1;
+                            variant_keys.insert(created.id, created.key);
+                        }
+                    }
+                }
+                (row.id, variant_keys)
+            }
+            None => {
+                let row = uow
+                    .create_flag(
+                        project_id,
+                        &flag.key,
+                        &flag.name,
+                        flag.description.as_deref(),
+                        &flag.flag_type,
+                        &flag.tags,
+                        flag.owner.as_deref(),
+                        &flag.kind,
+                        flag.temporary,
+                        flag.expires_at,
+                    )
+                    .await
+                    .map_err(failed)?;
+                if flag.archived {
+                    uow.set_flag_archived(row.id, true).await.map_err(failed)?;
+                }
+
+                let mut variant_keys = HashMap::new();
+                let mut first_variant = None;
+                for (i, variant) in flag.variants.iter().enumerate() {
+                    let created = uow
+                        .create_flag_variant(
+                            row.id,
+                            &variant.key,
+                            &variant.value,
+                            variant.description.as_deref(),
+                            i as i32,
+                        )
+                        .await
+                        .map_err(failed)?;
+                    first_variant.get_or_insert(created.id);
+                    variant_keys.insert(created.id, created.key);
+                }
+                for &environment_id in env_ids.values() {
+                    uow.create_flag_environment(row.id, environment_id, false, first_variant)
+                        .await
+                        .map_err(failed)?;
+                }
+                (row.id, variant_keys)
+            }
+        };
+
+        let ctx = ProposalContext {
+            variant_keys,
+            segment_keys: segment_keys.clone(),
+            rule_ids: Vec::new(),
+        };
+        for (slug, snapshot) in &flag.environments {
+            if before.and_then(|b| b.environments.get(slug)) == Some(snapshot) {
+                continue;
+            }
+            let environment_id = env_ids[slug];
+            let fe = match uow
+                .get_flag_environment(flag_id, environment_id)
+                .await
+                .map_err(failed)?
+            {
+                Some(fe) => fe,
+                None => uow
+                    .create_flag_environment(flag_id, environment_id, false, None)
+                    .await
+                    .map_err(failed)?,
+            };
+            specs.push((fe.id, resolve_spec(snapshot, &ctx)?));
+        }
+    }
+    uow.write_flag_environments(&specs).await.map_err(failed)?;
+
+    report.applied = true;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "project_imported",
+        "project",
+        Some(project_id),
+        None,
+        serde_json::to_value(&report).ok().as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    Ok(Json(report))
+}
@@ -0,0 +1,372 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::api::routes::environments::load_environment;
+use crate::state::AppState;
+use crate::store::models::{EnvironmentPermissionRow, ProjectMemberRow};
+
+// ============================================================
+// Request/Response types
+// ============================================================
+
+#[derive(Debug, Deserialize)]
+pub struct SetMemberRoleRequest {
+    pub role: Role,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EnvironmentGrantInput {
+    pub user_id: Uuid,
+    pub role: Role,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateEnvironmentPermissionsRequest {
+    /// Restricted environments can only be changed by admins and users
+    /// granted a role for them.
+    pub restricted: bool,
+    #[serde(default)]
+    pub grants: Vec<EnvironmentGrantInput>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProjectMemberResponse {
+    pub user_id: String,
+    pub clerk_user_id: String,
+    pub email: Option<String>,
+    pub role: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EnvironmentGrantResponse {
+    pub user_id: String,
+    pub clerk_user_id: String,
+    pub email: Option<String>,
+    pub role: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EnvironmentPermissionsResponse {
+    pub environment_id: String,
+    pub restricted: bool,
+    pub grants: Vec<EnvironmentGrantResponse>,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+// ============================================================
+// Helpers
+// ============================================================
+
+fn to_member_response(m: ProjectMemberRow) -> ProjectMemberResponse {
+    ProjectMemberResponse {
+        user_id: m.user_id.to_string(),
+        clerk_user_id: m.external_id,
+        email: m.email,
+        role: m.role,
+        created_at: m.created_at.to_rfc3339(),
+        updated_at: m.updated_at.to_rfc3339(),
+    }
+}
+
+fn to_grant_response(g: EnvironmentPermissionRow) -> EnvironmentGrantResponse {
+    EnvironmentGrantResponse {
+        user_id: g.user_id.to_string(),
+        clerk_user_id: g.external_id,
+        email: g.email,
+        role: g.role,
+    }
+}
+
+/// Roles may only be given to members of the project's organization.
+async fn check_org_member(
+    state: &AppState,
+    project_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), ApiError> {
+    let project = state
+        .store
+        .get_project(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Project not found"))?;
+
+    let is_member = state
+        .store
+        .list_organization_members(project.organization_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .iter()
+        .any(|m| m.user_id == user_id);
+
+    if is_member {
+        Ok(())
+    } else {
+        Err(err(
+            StatusCode::BAD_REQUEST,
+            "User is not a member of the project's organization",
+        ))
+    }
+}
+
+/// Refuse changes that would leave the project without an owner.
+fn check_keeps_owner(
+    members: &[ProjectMemberRow],
+    user_id: Uuid,
+    new_role: Option<Role>,
+) -> Result<(), ApiError> {
+    let owner = Role::Owner.as_str();
+    let is_owner = members
+        .iter()
+        .any(|m| m.user_id == user_id && m.role == owner);
+    let owners = members.iter().filter(|m| m.role == owner).count();
+
+    if is_owner && owners == 1 && new_role != Some(Role::Owner) {
+        return Err(err(
+            StatusCode::CONFLICT,
+            "A project must keep at least one owner",
+        ));
+    }
+    Ok(())
+}
+
+// ============================================================
+// Handlers
+// ============================================================
+
+/// List members with an explicit project role. Organization members not
+/// listed here are viewers.
+pub async fn list_members(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<Vec<ProjectMemberResponse>>, ApiError> {
+    access.require(&state, "member.read", Role::Viewer).await?;
+
+    let members = state
+        .store
+        .list_project_members(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(members.into_iter().map(to_member_response).collect()))
+}
+
+pub async fn set_member_role(
+    State(state): State<AppState>,
+    Path((project_id, user_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<SetMemberRoleRequest>,
+) -> Result<Json<ProjectMemberResponse>, ApiError> {
+    access.require(&state, "member.update", Role::Admin).await?;
+    check_org_member(&state, project_id, user_id).await?;
+
+    let members = state
+        .store
+        .list_project_members(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let before = members.iter().find(|m| m.user_id == user_id);
+
+    // Only owners can create owners or change an owner's role.
+    let touches_owner =
+        req.role == Role::Owner || before.is_some_and(|m| m.role == Role::Owner.as_str());
+    if touches_owner {
+        access.require(&state, "member.update", Role::Owner).await?;
+    }
+    check_keeps_owner(&members, user_id, Some(req.role))?;
+
+    let before = before.map(|m| serde_json::json!({ "role": m.role }));
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let member = uow
+        .set_project_member_role(project_id, user_id, req.role.as_str())
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "member_role_set",
+        "user",
+        Some(user_id),
+        before.as_ref(),
+        Some(&serde_json::json!({ "role": member.role })),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(to_member_response(member)))
+}
+
+pub async fn remove_member(
+    State(state): State<AppState>,
+    Path((project_id, user_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<StatusCode, ApiError> {
+    access.require(&state, "member.delete", Role::Admin).await?;
+
+    let members = state
+        .store
+        .list_project_members(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let member = members
+        .iter()
+        .find(|m| m.user_id == user_id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Member not found"))?;
+
+    if member.role == Role::Owner.as_str() {
+        access.require(&state, "member.delete", Role::Owner).await?;
+    }
+    check_keeps_owner(&members, user_id, None)?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.remove_project_member(project_id, user_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "member_removed",
+        "user",
+        Some(user_id),
+        Some(&serde_json::json!({ "role": member.role })),
+        None,
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_environment_permissions(
+    State(state): State<AppState>,
+    Path((project_id, environment_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<EnvironmentPermissionsResponse>, ApiError> {
+    access
+        .require(&state, "environment.read", Role::Viewer)
+        .await?;
+
+    let env = load_environment(&state, project_id, environment_id).await?;
+    let grants = state
+        .store
+        .list_environment_permissions(env.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(EnvironmentPermissionsResponse {
+        environment_id: env.id.to_string(),
+        restricted: env.restricted,
+        grants: grants.into_iter().map(to_grant_response).collect(),
+    }))
+}
+
+/// Replace an environment's restriction flag and role grants.
+pub async fn update_environment_permissions(
+    State(state): State<AppState>,
+    Path((project_id, environment_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<UpdateEnvironmentPermissionsRequest>,
+) -> Result<Json<EnvironmentPermissionsResponse>, ApiError> {
+    access
+        .require_env(
+            &state,
+            "environment.permissions",
+            environment_id,
+            Role::Admin,
+        )
+        .await?;
+    let env = load_environment(&state, project_id, environment_id).await?;
+
+    let mut grants: Vec<(Uuid, String)> = Vec::with_capacity(req.grants.len());
+    for grant in &req.grants {
+        if grant.role > access.role {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                "Cannot grant a role above your own",
+            ));
+        }
+        if grants.iter().any(|(id, _)| *id == grant.user_id) {
+            return Err(err(StatusCode::BAD_REQUEST, "Duplicate user in grants"));
+        }
+        check_org_member(&state, project_id, grant.user_id).await?;
+        grants.push((grant.user_id, grant.role.as_str().to_string()));
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let before = uow
+        .list_environment_permissions(env.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let updated = uow
+        .replace_environment_permissions(env.id, req.restricted, &grants)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let after = uow
+        .list_environment_permissions(env.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let snapshot = |restricted: bool, grants: &[EnvironmentPermissionRow]| {
+        serde_json::json!({
+            "restricted": restricted,
+            "grants": grants
+                .iter()
+                .map(|g| serde_json::json!({ "user_id": g.user_id, "role": g.role }))
+                .collect::<Vec<_>>(),
+        })
+    };
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(env.id),
+        "environment_permissions_updated",
+        "environment",
+        Some(env.id),
+        Some(&snapshot(env.restricted, &before)),
+        Some(&snapshot(updated.restricted, &after)),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(EnvironmentPermissionsResponse {
+        environment_id: updated.id.to_string(),
+        restricted: updated.restricted,
+        grants: after.into_iter().map(to_grant_response).collect(),
+    }))
+}
@@ -0,0 +1,95 @@
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use serde::Deserialize;
+
+use crate::api::middleware::auth::AuthInfo;
+use crate::state::AppState;
+
+/// Upper bound on events per request.
+const MAX_EVENTS: usize = 1000;
+
+/// One observation of an event. Guardrails compare the mean `value` per
+/// variant, so report every occurrence of the thing being measured, not
+/// only the bad ones: for an error rate, send each request with value 1 if
+/// it failed and 0 if it succeeded, so the mean is the rate.
+#[derive(Debug, Deserialize)]
+pub struct MetricEventInput {
+    pub flag_key: String,
+    /// The variant the service was served for this flag.
+    pub variant_key: String,
+    pub event_key: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReportMetricsRequest {
+    pub events: Vec<MetricEventInput>,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+/// Record metric events for the SDK key's environment. Guarded rollouts
+/// compare them between variants, and can halt a rollout on them, so only
+/// server keys may report; client keys ship in browsers and apps where
+/// anyone can read them.
+pub async fn report_metrics(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthInfo>,
+    Json(req): Json<ReportMetricsRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), ApiError> {
+    let AuthInfo::SdkKey {
+        environment_id,
+        key_type,
+        ..
+    } = auth
+    else {
+        return Err(err(
+            StatusCode::FORBIDDEN,
+            "Reporting metrics requires a server SDK key",
+        ));
+    };
+    if key_type != "server" {
+        return Err(err(
+            StatusCode::FORBIDDEN,
+            "Reporting metrics requires a server SDK key",
+        ));
+    }
+
+    if req.events.len() > MAX_EVENTS {
+        return Err(err(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            &format!("At most {MAX_EVENTS} events per request"),
+        ));
+    }
+
+    let mut events = Vec::with_capacity(req.events.len());
+    for e in req.events {
+        if e.flag_key.is_empty() || e.variant_key.is_empty() || e.event_key.is_empty() {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                "flag_key, variant_key and event_key are required",
+            ));
+        }
+        if !e.value.is_finite() {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                "value must be a finite number",
+            ));
+        }
+        events.push((e.flag_key, e.variant_key, e.event_key, e.value));
+    }
+
+    let accepted = state
+        .store
+        .insert_metric_events(environment_id, &events)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "accepted": accepted })),
+    ))
+}
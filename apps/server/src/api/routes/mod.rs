@@ -1,10 +1,24 @@
 pub mod audit_log;
+pub mod change_requests;
+pub mod code_refs;
+pub mod config_versions;
 pub mod environments;
 pub mod evaluate;
 pub mod flags;
 pub mod health;
+pub mod import_export;
+pub mod members;
+pub mod metrics;
+pub mod organizations;
+pub mod overrides;
 pub mod projects;
+pub mod promote;
+pub mod rollouts;
+pub mod rules;
+pub mod schedules;
 pub mod sdk_keys;
 pub mod segments;
 pub mod setup;
 pub mod stream;
+pub mod variants;
+pub mod webhooks;
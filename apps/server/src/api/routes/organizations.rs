@@ -0,0 +1,417 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::middleware::authz::{CurrentUser, ProjectAccess, Role};
+use crate::api::middleware::request_context::RequestContext;
+use crate::api::routes::projects::{to_project_response, ProjectResponse};
+use crate::state::AppState;
+use crate::store::models::{OrganizationMemberRow, OrganizationRow};
+
+// ============================================================
+// Request/Response types
+// ============================================================
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOrganizationRequest {
+    pub name: String,
+    pub slug: String,
+    /// Clerk organization to link. Must be the caller's active organization.
+    pub clerk_org_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateOrganizationRequest {
+    pub name: Option<String>,
+    pub slug: Option<String>,
+    pub clerk_org_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddMemberRequest {
+    /// Clerk user ID (the JWT `sub` claim).
+    pub user_id: String,
+    pub email: Option<String>,
+    /// Role in the organization; defaults to viewer.
+    pub role: Option<Role>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteOrganizationQuery {
+    /// Delete the organization even if it still has projects.
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrganizationResponse {
+    pub id: String,
+    pub name: String,
+    pub slug: String,
+    pub clerk_org_id: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemberResponse {
+    pub user_id: String,
+    pub clerk_user_id: String,
+    pub email: Option<String>,
+    pub role: String,
+    pub created_at: String,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+// ============================================================
+// Helpers
+// ============================================================
+
+pub(crate) async fn load_organization(
+    state: &AppState,
+    org_id: Uuid,
+) -> Result<OrganizationRow, ApiError> {
+    state
+        .store
+        .get_organization(org_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Organization not found"))
+}
+
+/// Refuse unless the caller holds at least `required` in the organization,
+/// and return their role.
+pub(crate) async fn require_org_role(
+    state: &AppState,
+    org_id: Uuid,
+    user: &CurrentUser,
+    action: &str,
+    required: Role,
+) -> Result<Role, ApiError> {
+    let role = state
+        .store
+        .get_organization_role(org_id, user.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .as_deref()
+        .and_then(Role::parse)
+        .unwrap_or(Role::Viewer);
+
+    if role >= required {
+        return Ok(role);
+    }
+    Err((
+        StatusCode::FORBIDDEN,
+        Json(serde_json::json!({
+            "error": format!("{action} requires the {} role", required.as_str()),
+            "code": "forbidden",
+            "details": { "action": action, "required_role": required, "role": role },
+        })),
+    ))
+}
+
+/// Only the caller's active Clerk organization may be linked, so nobody can
+/// claim the members of an organization they are not in.
+fn check_clerk_org(user: &CurrentUser, clerk_org_id: Option<&str>) -> Result<(), ApiError> {
+    match clerk_org_id {
+        Some(id) if user.clerk_org_id.as_deref() != Some(id) => Err(err(
+            StatusCode::FORBIDDEN,
+            "clerk_org_id must match the organization of the current session",
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn to_response(o: OrganizationRow) -> OrganizationResponse {
+    OrganizationResponse {
+        id: o.id.to_string(),
+        name: o.name,
+        slug: o.slug,
+        clerk_org_id: o.external_id,
+        created_at: o.created_at.to_rfc3339(),
+        updated_at: o.updated_at.to_rfc3339(),
+    }
+}
+
+fn to_member_response(m: OrganizationMemberRow) -> MemberResponse {
+    MemberResponse {
+        user_id: m.user_id.to_string(),
+        clerk_user_id: m.external_id,
+        email: m.email,
+        role: m.role,
+        created_at: m.created_at.to_rfc3339(),
+    }
+}
+
+// ============================================================
+// Handlers
+// ============================================================
+
+/// List the organizations the caller belongs to.
+pub async fn list_organizations(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+) -> Result<Json<Vec<OrganizationResponse>>, ApiError> {
+    let orgs = state
+        .store
+        .list_organizations_for_user(user.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(orgs.into_iter().map(to_response).collect()))
+}
+
+/// Create an organization. The caller becomes its first member and owner.
+pub async fn create_organization(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Extension(request): Extension<RequestContext>,
+    Json(req): Json<CreateOrganizationRequest>,
+) -> Result<(StatusCode, Json<OrganizationResponse>), ApiError> {
+    if req.name.trim().is_empty() || req.slug.trim().is_empty() {
+        return Err(err(StatusCode::BAD_REQUEST, "name and slug are required"));
+    }
+    check_clerk_org(&user, req.clerk_org_id.as_deref())?;
+
+    let org = state
+        .store
+        .create_organization(&req.name, &req.slug, req.clerk_org_id.as_deref())
+        .await
+        .map_err(|e| err(StatusCode::CONFLICT, &e.to_string()))?;
+
+    state
+        .store
+        .add_organization_member(
+            org.id,
+            &user.external_id,
+            None,
+            Role::Owner.as_str(),
+            &user.audit_context(&request),
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok((StatusCode::CREATED, Json(to_response(org))))
+}
+
+pub async fn get_organization(
+    State(state): State<AppState>,
+    Path(org_id): Path<Uuid>,
+) -> Result<Json<OrganizationResponse>, ApiError> {
+    let org = load_organization(&state, org_id).await?;
+    Ok(Json(to_response(org)))
+}
+
+/// Update an organization. Linking a Clerk organization makes all of its
+/// members members here, so it needs the owner role.
+pub async fn update_organization(
+    State(state): State<AppState>,
+    Path(org_id): Path<Uuid>,
+    Extension(user): Extension<CurrentUser>,
+    Json(req): Json<UpdateOrganizationRequest>,
+) -> Result<Json<OrganizationResponse>, ApiError> {
+    load_organization(&state, org_id).await?;
+    let required = if req.clerk_org_id.is_some() {
+        Role::Owner
+    } else {
+        Role::Admin
+    };
+    require_org_role(&state, org_id, &user, "organization.update", required).await?;
+    check_clerk_org(&user, req.clerk_org_id.as_deref())?;
+
+    let org = state
+        .store
+        .update_organization(
+            org_id,
+            req.name.as_deref(),
+            req.slug.as_deref(),
+            req.clerk_org_id.as_deref(),
+        )
+        .await
+        .map_err(|e| err(StatusCode::CONFLICT, &e.to_string()))?;
+
+    Ok(Json(to_response(org)))
+}
+
+/// Delete an organization, which needs the owner role in it. With
+/// `?force=true` its projects go too, which needs the owner role on every
+/// one of them.
+pub async fn delete_organization(
+    State(state): State<AppState>,
+    Path(org_id): Path<Uuid>,
+    Extension(user): Extension<CurrentUser>,
+    Extension(request): Extension<RequestContext>,
+    Query(query): Query<DeleteOrganizationQuery>,
+) -> Result<StatusCode, ApiError> {
+    load_organization(&state, org_id).await?;
+    require_org_role(&state, org_id, &user, "organization.delete", Role::Owner).await?;
+
+    let projects = state
+        .store
+        .list_projects_for_organization(org_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    if !projects.is_empty() && !query.force {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": "Organization still has projects; delete them or pass ?force=true",
+                "projects": projects.len(),
+            })),
+        ));
+    }
+
+    for project in &projects {
+        ProjectAccess::for_user(&state, project.id, &user, request.clone())
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+            .require(&state, "organization.delete", Role::Owner)
+            .await?;
+    }
+
+    // Cached configs outlive the cascade, so drop them explicitly.
+    let mut environment_ids = Vec::new();
+    for project in &projects {
+        let envs = state
+            .store
+            .list_environments(project.id)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+        environment_ids.extend(envs.into_iter().map(|e| e.id));
+    }
+
+    state
+        .store
+        .delete_organization(org_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    if let Some(ref redis) = state.redis {
+        for env_id in environment_ids {
+            let _ = redis.invalidate_config(env_id).await;
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_organization_projects(
+    State(state): State<AppState>,
+    Path(org_id): Path<Uuid>,
+) -> Result<Json<Vec<ProjectResponse>>, ApiError> {
+    load_organization(&state, org_id).await?;
+
+    let projects = state
+        .store
+        .list_projects_for_organization(org_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(
+        projects.into_iter().map(to_project_response).collect(),
+    ))
+}
+
+pub async fn list_members(
+    State(state): State<AppState>,
+    Path(org_id): Path<Uuid>,
+    Extension(user): Extension<CurrentUser>,
+) -> Result<Json<Vec<MemberResponse>>, ApiError> {
+    load_organization(&state, org_id).await?;
+    require_org_role(&state, org_id, &user, "member.read", Role::Admin).await?;
+
+    let members = state
+        .store
+        .list_organization_members(org_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(members.into_iter().map(to_member_response).collect()))
+}
+
+/// Add a user by Clerk user ID. They need not have signed in yet.
+pub async fn add_member(
+    State(state): State<AppState>,
+    Path(org_id): Path<Uuid>,
+    Extension(user): Extension<CurrentUser>,
+    Extension(request): Extension<RequestContext>,
+    Json(req): Json<AddMemberRequest>,
+) -> Result<(StatusCode, Json<MemberResponse>), ApiError> {
+    load_organization(&state, org_id).await?;
+    let caller_role = require_org_role(&state, org_id, &user, "member.create", Role::Admin).await?;
+    if req.user_id.trim().is_empty() {
+        return Err(err(StatusCode::BAD_REQUEST, "user_id is required"));
+    }
+    let role = req.role.unwrap_or(Role::Viewer);
+    if role > caller_role {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "Cannot grant a role above your own",
+        ));
+    }
+
+    let member = state
+        .store
+        .add_organization_member(
+            org_id,
+            &req.user_id,
+            req.email.as_deref(),
+            role.as_str(),
+            &user.audit_context(&request),
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok((StatusCode::CREATED, Json(to_member_response(member))))
+}
+
+/// Remove a member. Members who still carry the organization's linked Clerk
+/// `org_id` claim regain access on their next request, so remove them in
+/// Clerk as well. Only owners can remove an owner.
+pub async fn remove_member(
+    State(state): State<AppState>,
+    Path((org_id, user_id)): Path<(Uuid, Uuid)>,
+    Extension(user): Extension<CurrentUser>,
+    Extension(request): Extension<RequestContext>,
+) -> Result<StatusCode, ApiError> {
+    load_organization(&state, org_id).await?;
+    require_org_role(&state, org_id, &user, "member.delete", Role::Admin).await?;
+
+    let members = state
+        .store
+        .list_organization_members(org_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let member = members
+        .iter()
+        .find(|m| m.user_id == user_id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Member not found"))?;
+
+    let owner = Role::Owner.as_str();
+    if member.role == owner {
+        require_org_role(&state, org_id, &user, "member.delete", Role::Owner).await?;
+        if members.iter().filter(|m| m.role == owner).count() == 1 {
+            return Err(err(
+                StatusCode::CONFLICT,
+                "An organization must keep at least one owner",
+            ));
+        }
+    }
+
+    state
+        .store
+        .remove_organization_member(org_id, user_id, &user.audit_context(&request))
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,537 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use axum::{
+    body::Bytes,
+    extract::{Extension, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::api::routes::change_requests::propose_if_required;
+use crate::api::routes::flags::notify_environment_changes;
+use crate::api::routes::rules::load_flag_environment;
+use crate::state::AppState;
+use crate::store::models::{FlagOverrideRow, FlagVariantRow};
+use crate::store::postgres::UnitOfWork;
+
+/// Upper bound on a single bulk upload.
+const MAX_BULK_OVERRIDES: usize = 10_000;
+
+// ============================================================
+// Request/Response types
+// ============================================================
+
+/// A single override. The variant may be given by key or by ID.
+#[derive(Debug, Deserialize)]
+pub struct OverrideInput {
+    pub targeting_key: String,
+    pub variant_key: Option<String>,
+    pub variant_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OverrideResponse {
+    pub id: String,
+    pub targeting_key: String,
+    pub variant_id: String,
+    pub variant_key: String,
+    pub created_at: String,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+// ============================================================
+// Helpers
+// ============================================================
+
+fn resolve_variant(variants: &[FlagVariantRow], input: &OverrideInput) -> Result<Uuid, ApiError> {
+    let found = match (&input.variant_id, &input.variant_key) {
+        (Some(id), _) => variants.iter().find(|v| v.id == *id),
+        // A key always wins; a UUID that names no key is taken as an ID.
+        (None, Some(key)) => variants.iter().find(|v| v.key == *key).or_else(|| {
+            let id = key.parse::<Uuid>().ok()?;
+            variants.iter().find(|v| v.id == id)
+        }),
+        (None, None) => {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                &format!("Override for {} needs variant_key or variant_id", input.targeting_key),
+            ))
+        }
+    };
+
+    found.map(|v| v.id).ok_or_else(|| {
+        err(
+            StatusCode::BAD_REQUEST,
+            &format!("Unknown variant for override {}", input.targeting_key),
+        )
+    })
+}
+
+/// `(targeting_key, variant_id)` for each distinct key, in first-seen order.
+/// The last entry wins when the same key appears twice.
+fn resolve_overrides(
+    variants: &[FlagVariantRow],
+    inputs: &[OverrideInput],
+) -> Result<Vec<(String, Uuid)>, ApiError> {
+    let mut overrides: Vec<(String, Uuid)> = Vec::with_capacity(inputs.len());
+    let mut positions: HashMap<&str, usize> = HashMap::with_capacity(inputs.len());
+    for input in inputs {
+        if input.targeting_key.trim().is_empty() {
+            return Err(err(StatusCode::BAD_REQUEST, "targeting_key is required"));
+        }
+        let variant_id = resolve_variant(variants, input)?;
+        match positions.get(input.targeting_key.as_str()) {
+            Some(&i) => overrides[i].1 = variant_id,
+            None => {
+                positions.insert(&input.targeting_key, overrides.len());
+                overrides.push((input.targeting_key.clone(), variant_id));
+            }
+        }
+    }
+    Ok(overrides)
+}
+
+/// Parse `targeting_key,variant` rows (RFC 4180 quoting). The second column
+/// is a variant key or ID; a leading `targeting_key,...` header and `#`
+/// comments are skipped.
+fn parse_csv(body: &str) -> Result<Vec<OverrideInput>, ApiError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(body.as_bytes());
+
+    let mut inputs = Vec::new();
+    for record in reader.records() {
+        let record =
+            record.map_err(|e| err(StatusCode::BAD_REQUEST, &format!("Invalid CSV: {e}")))?;
+        let line = record.position().map_or(0, |p| p.line());
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+        if record.len() != 2 || record[0].is_empty() || record[1].is_empty() {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                &format!("CSV line {line}: expected targeting_key,variant"),
+            ));
+        }
+        if inputs.is_empty() && record[0].eq_ignore_ascii_case("targeting_key") {
+            continue;
+        }
+
+        inputs.push(OverrideInput {
+            targeting_key: record[0].to_string(),
+            variant_key: Some(record[1].to_string()),
+            variant_id: None,
+        });
+    }
+
+    Ok(inputs)
+}
+
+fn to_response(o: FlagOverrideRow, variants: &[FlagVariantRow]) -> OverrideResponse {
+    OverrideResponse {
+        id: o.id.to_string(),
+        targeting_key: o.targeting_key,
+        variant_id: o.variant_id.to_string(),
+        variant_key: variants
+            .iter()
+            .find(|v| v.id == o.variant_id)
+            .map(|v| v.key.clone())
+            .unwrap_or_default(),
+        created_at: o.created_at.to_rfc3339(),
+    }
+}
+
+/// `targeting_key → variant_key` for the given overrides, as recorded in the
+/// audit log.
+fn audit_overrides<'a>(
+    overrides: impl IntoIterator<Item = &'a FlagOverrideRow>,
+    variants: &[FlagVariantRow],
+) -> serde_json::Value {
+    let map: BTreeMap<&str, &str> = overrides
+        .into_iter()
+        .map(|o| {
+            let variant = variants
+                .iter()
+                .find(|v| v.id == o.variant_id)
+                .map_or("", |v| v.key.as_str());
+            (o.targeting_key.as_str(), variant)
+        })
+        .collect();
+    serde_json::json!(map)
+}
+
+/// The current overrides for `keys`, before they are replaced.
+async fn existing_overrides(
+    uow: &mut UnitOfWork,
+    flag_environment_id: Uuid,
+    keys: &[&str],
+) -> Result<Vec<FlagOverrideRow>, ApiError> {
+    let rows = uow
+        .get_flag_overrides(flag_environment_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let keys: HashSet<&str> = keys.iter().copied().collect();
+    Ok(rows
+        .into_iter()
+        .filter(|o| keys.contains(o.targeting_key.as_str()))
+        .collect())
+}
+
+// ============================================================
+// Handlers
+// ============================================================
+
+pub async fn list_overrides(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, environment_id)): Path<(Uuid, String, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<Vec<OverrideResponse>>, ApiError> {
+    access.require(&state, "override.read", Role::Viewer).await?;
+
+    let (flag, fe) = load_flag_environment(&state, project_id, &flag_key, environment_id).await?;
+
+    let variants = state
+        .store
+        .get_flag_variants(flag.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let overrides = state
+        .store
+        .get_flag_overrides(fe.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(
+        overrides
+            .into_iter()
+            .map(|o| to_response(o, &variants))
+            .collect(),
+    ))
+}
+
+pub async fn add_override(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, environment_id)): Path<(Uuid, String, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<OverrideInput>,
+) -> Result<Response, ApiError> {
+    access
+        .require_env(&state, "override.update", environment_id, Role::Editor)
+        .await?;
+
+    let (flag, fe) = load_flag_environment(&state, project_id, &flag_key, environment_id).await?;
+
+    if req.targeting_key.trim().is_empty() {
+        return Err(err(StatusCode::BAD_REQUEST, "targeting_key is required"));
+    }
+
+    let variants = state
+        .store
+        .get_flag_variants(flag.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let variant_id = resolve_variant(&variants, &req)?;
+
+    let proposal = propose_if_required(&state, &access, &flag, &fe, "override.update", |s, ctx| {
+        s.overrides
+            .insert(req.targeting_key.clone(), ctx.variant(variant_id));
+        Ok(())
+    })
+    .await?;
+    if let Some(response) = proposal {
+        return Ok(response);
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let before = existing_overrides(&mut uow, fe.id, &[req.targeting_key.as_str()]).await?;
+
+    let row = uow
+        .upsert_flag_overrides(fe.id, &[(req.targeting_key.clone(), variant_id)])
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .remove(0);
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(environment_id),
+        "override_set",
+        "flag_override",
+        Some(row.id),
+        Some(&audit_overrides(&before, &variants)),
+        Some(&audit_overrides([&row], &variants)),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    Ok((StatusCode::CREATED, Json(to_response(row, &variants))).into_response())
+}
+
+/// Bulk upsert overrides from a JSON array or a `text/csv` body.
+pub async fn bulk_upsert_overrides(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, environment_id)): Path<(Uuid, String, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    access
+        .require_env(&state, "override.update", environment_id, Role::Editor)
+        .await?;
+
+    let (flag, fe) = load_flag_environment(&state, project_id, &flag_key, environment_id).await?;
+
+    let is_csv = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/csv"));
+
+    let inputs: Vec<OverrideInput> = if is_csv {
+        let text = std::str::from_utf8(&body)
+            .map_err(|_| err(StatusCode::BAD_REQUEST, "CSV body must be UTF-8"))?;
+        parse_csv(text)?
+    } else {
+        serde_json::from_slice(&body)
+            .map_err(|e| err(StatusCode::BAD_REQUEST, &format!("Invalid JSON: {e}")))?
+    };
+
+    if inputs.len() > MAX_BULK_OVERRIDES {
+        return Err(err(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            &format!("At most {MAX_BULK_OVERRIDES} overrides per request"),
+        ));
+    }
+
+    let variants = state
+        .store
+        .get_flag_variants(flag.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let overrides = resolve_overrides(&variants, &inputs)?;
+
+    let proposal = propose_if_required(
+        &state,
+        &access,
+        &flag,
+        &fe,
+        "override.bulk_update",
+        |s, ctx| {
+            for (key, variant_id) in &overrides {
+                s.overrides.insert(key.clone(), ctx.variant(*variant_id));
+            }
+            Ok(())
+        },
+    )
+    .await?;
+    if let Some(response) = proposal {
+        return Ok(response);
+    }
+
+    let keys: Vec<&str> = overrides.iter().map(|(k, _)| k.as_str()).collect();
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let before = existing_overrides(&mut uow, fe.id, &keys).await?;
+
+    let rows = uow
+        .upsert_flag_overrides(fe.id, &overrides)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(environment_id),
+        "overrides_bulk_upserted",
+        "flag_environment",
+        Some(fe.id),
+        Some(&audit_overrides(&before, &variants)),
+        Some(&audit_overrides(&rows, &variants)),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    Ok(Json(
+        rows.into_iter()
+            .map(|o| to_response(o, &variants))
+            .collect::<Vec<_>>(),
+    )
+    .into_response())
+}
+
+pub async fn remove_override(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, environment_id, targeting_key)): Path<(Uuid, String, Uuid, String)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Response, ApiError> {
+    access
+        .require_env(&state, "override.delete", environment_id, Role::Editor)
+        .await?;
+
+    let (flag, fe) = load_flag_environment(&state, project_id, &flag_key, environment_id).await?;
+
+    let proposal = propose_if_required(&state, &access, &flag, &fe, "override.delete", |s, _| {
+        s.overrides
+            .remove(&targeting_key)
+            .map(|_| ())
+            .ok_or_else(|| err(StatusCode::NOT_FOUND, "Override not found"))
+    })
+    .await?;
+    if let Some(response) = proposal {
+        return Ok(response);
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let removed = uow
+        .delete_flag_override(fe.id, &targeting_key)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Override not found"))?;
+
+    let variants = uow
+        .get_flag_variants(flag.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(environment_id),
+        "override_removed",
+        "flag_override",
+        Some(removed.id),
+        Some(&audit_overrides([&removed], &variants)),
+        None,
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(key: &str) -> FlagVariantRow {
+        FlagVariantRow {
+            id: Uuid::new_v4(),
+            flag_id: Uuid::nil(),
+            key: key.to_string(),
+            value: serde_json::json!(key),
+            description: None,
+            sort_order: 0,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    fn pairs(inputs: &[OverrideInput]) -> Vec<(&str, &str)> {
+        inputs
+            .iter()
+            .map(|i| (i.targeting_key.as_str(), i.variant_key.as_deref().unwrap_or("")))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_csv_skips_header_and_comments() {
+        let inputs = parse_csv("targeting_key,variant\n# beta users\nuser-1,on\n").unwrap();
+        assert_eq!(pairs(&inputs), vec![("user-1", "on")]);
+    }
+
+    #[test]
+    fn test_parse_csv_header_only_first_row() {
+        let inputs = parse_csv("user-1,on\ntargeting_key,off\n").unwrap();
+        assert_eq!(pairs(&inputs), vec![("user-1", "on"), ("targeting_key", "off")]);
+    }
+
+    #[test]
+    fn test_parse_csv_quoted_fields() {
+        let inputs = parse_csv("\"acme, inc\",on\n\"say \"\"hi\"\"\", off \n").unwrap();
+        assert_eq!(pairs(&inputs), vec![("acme, inc", "on"), ("say \"hi\"", "off")]);
+    }
+
+    #[test]
+    fn test_parse_csv_blank_lines() {
+        let inputs = parse_csv("\nuser-1,on\n\n  \nuser-2,off\n").unwrap();
+        assert_eq!(pairs(&inputs), vec![("user-1", "on"), ("user-2", "off")]);
+    }
+
+    #[test]
+    fn test_parse_csv_rejects_malformed_rows() {
+        assert!(parse_csv("user-1\n").is_err());
+        assert!(parse_csv("user-1,on,extra\n").is_err());
+        assert!(parse_csv("user-1,\n").is_err());
+        assert!(parse_csv(",on\n").is_err());
+    }
+
+    #[test]
+    fn test_resolve_overrides_last_duplicate_wins() {
+        let variants = vec![variant("on"), variant("off")];
+        let inputs = parse_csv("user-1,on\nuser-2,on\nuser-1,off\n").unwrap();
+        let overrides = resolve_overrides(&variants, &inputs).unwrap();
+        assert_eq!(
+            overrides,
+            vec![
+                ("user-1".to_string(), variants[1].id),
+                ("user-2".to_string(), variants[0].id),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_overrides_unknown_variant() {
+        let variants = vec![variant("on")];
+        let inputs = parse_csv("user-1,missing\n").unwrap();
+        let (status, _) = resolve_overrides(&variants, &inputs).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_resolve_overrides_prefers_key_over_id() {
+        let mut variants = vec![variant("on"), variant("off")];
+        // A variant whose key looks like the other variant's ID.
+        variants[0].key = variants[1].id.to_string();
+        let inputs = parse_csv(&format!("user-1,{}\n", variants[1].id)).unwrap();
+        let overrides = resolve_overrides(&variants, &inputs).unwrap();
+        assert_eq!(overrides[0].1, variants[0].id);
+    }
+}
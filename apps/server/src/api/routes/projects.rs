@@ -1,12 +1,17 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Extension, Path, State},
     http::StatusCode,
     Json,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::api::middleware::authz::{CurrentUser, ProjectAccess, Role};
+use crate::api::middleware::request_context::RequestContext;
+use crate::api::routes::organizations::{load_organization, require_org_role};
+use crate::api::routes::setup::{provision_default_environments, EnvironmentInfo};
 use crate::state::AppState;
+use crate::store::models::ProjectRow;
 
 #[derive(Debug, Serialize)]
 pub struct ProjectResponse {
@@ -19,33 +24,68 @@ pub struct ProjectResponse {
     pub updated_at: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CreateProjectRequest {
+    pub organization_id: Uuid,
+    pub name: String,
+    pub slug: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateProjectRequest {
+    pub name: Option<String>,
+    pub slug: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Returned once on creation — this is the only time the SDK keys are shown.
+#[derive(Debug, Serialize)]
+pub struct CreateProjectResponse {
+    #[serde(flatten)]
+    pub project: ProjectResponse,
+    pub environments: Vec<EnvironmentInfo>,
+}
+
 type ApiError = (StatusCode, Json<serde_json::Value>);
 
 fn err(status: StatusCode, msg: &str) -> ApiError {
     (status, Json(serde_json::json!({ "error": msg })))
 }
 
+pub(crate) fn to_project_response(p: ProjectRow) -> ProjectResponse {
+    ProjectResponse {
+        id: p.id.to_string(),
+        organization_id: p.organization_id.to_string(),
+        name: p.name,
+        slug: p.slug,
+        description: p.description,
+        created_at: p.created_at.to_rfc3339(),
+        updated_at: p.updated_at.to_rfc3339(),
+    }
+}
+
+async fn load_project(state: &AppState, project_id: Uuid) -> Result<ProjectRow, ApiError> {
+    state
+        .store
+        .get_project(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Project not found"))
+}
+
+/// List projects in every organization the caller belongs to.
 pub async fn list_projects(
     State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
 ) -> Result<Json<Vec<ProjectResponse>>, ApiError> {
     let projects = state
         .store
-        .list_all_projects()
+        .list_projects_for_organizations(&user.organization_ids)
         .await
         .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
-    let responses: Vec<ProjectResponse> = projects
-        .into_iter()
-        .map(|p| ProjectResponse {
-            id: p.id.to_string(),
-            organization_id: p.organization_id.to_string(),
-            name: p.name,
-            slug: p.slug,
-            description: p.description,
-            created_at: p.created_at.to_rfc3339(),
-            updated_at: p.updated_at.to_rfc3339(),
-        })
-        .collect();
+    let responses: Vec<ProjectResponse> = projects.into_iter().map(to_project_response).collect();
 
     Ok(Json(responses))
 }
@@ -53,21 +93,160 @@ pub async fn list_projects(
 pub async fn get_project(
     State(state): State<AppState>,
     Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
 ) -> Result<Json<ProjectResponse>, ApiError> {
-    let project = state
+    access.require(&state, "project.read", Role::Viewer).await?;
+
+    let project = load_project(&state, project_id).await?;
+
+    Ok(Json(to_project_response(project)))
+}
+
+/// Create a project with default environments and SDK keys, the same way
+/// `/setup` bootstraps the first one. Needs the admin role in the organization;
+/// the caller becomes the project's owner.
+pub async fn create_project(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Extension(request): Extension<RequestContext>,
+    Json(req): Json<CreateProjectRequest>,
+) -> Result<(StatusCode, Json<CreateProjectResponse>), ApiError> {
+    if req.name.trim().is_empty() || req.slug.trim().is_empty() {
+        return Err(err(StatusCode::BAD_REQUEST, "name and slug are required"));
+    }
+    if !user.is_member(req.organization_id) {
+        return Err(err(StatusCode::NOT_FOUND, "Organization not found"));
+    }
+    load_organization(&state, req.organization_id).await?;
+    require_org_role(
+        &state,
+        req.organization_id,
+        &user,
+        "project.create",
+        Role::Admin,
+    )
+    .await?;
+
+    // One transaction, so a failure can't leave a project without an owner
+    // or environments that nobody could then repair.
+    let mut uow = state
         .store
-        .get_project(project_id)
+        .begin()
         .await
-        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
-        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Project not found"))?;
-
-    Ok(Json(ProjectResponse {
-        id: project.id.to_string(),
-        organization_id: project.organization_id.to_string(),
-        name: project.name,
-        slug: project.slug,
-        description: project.description,
-        created_at: project.created_at.to_rfc3339(),
-        updated_at: project.updated_at.to_rfc3339(),
-    }))
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let project = uow
+        .create_project(
+            req.organization_id,
+            &req.name,
+            &req.slug,
+            req.description.as_deref(),
+        )
+        .await
+        .map_err(|e| err(StatusCode::CONFLICT, &e.to_string()))?;
+
+    uow.set_project_member_role(project.id, user.id, Role::Owner.as_str())
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let environments = provision_default_environments(&mut uow, project.id).await?;
+
+    uow.create_audit_log(
+        project.id,
+        &user.audit_context(&request),
+        "project_created",
+        "project",
+        Some(project.id),
+        None,
+        serde_json::to_value(&project).ok().as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateProjectResponse {
+            project: to_project_response(project),
+            environments,
+        }),
+    ))
+}
+
+pub async fn update_project(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<UpdateProjectRequest>,
+) -> Result<Json<ProjectResponse>, ApiError> {
+    access.require(&state, "project.update", Role::Admin).await?;
+
+    let before = load_project(&state, project_id).await?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let updated = uow
+        .update_project(
+            project_id,
+            req.name.as_deref(),
+            req.slug.as_deref(),
+            req.description.as_deref(),
+        )
+        .await
+        .map_err(|e| err(StatusCode::CONFLICT, &e.to_string()))?;
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "project_updated",
+        "project",
+        Some(project_id),
+        serde_json::to_value(&before).ok().as_ref(),
+        serde_json::to_value(&updated).ok().as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(to_project_response(updated)))
+}
+
+/// Delete a project and everything in it. The project's audit log goes with
+/// it, so nothing is recorded here.
+pub async fn delete_project(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<StatusCode, ApiError> {
+    access.require(&state, "project.delete", Role::Owner).await?;
+
+    let project = load_project(&state, project_id).await?;
+
+    let environments = state
+        .store
+        .list_environments(project.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    state
+        .store
+        .delete_project(project.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    if let Some(ref redis) = state.redis {
+        for env in environments {
+            let _ = redis.invalidate_config(env.id).await;
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
 }
@@ -0,0 +1,512 @@
+use std::collections::{BTreeMap, HashMap};
+
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::api::routes::environments::load_environment;
+use crate::api::routes::change_requests::{resolve_spec, ProposalContext};
+use crate::api::routes::flags::notify_environment_changes;
+use crate::state::AppState;
+use crate::store::models::{FlagEnvironmentContents, FlagEnvironmentRow, FlagRow};
+use crate::store::postgres::UnitOfWork;
+
+// ============================================================
+// Request/Response types
+// ============================================================
+
+#[derive(Debug, Deserialize)]
+pub struct PromoteRequest {
+    pub source_environment_id: Uuid,
+    pub target_environment_id: Uuid,
+    /// Flags to promote by key.
+    #[serde(default)]
+    pub flag_keys: Vec<String>,
+    /// Promote every non-archived flag carrying this tag.
+    pub tag: Option<String>,
+    /// Without this the response is a preview and nothing is written.
+    #[serde(default)]
+    pub apply: bool,
+    /// The `diff_hash` of the preview being applied. Required with `apply`;
+    /// if either environment changed since the preview the request fails
+    /// with 409 instead of writing something the caller has not seen.
+    pub diff_hash: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PromoteResponse {
+    pub source_environment_id: String,
+    pub target_environment_id: String,
+    pub applied: bool,
+    pub flags: Vec<FlagPromotionDiff>,
+    /// Fingerprint of `flags`; send it back with `apply` to write exactly
+    /// this diff.
+    pub diff_hash: String,
+    /// Set when the target requires approval: one pending change request
+    /// per changed flag, created instead of applying.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub change_request_ids: Vec<String>,
+}
+
+/// What would change in the target environment for one flag.
+#[derive(Debug, Serialize)]
+pub struct FlagPromotionDiff {
+    pub flag_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<Change<bool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_variant: Option<Change<Option<String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules: Option<Change<Vec<RuleSnapshot>>>,
+    #[serde(skip_serializing_if = "OverridesDiff::is_empty")]
+    pub overrides: OverridesDiff,
+}
+
+impl FlagPromotionDiff {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.enabled.is_none()
+            && self.default_variant.is_none()
+            && self.rules.is_none()
+            && self.overrides.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Change<T> {
+    pub from: T,
+    pub to: T,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct OverridesDiff {
+    /// `targeting_key → variant_key` entries only in the source.
+    pub added: BTreeMap<String, String>,
+    /// Entries only in the target, which promotion removes.
+    pub removed: BTreeMap<String, String>,
+    pub changed: BTreeMap<String, Change<String>>,
+}
+
+impl OverridesDiff {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Key-based view of one flag's configuration in one environment.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FlagEnvironmentSnapshot {
+    pub enabled: bool,
+    pub default_variant: Option<String>,
+    #[serde(default)]
+    pub rules: Vec<RuleSnapshot>,
+    #[serde(default)]
+    pub overrides: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuleSnapshot {
+    pub description: Option<String>,
+    pub variant: Option<String>,
+    pub segments: Vec<RuleSegmentSnapshot>,
+    pub distributions: Vec<DistributionSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuleSegmentSnapshot {
+    pub segment: String,
+    pub negate: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DistributionSnapshot {
+    pub variant: String,
+    pub rollout_pct: i32,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+// ============================================================
+// Snapshots and diffing
+// ============================================================
+
+/// Build a snapshot that refers to variants and segments by key, so it can
+/// be compared across environments.
+pub(crate) async fn snapshot_flag_environment(
+    state: &AppState,
+    fe: &FlagEnvironmentRow,
+    variant_keys: &HashMap<Uuid, String>,
+    segment_keys: &HashMap<Uuid, String>,
+) -> Result<FlagEnvironmentSnapshot, ApiError> {
+    let contents = state
+        .store
+        .get_flag_environment_contents(fe.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    Ok(build_snapshot(fe, contents, variant_keys, segment_keys))
+}
+
+/// [`snapshot_flag_environment`] as seen inside `uow`.
+pub(crate) async fn snapshot_flag_environment_in(
+    uow: &mut UnitOfWork,
+    fe: &FlagEnvironmentRow,
+    variant_keys: &HashMap<Uuid, String>,
+    segment_keys: &HashMap<Uuid, String>,
+) -> Result<FlagEnvironmentSnapshot, ApiError> {
+    let contents = uow
+        .get_flag_environment_contents(fe.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    Ok(build_snapshot(fe, contents, variant_keys, segment_keys))
+}
+
+/// The key-based snapshot of already loaded flag environment contents.
+pub(crate) fn build_snapshot(
+    fe: &FlagEnvironmentRow,
+    contents: FlagEnvironmentContents,
+    variant_keys: &HashMap<Uuid, String>,
+    segment_keys: &HashMap<Uuid, String>,
+) -> FlagEnvironmentSnapshot {
+    let variant_key = |id: Uuid| {
+        variant_keys
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| id.to_string())
+    };
+
+    let rules = contents
+        .rules
+        .into_iter()
+        .map(|r| RuleSnapshot {
+            description: r.rule.description,
+            variant: r.rule.variant_id.map(variant_key),
+            segments: r
+                .segments
+                .into_iter()
+                .map(|s| RuleSegmentSnapshot {
+                    segment: segment_keys
+                        .get(&s.segment_id)
+                        .cloned()
+                        .unwrap_or_else(|| s.segment_id.to_string()),
+                    negate: s.negate,
+                })
+                .collect(),
+            distributions: r
+                .distributions
+                .into_iter()
+                .map(|d| DistributionSnapshot {
+                    variant: variant_key(d.variant_id),
+                    rollout_pct: d.rollout_pct,
+                })
+                .collect(),
+        })
+        .collect();
+
+    let overrides = contents
+        .overrides
+        .into_iter()
+        .map(|o| (o.targeting_key, variant_key(o.variant_id)))
+        .collect();
+
+    FlagEnvironmentSnapshot {
+        enabled: fe.enabled,
+        default_variant: fe.default_variant_id.map(variant_key),
+        rules,
+        overrides,
+    }
+}
+
+/// What applying `source` over `target` would change.
+pub(crate) fn diff_snapshots(
+    flag_key: &str,
+    source: &FlagEnvironmentSnapshot,
+    target: &FlagEnvironmentSnapshot,
+) -> FlagPromotionDiff {
+    let mut overrides = OverridesDiff::default();
+    for (key, variant) in &source.overrides {
+        match target.overrides.get(key) {
+            None => {
+                overrides.added.insert(key.clone(), variant.clone());
+            }
+            Some(current) if current != variant => {
+                overrides.changed.insert(
+                    key.clone(),
+                    Change {
+                        from: current.clone(),
+                        to: variant.clone(),
+                    },
+                );
+            }
+            Some(_) => {}
+        }
+    }
+    for (key, variant) in &target.overrides {
+        if !source.overrides.contains_key(key) {
+            overrides.removed.insert(key.clone(), variant.clone());
+        }
+    }
+
+    FlagPromotionDiff {
+        flag_key: flag_key.to_string(),
+        enabled: (source.enabled != target.enabled).then_some(Change {
+            from: target.enabled,
+            to: source.enabled,
+        }),
+        default_variant: (source.default_variant != target.default_variant).then(|| Change {
+            from: target.default_variant.clone(),
+            to: source.default_variant.clone(),
+        }),
+        rules: (source.rules != target.rules).then(|| Change {
+            from: target.rules.clone(),
+            to: source.rules.clone(),
+        }),
+        overrides,
+    }
+}
+
+/// Fingerprint of a promotion: the current and promoted state of every
+/// changed flag, in order.
+fn diff_hash(
+    changes: &[(&FlagRow, Uuid, FlagEnvironmentSnapshot, FlagEnvironmentSnapshot)],
+) -> String {
+    let mut hasher = Sha256::new();
+    for (flag, _, target, source) in changes {
+        let entry = serde_json::json!([flag.key, target, source]);
+        hasher.update(entry.to_string().as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+async fn select_flags(
+    state: &AppState,
+    project_id: Uuid,
+    req: &PromoteRequest,
+) -> Result<Vec<FlagRow>, ApiError> {
+    if req.flag_keys.is_empty() && req.tag.is_none() {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "Provide flag_keys or tag to select flags",
+        ));
+    }
+
+    let mut selected = Vec::new();
+    for key in &req.flag_keys {
+        let flag = state
+            .store
+            .get_flag_by_key(project_id, key)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+            .ok_or_else(|| err(StatusCode::NOT_FOUND, &format!("Flag {key} not found")))?;
+        selected.push(flag);
+    }
+
+    if let Some(ref tag) = req.tag {
+        let flags = state
+            .store
+            .list_flags(project_id)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+        for flag in flags {
+            if flag.tags.contains(tag) && !selected.iter().any(|f| f.id == flag.id) {
+                selected.push(flag);
+            }
+        }
+    }
+
+    Ok(selected)
+}
+
+// ============================================================
+// Handlers
+// ============================================================
+
+/// Preview, and optionally apply, the promotion of flag configuration from
+/// one environment to another.
+pub async fn promote(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<PromoteRequest>,
+) -> Result<Json<PromoteResponse>, ApiError> {
+    access.require(&state, "flag.promote", Role::Viewer).await?;
+    if req.apply {
+        access
+            .require_env(&state, "flag.promote", req.target_environment_id, Role::Editor)
+            .await?;
+    }
+
+    if req.source_environment_id == req.target_environment_id {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "Source and target environments must differ",
+        ));
+    }
+    let source_env = load_environment(&state, project_id, req.source_environment_id).await?;
+    let target_env = load_environment(&state, project_id, req.target_environment_id).await?;
+
+    let flags = select_flags(&state, project_id, &req).await?;
+
+    // Everything below reads through one transaction. Applying locks the
+    // flags' environment rows first, so what is compared against the
+    // preview's hash is what gets written.
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let segment_keys: HashMap<Uuid, String> = uow
+        .list_segments(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .map(|s| (s.id, s.key))
+        .collect();
+
+    let mut diffs = Vec::new();
+    let mut changes = Vec::new();
+    let mut variant_keys_by_flag = Vec::new();
+
+    for flag in &flags {
+        let variant_keys: HashMap<Uuid, String> = uow
+            .get_flag_variants(flag.id)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+            .into_iter()
+            .map(|v| (v.id, v.key))
+            .collect();
+
+        let envs = if req.apply {
+            uow.lock_flag_environments(flag.id).await
+        } else {
+            uow.list_flag_environments(flag.id).await
+        }
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+        let find = |env_id: Uuid| envs.iter().find(|fe| fe.environment_id == env_id);
+        let (Some(source_fe), Some(target_fe)) = (find(source_env.id), find(target_env.id)) else {
+            return Err(err(
+                StatusCode::CONFLICT,
+                &format!("Flag {} is not configured in both environments", flag.key),
+            ));
+        };
+
+        let source =
+            snapshot_flag_environment_in(&mut uow, source_fe, &variant_keys, &segment_keys)
+                .await?;
+        let target =
+            snapshot_flag_environment_in(&mut uow, target_fe, &variant_keys, &segment_keys)
+                .await?;
+
+        let diff = diff_snapshots(&flag.key, &source, &target);
+        if !diff.is_empty() {
+            changes.push((flag, target_fe.id, target, source));
+            variant_keys_by_flag.push(variant_keys);
+            diffs.push(diff);
+        }
+    }
+
+    let hash = diff_hash(&changes);
+    if req.apply {
+        match req.diff_hash.as_deref() {
+            None => {
+                return Err(err(
+                    StatusCode::BAD_REQUEST,
+                    "diff_hash from the preview is required to apply",
+                ))
+            }
+            Some(expected) if expected != hash => {
+                return Err(err(
+                    StatusCode::CONFLICT,
+                    "Configuration changed since the preview; preview again and re-check the diff",
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+
+    let mut change_request_ids = Vec::new();
+    if req.apply && target_env.requires_approval {
+        for (_, target_fe_id, before, after) in &changes {
+            let row = uow
+                .create_change_request(
+                    project_id,
+                    *target_fe_id,
+                    "flag.promote",
+                    &serde_json::to_value(before).unwrap_or_default(),
+                    &serde_json::to_value(after).unwrap_or_default(),
+                    access.user_id,
+                    access.actor_email.as_deref(),
+                )
+                .await
+                .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+            uow.create_audit_log(
+                project_id,
+                &access.audit_context().in_environment(req.target_environment_id),
+                "change_request_created",
+                "change_request",
+                Some(row.id),
+                None,
+                serde_json::to_value(&row).ok().as_ref(),
+            )
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+            change_request_ids.push(row.id.to_string());
+        }
+    } else if req.apply && !changes.is_empty() {
+        let mut specs = Vec::with_capacity(changes.len());
+        for ((_, target_fe_id, _, source), variant_keys) in changes.iter().zip(variant_keys_by_flag)
+        {
+            let ctx = ProposalContext {
+                variant_keys,
+                segment_keys: segment_keys.clone(),
+                rule_ids: Vec::new(),
+            };
+            specs.push((*target_fe_id, resolve_spec(source, &ctx)?));
+        }
+        uow.write_flag_environments(&specs)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+        for (flag, _, before, after) in &changes {
+            uow.create_audit_log(
+                project_id,
+                &access.audit_context().in_environment(req.target_environment_id),
+                "flag_promoted",
+                "flag",
+                Some(flag.id),
+                serde_json::to_value(before).ok().as_ref(),
+                serde_json::to_value(after).ok().as_ref(),
+            )
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+        }
+    }
+
+    let applied = req.apply && !target_env.requires_approval && !changes.is_empty();
+    if req.apply {
+        let changed = uow
+            .commit()
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+        notify_environment_changes(&state, &changed).await;
+    }
+
+    Ok(Json(PromoteResponse {
+        source_environment_id: source_env.id.to_string(),
+        target_environment_id: target_env.id.to_string(),
+        applied,
+        flags: diffs,
+        diff_hash: hash,
+        change_request_ids,
+    }))
+}
@@ -0,0 +1,575 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::api::routes::environments::load_environment;
+use crate::api::routes::flags::notify_environment_changes;
+use crate::api::routes::rules::load_flag_environment;
+use crate::state::AppState;
+use crate::store::models::{
+    AuditContext, Guardrail, MetricStatsRow, RolloutPlanRow, RolloutStep,
+};
+use crate::store::postgres::UnitOfWork;
+
+/// Upper bound on the number of steps in one plan.
+const MAX_STEPS: usize = 50;
+
+// ============================================================
+// Request/Response types
+// ============================================================
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRolloutRequest {
+    /// The rule whose distributions the plan drives.
+    pub rule_id: Uuid,
+    /// The variant being rolled out.
+    pub variant_id: Uuid,
+    /// Receives the rest of the rule's traffic. Without one, the rest falls
+    /// through to the default variant.
+    pub control_variant_id: Option<Uuid>,
+    pub steps: Vec<RolloutStep>,
+    /// Checked against metric events while the plan runs. Requires a
+    /// control variant to compare with.
+    #[serde(default)]
+    pub guardrails: Vec<Guardrail>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RolloutResponse {
+    pub id: String,
+    pub flag_key: String,
+    pub environment_id: String,
+    pub rule_id: String,
+    pub variant_id: String,
+    pub control_variant_id: Option<String>,
+    pub steps: serde_json::Value,
+    pub current_step: i32,
+    pub status: String,
+    pub next_step_at: Option<String>,
+    pub guardrails: serde_json::Value,
+    /// Set when a guardrail breach stopped the plan.
+    pub halted_reason: Option<serde_json::Value>,
+    pub created_by: Option<String>,
+    pub created_by_email: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+// ============================================================
+// Helpers
+// ============================================================
+
+fn to_response(p: RolloutPlanRow) -> RolloutResponse {
+    RolloutResponse {
+        id: p.id.to_string(),
+        flag_key: p.flag_key,
+        environment_id: p.environment_id.to_string(),
+        rule_id: p.rule_id.to_string(),
+        variant_id: p.variant_id.to_string(),
+        control_variant_id: p.control_variant_id.map(|id| id.to_string()),
+        steps: p.steps,
+        current_step: p.current_step,
+        status: p.status,
+        next_step_at: p.next_step_at.map(|t| t.to_rfc3339()),
+        guardrails: p.guardrails,
+        halted_reason: p.halted_reason,
+        created_by: p.created_by.map(|id| id.to_string()),
+        created_by_email: p.created_by_email,
+        created_at: p.created_at.to_rfc3339(),
+        updated_at: p.updated_at.to_rfc3339(),
+    }
+}
+
+fn validate_steps(steps: &[RolloutStep]) -> Result<(), ApiError> {
+    if steps.is_empty() || steps.len() > MAX_STEPS {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!("A rollout needs between 1 and {MAX_STEPS} steps"),
+        ));
+    }
+    for step in steps {
+        if !(0..=10000).contains(&step.rollout_pct) {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                "rollout_pct must be between 0 and 10000",
+            ));
+        }
+        if step.dwell_secs < 0 {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                "dwell_secs must not be negative",
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_guardrails(
+    guardrails: &[Guardrail],
+    control_variant_id: Option<Uuid>,
+) -> Result<(), ApiError> {
+    if !guardrails.is_empty() && control_variant_id.is_none() {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "Guardrails need a control_variant_id to compare with",
+        ));
+    }
+    for g in guardrails {
+        if g.event_key.trim().is_empty() {
+            return Err(err(StatusCode::BAD_REQUEST, "event_key is required"));
+        }
+        if !g.max_increase_pct.is_finite() || g.max_increase_pct < 0.0 {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                "max_increase_pct must be a non-negative number",
+            ));
+        }
+        if g.min_samples < 1 {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                "min_samples must be at least 1",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether the rollout variant's mean exceeds control's by more than the
+/// guardrail allows. Undecided until both variants have enough samples.
+///
+/// Each sample is one reported observation, so this compares rates only
+/// when events are reported per exposure with values 0 or 1; see
+/// [`Guardrail::event_key`].
+fn is_breached(g: &Guardrail, treatment: &MetricStatsRow, control: &MetricStatsRow) -> bool {
+    if treatment.samples < g.min_samples || control.samples < g.min_samples {
+        return false;
+    }
+    treatment.mean > control.mean * (1.0 + g.max_increase_pct / 100.0) && treatment.mean > 0.0
+}
+
+async fn load_rollout(
+    state: &AppState,
+    project_id: Uuid,
+    rollout_id: Uuid,
+) -> Result<RolloutPlanRow, ApiError> {
+    state
+        .store
+        .get_rollout_plan(rollout_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .filter(|p| p.project_id == project_id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Rollout not found"))
+}
+
+async fn audit(
+    uow: &mut UnitOfWork,
+    ctx: &AuditContext,
+    action: &str,
+    before: Option<&RolloutPlanRow>,
+    after: &RolloutPlanRow,
+) -> anyhow::Result<()> {
+    uow.create_audit_log(
+        after.project_id,
+        &ctx.clone().in_environment(after.environment_id),
+        action,
+        "rollout_plan",
+        Some(after.id),
+        before.and_then(|p| serde_json::to_value(p).ok()).as_ref(),
+        serde_json::to_value(after).ok().as_ref(),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Rollout steps apply without review, so they would bypass approval.
+fn check_no_approval(requires_approval: bool) -> Result<(), ApiError> {
+    if requires_approval {
+        return Err(err(
+            StatusCode::CONFLICT,
+            "Environment requires approval; submit a change request instead of a rollout",
+        ));
+    }
+    Ok(())
+}
+
+/// Advance a due plan by one step. Called by the background worker.
+///
+/// A plan whose environment has started to require approval is paused
+/// instead, since its steps would bypass review.
+pub(crate) async fn advance_rollout(state: &AppState, rollout_id: Uuid) {
+    // Left active, so the next tick retries.
+    if let Err(e) = step_rollout(state, rollout_id).await {
+        tracing::warn!(rollout_id = %rollout_id, "Rollout step failed: {e}");
+    }
+}
+
+async fn step_rollout(state: &AppState, rollout_id: Uuid) -> anyhow::Result<()> {
+    let before = state.store.get_rollout_plan(rollout_id).await?;
+    let mut uow = state.store.begin().await?;
+    if let Some(ref plan) = before {
+        let requires_approval = state
+            .store
+            .get_environment(plan.environment_id)
+            .await?
+            .is_some_and(|env| env.requires_approval);
+        if requires_approval {
+            if let Some(paused) = uow.pause_rollout_plan(plan.id).await? {
+                audit(
+                    &mut uow,
+                    &AuditContext::default(),
+                    "rollout_paused",
+                    Some(plan),
+                    &paused,
+                )
+                .await?;
+                uow.commit().await?;
+                tracing::warn!(rollout_id = %plan.id, flag = %plan.flag_key, "Rollout paused: environment requires approval");
+            }
+            return Ok(());
+        }
+    }
+
+    let Some(plan) = uow.advance_rollout_plan(rollout_id).await? else {
+        return Ok(());
+    };
+    let ctx = AuditContext::on_behalf_of(plan.created_by, plan.created_by_email.as_deref());
+    audit(&mut uow, &ctx, "rollout_advanced", before.as_ref(), &plan).await?;
+    let changed = uow.commit().await?;
+
+    tracing::info!(rollout_id = %plan.id, flag = %plan.flag_key, step = plan.current_step, "Rollout advanced");
+    notify_environment_changes(state, &changed).await;
+    Ok(())
+}
+
+/// Check a running plan's guardrails against the metric events reported
+/// since it started, and halt it on the first breach. Called by the
+/// background worker.
+pub(crate) async fn check_guardrails(
+    state: &AppState,
+    plan: &RolloutPlanRow,
+) -> anyhow::Result<()> {
+    let Some(control_variant_id) = plan.control_variant_id else {
+        return Ok(());
+    };
+    let guardrails: Vec<Guardrail> = serde_json::from_value(plan.guardrails.clone())?;
+
+    let variants = state.store.get_flag_variants(plan.flag_id).await?;
+    let key_of = |id: Uuid| variants.iter().find(|v| v.id == id).map(|v| v.key.clone());
+    let (Some(treatment_key), Some(control_key)) =
+        (key_of(plan.variant_id), key_of(control_variant_id))
+    else {
+        return Ok(());
+    };
+
+    for g in &guardrails {
+        let stats = state
+            .store
+            .metric_stats(
+                plan.environment_id,
+                &plan.flag_key,
+                &g.event_key,
+                plan.created_at,
+            )
+            .await?;
+        let find = |key: &str| stats.iter().find(|s| s.variant_key == key);
+        let (Some(treatment), Some(control)) = (find(&treatment_key), find(&control_key)) else {
+            continue;
+        };
+        if !is_breached(g, treatment, control) {
+            continue;
+        }
+
+        let reason = serde_json::json!({
+            "event_key": g.event_key,
+            "max_increase_pct": g.max_increase_pct,
+            "action": g.action,
+            "variant": { "key": treatment_key, "samples": treatment.samples, "mean": treatment.mean },
+            "control": { "key": control_key, "samples": control.samples, "mean": control.mean },
+        });
+        let mut uow = state.store.begin().await?;
+        let Some(halted) = uow.halt_rollout_plan(plan.id, g.action, &reason).await? else {
+            return Ok(());
+        };
+        // Halted by the worker itself, so there is no actor.
+        audit(
+            &mut uow,
+            &AuditContext::default(),
+            "rollout_guardrail_breached",
+            Some(plan),
+            &halted,
+        )
+        .await?;
+        let changed = uow.commit().await?;
+
+        tracing::warn!(rollout_id = %plan.id, flag = %plan.flag_key, event = %g.event_key, "Rollout guardrail breached");
+        notify_environment_changes(state, &changed).await;
+        return Ok(());
+    }
+
+    Ok(())
+}
+
+// ============================================================
+// Handlers
+// ============================================================
+
+pub async fn list_rollouts(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, environment_id)): Path<(Uuid, String, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<Vec<RolloutResponse>>, ApiError> {
+    access.require(&state, "rollout.read", Role::Viewer).await?;
+
+    let (_flag, fe) = load_flag_environment(&state, project_id, &flag_key, environment_id).await?;
+
+    let plans = state
+        .store
+        .list_rollout_plans(project_id, Some(fe.id))
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(plans.into_iter().map(to_response).collect()))
+}
+
+pub async fn get_rollout(
+    State(state): State<AppState>,
+    Path((project_id, rollout_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<RolloutResponse>, ApiError> {
+    access.require(&state, "rollout.read", Role::Viewer).await?;
+
+    let plan = load_rollout(&state, project_id, rollout_id).await?;
+    Ok(Json(to_response(plan)))
+}
+
+/// Start a progressive rollout. The first step is applied immediately; the
+/// worker applies each following one once the previous dwell time is over.
+///
+/// Like scheduled changes, rollouts bypass change requests, so they are
+/// refused in environments that require approval.
+pub async fn create_rollout(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, environment_id)): Path<(Uuid, String, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<CreateRolloutRequest>,
+) -> Result<(StatusCode, Json<RolloutResponse>), ApiError> {
+    let env = load_environment(&state, project_id, environment_id).await?;
+    access
+        .require_env(&state, "rollout.create", environment_id, Role::Editor)
+        .await?;
+    check_no_approval(env.requires_approval)?;
+
+    validate_steps(&req.steps)?;
+    validate_guardrails(&req.guardrails, req.control_variant_id)?;
+    let (flag, fe) = load_flag_environment(&state, project_id, &flag_key, environment_id).await?;
+
+    let variants = state
+        .store
+        .get_flag_variants(flag.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    for id in std::iter::once(req.variant_id).chain(req.control_variant_id) {
+        if !variants.iter().any(|v| v.id == id) {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                &format!("Variant {id} does not belong to flag {}", flag.key),
+            ));
+        }
+    }
+    if req.control_variant_id == Some(req.variant_id) {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "control_variant_id must differ from variant_id",
+        ));
+    }
+
+    state
+        .store
+        .get_targeting_rule(req.rule_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .filter(|r| r.flag_environment_id == fe.id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Rule not found"))?;
+
+    let running = state
+        .store
+        .list_rollout_plans(project_id, Some(fe.id))
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .any(|p| p.status == "active" || p.status == "paused");
+    if running {
+        return Err(err(
+            StatusCode::CONFLICT,
+            "A rollout is already running for this flag in this environment",
+        ));
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let plan = uow
+        .create_rollout_plan(
+            project_id,
+            fe.id,
+            req.rule_id,
+            req.variant_id,
+            req.control_variant_id,
+            &req.steps,
+            &req.guardrails,
+            access.user_id,
+            access.actor_email.as_deref(),
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    audit(
+        &mut uow,
+        &access.audit_context(),
+        "rollout_started",
+        None,
+        &plan,
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    Ok((StatusCode::CREATED, Json(to_response(plan))))
+}
+
+pub async fn pause_rollout(
+    State(state): State<AppState>,
+    Path((project_id, rollout_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<RolloutResponse>, ApiError> {
+    let plan = load_rollout(&state, project_id, rollout_id).await?;
+    access
+        .require_env(&state, "rollout.pause", plan.environment_id, Role::Editor)
+        .await?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let paused = uow
+        .pause_rollout_plan(plan.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::CONFLICT, "Rollout is not active"))?;
+
+    audit(
+        &mut uow,
+        &access.audit_context(),
+        "rollout_paused",
+        Some(&plan),
+        &paused,
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    Ok(Json(to_response(paused)))
+}
+
+/// Resume a paused rollout. The current step's dwell time starts over.
+pub async fn resume_rollout(
+    State(state): State<AppState>,
+    Path((project_id, rollout_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<RolloutResponse>, ApiError> {
+    let plan = load_rollout(&state, project_id, rollout_id).await?;
+    access
+        .require_env(&state, "rollout.resume", plan.environment_id, Role::Editor)
+        .await?;
+    let env = load_environment(&state, project_id, plan.environment_id).await?;
+    check_no_approval(env.requires_approval)?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let resumed = uow
+        .resume_rollout_plan(plan.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::CONFLICT, "Rollout is not paused"))?;
+
+    audit(
+        &mut uow,
+        &access.audit_context(),
+        "rollout_resumed",
+        Some(&plan),
+        &resumed,
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    Ok(Json(to_response(resumed)))
+}
+
+/// Stop a running rollout and restore the rule as it was before it started.
+pub async fn rollback_rollout(
+    State(state): State<AppState>,
+    Path((project_id, rollout_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<RolloutResponse>, ApiError> {
+    let plan = load_rollout(&state, project_id, rollout_id).await?;
+    access
+        .require_env(
+            &state,
+            "rollout.rollback",
+            plan.environment_id,
+            Role::Editor,
+        )
+        .await?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let rolled_back = uow
+        .rollback_rollout_plan(plan.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::CONFLICT, "Rollout is not running"))?;
+
+    audit(
+        &mut uow,
+        &access.audit_context(),
+        "rollout_rolled_back",
+        Some(&plan),
+        &rolled_back,
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    Ok(Json(to_response(rolled_back)))
+}
@@ -0,0 +1,577 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::api::middleware::precondition::{
+    check_revision, if_match, precondition_failed, with_etag,
+};
+use crate::api::routes::change_requests::{audit_snapshot_in, propose_if_required};
+use crate::api::routes::flags::notify_environment_changes;
+use crate::state::AppState;
+use crate::store::models::{FlagEnvironmentRow, FlagRow, TargetingRuleRow};
+
+// ============================================================
+// Request/Response types
+// ============================================================
+
+/// Body for creating or replacing a targeting rule.
+#[derive(Debug, Deserialize)]
+pub struct RuleRequest {
+    pub description: Option<String>,
+    /// Variant served directly when the rule has no distributions.
+    pub variant_id: Option<Uuid>,
+    #[serde(default)]
+    pub segments: Vec<RuleSegmentInput>,
+    #[serde(default)]
+    pub distributions: Vec<RuleDistributionInput>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RuleSegmentInput {
+    pub segment_id: Uuid,
+    #[serde(default)]
+    pub negate: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RuleDistributionInput {
+    pub variant_id: Uuid,
+    /// Share of traffic in basis points (0–10000).
+    pub rollout_pct: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReorderRulesRequest {
+    /// Every rule of the flag environment, in the desired evaluation order.
+    pub rule_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuleResponse {
+    pub id: String,
+    pub rank: i32,
+    pub description: Option<String>,
+    pub variant_id: Option<String>,
+    pub segments: Vec<RuleSegmentResponse>,
+    pub distributions: Vec<RuleDistributionResponse>,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Pass back in `If-Match` to update or delete the rule safely.
+    pub revision: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuleSegmentResponse {
+    pub segment_id: String,
+    pub negate: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuleDistributionResponse {
+    pub variant_id: String,
+    pub rollout_pct: i32,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+// ============================================================
+// Helpers
+// ============================================================
+
+/// Resolve the flag and its per-environment row from the path.
+pub(crate) async fn load_flag_environment(
+    state: &AppState,
+    project_id: Uuid,
+    flag_key: &str,
+    environment_id: Uuid,
+) -> Result<(FlagRow, FlagEnvironmentRow), ApiError> {
+    let flag = state
+        .store
+        .get_flag_by_key(project_id, flag_key)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Flag not found"))?;
+
+    let fe = state
+        .store
+        .get_flag_environment(flag.id, environment_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Flag is not configured in this environment"))?;
+
+    Ok((flag, fe))
+}
+
+/// Load a rule and make sure it belongs to the flag environment in the path.
+async fn load_rule(
+    state: &AppState,
+    fe: &FlagEnvironmentRow,
+    rule_id: Uuid,
+) -> Result<TargetingRuleRow, ApiError> {
+    state
+        .store
+        .get_targeting_rule(rule_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .filter(|r| r.flag_environment_id == fe.id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Rule not found"))
+}
+
+/// Check that every referenced variant belongs to the flag, every segment to
+/// the project, and that distributions add up to at most 100%.
+async fn validate_rule(
+    state: &AppState,
+    project_id: Uuid,
+    flag: &FlagRow,
+    req: &RuleRequest,
+) -> Result<(), ApiError> {
+    if req.variant_id.is_none() && req.distributions.is_empty() {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "A rule must set variant_id or at least one distribution",
+        ));
+    }
+
+    let variants = state
+        .store
+        .get_flag_variants(flag.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let variant_ids = req
+        .variant_id
+        .iter()
+        .chain(req.distributions.iter().map(|d| &d.variant_id));
+    for id in variant_ids {
+        if !variants.iter().any(|v| v.id == *id) {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                &format!("Variant {id} does not belong to flag {}", flag.key),
+            ));
+        }
+    }
+
+    let mut total_pct = 0;
+    for d in &req.distributions {
+        if !(0..=10000).contains(&d.rollout_pct) {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                "rollout_pct must be between 0 and 10000",
+            ));
+        }
+        total_pct += d.rollout_pct;
+    }
+    if total_pct > 10000 {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "Distributions must add up to at most 10000 basis points",
+        ));
+    }
+
+    if !req.segments.is_empty() {
+        let segments = state
+            .store
+            .list_segments(project_id)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+        for rs in &req.segments {
+            if !segments.iter().any(|s| s.id == rs.segment_id) {
+                return Err(err(
+                    StatusCode::BAD_REQUEST,
+                    &format!("Segment {} not found in project", rs.segment_id),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn build_rule_response(
+    state: &AppState,
+    rule: TargetingRuleRow,
+) -> Result<RuleResponse, ApiError> {
+    let segments = state
+        .store
+        .get_rule_segments(rule.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let distributions = state
+        .store
+        .get_rule_distributions(rule.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(RuleResponse {
+        id: rule.id.to_string(),
+        rank: rule.rank,
+        description: rule.description,
+        variant_id: rule.variant_id.map(|id| id.to_string()),
+        segments: segments
+            .into_iter()
+            .map(|s| RuleSegmentResponse {
+                segment_id: s.segment_id.to_string(),
+                negate: s.negate,
+            })
+            .collect(),
+        distributions: distributions
+            .into_iter()
+            .map(|d| RuleDistributionResponse {
+                variant_id: d.variant_id.to_string(),
+                rollout_pct: d.rollout_pct,
+            })
+            .collect(),
+        created_at: rule.created_at.to_rfc3339(),
+        updated_at: rule.updated_at.to_rfc3339(),
+        revision: rule.revision,
+    })
+}
+
+/// `(segment_id, negate)` pairs and `(variant_id, rollout_pct)` buckets.
+type RuleChildren = (Vec<(Uuid, bool)>, Vec<(Uuid, i32)>);
+
+fn rule_children(req: &RuleRequest) -> RuleChildren {
+    (
+        req.segments.iter().map(|s| (s.segment_id, s.negate)).collect(),
+        req.distributions
+            .iter()
+            .map(|d| (d.variant_id, d.rollout_pct))
+            .collect(),
+    )
+}
+
+// ============================================================
+// Handlers
+// ============================================================
+
+pub async fn list_rules(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, environment_id)): Path<(Uuid, String, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<Vec<RuleResponse>>, ApiError> {
+    access.require(&state, "rule.read", Role::Viewer).await?;
+
+    let (_flag, fe) = load_flag_environment(&state, project_id, &flag_key, environment_id).await?;
+
+    let rules = state
+        .store
+        .get_targeting_rules(fe.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let mut responses = Vec::new();
+    for rule in rules {
+        responses.push(build_rule_response(&state, rule).await?);
+    }
+
+    Ok(Json(responses))
+}
+
+pub async fn create_rule(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, environment_id)): Path<(Uuid, String, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<RuleRequest>,
+) -> Result<Response, ApiError> {
+    access
+        .require_env(&state, "rule.create", environment_id, Role::Editor)
+        .await?;
+
+    let (flag, fe) = load_flag_environment(&state, project_id, &flag_key, environment_id).await?;
+    validate_rule(&state, project_id, &flag, &req).await?;
+
+    let (segments, distributions) = rule_children(&req);
+    let proposal = propose_if_required(&state, &access, &flag, &fe, "rule.create", |s, ctx| {
+        s.rules.push(ctx.rule(
+            req.description.clone(),
+            req.variant_id,
+            &segments,
+            &distributions,
+        ));
+        Ok(())
+    })
+    .await?;
+    if let Some(response) = proposal {
+        return Ok(response);
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let before = audit_snapshot_in(&mut uow, project_id, flag.id, environment_id).await;
+
+    let rule = uow
+        .create_targeting_rule(
+            fe.id,
+            req.description.as_deref(),
+            req.variant_id,
+            &segments,
+            &distributions,
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let after = audit_snapshot_in(&mut uow, project_id, flag.id, environment_id).await;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(environment_id),
+        "rule_created",
+        "targeting_rule",
+        Some(rule.id),
+        before.as_ref(),
+        after.as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    Ok((StatusCode::CREATED, Json(build_rule_response(&state, rule).await?)).into_response())
+}
+
+pub async fn update_rule(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, environment_id, rule_id)): Path<(Uuid, String, Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    headers: HeaderMap,
+    Json(req): Json<RuleRequest>,
+) -> Result<Response, ApiError> {
+    access
+        .require_env(&state, "rule.update", environment_id, Role::Editor)
+        .await?;
+    let precondition = if_match(&headers)?;
+
+    let (flag, fe) = load_flag_environment(&state, project_id, &flag_key, environment_id).await?;
+    let current = load_rule(&state, &fe, rule_id).await?;
+    let expected_revision = check_revision(precondition.as_deref(), current.revision)?;
+    validate_rule(&state, project_id, &flag, &req).await?;
+
+    let (segments, distributions) = rule_children(&req);
+    let proposal = propose_if_required(&state, &access, &flag, &fe, "rule.update", |s, ctx| {
+        let index = ctx
+            .rule_index(rule_id)
+            .ok_or_else(|| err(StatusCode::NOT_FOUND, "Rule not found"))?;
+        s.rules[index] = ctx.rule(
+            req.description.clone(),
+            req.variant_id,
+            &segments,
+            &distributions,
+        );
+        Ok(())
+    })
+    .await?;
+    if let Some(response) = proposal {
+        return Ok(response);
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let before = audit_snapshot_in(&mut uow, project_id, flag.id, environment_id).await;
+
+    let rule = uow
+        .update_targeting_rule(
+            fe.id,
+            rule_id,
+            expected_revision,
+            req.description.as_deref(),
+            req.variant_id,
+            &segments,
+            &distributions,
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(precondition_failed)?;
+
+    let after = audit_snapshot_in(&mut uow, project_id, flag.id, environment_id).await;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(environment_id),
+        "rule_updated",
+        "targeting_rule",
+        Some(rule.id),
+        before.as_ref(),
+        after.as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    let response = build_rule_response(&state, rule).await?;
+    Ok(with_etag(response.revision, Json(response)))
+}
+
+pub async fn delete_rule(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, environment_id, rule_id)): Path<(Uuid, String, Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    access
+        .require_env(&state, "rule.delete", environment_id, Role::Editor)
+        .await?;
+    let precondition = if_match(&headers)?;
+
+    let (flag, fe) = load_flag_environment(&state, project_id, &flag_key, environment_id).await?;
+    let rule = load_rule(&state, &fe, rule_id).await?;
+    let expected_revision = check_revision(precondition.as_deref(), rule.revision)?;
+
+    let proposal = propose_if_required(&state, &access, &flag, &fe, "rule.delete", |s, ctx| {
+        let index = ctx
+            .rule_index(rule.id)
+            .ok_or_else(|| err(StatusCode::NOT_FOUND, "Rule not found"))?;
+        s.rules.remove(index);
+        Ok(())
+    })
+    .await?;
+    if let Some(response) = proposal {
+        return Ok(response);
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let before = audit_snapshot_in(&mut uow, project_id, flag.id, environment_id).await;
+
+    let deleted = uow
+        .delete_targeting_rule(fe.id, rule.id, expected_revision)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    if !deleted {
+        return Err(precondition_failed());
+    }
+
+    let after = audit_snapshot_in(&mut uow, project_id, flag.id, environment_id).await;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(environment_id),
+        "rule_deleted",
+        "targeting_rule",
+        Some(rule.id),
+        before.as_ref(),
+        after.as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+pub async fn reorder_rules(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, environment_id)): Path<(Uuid, String, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<ReorderRulesRequest>,
+) -> Result<Response, ApiError> {
+    access
+        .require_env(&state, "rule.reorder", environment_id, Role::Editor)
+        .await?;
+
+    let (flag, fe) = load_flag_environment(&state, project_id, &flag_key, environment_id).await?;
+
+    let existing = state
+        .store
+        .get_targeting_rules(fe.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    // The new order must be a permutation of the current rules.
+    let mut requested = req.rule_ids.clone();
+    requested.sort();
+    requested.dedup();
+    let mut current: Vec<Uuid> = existing.iter().map(|r| r.id).collect();
+    current.sort();
+    if requested.len() != req.rule_ids.len() || requested != current {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "rule_ids must list every rule of this flag environment exactly once",
+        ));
+    }
+
+    let proposal = propose_if_required(&state, &access, &flag, &fe, "rule.reorder", |s, ctx| {
+        let mut reordered = Vec::with_capacity(s.rules.len());
+        for id in &req.rule_ids {
+            let index = ctx
+                .rule_index(*id)
+                .ok_or_else(|| err(StatusCode::CONFLICT, "Rules changed while reordering"))?;
+            reordered.push(s.rules[index].clone());
+        }
+        s.rules = reordered;
+        Ok(())
+    })
+    .await?;
+    if let Some(response) = proposal {
+        return Ok(response);
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let before = audit_snapshot_in(&mut uow, project_id, flag.id, environment_id).await;
+    let rules = uow
+        .reorder_targeting_rules(fe.id, &req.rule_ids)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::CONFLICT, "Rules changed while reordering"))?;
+
+    let after = audit_snapshot_in(&mut uow, project_id, flag.id, environment_id).await;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(environment_id),
+        "rules_reordered",
+        "flag_environment",
+        Some(fe.id),
+        before.as_ref(),
+        after.as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    let mut responses = Vec::new();
+    for rule in rules {
+        responses.push(build_rule_response(&state, rule).await?);
+    }
+
+    Ok(Json(responses).into_response())
+}
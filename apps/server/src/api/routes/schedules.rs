@@ -0,0 +1,370 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::api::routes::environments::load_environment;
+use crate::api::routes::rules::load_flag_environment;
+use crate::state::AppState;
+use crate::store::models::{
+    DistributionSpec, FlagEnvironmentRow, FlagRow, ScheduledAction, ScheduledChangeRow,
+};
+
+// ============================================================
+// Request/Response types
+// ============================================================
+
+#[derive(Debug, Deserialize)]
+pub struct CreateScheduleRequest {
+    /// When to apply the change. Must be in the future.
+    pub run_at: DateTime<Utc>,
+    pub change: ScheduledAction,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListSchedulesQuery {
+    /// `pending`, `succeeded`, `failed` or `cancelled`.
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduleResponse {
+    pub id: String,
+    pub flag_key: String,
+    pub environment_id: String,
+    pub change: serde_json::Value,
+    pub run_at: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub created_by: Option<String>,
+    pub created_by_email: Option<String>,
+    pub executed_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+// ============================================================
+// Helpers
+// ============================================================
+
+fn to_response(sc: ScheduledChangeRow) -> ScheduleResponse {
+    ScheduleResponse {
+        id: sc.id.to_string(),
+        flag_key: sc.flag_key,
+        environment_id: sc.environment_id.to_string(),
+        change: sc.payload,
+        run_at: sc.run_at.to_rfc3339(),
+        status: sc.status,
+        error: sc.error,
+        created_by: sc.created_by.map(|id| id.to_string()),
+        created_by_email: sc.created_by_email,
+        executed_at: sc.executed_at.map(|t| t.to_rfc3339()),
+        created_at: sc.created_at.to_rfc3339(),
+        updated_at: sc.updated_at.to_rfc3339(),
+    }
+}
+
+/// Check the referenced variants, rule and segments now, so a schedule
+/// only fails later if they are deleted in the meantime.
+async fn validate_action(
+    state: &AppState,
+    project_id: Uuid,
+    flag: &FlagRow,
+    fe: &FlagEnvironmentRow,
+    action: &ScheduledAction,
+) -> Result<(), ApiError> {
+    let variants = state
+        .store
+        .get_flag_variants(flag.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let check_variant = |id: Uuid| {
+        if variants.iter().any(|v| v.id == id) {
+            Ok(())
+        } else {
+            Err(err(
+                StatusCode::BAD_REQUEST,
+                &format!("Variant {id} does not belong to flag {}", flag.key),
+            ))
+        }
+    };
+    let check_distributions = |distributions: &[DistributionSpec]| {
+        let mut total = 0;
+        for d in distributions {
+            check_variant(d.variant_id)?;
+            if !(0..=10000).contains(&d.rollout_pct) {
+                return Err(err(
+                    StatusCode::BAD_REQUEST,
+                    "rollout_pct must be between 0 and 10000",
+                ));
+            }
+            total += d.rollout_pct;
+        }
+        if total > 10000 {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                "Distributions must add up to at most 10000 basis points",
+            ));
+        }
+        Ok(())
+    };
+
+    let rule_id = match action {
+        ScheduledAction::Toggle { .. } => None,
+        ScheduledAction::DefaultVariant { variant_id } => {
+            check_variant(*variant_id)?;
+            None
+        }
+        ScheduledAction::Rule {
+            rule_id,
+            variant_id,
+            segments,
+            distributions,
+            ..
+        } => {
+            if variant_id.is_none() && distributions.is_empty() {
+                return Err(err(
+                    StatusCode::BAD_REQUEST,
+                    "A rule must set variant_id or at least one distribution",
+                ));
+            }
+            if let Some(id) = variant_id {
+                check_variant(*id)?;
+            }
+            check_distributions(distributions)?;
+
+            if !segments.is_empty() {
+                let known = state
+                    .store
+                    .list_segments(project_id)
+                    .await
+                    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+                for s in segments {
+                    if !known.iter().any(|k| k.id == s.segment_id) {
+                        return Err(err(
+                            StatusCode::BAD_REQUEST,
+                            &format!("Segment {} not found in project", s.segment_id),
+                        ));
+                    }
+                }
+            }
+            Some(*rule_id)
+        }
+        ScheduledAction::Rollout {
+            rule_id,
+            distributions,
+        } => {
+            if distributions.is_empty() {
+                return Err(err(
+                    StatusCode::BAD_REQUEST,
+                    "A rollout needs at least one distribution",
+                ));
+            }
+            check_distributions(distributions)?;
+            Some(*rule_id)
+        }
+    };
+
+    if let Some(rule_id) = rule_id {
+        state
+            .store
+            .get_targeting_rule(rule_id)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+            .filter(|r| r.flag_environment_id == fe.id)
+            .ok_or_else(|| err(StatusCode::NOT_FOUND, "Rule not found"))?;
+    }
+
+    Ok(())
+}
+
+async fn load_schedule(
+    state: &AppState,
+    project_id: Uuid,
+    schedule_id: Uuid,
+) -> Result<ScheduledChangeRow, ApiError> {
+    state
+        .store
+        .get_scheduled_change(schedule_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .filter(|sc| sc.project_id == project_id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Scheduled change not found"))
+}
+
+// ============================================================
+// Handlers
+// ============================================================
+
+pub async fn list_schedules(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+    Query(query): Query<ListSchedulesQuery>,
+) -> Result<Json<Vec<ScheduleResponse>>, ApiError> {
+    access
+        .require(&state, "schedule.read", Role::Viewer)
+        .await?;
+
+    let rows = state
+        .store
+        .list_scheduled_changes(project_id, None, query.status.as_deref())
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(rows.into_iter().map(to_response).collect()))
+}
+
+pub async fn list_flag_schedules(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, environment_id)): Path<(Uuid, String, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Query(query): Query<ListSchedulesQuery>,
+) -> Result<Json<Vec<ScheduleResponse>>, ApiError> {
+    access
+        .require(&state, "schedule.read", Role::Viewer)
+        .await?;
+
+    let (_flag, fe) = load_flag_environment(&state, project_id, &flag_key, environment_id).await?;
+
+    let rows = state
+        .store
+        .list_scheduled_changes(project_id, Some(fe.id), query.status.as_deref())
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(rows.into_iter().map(to_response).collect()))
+}
+
+pub async fn get_schedule(
+    State(state): State<AppState>,
+    Path((project_id, schedule_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<ScheduleResponse>, ApiError> {
+    access
+        .require(&state, "schedule.read", Role::Viewer)
+        .await?;
+
+    let sc = load_schedule(&state, project_id, schedule_id).await?;
+    Ok(Json(to_response(sc)))
+}
+
+/// Schedule a change to one flag environment.
+///
+/// Scheduled changes do not go through change requests, so they are refused
+/// in environments that require approval.
+pub async fn create_schedule(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, environment_id)): Path<(Uuid, String, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<CreateScheduleRequest>,
+) -> Result<(StatusCode, Json<ScheduleResponse>), ApiError> {
+    let env = load_environment(&state, project_id, environment_id).await?;
+    access
+        .require_env(&state, "schedule.create", environment_id, Role::Editor)
+        .await?;
+    // A scheduled change applies without review, so it would bypass approval.
+    if env.requires_approval {
+        return Err(err(
+            StatusCode::CONFLICT,
+            "Environment requires approval; submit a change request instead of scheduling",
+        ));
+    }
+
+    if req.run_at <= Utc::now() {
+        return Err(err(StatusCode::BAD_REQUEST, "run_at must be in the future"));
+    }
+
+    let (flag, fe) = load_flag_environment(&state, project_id, &flag_key, environment_id).await?;
+    validate_action(&state, project_id, &flag, &fe, &req.change).await?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let sc = uow
+        .create_scheduled_change(
+            project_id,
+            fe.id,
+            &req.change,
+            req.run_at,
+            access.user_id,
+            access.actor_email.as_deref(),
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(environment_id),
+        "scheduled_change_created",
+        "scheduled_change",
+        Some(sc.id),
+        None,
+        serde_json::to_value(&sc).ok().as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok((StatusCode::CREATED, Json(to_response(sc))))
+}
+
+/// Cancel a pending scheduled change.
+pub async fn cancel_schedule(
+    State(state): State<AppState>,
+    Path((project_id, schedule_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<ScheduleResponse>, ApiError> {
+    let sc = load_schedule(&state, project_id, schedule_id).await?;
+    access
+        .require_env(&state, "schedule.cancel", sc.environment_id, Role::Editor)
+        .await?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let cancelled = uow
+        .cancel_scheduled_change(sc.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| {
+            err(
+                StatusCode::CONFLICT,
+                "Scheduled change is no longer pending",
+            )
+        })?;
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(cancelled.environment_id),
+        "scheduled_change_cancelled",
+        "scheduled_change",
+        Some(cancelled.id),
+        serde_json::to_value(&sc).ok().as_ref(),
+        serde_json::to_value(&cancelled).ok().as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(to_response(cancelled)))
+}
@@ -6,9 +6,11 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::api::middleware::auth::AuthInfo;
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::api::routes::environments::load_environment;
 use crate::auth::api_keys;
 use crate::state::AppState;
+use crate::store::models::SdkKeyRow;
 
 #[derive(Debug, Deserialize)]
 pub struct CreateSdkKeyRequest {
@@ -50,8 +52,10 @@ fn err(status: StatusCode, msg: &str) -> ApiError {
 pub async fn list_sdk_keys(
     State(state): State<AppState>,
     Path(project_id): Path<Uuid>,
-    Extension(_auth): Extension<AuthInfo>,
+    Extension(access): Extension<ProjectAccess>,
 ) -> Result<Json<Vec<SdkKeyResponse>>, ApiError> {
+    access.require(&state, "sdk_key.read", Role::Viewer).await?;
+
     let keys = state
         .store
         .list_sdk_keys_for_project(project_id)
@@ -75,12 +79,30 @@ pub async fn list_sdk_keys(
     Ok(Json(responses))
 }
 
+/// An SDK key as recorded in the audit log; the key hash is left out.
+fn audit_snapshot(key: &SdkKeyRow) -> serde_json::Value {
+    serde_json::json!({
+        "id": key.id,
+        "environment_id": key.environment_id,
+        "name": key.name,
+        "key_type": key.key_type,
+        "key_prefix": key.key_prefix,
+        "revoked_at": key.revoked_at,
+    })
+}
+
 pub async fn create_sdk_key(
     State(state): State<AppState>,
-    Path(_project_id): Path<Uuid>,
-    Extension(_auth): Extension<AuthInfo>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
     Json(req): Json<CreateSdkKeyRequest>,
 ) -> Result<(StatusCode, Json<CreateSdkKeyResponse>), ApiError> {
+    access
+        .require_env(&state, "sdk_key.create", req.environment_id, Role::Admin)
+        .await?;
+
+    load_environment(&state, project_id, req.environment_id).await?;
+
     let prefix = if req.key_type == "client" {
         "cli_"
     } else {
@@ -89,8 +111,12 @@ pub async fn create_sdk_key(
 
     let (raw_key, key_hash, key_prefix) = api_keys::generate_sdk_key(prefix);
 
-    let key = state
+    let mut uow = state
         .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let key = uow
         .create_sdk_key(
             req.environment_id,
             &req.name,
@@ -101,18 +127,20 @@ pub async fn create_sdk_key(
         .await
         .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
-    let _ = state
-        .store
-        .create_audit_log(
-            _project_id,
-            None,
-            "sdk_key_created",
-            "sdk_key",
-            Some(key.id),
-            None,
-            None,
-        )
-        .await;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(key.environment_id),
+        "sdk_key_created",
+        "sdk_key",
+        Some(key.id),
+        None,
+        Some(&audit_snapshot(&key)),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
     Ok((
         StatusCode::CREATED,
@@ -134,27 +162,45 @@ pub async fn create_sdk_key(
 
 pub async fn revoke_sdk_key(
     State(state): State<AppState>,
-    Path((_project_id, key_id)): Path<(Uuid, Uuid)>,
-    Extension(_auth): Extension<AuthInfo>,
+    Path((project_id, key_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
 ) -> Result<Json<SdkKeyResponse>, ApiError> {
-    let key = state
+    let before = state
         .store
+        .list_sdk_keys_for_project(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .find(|k| k.id == key_id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "SDK key not found"))?;
+    access
+        .require_env(&state, "sdk_key.revoke", before.environment_id, Role::Admin)
+        .await?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let key = uow
         .revoke_sdk_key(key_id)
         .await
         .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
-    let _ = state
-        .store
-        .create_audit_log(
-            _project_id,
-            None,
-            "sdk_key_revoked",
-            "sdk_key",
-            Some(key.id),
-            None,
-            None,
-        )
-        .await;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context().in_environment(key.environment_id),
+        "sdk_key_revoked",
+        "sdk_key",
+        Some(key.id),
+        Some(&audit_snapshot(&before)),
+        Some(&audit_snapshot(&key)),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
     Ok(Json(SdkKeyResponse {
         id: key.id.to_string(),
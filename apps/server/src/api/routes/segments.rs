@@ -1,13 +1,21 @@
 use axum::{
-    extract::{Extension, Path, State},
-    http::StatusCode,
+    extract::{Extension, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
     Json,
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::api::middleware::auth::AuthInfo;
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::api::middleware::precondition::{
+    check_revision, if_match, precondition_failed, with_etag,
+};
+use crate::api::routes::change_requests::audit_snapshot_in;
+use crate::api::routes::flags::notify_environment_changes;
 use crate::state::AppState;
+use crate::store::models::{SegmentConstraintRow, SegmentRow, SegmentUsageRow};
+use crate::store::postgres::UnitOfWork;
 
 #[derive(Debug, Deserialize)]
 pub struct CreateSegmentRequest {
@@ -20,7 +28,7 @@ pub struct CreateSegmentRequest {
     pub constraints: Vec<ConstraintInput>,
 }
 
-fn default_match_type() -> String {
+pub(crate) fn default_match_type() -> String {
     "all".to_string()
 }
 
@@ -31,6 +39,32 @@ pub struct ConstraintInput {
     pub values: Vec<String>,
 }
 
+/// Partial update; `constraints`, when present, replaces the full list.
+#[derive(Debug, Deserialize)]
+pub struct UpdateSegmentRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub match_type: Option<String>,
+    pub constraints: Option<Vec<ConstraintInput>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteSegmentQuery {
+    /// Delete even if targeting rules still reference the segment.
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SegmentUsageResponse {
+    pub flag_id: String,
+    pub flag_key: String,
+    pub environment_id: String,
+    pub environment_name: String,
+    pub rule_id: String,
+    pub negate: bool,
+}
+
 #[derive(Debug, Serialize)]
 pub struct SegmentResponse {
     pub id: String,
@@ -41,6 +75,8 @@ pub struct SegmentResponse {
     pub constraints: Vec<ConstraintResponse>,
     pub created_at: String,
     pub updated_at: String,
+    /// Also sent as the `ETag`; pass it back in `If-Match` to update safely.
+    pub revision: i64,
 }
 
 #[derive(Debug, Serialize)]
@@ -57,79 +93,219 @@ fn err(status: StatusCode, msg: &str) -> ApiError {
     (status, Json(serde_json::json!({ "error": msg })))
 }
 
+/// Load a segment, treating segments of other projects as missing.
+async fn load_segment(
+    state: &AppState,
+    project_id: Uuid,
+    segment_id: Uuid,
+) -> Result<SegmentRow, ApiError> {
+    state
+        .store
+        .get_segment(segment_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .filter(|s| s.project_id == project_id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Segment not found"))
+}
+
+/// Lock a segment inside `uow`, treating segments of other projects as
+/// missing.
+async fn lock_segment(
+    uow: &mut UnitOfWork,
+    project_id: Uuid,
+    segment_id: Uuid,
+) -> Result<SegmentRow, ApiError> {
+    uow.lock_segment(segment_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .filter(|s| s.project_id == project_id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Segment not found"))
+}
+
+/// A segment and its constraints as recorded in the audit log.
+fn audit_snapshot(
+    segment: &SegmentRow,
+    constraints: &[SegmentConstraintRow],
+) -> Option<serde_json::Value> {
+    let mut snapshot = serde_json::to_value(segment).ok()?;
+    snapshot["constraints"] = serde_json::to_value(constraints).ok()?;
+    Some(snapshot)
+}
+
+async fn load_usage(
+    state: &AppState,
+    segment_id: Uuid,
+) -> Result<Vec<SegmentUsageResponse>, ApiError> {
+    let usage = state
+        .store
+        .get_segment_usage(segment_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    Ok(to_usage_response(&usage))
+}
+
+/// Segments are shared across environments, so changing one needs the
+/// editor role in every environment whose rules use it.
+async fn require_usage_environments(
+    state: &AppState,
+    access: &ProjectAccess,
+    action: &str,
+    usage: &[SegmentUsageRow],
+) -> Result<(), ApiError> {
+    let mut environment_ids: Vec<Uuid> = usage.iter().map(|u| u.environment_id).collect();
+    environment_ids.sort();
+    environment_ids.dedup();
+    access
+        .require_envs(state, action, &environment_ids, Role::Editor)
+        .await
+}
+
+/// The uses of a segment in environments that require approval.
+async fn protected_usage(
+    uow: &mut UnitOfWork,
+    project_id: Uuid,
+    usage: &[SegmentUsageRow],
+) -> Result<Vec<SegmentUsageRow>, ApiError> {
+    let protected: Vec<Uuid> = uow
+        .list_environments(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .filter(|env| env.requires_approval)
+        .map(|env| env.id)
+        .collect();
+    Ok(usage
+        .iter()
+        .filter(|u| protected.contains(&u.environment_id))
+        .cloned()
+        .collect())
+}
+
+fn to_usage_response(usage: &[SegmentUsageRow]) -> Vec<SegmentUsageResponse> {
+    usage
+        .iter()
+        .map(|u| SegmentUsageResponse {
+            flag_id: u.flag_id.to_string(),
+            flag_key: u.flag_key.clone(),
+            environment_id: u.environment_id.to_string(),
+            environment_name: u.environment_name.clone(),
+            rule_id: u.rule_id.to_string(),
+            negate: u.negate,
+        })
+        .collect()
+}
+
+/// Reject an unknown match type or operator before anything is written.
+fn validate_segment(
+    match_type: Option<&str>,
+    constraints: &[ConstraintInput],
+) -> Result<(), ApiError> {
+    if let Some(match_type) = match_type {
+        if match_type != "all" && match_type != "any" {
+            return Err(err(StatusCode::BAD_REQUEST, "match_type must be 'all' or 'any'"));
+        }
+    }
+    for c in constraints {
+        let operator = serde_json::Value::String(c.operator.clone());
+        if serde_json::from_value::<eval_core::types::Operator>(operator).is_err() {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                &format!("Unknown operator '{}'", c.operator),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn constraint_tuples(inputs: Vec<ConstraintInput>) -> Vec<(String, String, Vec<String>)> {
+    inputs
+        .into_iter()
+        .map(|c| (c.attribute, c.operator, c.values))
+        .collect()
+}
+
+fn to_segment_response(
+    segment: SegmentRow,
+    constraints: Vec<SegmentConstraintRow>,
+) -> SegmentResponse {
+    SegmentResponse {
+        id: segment.id.to_string(),
+        key: segment.key,
+        name: segment.name,
+        description: segment.description,
+        match_type: segment.match_type,
+        constraints: constraints
+            .into_iter()
+            .map(|c| ConstraintResponse {
+                id: c.id.to_string(),
+                attribute: c.attribute,
+                operator: c.operator,
+                values: c.values,
+            })
+            .collect(),
+        created_at: segment.created_at.to_rfc3339(),
+        updated_at: segment.updated_at.to_rfc3339(),
+        revision: segment.revision,
+    }
+}
+
 pub async fn create_segment(
     State(state): State<AppState>,
     Path(project_id): Path<Uuid>,
-    Extension(_auth): Extension<AuthInfo>,
+    Extension(access): Extension<ProjectAccess>,
     Json(req): Json<CreateSegmentRequest>,
 ) -> Result<(StatusCode, Json<SegmentResponse>), ApiError> {
-    let segment = state
+    access.require(&state, "segment.create", Role::Editor).await?;
+    validate_segment(Some(&req.match_type), &req.constraints)?;
+
+    let mut uow = state
         .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let (segment, constraints) = uow
         .create_segment(
             project_id,
             &req.key,
             &req.name,
             req.description.as_deref(),
             &req.match_type,
+            &constraint_tuples(req.constraints),
         )
         .await
         .map_err(|e| err(StatusCode::CONFLICT, &e.to_string()))?;
 
-    let mut constraint_responses = Vec::new();
-    for (i, c) in req.constraints.iter().enumerate() {
-        let constraint = state
-            .store
-            .create_segment_constraint(
-                segment.id,
-                &c.attribute,
-                &c.operator,
-                &c.values,
-                i as i32,
-            )
-            .await
-            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "segment_created",
+        "segment",
+        Some(segment.id),
+        None,
+        audit_snapshot(&segment, &constraints).as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
-        constraint_responses.push(ConstraintResponse {
-            id: constraint.id.to_string(),
-            attribute: constraint.attribute,
-            operator: constraint.operator,
-            values: constraint.values,
-        });
-    }
-
-    let _ = state
-        .store
-        .create_audit_log(
-            project_id,
-            None,
-            "segment_created",
-            "segment",
-            Some(segment.id),
-            None,
-            None,
-        )
-        .await;
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
 
     Ok((
         StatusCode::CREATED,
-        Json(SegmentResponse {
-            id: segment.id.to_string(),
-            key: segment.key,
-            name: segment.name,
-            description: segment.description,
-            match_type: segment.match_type,
-            constraints: constraint_responses,
-            created_at: segment.created_at.to_rfc3339(),
-            updated_at: segment.updated_at.to_rfc3339(),
-        }),
+        Json(to_segment_response(segment, constraints)),
     ))
 }
 
 pub async fn list_segments(
     State(state): State<AppState>,
     Path(project_id): Path<Uuid>,
-    Extension(_auth): Extension<AuthInfo>,
+    Extension(access): Extension<ProjectAccess>,
 ) -> Result<Json<Vec<SegmentResponse>>, ApiError> {
+    access.require(&state, "segment.read", Role::Viewer).await?;
+
     let segments = state
         .store
         .list_segments(project_id)
@@ -144,24 +320,7 @@ pub async fn list_segments(
             .await
             .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
-        responses.push(SegmentResponse {
-            id: seg.id.to_string(),
-            key: seg.key,
-            name: seg.name,
-            description: seg.description,
-            match_type: seg.match_type,
-            constraints: constraints
-                .into_iter()
-                .map(|c| ConstraintResponse {
-                    id: c.id.to_string(),
-                    attribute: c.attribute,
-                    operator: c.operator,
-                    values: c.values,
-                })
-                .collect(),
-            created_at: seg.created_at.to_rfc3339(),
-            updated_at: seg.updated_at.to_rfc3339(),
-        });
+        responses.push(to_segment_response(seg, constraints));
     }
 
     Ok(Json(responses))
@@ -169,38 +328,224 @@ pub async fn list_segments(
 
 pub async fn get_segment(
     State(state): State<AppState>,
-    Path((_project_id, segment_id)): Path<(Uuid, Uuid)>,
-    Extension(_auth): Extension<AuthInfo>,
-) -> Result<Json<SegmentResponse>, ApiError> {
-    let segment = state
+    Path((project_id, segment_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Response, ApiError> {
+    access.require(&state, "segment.read", Role::Viewer).await?;
+
+    let segment = load_segment(&state, project_id, segment_id).await?;
+
+    let constraints = state
         .store
-        .get_segment(segment_id)
+        .get_segment_constraints(segment.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let response = to_segment_response(segment, constraints);
+    Ok(with_etag(response.revision, Json(response)))
+}
+
+/// Update a segment. Its match type and constraints cannot change while
+/// rules in an environment that requires approval use it.
+pub async fn update_segment(
+    State(state): State<AppState>,
+    Path((project_id, segment_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    headers: HeaderMap,
+    Json(req): Json<UpdateSegmentRequest>,
+) -> Result<Response, ApiError> {
+    access.require(&state, "segment.update", Role::Editor).await?;
+    let precondition = if_match(&headers)?;
+    validate_segment(
+        req.match_type.as_deref(),
+        req.constraints.as_deref().unwrap_or_default(),
+    )?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let before_segment = lock_segment(&mut uow, project_id, segment_id).await?;
+    let expected_revision = check_revision(precondition.as_deref(), before_segment.revision)?;
+    let usage = uow
+        .get_segment_usage(segment_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    require_usage_environments(&state, &access, "segment.update", &usage).await?;
+    // Renaming is harmless, but new constraints change who the rules using
+    // the segment match.
+    if req.match_type.is_some() || req.constraints.is_some() {
+        let blocked = protected_usage(&mut uow, project_id, &usage).await?;
+        if !blocked.is_empty() {
+            return Err((
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({
+                    "error": "Segment is used in environments that require approval; change the rules there through change requests instead",
+                    "usage": to_usage_response(&blocked),
+                })),
+            ));
+        }
+    }
+    let before_constraints = uow
+        .get_segment_constraints(segment_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let before = audit_snapshot(&before_segment, &before_constraints);
+
+    let constraints = req.constraints.map(constraint_tuples);
+    let (segment, constraints) = uow
+        .update_segment(
+            segment_id,
+            expected_revision,
+            req.name.as_deref(),
+            req.description.as_deref(),
+            req.match_type.as_deref(),
+            constraints.as_deref(),
+        )
         .await
         .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
-        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Segment not found"))?;
+        .ok_or_else(precondition_failed)?;
 
-    let constraints = state
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "segment_updated",
+        "segment",
+        Some(segment.id),
+        before.as_ref(),
+        audit_snapshot(&segment, &constraints).as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    Ok(with_etag(
+        segment.revision,
+        Json(to_segment_response(segment, constraints)),
+    ))
+}
+
+/// Delete a segment. With `?force=true` the targeting rules that reference
+/// it are deleted too, each recorded in its flag environment's audit trail;
+/// rules in environments that require approval must be changed through
+/// change requests first.
+pub async fn delete_segment(
+    State(state): State<AppState>,
+    Path((project_id, segment_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Query(query): Query<DeleteSegmentQuery>,
+) -> Result<StatusCode, ApiError> {
+    access.require(&state, "segment.delete", Role::Editor).await?;
+
+    let mut uow = state
         .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let segment = lock_segment(&mut uow, project_id, segment_id).await?;
+    let constraints = uow
         .get_segment_constraints(segment.id)
         .await
         .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let before = audit_snapshot(&segment, &constraints);
 
-    Ok(Json(SegmentResponse {
-        id: segment.id.to_string(),
-        key: segment.key,
-        name: segment.name,
-        description: segment.description,
-        match_type: segment.match_type,
-        constraints: constraints
-            .into_iter()
-            .map(|c| ConstraintResponse {
-                id: c.id.to_string(),
-                attribute: c.attribute,
-                operator: c.operator,
-                values: c.values,
-            })
-            .collect(),
-        created_at: segment.created_at.to_rfc3339(),
-        updated_at: segment.updated_at.to_rfc3339(),
-    }))
+    let usage = uow
+        .get_segment_usage(segment.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    require_usage_environments(&state, &access, "segment.delete", &usage).await?;
+    if !usage.is_empty() && !query.force {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": "Segment is referenced by targeting rules; pass ?force=true to delete them with it",
+                "usage": to_usage_response(&usage),
+            })),
+        ));
+    }
+
+    let blocked = protected_usage(&mut uow, project_id, &usage).await?;
+    if !blocked.is_empty() {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": "Segment is used in environments that require approval; remove it from those rules through change requests first",
+                "usage": to_usage_response(&blocked),
+            })),
+        ));
+    }
+
+    // Rules grouped by flag environment, so each gets before/after snapshots.
+    let mut affected: Vec<(Uuid, Uuid, Vec<Uuid>)> = Vec::new();
+    for u in &usage {
+        match affected
+            .iter_mut()
+            .find(|(flag_id, env_id, _)| *flag_id == u.flag_id && *env_id == u.environment_id)
+        {
+            Some((_, _, rules)) => rules.push(u.rule_id),
+            None => affected.push((u.flag_id, u.environment_id, vec![u.rule_id])),
+        }
+    }
+    let mut befores = Vec::with_capacity(affected.len());
+    for (flag_id, environment_id, _) in &affected {
+        befores.push(audit_snapshot_in(&mut uow, project_id, *flag_id, *environment_id).await);
+    }
+
+    uow.delete_segment(&segment)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    for ((flag_id, environment_id, rules), before) in affected.iter().zip(&befores) {
+        let after = audit_snapshot_in(&mut uow, project_id, *flag_id, *environment_id).await;
+        for rule_id in rules {
+            uow.create_audit_log(
+                project_id,
+                &access.audit_context().in_environment(*environment_id),
+                "rule_deleted",
+                "targeting_rule",
+                Some(*rule_id),
+                before.as_ref(),
+                after.as_ref(),
+            )
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+        }
+    }
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "segment_deleted",
+        "segment",
+        Some(segment.id),
+        before.as_ref(),
+        None,
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List the flags, environments and rules that reference a segment.
+pub async fn get_segment_usage(
+    State(state): State<AppState>,
+    Path((project_id, segment_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<Vec<SegmentUsageResponse>>, ApiError> {
+    access.require(&state, "segment.read", Role::Viewer).await?;
+
+    let segment = load_segment(&state, project_id, segment_id).await?;
+    Ok(Json(load_usage(&state, segment.id).await?))
 }
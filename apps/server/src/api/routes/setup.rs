@@ -1,11 +1,20 @@
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    Json,
+};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
+use crate::api::middleware::authz::{CurrentUser, Role};
+use crate::api::middleware::request_context::RequestContext;
 use crate::auth::api_keys;
 use crate::state::AppState;
+use crate::store::postgres::UnitOfWork;
 
 /// Bootstrap request — creates an org, project, and default environments.
-/// Only used for initial setup (Phase 1 — no dashboard).
+/// Only used for initial setup (Phase 1 — no dashboard). The caller becomes
+/// the owner of both.
 #[derive(Debug, Deserialize)]
 pub struct SetupRequest {
     pub org_name: String,
@@ -30,32 +39,19 @@ pub struct EnvironmentInfo {
     pub client_key: String,
 }
 
-pub async fn setup(
-    State(state): State<AppState>,
-    Json(req): Json<SetupRequest>,
-) -> Result<(StatusCode, Json<SetupResponse>), (StatusCode, Json<serde_json::Value>)> {
-    let org = state
-        .store
-        .create_organization(&req.org_name, &req.org_slug)
-        .await
-        .map_err(|e| {
-            (
-                StatusCode::CONFLICT,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-        })?;
-
-    let project = state
-        .store
-        .create_project(org.id, &req.project_name, &req.project_slug, None)
-        .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-        })?;
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
 
+/// Create the Development/Staging/Production environments for a new project,
+/// each with a server and a client SDK key. The plaintext keys are only ever
+/// returned here.
+pub(crate) async fn provision_default_environments(
+    uow: &mut UnitOfWork,
+    project_id: Uuid,
+) -> Result<Vec<EnvironmentInfo>, ApiError> {
     let env_configs = [
         ("Development", "development", "#22c55e"),
         ("Staging", "staging", "#eab308"),
@@ -65,42 +61,34 @@ pub async fn setup(
     let mut environments = Vec::new();
 
     for (name, slug, color) in &env_configs {
-        let env = state
-            .store
-            .create_environment(project.id, name, slug, Some(color))
+        let env = uow
+            .create_environment(project_id, name, slug, Some(color), false)
             .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({ "error": e.to_string() })),
-                )
-            })?;
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
         // Generate SDK keys
         let (server_key, server_hash, server_prefix) = api_keys::generate_sdk_key("srv_");
         let (client_key, client_hash, client_prefix) = api_keys::generate_sdk_key("cli_");
 
-        state
-            .store
-            .create_sdk_key(env.id, &format!("{name} Server Key"), "server", &server_hash, &server_prefix)
-            .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({ "error": e.to_string() })),
-                )
-            })?;
-
-        state
-            .store
-            .create_sdk_key(env.id, &format!("{name} Client Key"), "client", &client_hash, &client_prefix)
-            .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({ "error": e.to_string() })),
-                )
-            })?;
+        uow.create_sdk_key(
+            env.id,
+            &format!("{name} Server Key"),
+            "server",
+            &server_hash,
+            &server_prefix,
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+        uow.create_sdk_key(
+            env.id,
+            &format!("{name} Client Key"),
+            "client",
+            &client_hash,
+            &client_prefix,
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
         environments.push(EnvironmentInfo {
             id: env.id.to_string(),
@@ -111,18 +99,56 @@ pub async fn setup(
         });
     }
 
-    let _ = state
+    Ok(environments)
+}
+
+pub async fn setup(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Extension(request): Extension<RequestContext>,
+    Json(req): Json<SetupRequest>,
+) -> Result<(StatusCode, Json<SetupResponse>), ApiError> {
+    let mut uow = state
         .store
-        .create_audit_log(
-            project.id,
-            None,
-            "project_created",
-            "project",
-            Some(project.id),
-            None,
-            None,
-        )
-        .await;
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let org = uow
+        .create_organization(&req.org_name, &req.org_slug, None)
+        .await
+        .map_err(|e| err(StatusCode::CONFLICT, &e.to_string()))?;
+
+    uow.add_organization_member(org.id, user.id, Role::Owner.as_str())
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let project = uow
+        .create_project(org.id, &req.project_name, &req.project_slug, None)
+        .await
+        .map_err(|e| err(StatusCode::CONFLICT, &e.to_string()))?;
+
+    uow.set_project_member_role(project.id, user.id, Role::Owner.as_str())
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let environments = provision_default_environments(&mut uow, project.id).await?;
+
+    uow.create_audit_log(
+        project.id,
+        &user.audit_context(&request),
+        "project_created",
+        "project",
+        Some(project.id),
+        None,
+        serde_json::to_value(&project).ok().as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
 
     Ok((
         StatusCode::CREATED,
@@ -0,0 +1,370 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::api::routes::flags::{
+    notify_config_change, notify_environment_changes, refuse_approval_environments,
+    VariantResponse,
+};
+use crate::state::AppState;
+use crate::store::models::{FlagRow, FlagVariantRow};
+
+// ============================================================
+// Request types
+// ============================================================
+
+#[derive(Debug, Deserialize)]
+pub struct CreateVariantRequest {
+    pub key: String,
+    pub value: serde_json::Value,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateVariantRequest {
+    pub value: Option<serde_json::Value>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReorderVariantsRequest {
+    /// Every variant of the flag, in the desired order.
+    pub variant_ids: Vec<Uuid>,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+// ============================================================
+// Helpers
+// ============================================================
+
+/// Check that a variant value matches the flag's declared type.
+pub(crate) fn validate_variant_value(
+    flag_type: &str,
+    value: &serde_json::Value,
+) -> Result<(), ApiError> {
+    let ok = match flag_type {
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        _ => !value.is_null(),
+    };
+
+    if ok {
+        Ok(())
+    } else {
+        Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!("Variant value {value} is not a valid {flag_type}"),
+        ))
+    }
+}
+
+async fn load_flag(
+    state: &AppState,
+    project_id: Uuid,
+    flag_key: &str,
+) -> Result<FlagRow, ApiError> {
+    state
+        .store
+        .get_flag_by_key(project_id, flag_key)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Flag not found"))
+}
+
+async fn load_variant(
+    state: &AppState,
+    flag: &FlagRow,
+    variant_id: Uuid,
+) -> Result<FlagVariantRow, ApiError> {
+    state
+        .store
+        .get_flag_variants(flag.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .find(|v| v.id == variant_id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Variant not found"))
+}
+
+/// A variant is shared by every environment of its flag, so changing it
+/// needs the editor role in each environment that uses it.
+async fn require_variant_environments(
+    state: &AppState,
+    access: &ProjectAccess,
+    action: &str,
+    variant_id: Uuid,
+) -> Result<(), ApiError> {
+    let environment_ids = state
+        .store
+        .get_variant_environments(variant_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    access
+        .require_envs(state, action, &environment_ids, Role::Editor)
+        .await
+}
+
+fn to_response(v: FlagVariantRow) -> VariantResponse {
+    VariantResponse {
+        id: v.id.to_string(),
+        key: v.key,
+        value: v.value,
+        description: v.description,
+    }
+}
+
+// ============================================================
+// Handlers
+// ============================================================
+
+pub async fn create_variant(
+    State(state): State<AppState>,
+    Path((project_id, flag_key)): Path<(Uuid, String)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<CreateVariantRequest>,
+) -> Result<(StatusCode, Json<VariantResponse>), ApiError> {
+    access.require(&state, "variant.create", Role::Editor).await?;
+
+    let flag = load_flag(&state, project_id, &flag_key).await?;
+    validate_variant_value(&flag.flag_type, &req.value)?;
+
+    let existing = state
+        .store
+        .get_flag_variants(flag.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let sort_order = existing.iter().map(|v| v.sort_order + 1).max().unwrap_or(0);
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let variant = uow
+        .create_flag_variant(
+            flag.id,
+            &req.key,
+            &req.value,
+            req.description.as_deref(),
+            sort_order,
+        )
+        .await
+        .map_err(|e| err(StatusCode::CONFLICT, &e.to_string()))?;
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "variant_created",
+        "flag_variant",
+        Some(variant.id),
+        None,
+        serde_json::to_value(&variant).ok().as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_config_change(&state, project_id).await;
+
+    Ok((StatusCode::CREATED, Json(to_response(variant))))
+}
+
+pub async fn update_variant(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, variant_id)): Path<(Uuid, String, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<UpdateVariantRequest>,
+) -> Result<Json<VariantResponse>, ApiError> {
+    access.require(&state, "variant.update", Role::Editor).await?;
+
+    let flag = load_flag(&state, project_id, &flag_key).await?;
+    let before = load_variant(&state, &flag, variant_id).await?;
+    require_variant_environments(&state, &access, "variant.update", before.id).await?;
+
+    if let Some(ref value) = req.value {
+        validate_variant_value(&flag.flag_type, value)?;
+    }
+
+    // A new value changes what every environment serving the variant returns.
+    if req.value.as_ref().is_some_and(|value| *value != before.value) {
+        let variant_environments = state
+            .store
+            .get_variant_environments(before.id)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+        let environments: Vec<_> = state
+            .store
+            .list_environments(project_id)
+            .await
+            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+            .into_iter()
+            .filter(|env| variant_environments.contains(&env.id))
+            .collect();
+        refuse_approval_environments(&environments, "Changing a variant's value")?;
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let updated = uow
+        .update_flag_variant(variant_id, req.value.as_ref(), req.description.as_deref())
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "variant_updated",
+        "flag_variant",
+        Some(updated.id),
+        serde_json::to_value(&before).ok().as_ref(),
+        serde_json::to_value(&updated).ok().as_ref(),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_config_change(&state, project_id).await;
+
+    Ok(Json(to_response(updated)))
+}
+
+pub async fn reorder_variants(
+    State(state): State<AppState>,
+    Path((project_id, flag_key)): Path<(Uuid, String)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<ReorderVariantsRequest>,
+) -> Result<Json<Vec<VariantResponse>>, ApiError> {
+    access.require(&state, "variant.reorder", Role::Editor).await?;
+
+    let flag = load_flag(&state, project_id, &flag_key).await?;
+
+    let before = state
+        .store
+        .get_flag_variants(flag.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    // The new order must be a permutation of the current variants.
+    let mut requested = req.variant_ids.clone();
+    requested.sort();
+    requested.dedup();
+    let mut current: Vec<Uuid> = before.iter().map(|v| v.id).collect();
+    current.sort();
+    if requested.len() != req.variant_ids.len() || requested != current {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "variant_ids must list every variant of this flag exactly once",
+        ));
+    }
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let after = uow
+        .reorder_flag_variants(flag.id, &req.variant_ids)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let order =
+        |vs: &[FlagVariantRow]| serde_json::json!(vs.iter().map(|v| &v.key).collect::<Vec<_>>());
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "variants_reordered",
+        "flag",
+        Some(flag.id),
+        Some(&order(&before)),
+        Some(&order(&after)),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_config_change(&state, project_id).await;
+
+    Ok(Json(after.into_iter().map(to_response).collect()))
+}
+
+pub async fn delete_variant(
+    State(state): State<AppState>,
+    Path((project_id, flag_key, variant_id)): Path<(Uuid, String, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<StatusCode, ApiError> {
+    access.require(&state, "variant.delete", Role::Editor).await?;
+
+    let flag = load_flag(&state, project_id, &flag_key).await?;
+    let variant = load_variant(&state, &flag, variant_id).await?;
+    require_variant_environments(&state, &access, "variant.delete", variant.id).await?;
+
+    // The lock makes writes that would reference the variant wait, so the
+    // usage check holds until the delete commits.
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let variant = uow
+        .lock_flag_variant(flag.id, variant.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Variant not found"))?;
+    let usage = uow
+        .get_variant_usage(variant.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    if !usage.is_unused() {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": "Variant is still in use",
+                "usage": usage,
+            })),
+        ));
+    }
+
+    uow.delete_flag_variant(project_id, variant.id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "variant_deleted",
+        "flag_variant",
+        Some(variant.id),
+        serde_json::to_value(&variant).ok().as_ref(),
+        None,
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let changed = uow
+        .commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    notify_environment_changes(&state, &changed).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,431 @@
+use std::net::IpAddr;
+
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::middleware::authz::{ProjectAccess, Role};
+use crate::auth::api_keys;
+use crate::state::AppState;
+use crate::store::models::{WebhookDeliveryRow, WebhookRow};
+use crate::webhook_worker;
+
+/// Upper bound on `limit` when listing deliveries.
+const MAX_DELIVERY_PAGE_SIZE: i64 = 200;
+
+const DELIVERY_STATUSES: &[&str] = &["pending", "succeeded", "failed"];
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub description: Option<String>,
+    /// Audit log actions such as `flag_toggled` or `sdk_key_revoked`, or
+    /// `config_changed`. Empty subscribes to every event.
+    #[serde(default)]
+    pub events: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhookRequest {
+    pub url: Option<String>,
+    pub description: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+    /// Replace the signing secret; the new one is returned once.
+    #[serde(default)]
+    pub rotate_secret: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListDeliveriesQuery {
+    pub status: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+}
+
+fn default_limit() -> i64 {
+    50
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookResponse {
+    pub id: String,
+    pub url: String,
+    pub description: Option<String>,
+    pub events: Vec<String>,
+    pub enabled: bool,
+    /// Only returned when the webhook is created or its secret rotated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookDeliveryResponse {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    /// When the next attempt is due, for pending deliveries.
+    pub next_attempt_at: Option<String>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<String>,
+    pub created_at: String,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+fn to_response(hook: WebhookRow, show_secret: bool) -> WebhookResponse {
+    WebhookResponse {
+        id: hook.id.to_string(),
+        url: hook.url,
+        description: hook.description,
+        events: hook.events,
+        enabled: hook.enabled,
+        secret: show_secret.then_some(hook.secret),
+        created_by: hook.created_by.map(|id| id.to_string()),
+        created_at: hook.created_at.to_rfc3339(),
+        updated_at: hook.updated_at.to_rfc3339(),
+    }
+}
+
+fn to_delivery_response(d: WebhookDeliveryRow) -> WebhookDeliveryResponse {
+    WebhookDeliveryResponse {
+        id: d.id.to_string(),
+        webhook_id: d.webhook_id.to_string(),
+        event: d.event,
+        payload: d.payload,
+        next_attempt_at: (d.status == "pending").then(|| d.next_attempt_at.to_rfc3339()),
+        status: d.status,
+        attempts: d.attempts,
+        last_status_code: d.last_status_code,
+        last_error: d.last_error,
+        delivered_at: d.delivered_at.map(|t| t.to_rfc3339()),
+        created_at: d.created_at.to_rfc3339(),
+    }
+}
+
+/// A webhook as recorded in the audit log; the secret is left out.
+fn audit_snapshot(hook: &WebhookRow) -> serde_json::Value {
+    serde_json::json!({
+        "id": hook.id,
+        "url": hook.url,
+        "description": hook.description,
+        "events": hook.events,
+        "enabled": hook.enabled,
+    })
+}
+
+/// Rejects anything but absolute http(s) URLs whose host resolves only to
+/// public addresses. Deliveries re-check at connect time, since DNS can
+/// change after the webhook is saved.
+async fn validate_url(url: &str) -> Result<(), ApiError> {
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => parsed,
+        _ => {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                "url must be an absolute http or https URL",
+            ))
+        }
+    };
+    let host = parsed.host_str().unwrap_or_default();
+    let addrs: Vec<IpAddr> = match webhook_worker::literal_ip(&parsed) {
+        Some(ip) => vec![ip],
+        None => {
+            let port = parsed.port_or_known_default().unwrap_or(0);
+            tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|_| {
+                    err(StatusCode::BAD_REQUEST, &format!("Could not resolve host '{host}'"))
+                })?
+                .map(|addr| addr.ip())
+                .collect()
+        }
+    };
+    if addrs.iter().any(|ip| !webhook_worker::is_public(*ip)) {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "url must point to a public address, not a loopback or private network",
+        ));
+    }
+    Ok(())
+}
+
+fn validate_events(events: &[String]) -> Result<(), ApiError> {
+    let valid = |e: &String| !e.is_empty() && e.chars().all(|c| c.is_ascii_lowercase() || c == '_');
+    if let Some(bad) = events.iter().find(|e| !valid(e)) {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!("Invalid event type '{bad}'"),
+        ));
+    }
+    Ok(())
+}
+
+async fn load_webhook(
+    state: &AppState,
+    project_id: Uuid,
+    webhook_id: Uuid,
+) -> Result<WebhookRow, ApiError> {
+    state
+        .store
+        .get_webhook(project_id, webhook_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Webhook not found"))
+}
+
+pub async fn list_webhooks(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<Vec<WebhookResponse>>, ApiError> {
+    access.require(&state, "webhook.read", Role::Viewer).await?;
+
+    let hooks = state
+        .store
+        .list_webhooks(project_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(
+        hooks.into_iter().map(|h| to_response(h, false)).collect(),
+    ))
+}
+
+pub async fn get_webhook(
+    State(state): State<AppState>,
+    Path((project_id, webhook_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<Json<WebhookResponse>, ApiError> {
+    access.require(&state, "webhook.read", Role::Viewer).await?;
+
+    let hook = load_webhook(&state, project_id, webhook_id).await?;
+    Ok(Json(to_response(hook, false)))
+}
+
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    Path(project_id): Path<Uuid>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<WebhookResponse>), ApiError> {
+    access
+        .require(&state, "webhook.create", Role::Admin)
+        .await?;
+
+    validate_url(&req.url).await?;
+    validate_events(&req.events)?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let hook = uow
+        .create_webhook(
+            project_id,
+            &req.url,
+            req.description.as_deref(),
+            &api_keys::generate_webhook_secret(),
+            &req.events,
+            req.enabled,
+            access.user_id,
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "webhook_created",
+        "webhook",
+        Some(hook.id),
+        None,
+        Some(&audit_snapshot(&hook)),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok((StatusCode::CREATED, Json(to_response(hook, true))))
+}
+
+pub async fn update_webhook(
+    State(state): State<AppState>,
+    Path((project_id, webhook_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Json(req): Json<UpdateWebhookRequest>,
+) -> Result<Json<WebhookResponse>, ApiError> {
+    access
+        .require(&state, "webhook.update", Role::Admin)
+        .await?;
+
+    let before = load_webhook(&state, project_id, webhook_id).await?;
+    if let Some(ref url) = req.url {
+        validate_url(url).await?;
+    }
+    if let Some(ref events) = req.events {
+        validate_events(events)?;
+    }
+    let secret = req.rotate_secret.then(api_keys::generate_webhook_secret);
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let hook = uow
+        .update_webhook(
+            webhook_id,
+            req.url.as_deref(),
+            req.description.as_deref(),
+            req.events.as_deref(),
+            req.enabled,
+            secret.as_deref(),
+        )
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    let action = if req.rotate_secret {
+        "webhook_secret_rotated"
+    } else {
+        "webhook_updated"
+    };
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        action,
+        "webhook",
+        Some(hook.id),
+        Some(&audit_snapshot(&before)),
+        Some(&audit_snapshot(&hook)),
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(to_response(hook, req.rotate_secret)))
+}
+
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Path((project_id, webhook_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<StatusCode, ApiError> {
+    access
+        .require(&state, "webhook.delete", Role::Admin)
+        .await?;
+
+    let before = load_webhook(&state, project_id, webhook_id).await?;
+
+    let mut uow = state
+        .store
+        .begin()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.delete_webhook(webhook_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    uow.create_audit_log(
+        project_id,
+        &access.audit_context(),
+        "webhook_deleted",
+        "webhook",
+        Some(webhook_id),
+        Some(&audit_snapshot(&before)),
+        None,
+    )
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    uow.commit()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The delivery log of a webhook, newest first.
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    Path((project_id, webhook_id)): Path<(Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+    Query(query): Query<ListDeliveriesQuery>,
+) -> Result<Json<Vec<WebhookDeliveryResponse>>, ApiError> {
+    access
+        .require(&state, "webhook.read_deliveries", Role::Admin)
+        .await?;
+
+    load_webhook(&state, project_id, webhook_id).await?;
+    if let Some(ref status) = query.status {
+        if !DELIVERY_STATUSES.contains(&status.as_str()) {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                &format!("status must be one of: {}", DELIVERY_STATUSES.join(", ")),
+            ));
+        }
+    }
+    if !(1..=MAX_DELIVERY_PAGE_SIZE).contains(&query.limit) {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!("limit must be between 1 and {MAX_DELIVERY_PAGE_SIZE}"),
+        ));
+    }
+
+    let deliveries = state
+        .store
+        .list_webhook_deliveries(webhook_id, query.status.as_deref(), query.limit)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(
+        deliveries.into_iter().map(to_delivery_response).collect(),
+    ))
+}
+
+/// Queue a past delivery again with the same event and payload. The new
+/// delivery is signed afresh when it is sent.
+pub async fn redeliver(
+    State(state): State<AppState>,
+    Path((project_id, webhook_id, delivery_id)): Path<(Uuid, Uuid, Uuid)>,
+    Extension(access): Extension<ProjectAccess>,
+) -> Result<(StatusCode, Json<WebhookDeliveryResponse>), ApiError> {
+    access
+        .require(&state, "webhook.redeliver", Role::Admin)
+        .await?;
+
+    load_webhook(&state, project_id, webhook_id).await?;
+    let delivery = state
+        .store
+        .redeliver_webhook_delivery(webhook_id, delivery_id)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Delivery not found"))?;
+
+    Ok((StatusCode::ACCEPTED, Json(to_delivery_response(delivery))))
+}
@@ -17,3 +17,10 @@ pub fn hash_key(key: &str) -> String {
     hasher.update(key.as_bytes());
     hex::encode(hasher.finalize())
 }
+
+/// Generate a webhook signing secret. Unlike SDK keys it is stored as is,
+/// since the server needs it to sign deliveries.
+pub fn generate_webhook_secret() -> String {
+    let random_bytes: Vec<u8> = (0..32).map(|_| rand::thread_rng().gen()).collect();
+    format!("whsec_{}", hex::encode(&random_bytes))
+}
@@ -1,4 +1,7 @@
 use std::env;
+use std::net::IpAddr;
+
+use ipnet::IpNet;
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -7,7 +10,13 @@ pub struct Config {
     pub database_url: String,
     pub redis_url: String,
     pub clerk_domain: String,
+    /// Secret keying the audit log's hash chain.
+    pub audit_hmac_key: String,
     pub log_level: String,
+    /// Reverse proxies whose `X-Forwarded-For` entries are believed.
+    pub trusted_proxies: Vec<IpNet>,
+    /// Clerk user ID made owner of organizations that have no members yet.
+    pub bootstrap_owner: Option<String>,
 }
 
 impl Config {
@@ -24,8 +33,18 @@ impl Config {
                 .unwrap_or_else(|_| "redis://127.0.0.1:6379".into()),
             clerk_domain: env::var("CLERK_DOMAIN")
                 .expect("CLERK_DOMAIN must be set (e.g. your-app.clerk.accounts.dev)"),
+            audit_hmac_key: env::var("AUDIT_HMAC_KEY")
+                .ok()
+                .filter(|v| v.len() >= 32)
+                .expect("AUDIT_HMAC_KEY must be set to a secret of at least 32 characters"),
             log_level: env::var("LOG_LEVEL")
                 .unwrap_or_else(|_| "info".into()),
+            trusted_proxies: env::var("TRUSTED_PROXIES")
+                .map(|v| parse_networks(&v))
+                .unwrap_or_default(),
+            bootstrap_owner: env::var("BOOTSTRAP_OWNER")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
         }
     }
 
@@ -33,3 +52,17 @@ impl Config {
         format!("{}:{}", self.host, self.port)
     }
 }
+
+/// Comma-separated addresses or CIDR ranges, e.g. `10.0.0.0/8,127.0.0.1`.
+fn parse_networks(value: &str) -> Vec<IpNet> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            v.parse::<IpNet>()
+                .or_else(|_| v.parse::<IpAddr>().map(IpNet::from))
+                .unwrap_or_else(|_| panic!("TRUSTED_PROXIES: invalid address or range {v:?}"))
+        })
+        .collect()
+}
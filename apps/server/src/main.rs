@@ -2,14 +2,16 @@ mod api;
 mod auth;
 mod broadcaster;
 mod config;
+mod scheduler;
 mod state;
 mod store;
+mod webhook_worker;
 
 use std::sync::Arc;
 
 use axum::{
     middleware as axum_mw,
-    routing::{get, patch, post},
+    routing::{delete, get, patch, post, put},
     Router,
 };
 use tower_http::cors::{Any, CorsLayer};
@@ -17,7 +19,9 @@ use tower_http::trace::TraceLayer;
 use tracing_subscriber::{fmt, EnvFilter};
 use axum::http::Request;
 
-use crate::api::middleware::auth::{require_auth, require_sdk_key};
+use crate::api::middleware::auth::{require_auth, require_jwt, require_sdk_key};
+use crate::api::middleware::authz::authorize;
+use crate::api::middleware::request_context::{request_context, TrustedProxies};
 use crate::api::routes::*;
 use crate::auth::jwt::JwksCache;
 use crate::broadcaster::{Broadcaster, ConfigChangeEvent};
@@ -42,10 +46,17 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("Starting FlagForge server v{}", env!("CARGO_PKG_VERSION"));
 
     // Connect to PostgreSQL
-    let store = PostgresStore::new(&config.database_url).await?;
+    let store = PostgresStore::new(&config.database_url, config.audit_hmac_key.as_bytes()).await?;
     store.run_migrations().await?;
     tracing::info!("Database connected and migrations applied");
 
+    if let Some(ref owner) = config.bootstrap_owner {
+        let claimed = store.claim_unowned_organizations(owner).await?;
+        if !claimed.is_empty() {
+            tracing::info!("Made {owner} the owner of {} unclaimed organization(s)", claimed.len());
+        }
+    }
+
     // Connect to Redis (optional — graceful degradation)
     let redis = match RedisStore::new(&config.redis_url).await {
         Ok(r) => {
@@ -101,27 +112,42 @@ async fn main() -> anyhow::Result<()> {
     }
 
     let state = AppState {
-        config: config.clone(),
         store,
         redis,
         jwks,
         broadcaster,
     };
 
+    // Spawn scheduled change and rollout worker
+    tokio::spawn(scheduler::run(state.clone()));
+
+    // Spawn webhook delivery worker
+    tokio::spawn(webhook_worker::run(state.clone()));
+
     // Build router
     let app = Router::new()
         // Public endpoints
         .route("/health", get(health::health_check))
-        .route("/api/v1/setup", post(setup::setup))
-        // Projects API (JWT auth, top-level)
+        // Organizations & projects API (JWT auth, top-level)
         .nest(
             "/api/v1",
-            projects_routes().layer(axum_mw::from_fn_with_state(state.clone(), require_auth)),
+            projects_routes()
+                .route_layer(axum_mw::from_fn_with_state(state.clone(), authorize))
+                .layer(axum_mw::from_fn_with_state(state.clone(), require_jwt)),
         )
         // Management API (JWT auth)
         .nest(
             "/api/v1/projects/{project_id}",
-            management_routes().layer(axum_mw::from_fn_with_state(state.clone(), require_auth)),
+            management_routes()
+                .route_layer(axum_mw::from_fn_with_state(state.clone(), authorize))
+                .layer(axum_mw::from_fn_with_state(state.clone(), require_jwt)),
+        )
+        // Machine API (JWT or server SDK key)
+        .nest(
+            "/api/v1/projects/{project_id}",
+            machine_routes()
+                .route_layer(axum_mw::from_fn_with_state(state.clone(), authorize))
+                .layer(axum_mw::from_fn_with_state(state.clone(), require_auth)),
         )
         // Evaluation API (SDK key auth)
         .nest(
@@ -129,6 +155,10 @@ async fn main() -> anyhow::Result<()> {
             evaluation_routes().layer(axum_mw::from_fn_with_state(state.clone(), require_sdk_key)),
         )
         // Global middleware
+        .layer(axum_mw::from_fn_with_state(
+            TrustedProxies::new(config.trusted_proxies.clone()),
+            request_context,
+        ))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(|request: &Request<_>| {
@@ -166,7 +196,11 @@ async fn main() -> anyhow::Result<()> {
     let addr = config.addr();
     tracing::info!("Listening on {addr}");
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -199,13 +233,46 @@ async fn subscribe_redis_changes(
 
 fn projects_routes() -> Router<AppState> {
     Router::new()
-        .route("/projects", get(projects::list_projects))
-        .route("/projects/{project_id}", get(projects::get_project))
+        .route("/setup", post(setup::setup))
+        .route(
+            "/organizations",
+            get(organizations::list_organizations).post(organizations::create_organization),
+        )
+        .route(
+            "/organizations/{org_id}",
+            get(organizations::get_organization)
+                .patch(organizations::update_organization)
+                .delete(organizations::delete_organization),
+        )
+        .route(
+            "/organizations/{org_id}/projects",
+            get(organizations::list_organization_projects),
+        )
+        .route(
+            "/organizations/{org_id}/members",
+            get(organizations::list_members).post(organizations::add_member),
+        )
+        .route(
+            "/organizations/{org_id}/members/{user_id}",
+            delete(organizations::remove_member),
+        )
+        .route(
+            "/projects",
+            get(projects::list_projects).post(projects::create_project),
+        )
+        .route(
+            "/projects/{project_id}",
+            get(projects::get_project)
+                .patch(projects::update_project)
+                .delete(projects::delete_project),
+        )
 }
 
 fn management_routes() -> Router<AppState> {
     Router::new()
         .route("/flags", post(flags::create_flag).get(flags::list_flags))
+        .route("/flags/expiring", get(flags::list_expiring_flags))
+        .route("/flags/bulk", post(flags::bulk_update_flags))
         .route(
             "/flags/{flag_key}",
             get(flags::get_flag)
@@ -213,21 +280,169 @@ fn management_routes() -> Router<AppState> {
                 .delete(flags::delete_flag),
         )
         .route("/flags/{flag_key}/toggle", patch(flags::toggle_flag))
+        .route("/flags/{flag_key}/variants", post(variants::create_variant))
+        .route(
+            "/flags/{flag_key}/variants/reorder",
+            post(variants::reorder_variants),
+        )
+        .route(
+            "/flags/{flag_key}/variants/{variant_id}",
+            patch(variants::update_variant).delete(variants::delete_variant),
+        )
+        .route(
+            "/flags/{flag_key}/environments/{environment_id}/rules",
+            get(rules::list_rules).post(rules::create_rule),
+        )
+        .route(
+            "/flags/{flag_key}/environments/{environment_id}/rules/reorder",
+            post(rules::reorder_rules),
+        )
+        .route(
+            "/flags/{flag_key}/environments/{environment_id}/rules/{rule_id}",
+            put(rules::update_rule).delete(rules::delete_rule),
+        )
+        .route(
+            "/flags/{flag_key}/environments/{environment_id}/overrides",
+            get(overrides::list_overrides)
+                .post(overrides::add_override)
+                .put(overrides::bulk_upsert_overrides),
+        )
+        .route(
+            "/flags/{flag_key}/environments/{environment_id}/overrides/{targeting_key}",
+            delete(overrides::remove_override),
+        )
+        .route(
+            "/flags/{flag_key}/environments/{environment_id}/default-variant",
+            put(flags::set_default_variant),
+        )
+        .route(
+            "/flags/{flag_key}/environments/{environment_id}/schedules",
+            get(schedules::list_flag_schedules).post(schedules::create_schedule),
+        )
+        .route(
+            "/flags/{flag_key}/environments/{environment_id}/rollouts",
+            get(rollouts::list_rollouts).post(rollouts::create_rollout),
+        )
+        .route("/promote", post(promote::promote))
+        .route("/export", get(import_export::export_project))
+        .route("/import", post(import_export::import_project))
+        .route(
+            "/change-requests",
+            get(change_requests::list_change_requests),
+        )
+        .route(
+            "/change-requests/{change_request_id}",
+            get(change_requests::get_change_request),
+        )
+        .route(
+            "/change-requests/{change_request_id}/approve",
+            post(change_requests::approve_change_request),
+        )
+        .route(
+            "/change-requests/{change_request_id}/reject",
+            post(change_requests::reject_change_request),
+        )
+        .route(
+            "/change-requests/{change_request_id}/cancel",
+            post(change_requests::cancel_change_request),
+        )
         .route(
             "/segments",
             post(segments::create_segment).get(segments::list_segments),
         )
-        .route("/segments/{segment_id}", get(segments::get_segment))
+        .route(
+            "/segments/{segment_id}",
+            get(segments::get_segment)
+                .put(segments::update_segment)
+                .patch(segments::update_segment)
+                .delete(segments::delete_segment),
+        )
+        .route("/segments/{segment_id}/usage", get(segments::get_segment_usage))
         .route(
             "/environments",
             get(environments::list_environments).post(environments::create_environment),
         )
+        .route(
+            "/environments/{environment_id}",
+            patch(environments::update_environment).delete(environments::delete_environment),
+        )
+        .route(
+            "/environments/{environment_id}/clone",
+            post(environments::clone_environment),
+        )
+        .route(
+            "/environments/{environment_id}/config-versions",
+            get(config_versions::list_config_versions),
+        )
+        .route(
+            "/environments/{environment_id}/config-versions/diff",
+            get(config_versions::diff_config_versions),
+        )
+        .route(
+            "/environments/{environment_id}/config-versions/{version}",
+            get(config_versions::get_config_version),
+        )
+        .route(
+            "/environments/{environment_id}/config-versions/{version}/rollback",
+            post(config_versions::rollback_config_version),
+        )
+        .route(
+            "/environments/{environment_id}/evaluate-at",
+            post(config_versions::evaluate_at),
+        )
+        .route(
+            "/environments/{environment_id}/permissions",
+            get(members::get_environment_permissions).put(members::update_environment_permissions),
+        )
         .route(
             "/sdk-keys",
             get(sdk_keys::list_sdk_keys).post(sdk_keys::create_sdk_key),
         )
         .route("/sdk-keys/{key_id}/revoke", post(sdk_keys::revoke_sdk_key))
+        .route("/schedules", get(schedules::list_schedules))
+        .route(
+            "/schedules/{schedule_id}",
+            get(schedules::get_schedule).delete(schedules::cancel_schedule),
+        )
+        .route("/rollouts/{rollout_id}", get(rollouts::get_rollout))
+        .route("/rollouts/{rollout_id}/pause", post(rollouts::pause_rollout))
+        .route("/rollouts/{rollout_id}/resume", post(rollouts::resume_rollout))
+        .route(
+            "/rollouts/{rollout_id}/rollback",
+            post(rollouts::rollback_rollout),
+        )
         .route("/audit-log", get(audit_log::list_audit_log))
+        .route("/audit-log/export", get(audit_log::export_audit_log))
+        .route("/audit-log/verify", get(audit_log::verify_audit_log))
+        .route("/audit-log/head", get(audit_log::get_audit_chain_head))
+        .route(
+            "/webhooks",
+            get(webhooks::list_webhooks).post(webhooks::create_webhook),
+        )
+        .route(
+            "/webhooks/{webhook_id}",
+            get(webhooks::get_webhook)
+                .patch(webhooks::update_webhook)
+                .delete(webhooks::delete_webhook),
+        )
+        .route(
+            "/webhooks/{webhook_id}/deliveries",
+            get(webhooks::list_deliveries),
+        )
+        .route(
+            "/webhooks/{webhook_id}/deliveries/{delivery_id}/redeliver",
+            post(webhooks::redeliver),
+        )
+        .route("/members", get(members::list_members))
+        .route(
+            "/members/{user_id}",
+            put(members::set_member_role).delete(members::remove_member),
+        )
+}
+
+/// Project routes for CI jobs and services, which may use a server SDK key.
+fn machine_routes() -> Router<AppState> {
+    Router::new().route("/code-refs", put(code_refs::upload_code_references))
 }
 
 fn evaluation_routes() -> Router<AppState> {
@@ -235,5 +450,6 @@ fn evaluation_routes() -> Router<AppState> {
         .route("/evaluate", post(evaluate::evaluate))
         .route("/evaluate/batch", post(evaluate::evaluate_batch))
         .route("/flags-config", get(evaluate::flags_config))
+        .route("/metrics", post(metrics::report_metrics))
         .route("/stream", get(crate::api::routes::stream::stream))
 }
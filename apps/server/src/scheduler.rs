@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::api::routes::change_requests::{audit_snapshot, audit_snapshot_in};
+use crate::api::routes::flags::notify_environment_changes;
+use crate::api::routes::rollouts::{advance_rollout, check_guardrails};
+use crate::state::AppState;
+use crate::store::models::{AuditContext, ScheduledChangeRow};
+
+/// How often the worker looks for due jobs.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Upper bound on jobs started per tick, so a backlog drains gradually.
+const BATCH_SIZE: i64 = 100;
+
+/// Background worker that runs scheduled flag changes, advances rollout
+/// plans and watches their guardrails.
+///
+/// Every replica runs one; the store's advisory lock makes sure each job is
+/// executed by exactly one of them.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = tick(&state).await {
+            tracing::error!("Scheduler tick failed: {e}");
+        }
+    }
+}
+
+async fn tick(state: &AppState) -> anyhow::Result<()> {
+    for id in state.store.list_due_scheduled_changes(BATCH_SIZE).await? {
+        run_scheduled_change(state, id).await;
+    }
+    // Guardrails go first so a breached plan is not advanced another step.
+    for plan in state.store.list_guarded_rollout_plans().await? {
+        if let Err(e) = check_guardrails(state, &plan).await {
+            tracing::warn!(rollout_id = %plan.id, "Guardrail check failed: {e}");
+        }
+    }
+    for id in state.store.list_due_rollout_plans(BATCH_SIZE).await? {
+        advance_rollout(state, id).await;
+    }
+    Ok(())
+}
+
+async fn run_scheduled_change(state: &AppState, id: Uuid) {
+    let before = match state.store.get_scheduled_change(id).await {
+        Ok(Some(job)) => {
+            audit_snapshot(state, job.project_id, job.flag_id, job.environment_id).await
+        }
+        _ => None,
+    };
+
+    match apply_scheduled_change(state, id, before).await {
+        Ok(Some(job)) => {
+            tracing::info!(schedule_id = %id, flag = %job.flag_key, "Scheduled change applied");
+        }
+        // Another replica has it, or it was cancelled in the meantime.
+        Ok(None) => {}
+        Err(e) => {
+            tracing::warn!(schedule_id = %id, "Scheduled change failed: {e}");
+            if let Err(e) = fail_scheduled_change(state, id, &e.to_string()).await {
+                tracing::warn!(schedule_id = %id, "Recording the failure failed: {e}");
+            }
+        }
+    }
+}
+
+/// Apply the change and write its audit entry in one transaction.
+async fn apply_scheduled_change(
+    state: &AppState,
+    id: Uuid,
+    before: Option<serde_json::Value>,
+) -> anyhow::Result<Option<ScheduledChangeRow>> {
+    let mut uow = state.store.begin().await?;
+    let Some(job) = uow.run_scheduled_change(id).await? else {
+        return Ok(None);
+    };
+
+    let after = audit_snapshot_in(&mut uow, job.project_id, job.flag_id, job.environment_id).await;
+    let ctx = AuditContext::on_behalf_of(job.created_by, job.created_by_email.as_deref())
+        .in_environment(job.environment_id);
+    uow.create_audit_log(
+        job.project_id,
+        &ctx,
+        "scheduled_change_applied",
+        "flag",
+        Some(job.flag_id),
+        before.as_ref(),
+        after.as_ref(),
+    )
+    .await?;
+
+    let changed = uow.commit().await?;
+    notify_environment_changes(state, &changed).await;
+    Ok(Some(job))
+}
+
+async fn fail_scheduled_change(state: &AppState, id: Uuid, error: &str) -> anyhow::Result<()> {
+    let mut uow = state.store.begin().await?;
+    uow.fail_scheduled_change(id, error).await?;
+    if let Some(job) = uow.get_scheduled_change(id).await? {
+        let ctx = AuditContext::on_behalf_of(job.created_by, job.created_by_email.as_deref())
+            .in_environment(job.environment_id);
+        uow.create_audit_log(
+            job.project_id,
+            &ctx,
+            "scheduled_change_failed",
+            "scheduled_change",
+            Some(job.id),
+            None,
+            serde_json::to_value(&job).ok().as_ref(),
+        )
+        .await?;
+    }
+    uow.commit().await?;
+    Ok(())
+}
@@ -14,6 +14,44 @@ pub struct OrganizationRow {
     pub slug: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub external_id: Option<String>,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct UserRow {
+    pub id: Uuid,
+    pub external_id: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct OrganizationMemberRow {
+    pub user_id: Uuid,
+    pub external_id: String,
+    pub email: Option<String>,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct ProjectMemberRow {
+    pub user_id: Uuid,
+    pub external_id: String,
+    pub email: Option<String>,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct EnvironmentPermissionRow {
+    pub user_id: Uuid,
+    pub external_id: String,
+    pub email: Option<String>,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
@@ -37,6 +75,8 @@ pub struct EnvironmentRow {
     pub sort_order: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub restricted: bool,
+    pub requires_approval: bool,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
@@ -51,6 +91,24 @@ pub struct FlagRow {
     pub archived: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// User email or team handle responsible for the flag.
+    pub owner: Option<String>,
+    /// `release`, `experiment`, `ops` or `permission`.
+    pub kind: String,
+    pub temporary: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Bumped on every update; exposed to clients as the `ETag`.
+    pub revision: i64,
+}
+
+/// Filters for listing flags; `None` fields match everything.
+#[derive(Debug, Default)]
+pub struct FlagFilter {
+    pub owner: Option<String>,
+    pub kind: Option<String>,
+    pub temporary: Option<bool>,
+    /// Only flags whose `expires_at` is (or is not) in the past.
+    pub expired: Option<bool>,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
@@ -64,6 +122,24 @@ pub struct FlagVariantRow {
     pub created_at: DateTime<Utc>,
 }
 
+/// How many places still point at a variant, across all environments.
+#[derive(Debug, FromRow, Serialize)]
+pub struct VariantUsageRow {
+    pub default_count: i64,
+    pub override_count: i64,
+    pub rule_count: i64,
+    pub distribution_count: i64,
+}
+
+impl VariantUsageRow {
+    pub fn is_unused(&self) -> bool {
+        self.default_count == 0
+            && self.override_count == 0
+            && self.rule_count == 0
+            && self.distribution_count == 0
+    }
+}
+
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct FlagEnvironmentRow {
     pub id: Uuid,
@@ -73,6 +149,7 @@ pub struct FlagEnvironmentRow {
     pub default_variant_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub revision: i64,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
@@ -85,6 +162,7 @@ pub struct SegmentRow {
     pub match_type: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub revision: i64,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
@@ -98,6 +176,17 @@ pub struct SegmentConstraintRow {
     pub created_at: DateTime<Utc>,
 }
 
+/// A rule that references a segment, joined with its flag and environment.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct SegmentUsageRow {
+    pub rule_id: Uuid,
+    pub negate: bool,
+    pub flag_id: Uuid,
+    pub flag_key: String,
+    pub environment_id: Uuid,
+    pub environment_name: String,
+}
+
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct TargetingRuleRow {
     pub id: Uuid,
@@ -107,6 +196,7 @@ pub struct TargetingRuleRow {
     pub variant_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub revision: i64,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
@@ -135,6 +225,21 @@ pub struct FlagOverrideRow {
     pub created_at: DateTime<Utc>,
 }
 
+/// A targeting rule with its segments and distributions.
+#[derive(Debug)]
+pub struct RuleContents {
+    pub rule: TargetingRuleRow,
+    pub segments: Vec<RuleSegmentRow>,
+    pub distributions: Vec<RuleDistributionRow>,
+}
+
+/// A flag environment's rules, in rank order, and its overrides.
+#[derive(Debug)]
+pub struct FlagEnvironmentContents {
+    pub rules: Vec<RuleContents>,
+    pub overrides: Vec<FlagOverrideRow>,
+}
+
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct SdkKeyRow {
     pub id: Uuid,
@@ -161,6 +266,123 @@ pub struct AuditLogRow {
     pub after_state: Option<serde_json::Value>,
     pub metadata: Option<serde_json::Value>,
     pub created_at: DateTime<Utc>,
+    pub diff: Option<serde_json::Value>,
+    pub environment_id: Option<Uuid>,
+    /// Position in the project's hash chain, starting at 1.
+    pub seq: i64,
+    pub prev_hash: Option<String>,
+    pub hash: Option<String>,
+}
+
+/// Result of walking a project's audit hash chain.
+#[derive(Debug, Serialize)]
+pub struct AuditChainReport {
+    /// Entries whose hash was recomputed and matched.
+    pub verified: i64,
+    /// Entries from before hash chaining, which cannot be verified.
+    pub unchained: i64,
+    /// Verified entries hashed before the chain was keyed. Anyone with
+    /// database access could have rewritten them consistently.
+    pub unkeyed: i64,
+    pub first_broken: Option<AuditChainBreak>,
+    /// Last hashed entry verified. Compare with an anchored head to detect
+    /// a chain rewritten or truncated from that point on.
+    pub head: Option<AuditChainHead>,
+}
+
+/// Position and hash of the latest entry in a project's audit chain.
+#[derive(Debug, FromRow, Serialize)]
+pub struct AuditChainHead {
+    pub seq: i64,
+    pub hash: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditChainBreak {
+    pub seq: i64,
+    pub id: Uuid,
+    pub reason: String,
+}
+
+/// Filters for querying the audit log; `None` fields match everything.
+#[derive(Debug, Default)]
+pub struct AuditLogFilter {
+    pub entity_type: Option<String>,
+    pub entity_id: Option<Uuid>,
+    pub action: Option<String>,
+    /// Actor email or user ID.
+    pub actor: Option<String>,
+    pub environment_id: Option<Uuid>,
+    /// Inclusive lower bound on `created_at`.
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on `created_at`.
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Who made a change, and through which request, for an audit entry.
+#[derive(Debug, Clone, Default)]
+pub struct AuditContext {
+    pub actor_id: Option<Uuid>,
+    pub actor_email: Option<String>,
+    /// Set when the change was made with a server SDK key.
+    pub sdk_key_id: Option<Uuid>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    /// Environment the change applies to, if it is scoped to one.
+    pub environment_id: Option<Uuid>,
+}
+
+impl AuditContext {
+    pub fn in_environment(self, environment_id: Uuid) -> Self {
+        Self {
+            environment_id: Some(environment_id),
+            ..self
+        }
+    }
+
+    /// Changes made by a background job on behalf of the user who set it up.
+    pub fn on_behalf_of(actor_id: Option<Uuid>, actor_email: Option<&str>) -> Self {
+        Self {
+            actor_id,
+            actor_email: actor_email.map(str::to_string),
+            ..Self::default()
+        }
+    }
+
+    /// The request metadata stored in `audit_log.metadata`.
+    pub fn metadata(&self) -> serde_json::Value {
+        let mut metadata = serde_json::Map::new();
+        let fields = [
+            ("sdk_key_id", self.sdk_key_id.map(|id| id.to_string())),
+            ("ip", self.ip.clone()),
+            ("user_agent", self.user_agent.clone()),
+            ("request_id", self.request_id.clone()),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                metadata.insert(key.to_string(), value.into());
+            }
+        }
+        if self.request_id.is_none() {
+            metadata.insert("source".to_string(), "system".into());
+        }
+        serde_json::Value::Object(metadata)
+    }
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct CodeReferenceRow {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub flag_id: Uuid,
+    pub repository: String,
+    pub branch: Option<String>,
+    pub file_path: String,
+    pub line_number: i32,
+    pub line_content: Option<String>,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, FromRow, Serialize)]
@@ -169,3 +391,254 @@ pub struct ConfigVersionRow {
     pub version: i64,
     pub updated_at: DateTime<Utc>,
 }
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct ChangeRequestRow {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub flag_environment_id: Uuid,
+    pub flag_id: Uuid,
+    pub flag_key: String,
+    pub environment_id: Uuid,
+    pub action: String,
+    pub base_state: serde_json::Value,
+    pub proposed_state: serde_json::Value,
+    pub status: String,
+    pub requested_by: Option<Uuid>,
+    pub requested_by_email: Option<String>,
+    pub reviewed_by: Option<Uuid>,
+    pub reviewed_by_email: Option<String>,
+    pub review_comment: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Complete configuration of one flag in one environment, written in a
+/// single step by [`super::postgres::UnitOfWork::apply_change_request`].
+#[derive(Debug, Clone)]
+pub struct FlagEnvironmentSpec {
+    pub enabled: bool,
+    pub default_variant_id: Option<Uuid>,
+    /// In evaluation order.
+    pub rules: Vec<TargetingRuleSpec>,
+    /// `(targeting_key, variant_id)` pairs.
+    pub overrides: Vec<(String, Uuid)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TargetingRuleSpec {
+    pub description: Option<String>,
+    pub variant_id: Option<Uuid>,
+    /// `(segment_id, negate)` pairs.
+    pub segments: Vec<(Uuid, bool)>,
+    /// `(variant_id, rollout_pct)` buckets.
+    pub distributions: Vec<(Uuid, i32)>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct ScheduledChangeRow {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub flag_environment_id: Uuid,
+    pub flag_id: Uuid,
+    pub flag_key: String,
+    pub environment_id: Uuid,
+    pub action: String,
+    pub payload: serde_json::Value,
+    pub run_at: DateTime<Utc>,
+    pub status: String,
+    pub error: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_by_email: Option<String>,
+    pub executed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A change to one flag environment, applied at a later time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScheduledAction {
+    Toggle {
+        enabled: bool,
+    },
+    DefaultVariant {
+        variant_id: Uuid,
+    },
+    /// Replace a targeting rule's description, variant, segments and
+    /// distributions.
+    Rule {
+        rule_id: Uuid,
+        description: Option<String>,
+        variant_id: Option<Uuid>,
+        #[serde(default)]
+        segments: Vec<RuleSegmentSpec>,
+        #[serde(default)]
+        distributions: Vec<DistributionSpec>,
+    },
+    /// Replace only a rule's distributions, e.g. to ramp a rollout.
+    Rollout {
+        rule_id: Uuid,
+        distributions: Vec<DistributionSpec>,
+    },
+}
+
+impl ScheduledAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScheduledAction::Toggle { .. } => "toggle",
+            ScheduledAction::DefaultVariant { .. } => "default_variant",
+            ScheduledAction::Rule { .. } => "rule",
+            ScheduledAction::Rollout { .. } => "rollout",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleSegmentSpec {
+    pub segment_id: Uuid,
+    #[serde(default)]
+    pub negate: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DistributionSpec {
+    pub variant_id: Uuid,
+    /// Share of traffic in basis points (0–10000).
+    pub rollout_pct: i32,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct RolloutPlanRow {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub flag_environment_id: Uuid,
+    pub flag_id: Uuid,
+    pub flag_key: String,
+    pub environment_id: Uuid,
+    pub rule_id: Uuid,
+    pub variant_id: Uuid,
+    pub control_variant_id: Option<Uuid>,
+    pub steps: serde_json::Value,
+    pub current_step: i32,
+    pub status: String,
+    pub next_step_at: Option<DateTime<Utc>>,
+    pub original_variant_id: Option<Uuid>,
+    pub original_distributions: serde_json::Value,
+    pub guardrails: serde_json::Value,
+    pub halted_reason: Option<serde_json::Value>,
+    pub created_by: Option<Uuid>,
+    pub created_by_email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RolloutStep {
+    /// Share of traffic for the rollout variant, in basis points.
+    pub rollout_pct: i32,
+    /// How long to stay at this step before advancing.
+    pub dwell_secs: i64,
+}
+
+impl RolloutStep {
+    /// Distribution buckets for this step. Traffic not given to the rollout
+    /// variant goes to the control variant, or falls through to the default.
+    pub fn distributions(&self, variant_id: Uuid, control_variant_id: Option<Uuid>) -> Vec<(Uuid, i32)> {
+        let mut buckets = vec![(variant_id, self.rollout_pct)];
+        if let Some(control) = control_variant_id {
+            buckets.push((control, 10000 - self.rollout_pct));
+        }
+        buckets
+    }
+}
+
+/// A limit on how much worse the rollout variant may do than the control
+/// variant on one metric event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Guardrail {
+    /// Event whose mean value is compared, e.g. `request` reported for every
+    /// request with value 1 for an error and 0 otherwise, so the mean is the
+    /// error rate. Reporting only errors would make both means 1.
+    pub event_key: String,
+    /// How far, in percent, the rollout variant's mean may exceed control's.
+    pub max_increase_pct: f64,
+    /// Samples each variant needs before the guardrail is checked.
+    #[serde(default = "default_min_samples")]
+    pub min_samples: i64,
+    #[serde(default)]
+    pub action: GuardrailAction,
+}
+
+fn default_min_samples() -> i64 {
+    100
+}
+
+/// What to do when a guardrail is breached.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardrailAction {
+    /// Send all of the rule's traffic to the control variant.
+    #[default]
+    Rollback,
+    /// Turn the flag off in the environment.
+    Disable,
+}
+
+#[derive(Debug, FromRow)]
+pub struct MetricStatsRow {
+    pub variant_key: String,
+    pub samples: i64,
+    pub mean: f64,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct ConfigSnapshotRow {
+    pub environment_id: Uuid,
+    pub version: i64,
+    pub project_id: Uuid,
+    pub config: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct WebhookRow {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub url: String,
+    pub description: Option<String>,
+    pub secret: String,
+    pub events: Vec<String>,
+    pub enabled: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct WebhookDeliveryRow {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub project_id: Uuid,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A delivery claimed by the worker, with where and how to send it.
+#[derive(Debug, FromRow)]
+pub struct PendingWebhookDelivery {
+    pub id: Uuid,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+}
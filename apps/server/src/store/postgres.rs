@@ -1,4 +1,8 @@
+use std::sync::Arc;
+
 use anyhow::Result;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
 use uuid::Uuid;
@@ -7,31 +11,67 @@ use super::models::*;
 use eval_core::types as eval;
 
 // Column lists with enum→TEXT casts for sqlx compatibility
-const FLAG_COLS: &str = "id, project_id, key, name, description, flag_type::TEXT AS flag_type, tags, archived, created_at, updated_at";
-const SEGMENT_COLS: &str = "id, project_id, key, name, description, match_type::TEXT AS match_type, created_at, updated_at";
+const FLAG_COLS: &str = "id, project_id, key, name, description, flag_type::TEXT AS flag_type, tags, archived, created_at, updated_at, owner, kind::TEXT AS kind, temporary, expires_at, revision";
+const SEGMENT_COLS: &str = "id, project_id, key, name, description, match_type::TEXT AS match_type, created_at, updated_at, revision";
 const CONSTRAINT_COLS: &str = "id, segment_id, attribute, operator::TEXT AS operator, values, sort_order, created_at";
 const SDK_KEY_COLS: &str = "id, environment_id, name, key_type::TEXT AS key_type, key_hash, key_prefix, last_used_at, created_at, revoked_at";
+const CHANGE_REQUEST_COLS: &str = "cr.id, cr.project_id, cr.flag_environment_id, fe.flag_id, f.key AS flag_key, fe.environment_id, cr.action, cr.base_state, cr.proposed_state, cr.status::TEXT AS status, cr.requested_by, cr.requested_by_email, cr.reviewed_by, cr.reviewed_by_email, cr.review_comment, cr.reviewed_at, cr.created_at, cr.updated_at";
+const CHANGE_REQUEST_FROM: &str = "change_requests cr JOIN flag_environments fe ON fe.id = cr.flag_environment_id JOIN flags f ON f.id = fe.flag_id";
+const SCHEDULED_CHANGE_COLS: &str = "sc.id, sc.project_id, sc.flag_environment_id, fe.flag_id, f.key AS flag_key, fe.environment_id, sc.action, sc.payload, sc.run_at, sc.status::TEXT AS status, sc.error, sc.created_by, sc.created_by_email, sc.executed_at, sc.created_at, sc.updated_at";
+const SCHEDULED_CHANGE_FROM: &str = "scheduled_changes sc JOIN flag_environments fe ON fe.id = sc.flag_environment_id JOIN flags f ON f.id = fe.flag_id";
+const ROLLOUT_PLAN_COLS: &str = "rp.id, rp.project_id, rp.flag_environment_id, fe.flag_id, f.key AS flag_key, fe.environment_id, rp.rule_id, rp.variant_id, rp.control_variant_id, rp.steps, rp.current_step, rp.status::TEXT AS status, rp.next_step_at, rp.original_variant_id, rp.original_distributions, rp.guardrails, rp.halted_reason, rp.created_by, rp.created_by_email, rp.created_at, rp.updated_at";
+const ROLLOUT_PLAN_FROM: &str = "rollout_plans rp JOIN flag_environments fe ON fe.id = rp.flag_environment_id JOIN flags f ON f.id = fe.flag_id";
+/// Audit entity types whose changes show up in an environment's config.
+const CONFIG_ENTITY_TYPES: &[&str] = &[
+    "flag",
+    "flag_variant",
+    "flag_environment",
+    "flag_override",
+    "targeting_rule",
+    "segment",
+    "change_request",
+    "scheduled_change",
+    "rollout_plan",
+    "environment",
+];
+/// When the current step of a plan is due to end.
+const ROLLOUT_STEP_END: &str = "NOW() + (steps -> current_step ->> 'dwell_secs')::BIGINT * INTERVAL '1 second'";
 
 /// PostgreSQL store for all FlagForge data.
 #[derive(Clone)]
 pub struct PostgresStore {
     pool: PgPool,
+    /// Server secret keying the audit hash chain, so nobody with only
+    /// database access can rewrite entries and recompute their hashes.
+    audit_key: Arc<[u8]>,
 }
 
 impl PostgresStore {
-    pub async fn new(database_url: &str) -> Result<Self> {
+    pub async fn new(database_url: &str, audit_key: &[u8]) -> Result<Self> {
         let pool = PgPoolOptions::new()
             .max_connections(20)
             .connect(database_url)
             .await?;
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            audit_key: audit_key.into(),
+        })
     }
 
     pub fn pool(&self) -> &PgPool {
         &self.pool
     }
 
+    /// Start a [`UnitOfWork`] for a write that spans several rows.
+    pub async fn begin(&self) -> Result<UnitOfWork> {
+        Ok(UnitOfWork {
+            tx: self.pool.begin().await?,
+            audit_key: self.audit_key.clone(),
+            changed_environments: Vec::new(),
+        })
+    }
+
     pub async fn run_migrations(&self) -> Result<()> {
         // If migrations fail due to checksum mismatch (e.g. manually applied),
         // fix the checksum in _sqlx_migrations and retry.
@@ -67,133 +107,309 @@ impl PostgresStore {
     // ============================================================
     // Organizations
     // ============================================================
-    pub async fn create_organization(&self, name: &str, slug: &str) -> Result<OrganizationRow> {
-        let row = sqlx::query_as::<_, OrganizationRow>(
-            "INSERT INTO organizations (name, slug) VALUES ($1, $2) RETURNING *",
+    pub async fn create_organization(
+        &self,
+        name: &str,
+        slug: &str,
+        external_id: Option<&str>,
+    ) -> Result<OrganizationRow> {
+        insert_organization(&mut *self.pool.acquire().await?, name, slug, external_id).await
+    }
+
+    pub async fn list_organizations_for_user(&self, user_id: Uuid) -> Result<Vec<OrganizationRow>> {
+        let rows = sqlx::query_as::<_, OrganizationRow>(
+            "SELECT o.* FROM organizations o
+             JOIN organization_members m ON m.organization_id = o.id
+             WHERE m.user_id = $1
+             ORDER BY o.name",
         )
-        .bind(name)
-        .bind(slug)
-        .fetch_one(&self.pool)
+        .bind(user_id)
+        .fetch_all(&self.pool)
         .await?;
+        Ok(rows)
+    }
+
+    pub async fn get_organization(&self, org_id: Uuid) -> Result<Option<OrganizationRow>> {
+        let row = sqlx::query_as::<_, OrganizationRow>("SELECT * FROM organizations WHERE id = $1")
+            .bind(org_id)
+            .fetch_optional(&self.pool)
+            .await?;
         Ok(row)
     }
 
-    // ============================================================
-    // Projects
-    // ============================================================
-    pub async fn create_project(
+    pub async fn update_organization(
         &self,
         org_id: Uuid,
-        name: &str,
-        slug: &str,
-        description: Option<&str>,
-    ) -> Result<ProjectRow> {
-        let row = sqlx::query_as::<_, ProjectRow>(
-            "INSERT INTO projects (organization_id, name, slug, description) VALUES ($1, $2, $3, $4) RETURNING *",
+        name: Option<&str>,
+        slug: Option<&str>,
+        external_id: Option<&str>,
+    ) -> Result<OrganizationRow> {
+        let row = sqlx::query_as::<_, OrganizationRow>(
+            "UPDATE organizations SET
+                name = COALESCE($2, name),
+                slug = COALESCE($3, slug),
+                external_id = COALESCE($4, external_id)
+             WHERE id = $1
+             RETURNING *",
         )
         .bind(org_id)
         .bind(name)
         .bind(slug)
-        .bind(description)
+        .bind(external_id)
         .fetch_one(&self.pool)
         .await?;
         Ok(row)
     }
 
-    pub async fn get_project(&self, project_id: Uuid) -> Result<Option<ProjectRow>> {
-        let row =
-            sqlx::query_as::<_, ProjectRow>("SELECT * FROM projects WHERE id = $1")
-                .bind(project_id)
-                .fetch_optional(&self.pool)
-                .await?;
-        Ok(row)
+    pub async fn delete_organization(&self, org_id: Uuid) -> Result<()> {
+        sqlx::query("DELETE FROM organizations WHERE id = $1")
+            .bind(org_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
     }
 
     // ============================================================
-    // Environments
+    // Users & Memberships
     // ============================================================
-    pub async fn create_environment(
+
+    /// Upsert the user behind a verified JWT and return the organizations
+    /// they belong to. A Clerk `org_id` claim that is linked to an
+    /// organization grants membership of it.
+    pub async fn resolve_user(
         &self,
-        project_id: Uuid,
-        name: &str,
-        slug: &str,
-        color: Option<&str>,
-    ) -> Result<EnvironmentRow> {
-        let row = sqlx::query_as::<_, EnvironmentRow>(
-            "INSERT INTO environments (project_id, name, slug, color) VALUES ($1, $2, $3, $4) RETURNING *",
+        external_id: &str,
+        email: Option<&str>,
+        clerk_org_id: Option<&str>,
+    ) -> Result<(UserRow, Vec<Uuid>)> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = upsert_user(&mut tx, external_id, email).await?;
+
+        if let Some(clerk_org_id) = clerk_org_id {
+            sqlx::query(
+                "INSERT INTO organization_members (organization_id, user_id)
+                 SELECT id, $2 FROM organizations WHERE external_id = $1
+                 ON CONFLICT (organization_id, user_id) DO NOTHING",
+            )
+            .bind(clerk_org_id)
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let org_ids: Vec<Uuid> = sqlx::query_scalar(
+            "SELECT organization_id FROM organization_members WHERE user_id = $1",
         )
-        .bind(project_id)
-        .bind(name)
-        .bind(slug)
-        .bind(color)
-        .fetch_one(&self.pool)
+        .bind(user.id)
+        .fetch_all(&mut *tx)
         .await?;
 
-        // Initialize config version
-        sqlx::query("INSERT INTO config_versions (environment_id) VALUES ($1) ON CONFLICT DO NOTHING")
-            .bind(row.id)
-            .execute(&self.pool)
+        tx.commit().await?;
+        Ok((user, org_ids))
+    }
+
+    /// Add a user to an organization with `role`, creating the user row if
+    /// they have never signed in. Existing members keep their role. The
+    /// addition is audited in every project of the organization, since it
+    /// grants read access to them.
+    pub async fn add_organization_member(
+        &self,
+        org_id: Uuid,
+        external_id: &str,
+        email: Option<&str>,
+        role: &str,
+        ctx: &AuditContext,
+    ) -> Result<OrganizationMemberRow> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = upsert_user(&mut tx, external_id, email).await?;
+
+        let added = insert_organization_member(&mut tx, org_id, user.id, role).await?;
+
+        let member = sqlx::query_as::<_, OrganizationMemberRow>(
+            "SELECT u.id AS user_id, u.external_id, u.email, m.role::TEXT AS role, m.created_at
+             FROM organization_members m
+             JOIN users u ON u.id = m.user_id
+             WHERE m.organization_id = $1 AND m.user_id = $2",
+        )
+        .bind(org_id)
+        .bind(user.id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if added {
+            let after = serde_json::json!({ "organization_id": org_id, "role": member.role });
+            audit_organization(
+                &mut tx,
+                &self.audit_key,
+                org_id,
+                ctx,
+                "organization_member_added",
+                user.id,
+                None,
+                Some(&after),
+            )
             .await?;
+        }
 
-        Ok(row)
+        tx.commit().await?;
+        Ok(member)
     }
 
-    pub async fn list_environments(&self, project_id: Uuid) -> Result<Vec<EnvironmentRow>> {
-        let rows = sqlx::query_as::<_, EnvironmentRow>(
-            "SELECT * FROM environments WHERE project_id = $1 ORDER BY sort_order",
+    /// Make the user with `external_id` the owner of every organization that
+    /// has no members, and of every project in them. Organizations created
+    /// before users existed have no members, so nobody could reach them
+    /// otherwise. Returns the claimed organizations.
+    pub async fn claim_unowned_organizations(&self, external_id: &str) -> Result<Vec<Uuid>> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = upsert_user(&mut tx, external_id, None).await?;
+
+        let org_ids: Vec<Uuid> = sqlx::query_scalar(
+            "SELECT id FROM organizations o
+             WHERE NOT EXISTS (SELECT 1 FROM organization_members m WHERE m.organization_id = o.id)
+             ORDER BY id
+             FOR UPDATE",
         )
-        .bind(project_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let ctx = AuditContext::default();
+        let mut claimed = Vec::new();
+        for org_id in org_ids {
+            if !insert_organization_member(&mut tx, org_id, user.id, "owner").await? {
+                continue;
+            }
+
+            let project_ids: Vec<Uuid> =
+                sqlx::query_scalar("SELECT id FROM projects WHERE organization_id = $1")
+                    .bind(org_id)
+                    .fetch_all(&mut *tx)
+                    .await?;
+            for project_id in project_ids {
+                upsert_project_member(&mut tx, project_id, user.id, "owner").await?;
+            }
+
+            let after = serde_json::json!({ "organization_id": org_id, "role": "owner" });
+            audit_organization(
+                &mut tx,
+                &self.audit_key,
+                org_id,
+                &ctx,
+                "organization_member_added",
+                user.id,
+                None,
+                Some(&after),
+            )
+            .await?;
+            claimed.push(org_id);
+        }
+
+        tx.commit().await?;
+        Ok(claimed)
+    }
+
+    pub async fn list_organization_members(&self, org_id: Uuid) -> Result<Vec<OrganizationMemberRow>> {
+        let rows = sqlx::query_as::<_, OrganizationMemberRow>(
+            "SELECT u.id AS user_id, u.external_id, u.email, m.role::TEXT AS role, m.created_at
+             FROM organization_members m
+             JOIN users u ON u.id = m.user_id
+             WHERE m.organization_id = $1
+             ORDER BY m.created_at",
+        )
+        .bind(org_id)
         .fetch_all(&self.pool)
         .await?;
         Ok(rows)
     }
 
+    pub async fn get_organization_role(&self, org_id: Uuid, user_id: Uuid) -> Result<Option<String>> {
+        let role = sqlx::query_scalar(
+            "SELECT role::TEXT FROM organization_members WHERE organization_id = $1 AND user_id = $2",
+        )
+        .bind(org_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(role)
+    }
+
+    /// Remove a member, auditing it in every project of the organization.
+    /// Returns false if the user was not a member.
+    pub async fn remove_organization_member(
+        &self,
+        org_id: Uuid,
+        user_id: Uuid,
+        ctx: &AuditContext,
+    ) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let role: Option<String> = sqlx::query_scalar(
+            "DELETE FROM organization_members WHERE organization_id = $1 AND user_id = $2
+             RETURNING role::TEXT",
+        )
+        .bind(org_id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(role) = role else {
+            return Ok(false);
+        };
+
+        let before = serde_json::json!({ "organization_id": org_id, "role": role });
+        audit_organization(
+            &mut tx,
+            &self.audit_key,
+            org_id,
+            ctx,
+            "organization_member_removed",
+            user_id,
+            Some(&before),
+            None,
+        )
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
     // ============================================================
-    // Flags
+    // Project Roles & Environment Permissions
     // ============================================================
-    pub async fn create_flag(
-        &self,
-        project_id: Uuid,
-        key: &str,
-        name: &str,
-        description: Option<&str>,
-        flag_type: &str,
-        tags: &[String],
-    ) -> Result<FlagRow> {
-        let row = sqlx::query_as::<_, FlagRow>(
-            &format!("INSERT INTO flags (project_id, key, name, description, flag_type, tags)
-             VALUES ($1, $2, $3, $4, $5::flag_type, $6)
-             RETURNING {FLAG_COLS}"),
+    pub async fn get_project_role(&self, project_id: Uuid, user_id: Uuid) -> Result<Option<String>> {
+        let role = sqlx::query_scalar(
+            "SELECT role::TEXT FROM project_members WHERE project_id = $1 AND user_id = $2",
         )
         .bind(project_id)
-        .bind(key)
-        .bind(name)
-        .bind(description)
-        .bind(flag_type)
-        .bind(tags)
-        .fetch_one(&self.pool)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
         .await?;
-        Ok(row)
+        Ok(role)
     }
 
-    pub async fn get_flag_by_key(
+    pub async fn get_environment_role(
         &self,
-        project_id: Uuid,
-        key: &str,
-    ) -> Result<Option<FlagRow>> {
-        let row = sqlx::query_as::<_, FlagRow>(
-            &format!("SELECT {FLAG_COLS} FROM flags WHERE project_id = $1 AND key = $2"),
+        environment_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<String>> {
+        let role = sqlx::query_scalar(
+            "SELECT role::TEXT FROM environment_permissions WHERE environment_id = $1 AND user_id = $2",
         )
-        .bind(project_id)
-        .bind(key)
+        .bind(environment_id)
+        .bind(user_id)
         .fetch_optional(&self.pool)
         .await?;
-        Ok(row)
+        Ok(role)
     }
 
-    pub async fn list_flags(&self, project_id: Uuid) -> Result<Vec<FlagRow>> {
-        let rows = sqlx::query_as::<_, FlagRow>(
-            &format!("SELECT {FLAG_COLS} FROM flags WHERE project_id = $1 AND archived = FALSE ORDER BY created_at DESC"),
+    pub async fn list_project_members(&self, project_id: Uuid) -> Result<Vec<ProjectMemberRow>> {
+        let rows = sqlx::query_as::<_, ProjectMemberRow>(
+            "SELECT u.id AS user_id, u.external_id, u.email, pm.role::TEXT AS role, pm.created_at, pm.updated_at
+             FROM project_members pm
+             JOIN users u ON u.id = pm.user_id
+             WHERE pm.project_id = $1
+             ORDER BY pm.created_at",
         )
         .bind(project_id)
         .fetch_all(&self.pool)
@@ -201,208 +417,399 @@ impl PostgresStore {
         Ok(rows)
     }
 
-    pub async fn update_flag(
+    pub async fn list_environment_permissions(
         &self,
-        flag_id: Uuid,
-        name: Option<&str>,
-        description: Option<&str>,
-        tags: Option<&[String]>,
-        archived: Option<bool>,
-    ) -> Result<FlagRow> {
-        let row = sqlx::query_as::<_, FlagRow>(
-            &format!("UPDATE flags SET
-                name = COALESCE($2, name),
-                description = COALESCE($3, description),
-                tags = COALESCE($4, tags),
-                archived = COALESCE($5, archived)
-             WHERE id = $1
-             RETURNING {FLAG_COLS}"),
-        )
-        .bind(flag_id)
-        .bind(name)
-        .bind(description)
-        .bind(tags)
-        .bind(archived)
-        .fetch_one(&self.pool)
-        .await?;
-        Ok(row)
+        environment_id: Uuid,
+    ) -> Result<Vec<EnvironmentPermissionRow>> {
+        fetch_environment_permissions(&mut *self.pool.acquire().await?, environment_id).await
     }
 
-    pub async fn delete_flag(&self, flag_id: Uuid) -> Result<()> {
-        sqlx::query("DELETE FROM flags WHERE id = $1")
-            .bind(flag_id)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+    // ============================================================
+    // Change Requests
+    // ============================================================
+    pub async fn get_change_request(&self, id: Uuid) -> Result<Option<ChangeRequestRow>> {
+        fetch_change_request(&mut *self.pool.acquire().await?, id).await
+    }
+
+    pub async fn list_change_requests(
+        &self,
+        project_id: Uuid,
+        status: Option<&str>,
+    ) -> Result<Vec<ChangeRequestRow>> {
+        let rows = sqlx::query_as::<_, ChangeRequestRow>(&format!(
+            "SELECT {CHANGE_REQUEST_COLS} FROM {CHANGE_REQUEST_FROM}
+             WHERE cr.project_id = $1 AND ($2::TEXT IS NULL OR cr.status::TEXT = $2)
+             ORDER BY cr.created_at DESC"
+        ))
+        .bind(project_id)
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
     }
 
     // ============================================================
-    // Flag Variants
+    // Scheduled Changes
     // ============================================================
-    pub async fn create_flag_variant(
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_scheduled_change(&self, id: Uuid) -> Result<Option<ScheduledChangeRow>> {
+        fetch_scheduled_change(&mut *self.pool.acquire().await?, id).await
+    }
+
+    pub async fn list_scheduled_changes(
         &self,
-        flag_id: Uuid,
-        key: &str,
-        value: &serde_json::Value,
-        description: Option<&str>,
-        sort_order: i32,
-    ) -> Result<FlagVariantRow> {
-        let row = sqlx::query_as::<_, FlagVariantRow>(
-            "INSERT INTO flag_variants (flag_id, key, value, description, sort_order)
-             VALUES ($1, $2, $3, $4, $5) RETURNING *",
+        project_id: Uuid,
+        flag_environment_id: Option<Uuid>,
+        status: Option<&str>,
+    ) -> Result<Vec<ScheduledChangeRow>> {
+        let rows = sqlx::query_as::<_, ScheduledChangeRow>(&format!(
+            "SELECT {SCHEDULED_CHANGE_COLS} FROM {SCHEDULED_CHANGE_FROM}
+             WHERE sc.project_id = $1
+               AND ($2::UUID IS NULL OR sc.flag_environment_id = $2)
+               AND ($3::TEXT IS NULL OR sc.status::TEXT = $3)
+             ORDER BY sc.run_at"
+        ))
+        .bind(project_id)
+        .bind(flag_environment_id)
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// IDs of pending changes whose time has come, oldest first.
+    pub async fn list_due_scheduled_changes(&self, limit: i64) -> Result<Vec<Uuid>> {
+        let ids = sqlx::query_scalar(
+            "SELECT id FROM scheduled_changes
+             WHERE status = 'pending' AND run_at <= NOW()
+             ORDER BY run_at
+             LIMIT $1",
         )
-        .bind(flag_id)
-        .bind(key)
-        .bind(value)
-        .bind(description)
-        .bind(sort_order)
-        .fetch_one(&self.pool)
+        .bind(limit)
+        .fetch_all(&self.pool)
         .await?;
-        Ok(row)
+        Ok(ids)
     }
 
-    pub async fn get_flag_variants(&self, flag_id: Uuid) -> Result<Vec<FlagVariantRow>> {
-        let rows = sqlx::query_as::<_, FlagVariantRow>(
-            "SELECT * FROM flag_variants WHERE flag_id = $1 ORDER BY sort_order",
+    // ============================================================
+    // Rollout Plans
+    // ============================================================
+    pub async fn get_rollout_plan(&self, id: Uuid) -> Result<Option<RolloutPlanRow>> {
+        fetch_rollout_plan(&mut *self.pool.acquire().await?, id).await
+    }
+
+    pub async fn list_rollout_plans(
+        &self,
+        project_id: Uuid,
+        flag_environment_id: Option<Uuid>,
+    ) -> Result<Vec<RolloutPlanRow>> {
+        let rows = sqlx::query_as::<_, RolloutPlanRow>(&format!(
+            "SELECT {ROLLOUT_PLAN_COLS} FROM {ROLLOUT_PLAN_FROM}
+             WHERE rp.project_id = $1 AND ($2::UUID IS NULL OR rp.flag_environment_id = $2)
+             ORDER BY rp.created_at DESC"
+        ))
+        .bind(project_id)
+        .bind(flag_environment_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// IDs of active plans whose current step has ended.
+    pub async fn list_due_rollout_plans(&self, limit: i64) -> Result<Vec<Uuid>> {
+        let ids = sqlx::query_scalar(
+            "SELECT id FROM rollout_plans
+             WHERE status = 'active' AND next_step_at <= NOW()
+             ORDER BY next_step_at
+             LIMIT $1",
         )
-        .bind(flag_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(ids)
+    }
+
+    /// Running plans that have guardrails to watch.
+    pub async fn list_guarded_rollout_plans(&self) -> Result<Vec<RolloutPlanRow>> {
+        let rows = sqlx::query_as::<_, RolloutPlanRow>(&format!(
+            "SELECT {ROLLOUT_PLAN_COLS} FROM {ROLLOUT_PLAN_FROM}
+             WHERE rp.status IN ('active', 'paused') AND rp.guardrails <> '[]'::JSONB"
+        ))
         .fetch_all(&self.pool)
         .await?;
         Ok(rows)
     }
 
     // ============================================================
-    // Flag Environments
+    // Metric Events
     // ============================================================
-    pub async fn create_flag_environment(
+    /// Insert `(flag_key, variant_key, event_key, value)` events.
+    pub async fn insert_metric_events(
         &self,
-        flag_id: Uuid,
         environment_id: Uuid,
-        enabled: bool,
-        default_variant_id: Option<Uuid>,
-    ) -> Result<FlagEnvironmentRow> {
-        let row = sqlx::query_as::<_, FlagEnvironmentRow>(
-            "INSERT INTO flag_environments (flag_id, environment_id, enabled, default_variant_id)
-             VALUES ($1, $2, $3, $4) RETURNING *",
+        events: &[(String, String, String, f64)],
+    ) -> Result<u64> {
+        let mut flag_keys = Vec::with_capacity(events.len());
+        let mut variant_keys = Vec::with_capacity(events.len());
+        let mut event_keys = Vec::with_capacity(events.len());
+        let mut values = Vec::with_capacity(events.len());
+        for (flag_key, variant_key, event_key, value) in events {
+            flag_keys.push(flag_key.as_str());
+            variant_keys.push(variant_key.as_str());
+            event_keys.push(event_key.as_str());
+            values.push(*value);
+        }
+
+        let result = sqlx::query(
+            "INSERT INTO metric_events (environment_id, flag_key, variant_key, event_key, value)
+             SELECT $1, * FROM UNNEST($2::TEXT[], $3::TEXT[], $4::TEXT[], $5::FLOAT8[])",
         )
-        .bind(flag_id)
         .bind(environment_id)
-        .bind(enabled)
-        .bind(default_variant_id)
-        .fetch_one(&self.pool)
+        .bind(&flag_keys)
+        .bind(&variant_keys)
+        .bind(&event_keys)
+        .bind(&values)
+        .execute(&self.pool)
         .await?;
-        Ok(row)
+        Ok(result.rows_affected())
     }
 
-    pub async fn get_flag_environment(
+    /// Sample count and mean value of an event per variant since `since`.
+    pub async fn metric_stats(
         &self,
-        flag_id: Uuid,
         environment_id: Uuid,
-    ) -> Result<Option<FlagEnvironmentRow>> {
-        let row = sqlx::query_as::<_, FlagEnvironmentRow>(
-            "SELECT * FROM flag_environments WHERE flag_id = $1 AND environment_id = $2",
+        flag_key: &str,
+        event_key: &str,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<MetricStatsRow>> {
+        let rows = sqlx::query_as::<_, MetricStatsRow>(
+            "SELECT variant_key, COUNT(*) AS samples, AVG(value) AS mean
+             FROM metric_events
+             WHERE environment_id = $1 AND flag_key = $2 AND event_key = $3 AND created_at >= $4
+             GROUP BY variant_key",
         )
-        .bind(flag_id)
         .bind(environment_id)
-        .fetch_optional(&self.pool)
+        .bind(flag_key)
+        .bind(event_key)
+        .bind(since)
+        .fetch_all(&self.pool)
         .await?;
+        Ok(rows)
+    }
+
+    // ============================================================
+    // Projects
+    // ============================================================
+    pub async fn get_project(&self, project_id: Uuid) -> Result<Option<ProjectRow>> {
+        let row =
+            sqlx::query_as::<_, ProjectRow>("SELECT * FROM projects WHERE id = $1")
+                .bind(project_id)
+                .fetch_optional(&self.pool)
+                .await?;
         Ok(row)
     }
 
-    pub async fn toggle_flag(
-        &self,
-        flag_id: Uuid,
-        environment_id: Uuid,
-        enabled: bool,
-    ) -> Result<FlagEnvironmentRow> {
-        let row = sqlx::query_as::<_, FlagEnvironmentRow>(
-            "UPDATE flag_environments SET enabled = $3 WHERE flag_id = $1 AND environment_id = $2 RETURNING *",
+    pub async fn list_projects_for_organization(&self, org_id: Uuid) -> Result<Vec<ProjectRow>> {
+        let rows = sqlx::query_as::<_, ProjectRow>(
+            "SELECT * FROM projects WHERE organization_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    pub async fn delete_project(&self, project_id: Uuid) -> Result<()> {
+        sqlx::query("DELETE FROM projects WHERE id = $1")
+            .bind(project_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // ============================================================
+    // Environments
+    // ============================================================
+    pub async fn get_environment(&self, environment_id: Uuid) -> Result<Option<EnvironmentRow>> {
+        let row = sqlx::query_as::<_, EnvironmentRow>("SELECT * FROM environments WHERE id = $1")
+            .bind(environment_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row)
+    }
+
+
+    /// Count SDK keys of an environment that have not been revoked.
+    pub async fn count_active_sdk_keys(&self, environment_id: Uuid) -> Result<i64> {
+        let (count,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM sdk_keys WHERE environment_id = $1 AND revoked_at IS NULL",
         )
-        .bind(flag_id)
         .bind(environment_id)
-        .bind(enabled)
         .fetch_one(&self.pool)
         .await?;
-        Ok(row)
+        Ok(count)
+    }
+
+    pub async fn list_environments(&self, project_id: Uuid) -> Result<Vec<EnvironmentRow>> {
+        fetch_environments(&mut *self.pool.acquire().await?, project_id).await
     }
 
     // ============================================================
-    // Segments
+    // Flags
     // ============================================================
-    pub async fn create_segment(
+    pub async fn get_flag_by_key(
         &self,
         project_id: Uuid,
         key: &str,
-        name: &str,
-        description: Option<&str>,
-        match_type: &str,
-    ) -> Result<SegmentRow> {
-        let row = sqlx::query_as::<_, SegmentRow>(
-            &format!("INSERT INTO segments (project_id, key, name, description, match_type)
-             VALUES ($1, $2, $3, $4, $5::match_type) RETURNING {SEGMENT_COLS}"),
+    ) -> Result<Option<FlagRow>> {
+        let row = sqlx::query_as::<_, FlagRow>(
+            &format!("SELECT {FLAG_COLS} FROM flags WHERE project_id = $1 AND key = $2"),
         )
         .bind(project_id)
         .bind(key)
-        .bind(name)
-        .bind(description)
-        .bind(match_type)
-        .fetch_one(&self.pool)
+        .fetch_optional(&self.pool)
         .await?;
         Ok(row)
     }
 
-    pub async fn get_segment(&self, segment_id: Uuid) -> Result<Option<SegmentRow>> {
-        let row = sqlx::query_as::<_, SegmentRow>(
-            &format!("SELECT {SEGMENT_COLS} FROM segments WHERE id = $1"))
-            .bind(segment_id)
-            .fetch_optional(&self.pool)
-            .await?;
-        Ok(row)
+    pub async fn list_flags(&self, project_id: Uuid) -> Result<Vec<FlagRow>> {
+        let rows = sqlx::query_as::<_, FlagRow>(
+            &format!("SELECT {FLAG_COLS} FROM flags WHERE project_id = $1 AND archived = FALSE ORDER BY created_at DESC"),
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
     }
 
-    pub async fn list_segments(&self, project_id: Uuid) -> Result<Vec<SegmentRow>> {
-        let rows = sqlx::query_as::<_, SegmentRow>(
-            &format!("SELECT {SEGMENT_COLS} FROM segments WHERE project_id = $1 ORDER BY name"),
-        )
+    /// Non-archived flags matching `filter`, newest first.
+    pub async fn list_flags_filtered(
+        &self,
+        project_id: Uuid,
+        filter: &FlagFilter,
+    ) -> Result<Vec<FlagRow>> {
+        let rows = sqlx::query_as::<_, FlagRow>(&format!(
+            "SELECT {FLAG_COLS} FROM flags
+             WHERE project_id = $1 AND archived = FALSE
+               AND ($2::TEXT IS NULL OR owner = $2)
+               AND ($3::TEXT IS NULL OR kind::TEXT = $3)
+               AND ($4::BOOLEAN IS NULL OR temporary = $4)
+               AND ($5::BOOLEAN IS NULL
+                    OR (expires_at IS NOT NULL AND expires_at <= NOW()) = $5)
+             ORDER BY created_at DESC"
+        ))
         .bind(project_id)
+        .bind(filter.owner.as_deref())
+        .bind(filter.kind.as_deref())
+        .bind(filter.temporary)
+        .bind(filter.expired)
         .fetch_all(&self.pool)
         .await?;
         Ok(rows)
     }
 
-    pub async fn create_segment_constraint(
+    /// Non-archived flags that expire before `before` (already expired
+    /// included), soonest first.
+    pub async fn list_expiring_flags(
         &self,
-        segment_id: Uuid,
-        attribute: &str,
-        operator: &str,
-        values: &[String],
-        sort_order: i32,
-    ) -> Result<SegmentConstraintRow> {
-        let row = sqlx::query_as::<_, SegmentConstraintRow>(
-            &format!("INSERT INTO segment_constraints (segment_id, attribute, operator, values, sort_order)
-             VALUES ($1, $2, $3::operator_type, $4, $5) RETURNING {CONSTRAINT_COLS}"),
+        project_id: Uuid,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<FlagRow>> {
+        let rows = sqlx::query_as::<_, FlagRow>(&format!(
+            "SELECT {FLAG_COLS} FROM flags
+             WHERE project_id = $1 AND archived = FALSE AND expires_at <= $2
+             ORDER BY expires_at"
+        ))
+        .bind(project_id)
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Map every flag key in a project (archived included) to its ID.
+    pub async fn list_flag_ids_by_key(
+        &self,
+        project_id: Uuid,
+    ) -> Result<std::collections::HashMap<String, Uuid>> {
+        let rows: Vec<(String, Uuid)> =
+            sqlx::query_as("SELECT key, id FROM flags WHERE project_id = $1")
+                .bind(project_id)
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(rows.into_iter().collect())
+    }
+
+    // ============================================================
+    // Flag Variants
+    // ============================================================
+    pub async fn get_flag_variants(&self, flag_id: Uuid) -> Result<Vec<FlagVariantRow>> {
+        fetch_flag_variants(&mut *self.pool.acquire().await?, flag_id).await
+    }
+
+    /// Environments whose configuration uses the variant anywhere.
+    pub async fn get_variant_environments(&self, variant_id: Uuid) -> Result<Vec<Uuid>> {
+        let ids = sqlx::query_scalar(
+            "SELECT DISTINCT fe.environment_id FROM flag_environments fe
+             WHERE fe.default_variant_id = $1
+                OR EXISTS (SELECT 1 FROM flag_overrides o
+                           WHERE o.flag_environment_id = fe.id AND o.variant_id = $1)
+                OR EXISTS (SELECT 1 FROM targeting_rules r
+                           WHERE r.flag_environment_id = fe.id AND r.variant_id = $1)
+                OR EXISTS (SELECT 1 FROM targeting_rules r
+                           JOIN rule_distributions d ON d.rule_id = r.id
+                           WHERE r.flag_environment_id = fe.id AND d.variant_id = $1)",
         )
-        .bind(segment_id)
-        .bind(attribute)
-        .bind(operator)
-        .bind(values)
-        .bind(sort_order)
-        .fetch_one(&self.pool)
+        .bind(variant_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(ids)
+    }
+
+    // ============================================================
+    // Flag Environments
+    // ============================================================
+    pub async fn get_flag_environment(
+        &self,
+        flag_id: Uuid,
+        environment_id: Uuid,
+    ) -> Result<Option<FlagEnvironmentRow>> {
+        let row = sqlx::query_as::<_, FlagEnvironmentRow>(
+            "SELECT * FROM flag_environments WHERE flag_id = $1 AND environment_id = $2",
+        )
+        .bind(flag_id)
+        .bind(environment_id)
+        .fetch_optional(&self.pool)
         .await?;
         Ok(row)
     }
 
+    // ============================================================
+    // Segments
+    // ============================================================
+    pub async fn get_segment(&self, segment_id: Uuid) -> Result<Option<SegmentRow>> {
+        let row = sqlx::query_as::<_, SegmentRow>(
+            &format!("SELECT {SEGMENT_COLS} FROM segments WHERE id = $1"))
+            .bind(segment_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row)
+    }
+
+    pub async fn list_segments(&self, project_id: Uuid) -> Result<Vec<SegmentRow>> {
+        fetch_segments(&mut *self.pool.acquire().await?, project_id).await
+    }
+
+    /// List every targeting rule that references a segment.
+    pub async fn get_segment_usage(&self, segment_id: Uuid) -> Result<Vec<SegmentUsageRow>> {
+        fetch_segment_usage(&mut *self.pool.acquire().await?, segment_id).await
+    }
+
     pub async fn get_segment_constraints(
         &self,
         segment_id: Uuid,
     ) -> Result<Vec<SegmentConstraintRow>> {
-        let rows = sqlx::query_as::<_, SegmentConstraintRow>(
-            &format!("SELECT {CONSTRAINT_COLS} FROM segment_constraints WHERE segment_id = $1 ORDER BY sort_order"),
-        )
-        .bind(segment_id)
-        .fetch_all(&self.pool)
-        .await?;
-        Ok(rows)
+        fetch_segment_constraints(&mut *self.pool.acquire().await?, segment_id).await
     }
 
     // ============================================================
@@ -421,6 +828,16 @@ impl PostgresStore {
         Ok(rows)
     }
 
+    pub async fn get_targeting_rule(&self, rule_id: Uuid) -> Result<Option<TargetingRuleRow>> {
+        let row = sqlx::query_as::<_, TargetingRuleRow>(
+            "SELECT * FROM targeting_rules WHERE id = $1",
+        )
+        .bind(rule_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
     pub async fn get_rule_segments(&self, rule_id: Uuid) -> Result<Vec<RuleSegmentRow>> {
         let rows = sqlx::query_as::<_, RuleSegmentRow>(
             "SELECT * FROM rule_segments WHERE rule_id = $1",
@@ -448,40 +865,32 @@ impl PostgresStore {
         &self,
         flag_environment_id: Uuid,
     ) -> Result<Vec<FlagOverrideRow>> {
-        let rows = sqlx::query_as::<_, FlagOverrideRow>(
-            "SELECT * FROM flag_overrides WHERE flag_environment_id = $1",
-        )
-        .bind(flag_environment_id)
-        .fetch_all(&self.pool)
-        .await?;
-        Ok(rows)
+        fetch_flag_overrides(&mut *self.pool.acquire().await?, flag_environment_id).await
     }
 
     // ============================================================
-    // SDK Keys
+    // Code References
     // ============================================================
-    pub async fn create_sdk_key(
+
+    /// Replace every stored reference for a repository and branch with a
+    /// fresh scan. Other branches of the repository keep their references.
+    /// Each entry is `(flag_id, file_path, line_number, line_content)`.
+    pub async fn list_code_references_for_flag(
         &self,
-        environment_id: Uuid,
-        name: &str,
-        key_type: &str,
-        key_hash: &str,
-        key_prefix: &str,
-    ) -> Result<SdkKeyRow> {
-        let row = sqlx::query_as::<_, SdkKeyRow>(
-            &format!("INSERT INTO sdk_keys (environment_id, name, key_type, key_hash, key_prefix)
-             VALUES ($1, $2, $3::sdk_key_type, $4, $5) RETURNING {SDK_KEY_COLS}"),
+        flag_id: Uuid,
+    ) -> Result<Vec<CodeReferenceRow>> {
+        let rows = sqlx::query_as::<_, CodeReferenceRow>(
+            "SELECT * FROM code_references WHERE flag_id = $1 ORDER BY repository, file_path, line_number",
         )
-        .bind(environment_id)
-        .bind(name)
-        .bind(key_type)
-        .bind(key_hash)
-        .bind(key_prefix)
-        .fetch_one(&self.pool)
+        .bind(flag_id)
+        .fetch_all(&self.pool)
         .await?;
-        Ok(row)
+        Ok(rows)
     }
 
+    // ============================================================
+    // SDK Keys
+    // ============================================================
     pub async fn get_sdk_key_by_hash(&self, key_hash: &str) -> Result<Option<SdkKeyRow>> {
         let row = sqlx::query_as::<_, SdkKeyRow>(
             &format!("SELECT {SDK_KEY_COLS} FROM sdk_keys WHERE key_hash = $1 AND revoked_at IS NULL"),
@@ -505,11 +914,11 @@ impl PostgresStore {
         project_id: Uuid,
     ) -> Result<Vec<SdkKeyRow>> {
         let rows = sqlx::query_as::<_, SdkKeyRow>(
-            &format!("SELECT sk.id, sk.environment_id, sk.name, sk.key_type::TEXT AS key_type, sk.key_hash, sk.key_prefix, sk.last_used_at, sk.created_at, sk.revoked_at
+            "SELECT sk.id, sk.environment_id, sk.name, sk.key_type::TEXT AS key_type, sk.key_hash, sk.key_prefix, sk.last_used_at, sk.created_at, sk.revoked_at
              FROM sdk_keys sk
              JOIN environments e ON sk.environment_id = e.id
              WHERE e.project_id = $1
-             ORDER BY sk.created_at DESC"),
+             ORDER BY sk.created_at DESC",
         )
         .bind(project_id)
         .fetch_all(&self.pool)
@@ -517,20 +926,11 @@ impl PostgresStore {
         Ok(rows)
     }
 
-    pub async fn revoke_sdk_key(&self, key_id: Uuid) -> Result<SdkKeyRow> {
-        let row = sqlx::query_as::<_, SdkKeyRow>(
-            &format!("UPDATE sdk_keys SET revoked_at = NOW() WHERE id = $1 RETURNING {SDK_KEY_COLS}"),
-        )
-        .bind(key_id)
-        .fetch_one(&self.pool)
-        .await?;
-        Ok(row)
-    }
-
-    pub async fn list_all_projects(&self) -> Result<Vec<ProjectRow>> {
+    pub async fn list_projects_for_organizations(&self, org_ids: &[Uuid]) -> Result<Vec<ProjectRow>> {
         let rows = sqlx::query_as::<_, ProjectRow>(
-            "SELECT * FROM projects ORDER BY created_at DESC",
+            "SELECT * FROM projects WHERE organization_id = ANY($1) ORDER BY created_at DESC",
         )
+        .bind(org_ids)
         .fetch_all(&self.pool)
         .await?;
         Ok(rows)
@@ -540,13 +940,17 @@ impl PostgresStore {
         &self,
         flag_id: Uuid,
     ) -> Result<Vec<FlagEnvironmentRow>> {
-        let rows = sqlx::query_as::<_, FlagEnvironmentRow>(
-            "SELECT * FROM flag_environments WHERE flag_id = $1",
-        )
-        .bind(flag_id)
-        .fetch_all(&self.pool)
-        .await?;
-        Ok(rows)
+        fetch_flag_environments(&mut *self.pool.acquire().await?, flag_id).await
+    }
+
+    /// A flag environment's rules, with their segments and distributions, and
+    /// its overrides.
+    pub async fn get_flag_environment_contents(
+        &self,
+        flag_environment_id: Uuid,
+    ) -> Result<FlagEnvironmentContents> {
+        fetch_flag_environment_contents(&mut *self.pool.acquire().await?, flag_environment_id)
+            .await
     }
 
     // ============================================================
@@ -557,204 +961,3615 @@ impl PostgresStore {
         project_id: Uuid,
         environment_id: Uuid,
     ) -> Result<eval::FlagsConfig> {
-        let flags = self.list_flags(project_id).await?;
-        let all_segments = self.list_segments(project_id).await?;
-
-        let mut flag_configs = std::collections::HashMap::new();
-        let mut segment_map = std::collections::HashMap::new();
-
-        // Build segment map
-        for seg in &all_segments {
-            let constraints = self.get_segment_constraints(seg.id).await?;
-            segment_map.insert(
-                seg.id,
-                eval::Segment {
-                    id: seg.id,
-                    key: seg.key.clone(),
-                    name: seg.name.clone(),
-                    match_type: match seg.match_type.as_str() {
-                        "any" => eval::MatchType::Any,
-                        _ => eval::MatchType::All,
-                    },
-                    constraints: constraints
-                        .into_iter()
-                        .map(|c| eval::SegmentConstraint {
-                            attribute: c.attribute,
-                            operator: parse_operator(&c.operator),
-                            values: c.values,
-                        })
-                        .collect(),
-                },
-            );
-        }
-
-        // Build flag configs
-        for flag in &flags {
-            let variants = self.get_flag_variants(flag.id).await?;
-            let flag_env = self.get_flag_environment(flag.id, environment_id).await?;
-
-            let Some(fe) = flag_env else { continue };
-
-            let rules = self.get_targeting_rules(fe.id).await?;
-            let overrides = self.get_flag_overrides(fe.id).await?;
-
-            let mut eval_rules = Vec::new();
-            for rule in rules {
-                let rule_segs = self.get_rule_segments(rule.id).await?;
-                let rule_dists = self.get_rule_distributions(rule.id).await?;
-
-                eval_rules.push(eval::TargetingRule {
-                    id: rule.id,
-                    rank: rule.rank,
-                    description: rule.description,
-                    segments: rule_segs
-                        .into_iter()
-                        .map(|rs| eval::RuleSegment {
-                            segment_id: rs.segment_id,
-                            negate: rs.negate,
-                        })
-                        .collect(),
-                    distributions: rule_dists
-                        .into_iter()
-                        .map(|rd| eval::RuleDistribution {
-                            variant_id: rd.variant_id,
-                            rollout_pct: rd.rollout_pct,
-                        })
-                        .collect(),
-                    variant_id: rule.variant_id,
-                });
-            }
+        fetch_flags_config(&mut *self.pool.acquire().await?, project_id, environment_id).await
+    }
 
-            let default_variant_id = fe.default_variant_id.unwrap_or_else(|| {
-                variants.first().map(|v| v.id).unwrap_or_default()
-            });
+    // ============================================================
+    // Config Snapshots
+    // ============================================================
 
-            flag_configs.insert(
-                flag.key.clone(),
-                eval::FlagConfig {
-                    key: flag.key.clone(),
-                    flag_type: match flag.flag_type.as_str() {
-                        "string" => eval::FlagType::String,
-                        "number" => eval::FlagType::Number,
-                        "json" => eval::FlagType::Json,
-                        _ => eval::FlagType::Boolean,
-                    },
-                    variants: variants
-                        .into_iter()
-                        .map(|v| eval::Variant {
-                            id: v.id,
-                            key: v.key,
-                            value: v.value,
-                            description: v.description,
-                        })
-                        .collect(),
-                    environment: eval::FlagEnvironment {
-                        enabled: fe.enabled,
-                        default_variant_id,
-                        rules: eval_rules,
-                        overrides: overrides
-                            .into_iter()
-                            .map(|o| eval::FlagOverride {
-                                targeting_key: o.targeting_key,
-                                variant_id: o.variant_id,
-                            })
-                            .collect(),
-                    },
-                },
-            );
-        }
+    /// Bump the config version of an environment and store its full config
+    /// as of the new version, returning that version.
+    ///
+    /// Both happen in one transaction holding the `config_versions` row
+    /// lock, so concurrent bumps are serialized and every version's snapshot
+    /// is built from data committed before it.
+    pub async fn publish_config_version(
+        &self,
+        project_id: Uuid,
+        environment_id: Uuid,
+    ) -> Result<i64> {
+        let mut tx = self.pool.begin().await?;
+        let version: i64 = sqlx::query_scalar(
+            "UPDATE config_versions SET version = version + 1, updated_at = NOW()
+             WHERE environment_id = $1 RETURNING version",
+        )
+        .bind(environment_id)
+        .fetch_one(&mut *tx)
+        .await?;
 
-        // Get config version
-        let version_row = sqlx::query_as::<_, ConfigVersionRow>(
-            "SELECT * FROM config_versions WHERE environment_id = $1",
+        let mut config = fetch_flags_config(&mut tx, project_id, environment_id).await?;
+        config.version = version;
+        sqlx::query(
+            "INSERT INTO config_snapshots (environment_id, version, project_id, config)
+             VALUES ($1, $2, $3, $4)",
         )
         .bind(environment_id)
-        .fetch_optional(&self.pool)
+        .bind(version)
+        .bind(project_id)
+        .bind(serde_json::to_value(&config)?)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(eval::FlagsConfig {
-            flags: flag_configs,
-            segments: segment_map,
-            version: version_row.map(|v| v.version).unwrap_or(1),
-        })
+        tx.commit().await?;
+        Ok(version)
     }
 
-    /// Increment config version for cache invalidation.
-    pub async fn increment_config_version(&self, environment_id: Uuid) -> Result<i64> {
-        let row = sqlx::query_as::<_, ConfigVersionRow>(
-            "UPDATE config_versions SET version = version + 1, updated_at = NOW()
-             WHERE environment_id = $1 RETURNING *",
+    /// Versions of an environment with when they were recorded, newest
+    /// first, starting below `before` if given.
+    pub async fn list_config_versions(
+        &self,
+        environment_id: Uuid,
+        before: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<(i64, chrono::DateTime<chrono::Utc>)>> {
+        let rows = sqlx::query_as(
+            "SELECT version, created_at FROM config_snapshots
+             WHERE environment_id = $1 AND ($2::BIGINT IS NULL OR version < $2)
+             ORDER BY version DESC
+             LIMIT $3",
         )
         .bind(environment_id)
-        .fetch_one(&self.pool)
+        .bind(before)
+        .bind(limit)
+        .fetch_all(&self.pool)
         .await?;
-        Ok(row.version)
+        Ok(rows)
     }
 
-    // ============================================================
-    // Audit Log
-    // ============================================================
+    pub async fn get_config_snapshot(
+        &self,
+        environment_id: Uuid,
+        version: i64,
+    ) -> Result<Option<ConfigSnapshotRow>> {
+        let row = sqlx::query_as::<_, ConfigSnapshotRow>(
+            "SELECT * FROM config_snapshots WHERE environment_id = $1 AND version = $2",
+        )
+        .bind(environment_id)
+        .bind(version)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    /// The version that was live in an environment at `at`: the newest one
+    /// recorded at or before it.
+    pub async fn get_config_snapshot_at(
+        &self,
+        environment_id: Uuid,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<ConfigSnapshotRow>> {
+        let row = sqlx::query_as::<_, ConfigSnapshotRow>(
+            "SELECT * FROM config_snapshots
+             WHERE environment_id = $1 AND created_at <= $2
+             ORDER BY version DESC
+             LIMIT 1",
+        )
+        .bind(environment_id)
+        .bind(at)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    /// Audit entries that can change an environment's config, recorded in
+    /// `[from, to)`, oldest first. Project-wide entries, such as segment
+    /// edits, are included.
+    pub async fn list_config_audit_log(
+        &self,
+        project_id: Uuid,
+        environment_id: Uuid,
+        from: chrono::DateTime<chrono::Utc>,
+        to: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<Vec<AuditLogRow>> {
+        let rows = sqlx::query_as::<_, AuditLogRow>(
+            "SELECT * FROM audit_log
+             WHERE project_id = $1
+               AND (environment_id = $2 OR environment_id IS NULL)
+               AND entity_type = ANY($3)
+               AND action <> 'permission_denied'
+               AND created_at >= $4 AND ($5::TIMESTAMPTZ IS NULL OR created_at < $5)
+             ORDER BY created_at, id",
+        )
+        .bind(project_id)
+        .bind(environment_id)
+        .bind(CONFIG_ENTITY_TYPES)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Leaf-level differences between two stored versions, keyed by path
+    /// (`flags.checkout.environment.enabled`). `None` if either is missing.
+    pub async fn diff_config_versions(
+        &self,
+        environment_id: Uuid,
+        from: i64,
+        to: i64,
+    ) -> Result<Option<serde_json::Map<String, serde_json::Value>>> {
+        let (Some(mut before), Some(mut after)) = (
+            self.get_config_snapshot(environment_id, from).await?,
+            self.get_config_snapshot(environment_id, to).await?,
+        ) else {
+            return Ok(None);
+        };
+        for config in [&mut before.config, &mut after.config] {
+            if let Some(obj) = config.as_object_mut() {
+                obj.remove("version");
+            }
+        }
+
+        let mut changes = serde_json::Map::new();
+        json_diff(String::new(), &before.config, &after.config, &mut changes);
+        Ok(Some(changes))
+    }
+
+    // ============================================================
+    // Audit Log
+    // ============================================================
+    /// Record an audit entry. The diff between `before_state` and
+    /// `after_state` is computed here, and the request details in `ctx` are
+    /// stored as `metadata`.
+    ///
+    /// The entry is appended to the project's hash chain; writers for the
+    /// same project are serialised by an advisory lock.
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_audit_log(
         &self,
         project_id: Uuid,
-        actor_email: Option<&str>,
+        ctx: &AuditContext,
         action: &str,
         entity_type: &str,
         entity_id: Option<Uuid>,
         before_state: Option<&serde_json::Value>,
         after_state: Option<&serde_json::Value>,
     ) -> Result<AuditLogRow> {
-        let row = sqlx::query_as::<_, AuditLogRow>(
-            "INSERT INTO audit_log (project_id, actor_email, action, entity_type, entity_id, before_state, after_state)
-             VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *",
+        let mut tx = self.pool.begin().await?;
+        let row = insert_audit_log(
+            &mut tx,
+            &self.audit_key,
+            project_id,
+            ctx,
+            action,
+            entity_type,
+            entity_id,
+            before_state,
+            after_state,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
+    }
+
+    /// Walk a project's audit hash chain from the first entry and report the
+    /// first entry that is missing, altered or out of place.
+    ///
+    /// Entries hashed before the chain was keyed still verify with plain
+    /// SHA-256, but only ahead of the first keyed entry.
+    pub async fn verify_audit_chain(&self, project_id: Uuid) -> Result<AuditChainReport> {
+        const BATCH_SIZE: i64 = 1000;
+
+        let mut walk = AuditChainWalk::new(&self.audit_key);
+        loop {
+            let batch = sqlx::query_as::<_, AuditLogRow>(
+                "SELECT * FROM audit_log WHERE project_id = $1 AND seq >= $2
+                 ORDER BY seq LIMIT $3",
+            )
+            .bind(project_id)
+            .bind(walk.expected_seq)
+            .bind(BATCH_SIZE)
+            .fetch_all(&self.pool)
+            .await?;
+
+            for row in &batch {
+                if !walk.push(row) {
+                    return Ok(walk.report);
+                }
+            }
+
+            if (batch.len() as i64) < BATCH_SIZE {
+                return Ok(walk.report);
+            }
+        }
+    }
+
+    /// The project's latest audit entry, for anchoring the chain outside the
+    /// database. `None` until the project has a hashed entry.
+    pub async fn get_audit_chain_head(&self, project_id: Uuid) -> Result<Option<AuditChainHead>> {
+        let row = sqlx::query_as::<_, AuditChainHead>(
+            "SELECT seq, hash, created_at FROM audit_log
+             WHERE project_id = $1 AND hash IS NOT NULL
+             ORDER BY seq DESC LIMIT 1",
         )
         .bind(project_id)
-        .bind(actor_email)
-        .bind(action)
-        .bind(entity_type)
-        .bind(entity_id)
-        .bind(before_state)
-        .bind(after_state)
-        .fetch_one(&self.pool)
+        .fetch_optional(&self.pool)
         .await?;
         Ok(row)
     }
 
+    /// Entries matching `filter`, newest first. `before_seq` is the chain
+    /// position of the last entry of the previous page; positions are unique
+    /// and follow insertion order, so pages never overlap or skip, even when
+    /// entries share a timestamp.
     pub async fn list_audit_log(
         &self,
         project_id: Uuid,
+        filter: &AuditLogFilter,
+        before_seq: Option<i64>,
         limit: i64,
-        offset: i64,
     ) -> Result<Vec<AuditLogRow>> {
         let rows = sqlx::query_as::<_, AuditLogRow>(
-            "SELECT * FROM audit_log WHERE project_id = $1 ORDER BY created_at DESC LIMIT $2 OFFSET $3",
+            "SELECT * FROM audit_log
+             WHERE project_id = $1
+               AND ($2::TEXT IS NULL OR entity_type = $2)
+               AND ($3::UUID IS NULL OR entity_id = $3)
+               AND ($4::TEXT IS NULL OR action = $4)
+               AND ($5::TEXT IS NULL OR actor_email = $5 OR actor_id::TEXT = $5)
+               AND ($6::UUID IS NULL OR environment_id = $6)
+               AND ($7::TIMESTAMPTZ IS NULL OR created_at >= $7)
+               AND ($8::TIMESTAMPTZ IS NULL OR created_at < $8)
+               AND ($9::BIGINT IS NULL OR seq < $9)
+             ORDER BY seq DESC
+             LIMIT $10",
+        )
+        .bind(project_id)
+        .bind(filter.entity_type.as_deref())
+        .bind(filter.entity_id)
+        .bind(filter.action.as_deref())
+        .bind(filter.actor.as_deref())
+        .bind(filter.environment_id)
+        .bind(filter.from)
+        .bind(filter.to)
+        .bind(before_seq)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    // ============================================================
+    // Webhooks
+    // ============================================================
+
+    pub async fn list_webhooks(&self, project_id: Uuid) -> Result<Vec<WebhookRow>> {
+        let rows = sqlx::query_as::<_, WebhookRow>(
+            "SELECT * FROM webhooks WHERE project_id = $1 ORDER BY created_at",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    pub async fn get_webhook(&self, project_id: Uuid, webhook_id: Uuid) -> Result<Option<WebhookRow>> {
+        let row = sqlx::query_as::<_, WebhookRow>(
+            "SELECT * FROM webhooks WHERE id = $1 AND project_id = $2",
         )
+        .bind(webhook_id)
         .bind(project_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    /// Queue `event` for every enabled webhook of the project subscribed to
+    /// it. Audit log entries are queued by [`Self::create_audit_log`].
+    pub async fn enqueue_webhook_event(
+        &self,
+        project_id: Uuid,
+        event: &str,
+        data: &serde_json::Value,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        enqueue_webhook_event(&mut conn, project_id, event, chrono::Utc::now(), data).await
+    }
+
+    /// Claim up to `limit` due deliveries for sending.
+    ///
+    /// Claimed deliveries are leased by pushing `next_attempt_at` forward, so
+    /// other replicas skip them and a worker that dies mid-send only delays
+    /// the delivery until the lease runs out.
+    pub async fn claim_webhook_deliveries(&self, limit: i64) -> Result<Vec<PendingWebhookDelivery>> {
+        let rows = sqlx::query_as::<_, PendingWebhookDelivery>(
+            "WITH due AS (
+                SELECT d.id FROM webhook_deliveries d
+                JOIN webhooks w ON w.id = d.webhook_id
+                WHERE d.status = 'pending' AND d.next_attempt_at <= NOW() AND w.enabled
+                ORDER BY d.next_attempt_at
+                LIMIT $1
+                FOR UPDATE OF d SKIP LOCKED
+             )
+             UPDATE webhook_deliveries d SET next_attempt_at = NOW() + INTERVAL '5 minutes'
+             FROM due, webhooks w
+             WHERE d.id = due.id AND w.id = d.webhook_id
+             RETURNING d.id, d.event, d.payload, d.attempts, w.url, w.secret",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Record the outcome of one delivery attempt. A failed attempt is
+    /// retried after `retry_in`, or given up on if that is `None`.
+    pub async fn record_webhook_attempt(
+        &self,
+        delivery_id: Uuid,
+        status_code: Option<i32>,
+        error: Option<&str>,
+        retry_in: Option<chrono::Duration>,
+    ) -> Result<()> {
+        let status = match (error, retry_in) {
+            (None, _) => "succeeded",
+            (Some(_), Some(_)) => "pending",
+            (Some(_), None) => "failed",
+        };
+        sqlx::query(
+            "UPDATE webhook_deliveries SET
+                status = $2::webhook_delivery_status,
+                attempts = attempts + 1,
+                last_status_code = $3,
+                last_error = $4,
+                next_attempt_at = NOW() + COALESCE($5, 0) * INTERVAL '1 second',
+                delivered_at = CASE WHEN $2 = 'succeeded' THEN NOW() END
+             WHERE id = $1",
+        )
+        .bind(delivery_id)
+        .bind(status)
+        .bind(status_code)
+        .bind(error)
+        .bind(retry_in.map(|d| d.num_seconds()))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Deliveries of a webhook, newest first.
+    pub async fn list_webhook_deliveries(
+        &self,
+        webhook_id: Uuid,
+        status: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<WebhookDeliveryRow>> {
+        let rows = sqlx::query_as::<_, WebhookDeliveryRow>(
+            "SELECT id, webhook_id, project_id, event, payload, status::TEXT AS status, attempts,
+                    next_attempt_at, last_status_code, last_error, delivered_at, created_at
+             FROM webhook_deliveries
+             WHERE webhook_id = $1 AND ($2::TEXT IS NULL OR status::TEXT = $2)
+             ORDER BY created_at DESC
+             LIMIT $3",
+        )
+        .bind(webhook_id)
+        .bind(status)
         .bind(limit)
-        .bind(offset)
         .fetch_all(&self.pool)
         .await?;
         Ok(rows)
     }
+
+    /// Queue a fresh copy of a past delivery, leaving the original in the
+    /// log. Returns `None` if the delivery does not belong to the webhook.
+    pub async fn redeliver_webhook_delivery(
+        &self,
+        webhook_id: Uuid,
+        delivery_id: Uuid,
+    ) -> Result<Option<WebhookDeliveryRow>> {
+        let row = sqlx::query_as::<_, WebhookDeliveryRow>(
+            "INSERT INTO webhook_deliveries (webhook_id, project_id, event, payload)
+             SELECT webhook_id, project_id, event, payload FROM webhook_deliveries
+             WHERE id = $1 AND webhook_id = $2
+             RETURNING id, webhook_id, project_id, event, payload, status::TEXT AS status,
+                       attempts, next_attempt_at, last_status_code, last_error, delivered_at,
+                       created_at",
+        )
+        .bind(delivery_id)
+        .bind(webhook_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
 }
 
-fn parse_operator(s: &str) -> eval::Operator {
-    match s {
-        "eq" => eval::Operator::Eq,
-        "neq" => eval::Operator::Neq,
-        "gt" => eval::Operator::Gt,
-        "gte" => eval::Operator::Gte,
-        "lt" => eval::Operator::Lt,
-        "lte" => eval::Operator::Lte,
-        "in" => eval::Operator::In,
-        "not_in" => eval::Operator::NotIn,
-        "contains" => eval::Operator::Contains,
-        "starts_with" => eval::Operator::StartsWith,
-        "ends_with" => eval::Operator::EndsWith,
-        "matches" => eval::Operator::Matches,
-        "semver_eq" => eval::Operator::SemverEq,
-        "semver_gt" => eval::Operator::SemverGt,
-        "semver_lt" => eval::Operator::SemverLt,
-        _ => eval::Operator::Eq,
+// ============================================================
+// Unit of Work
+// ============================================================
+
+/// One transaction spanning a multi-row management write (creating a flag
+/// with its variants, cloning an environment, promoting, reordering rules).
+///
+/// Nothing is visible to other connections until [`UnitOfWork::commit`], and
+/// dropping the unit of work rolls everything back. Writes that change an
+/// environment's evaluation config record that environment; `commit` returns
+/// them so the caller publishes config changes only once the data is durable.
+pub struct UnitOfWork {
+    tx: sqlx::Transaction<'static, sqlx::Postgres>,
+    audit_key: Arc<[u8]>,
+    changed_environments: Vec<Uuid>,
+}
+
+impl UnitOfWork {
+    /// Commit, returning the environments whose config changed.
+    pub async fn commit(self) -> Result<Vec<Uuid>> {
+        self.tx.commit().await?;
+        Ok(self.changed_environments)
+    }
+
+    fn mark_changed(&mut self, environment_id: Uuid) {
+        if !self.changed_environments.contains(&environment_id) {
+            self.changed_environments.push(environment_id);
+        }
+    }
+
+    /// Record every environment of the project, for changes that reach all
+    /// of their configs.
+    async fn mark_project_changed(&mut self, project_id: Uuid) -> Result<()> {
+        let environment_ids: Vec<Uuid> =
+            sqlx::query_scalar("SELECT id FROM environments WHERE project_id = $1")
+                .bind(project_id)
+                .fetch_all(&mut *self.tx)
+                .await?;
+        for environment_id in environment_ids {
+            self.mark_changed(environment_id);
+        }
+        Ok(())
+    }
+
+    // Reads that must see this unit of work's own writes, or that a write
+    // depends on, go through the transaction rather than the pool.
+
+    pub async fn list_environments(&mut self, project_id: Uuid) -> Result<Vec<EnvironmentRow>> {
+        fetch_environments(&mut self.tx, project_id).await
+    }
+
+    pub async fn lock_environment(
+        &mut self,
+        environment_id: Uuid,
+    ) -> Result<Option<EnvironmentRow>> {
+        let row = sqlx::query_as::<_, EnvironmentRow>(
+            "SELECT * FROM environments WHERE id = $1 FOR UPDATE",
+        )
+        .bind(environment_id)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    pub async fn update_environment(
+        &mut self,
+        environment_id: Uuid,
+        name: Option<&str>,
+        color: Option<&str>,
+        sort_order: Option<i32>,
+        requires_approval: Option<bool>,
+    ) -> Result<EnvironmentRow> {
+        let row = sqlx::query_as::<_, EnvironmentRow>(
+            "UPDATE environments SET
+                name = COALESCE($2, name),
+                color = COALESCE($3, color),
+                sort_order = COALESCE($4, sort_order),
+                requires_approval = COALESCE($5, requires_approval)
+             WHERE id = $1
+             RETURNING *",
+        )
+        .bind(environment_id)
+        .bind(name)
+        .bind(color)
+        .bind(sort_order)
+        .bind(requires_approval)
+        .fetch_one(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    /// Every flag of a project, archived included, ordered by key.
+    pub async fn list_all_flags(&mut self, project_id: Uuid) -> Result<Vec<FlagRow>> {
+        fetch_all_flags(&mut self.tx, project_id).await
+    }
+
+    /// Lock every flag, flag environment and segment of a project until
+    /// commit, for writes that read the whole project first and must not
+    /// overwrite changes made in between.
+    pub async fn lock_project(&mut self, project_id: Uuid) -> Result<()> {
+        sqlx::query("SELECT id FROM flags WHERE project_id = $1 ORDER BY id FOR UPDATE")
+            .bind(project_id)
+            .execute(&mut *self.tx)
+            .await?;
+        sqlx::query(
+            "SELECT fe.id FROM flag_environments fe
+             JOIN flags f ON fe.flag_id = f.id
+             WHERE f.project_id = $1
+             ORDER BY fe.id
+             FOR UPDATE OF fe",
+        )
+        .bind(project_id)
+        .execute(&mut *self.tx)
+        .await?;
+        sqlx::query("SELECT id FROM segments WHERE project_id = $1 ORDER BY id FOR UPDATE")
+            .bind(project_id)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_flag_variants(&mut self, flag_id: Uuid) -> Result<Vec<FlagVariantRow>> {
+        fetch_flag_variants(&mut self.tx, flag_id).await
+    }
+
+    pub async fn list_flag_environments(
+        &mut self,
+        flag_id: Uuid,
+    ) -> Result<Vec<FlagEnvironmentRow>> {
+        fetch_flag_environments(&mut self.tx, flag_id).await
+    }
+
+    /// [`Self::list_flag_environments`], locking the rows until commit so
+    /// no other write can change the flag's state while this one decides.
+    pub async fn lock_flag_environments(
+        &mut self,
+        flag_id: Uuid,
+    ) -> Result<Vec<FlagEnvironmentRow>> {
+        let rows = sqlx::query_as::<_, FlagEnvironmentRow>(
+            "SELECT * FROM flag_environments WHERE flag_id = $1 FOR UPDATE",
+        )
+        .bind(flag_id)
+        .fetch_all(&mut *self.tx)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Lock one flag environment against concurrent writers until commit.
+    pub async fn lock_flag_environment(
+        &mut self,
+        flag_id: Uuid,
+        environment_id: Uuid,
+    ) -> Result<Option<FlagEnvironmentRow>> {
+        let row = sqlx::query_as::<_, FlagEnvironmentRow>(
+            "SELECT * FROM flag_environments WHERE flag_id = $1 AND environment_id = $2 FOR UPDATE",
+        )
+        .bind(flag_id)
+        .bind(environment_id)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    pub async fn get_flag_environment_contents(
+        &mut self,
+        flag_environment_id: Uuid,
+    ) -> Result<FlagEnvironmentContents> {
+        fetch_flag_environment_contents(&mut self.tx, flag_environment_id).await
+    }
+
+    pub async fn list_segments(&mut self, project_id: Uuid) -> Result<Vec<SegmentRow>> {
+        fetch_segments(&mut self.tx, project_id).await
+    }
+
+    pub async fn get_segment_constraints(
+        &mut self,
+        segment_id: Uuid,
+    ) -> Result<Vec<SegmentConstraintRow>> {
+        fetch_segment_constraints(&mut self.tx, segment_id).await
+    }
+
+    pub async fn create_organization(
+        &mut self,
+        name: &str,
+        slug: &str,
+        external_id: Option<&str>,
+    ) -> Result<OrganizationRow> {
+        insert_organization(&mut self.tx, name, slug, external_id).await
+    }
+
+    /// Returns false if the user was already a member; their role is kept.
+    pub async fn add_organization_member(
+        &mut self,
+        org_id: Uuid,
+        user_id: Uuid,
+        role: &str,
+    ) -> Result<bool> {
+        insert_organization_member(&mut self.tx, org_id, user_id, role).await
+    }
+
+    pub async fn create_project(
+        &mut self,
+        org_id: Uuid,
+        name: &str,
+        slug: &str,
+        description: Option<&str>,
+    ) -> Result<ProjectRow> {
+        let row = sqlx::query_as::<_, ProjectRow>(
+            "INSERT INTO projects (organization_id, name, slug, description)
+             VALUES ($1, $2, $3, $4) RETURNING *",
+        )
+        .bind(org_id)
+        .bind(name)
+        .bind(slug)
+        .bind(description)
+        .fetch_one(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    pub async fn set_project_member_role(
+        &mut self,
+        project_id: Uuid,
+        user_id: Uuid,
+        role: &str,
+    ) -> Result<ProjectMemberRow> {
+        upsert_project_member(&mut self.tx, project_id, user_id, role).await
+    }
+
+    /// Returns false if the user had no explicit role.
+    pub async fn remove_project_member(&mut self, project_id: Uuid, user_id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM project_members WHERE project_id = $1 AND user_id = $2")
+            .bind(project_id)
+            .bind(user_id)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list_environment_permissions(
+        &mut self,
+        environment_id: Uuid,
+    ) -> Result<Vec<EnvironmentPermissionRow>> {
+        fetch_environment_permissions(&mut self.tx, environment_id).await
+    }
+
+    /// Set whether an environment is restricted and replace its grants.
+    pub async fn replace_environment_permissions(
+        &mut self,
+        environment_id: Uuid,
+        restricted: bool,
+        grants: &[(Uuid, String)],
+    ) -> Result<EnvironmentRow> {
+        let env = sqlx::query_as::<_, EnvironmentRow>(
+            "UPDATE environments SET restricted = $2 WHERE id = $1 RETURNING *",
+        )
+        .bind(environment_id)
+        .bind(restricted)
+        .fetch_one(&mut *self.tx)
+        .await?;
+
+        sqlx::query("DELETE FROM environment_permissions WHERE environment_id = $1")
+            .bind(environment_id)
+            .execute(&mut *self.tx)
+            .await?;
+
+        for (user_id, role) in grants {
+            sqlx::query(
+                "INSERT INTO environment_permissions (environment_id, user_id, role)
+                 VALUES ($1, $2, $3::project_role)",
+            )
+            .bind(environment_id)
+            .bind(user_id)
+            .bind(role)
+            .execute(&mut *self.tx)
+            .await?;
+        }
+
+        Ok(env)
+    }
+
+    pub async fn update_project(
+        &mut self,
+        project_id: Uuid,
+        name: Option<&str>,
+        slug: Option<&str>,
+        description: Option<&str>,
+    ) -> Result<ProjectRow> {
+        let row = sqlx::query_as::<_, ProjectRow>(
+            "UPDATE projects SET
+                name = COALESCE($2, name),
+                slug = COALESCE($3, slug),
+                description = COALESCE($4, description)
+             WHERE id = $1
+             RETURNING *",
+        )
+        .bind(project_id)
+        .bind(name)
+        .bind(slug)
+        .bind(description)
+        .fetch_one(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    pub async fn create_sdk_key(
+        &mut self,
+        environment_id: Uuid,
+        name: &str,
+        key_type: &str,
+        key_hash: &str,
+        key_prefix: &str,
+    ) -> Result<SdkKeyRow> {
+        insert_sdk_key(&mut self.tx, environment_id, name, key_type, key_hash, key_prefix).await
+    }
+
+    pub async fn revoke_sdk_key(&mut self, key_id: Uuid) -> Result<SdkKeyRow> {
+        let row = sqlx::query_as::<_, SdkKeyRow>(
+            &format!("UPDATE sdk_keys SET revoked_at = NOW() WHERE id = $1 RETURNING {SDK_KEY_COLS}"),
+        )
+        .bind(key_id)
+        .fetch_one(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_webhook(
+        &mut self,
+        project_id: Uuid,
+        url: &str,
+        description: Option<&str>,
+        secret: &str,
+        events: &[String],
+        enabled: bool,
+        created_by: Option<Uuid>,
+    ) -> Result<WebhookRow> {
+        let row = sqlx::query_as::<_, WebhookRow>(
+            "INSERT INTO webhooks (project_id, url, description, secret, events, enabled, created_by)
+             VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *",
+        )
+        .bind(project_id)
+        .bind(url)
+        .bind(description)
+        .bind(secret)
+        .bind(events)
+        .bind(enabled)
+        .bind(created_by)
+        .fetch_one(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    pub async fn update_webhook(
+        &mut self,
+        webhook_id: Uuid,
+        url: Option<&str>,
+        description: Option<&str>,
+        events: Option<&[String]>,
+        enabled: Option<bool>,
+        secret: Option<&str>,
+    ) -> Result<WebhookRow> {
+        let row = sqlx::query_as::<_, WebhookRow>(
+            "UPDATE webhooks SET
+                url = COALESCE($2, url),
+                description = COALESCE($3, description),
+                events = COALESCE($4, events),
+                enabled = COALESCE($5, enabled),
+                secret = COALESCE($6, secret)
+             WHERE id = $1
+             RETURNING *",
+        )
+        .bind(webhook_id)
+        .bind(url)
+        .bind(description)
+        .bind(events)
+        .bind(enabled)
+        .bind(secret)
+        .fetch_one(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    pub async fn delete_webhook(&mut self, webhook_id: Uuid) -> Result<()> {
+        sqlx::query("DELETE FROM webhooks WHERE id = $1")
+            .bind(webhook_id)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn create_environment(
+        &mut self,
+        project_id: Uuid,
+        name: &str,
+        slug: &str,
+        color: Option<&str>,
+        requires_approval: bool,
+    ) -> Result<EnvironmentRow> {
+        let mut row = insert_environment(&mut self.tx, project_id, name, slug, color).await?;
+        if requires_approval {
+            row = sqlx::query_as::<_, EnvironmentRow>(
+                "UPDATE environments SET requires_approval = TRUE WHERE id = $1 RETURNING *",
+            )
+            .bind(row.id)
+            .fetch_one(&mut *self.tx)
+            .await?;
+        }
+        self.mark_changed(row.id);
+        Ok(row)
+    }
+
+    pub async fn delete_environment(&mut self, environment_id: Uuid) -> Result<()> {
+        sqlx::query("DELETE FROM environments WHERE id = $1")
+            .bind(environment_id)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+
+    /// Create a segment with its constraints, each `(attribute, operator,
+    /// values)`.
+    pub async fn create_segment(
+        &mut self,
+        project_id: Uuid,
+        key: &str,
+        name: &str,
+        description: Option<&str>,
+        match_type: &str,
+        constraints: &[(String, String, Vec<String>)],
+    ) -> Result<(SegmentRow, Vec<SegmentConstraintRow>)> {
+        let row = sqlx::query_as::<_, SegmentRow>(&format!(
+            "INSERT INTO segments (project_id, key, name, description, match_type)
+             VALUES ($1, $2, $3, $4, $5::match_type) RETURNING {SEGMENT_COLS}"
+        ))
+        .bind(project_id)
+        .bind(key)
+        .bind(name)
+        .bind(description)
+        .bind(match_type)
+        .fetch_one(&mut *self.tx)
+        .await?;
+        let constraints = insert_segment_constraints(&mut self.tx, row.id, constraints).await?;
+        self.mark_project_changed(project_id).await?;
+        Ok((row, constraints))
+    }
+
+    /// Update a segment (`None` fields are kept) and, when given, replace its
+    /// constraints. Returns `None` (and changes nothing) when
+    /// `expected_revision` no longer matches the row.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_segment(
+        &mut self,
+        segment_id: Uuid,
+        expected_revision: Option<i64>,
+        name: Option<&str>,
+        description: Option<&str>,
+        match_type: Option<&str>,
+        constraints: Option<&[(String, String, Vec<String>)]>,
+    ) -> Result<Option<(SegmentRow, Vec<SegmentConstraintRow>)>> {
+        let Some(row) = sqlx::query_as::<_, SegmentRow>(&format!(
+            "UPDATE segments SET
+                name = COALESCE($2, name),
+                description = COALESCE($3, description),
+                match_type = COALESCE($4::match_type, match_type)
+             WHERE id = $1 AND ($5::BIGINT IS NULL OR revision = $5)
+             RETURNING {SEGMENT_COLS}"
+        ))
+        .bind(segment_id)
+        .bind(name)
+        .bind(description)
+        .bind(match_type)
+        .bind(expected_revision)
+        .fetch_optional(&mut *self.tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let constraints = match constraints {
+            Some(constraints) => {
+                sqlx::query("DELETE FROM segment_constraints WHERE segment_id = $1")
+                    .bind(segment_id)
+                    .execute(&mut *self.tx)
+                    .await?;
+                insert_segment_constraints(&mut self.tx, segment_id, constraints).await?
+            }
+            None => fetch_segment_constraints(&mut self.tx, segment_id).await?,
+        };
+
+        self.mark_project_changed(row.project_id).await?;
+        Ok(Some((row, constraints)))
+    }
+
+    /// Load and lock a segment until commit. While it is held no other
+    /// transaction can change it or start referencing it from a rule.
+    pub async fn lock_segment(&mut self, segment_id: Uuid) -> Result<Option<SegmentRow>> {
+        let row = sqlx::query_as::<_, SegmentRow>(&format!(
+            "SELECT {SEGMENT_COLS} FROM segments WHERE id = $1 FOR UPDATE"
+        ))
+        .bind(segment_id)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    /// List every targeting rule that references a segment.
+    pub async fn get_segment_usage(&mut self, segment_id: Uuid) -> Result<Vec<SegmentUsageRow>> {
+        fetch_segment_usage(&mut self.tx, segment_id).await
+    }
+
+    /// Delete a segment together with every targeting rule that references
+    /// it. Dropping only the reference would leave a rule with fewer segments,
+    /// which matches more users than it did.
+    pub async fn delete_segment(&mut self, segment: &SegmentRow) -> Result<()> {
+        sqlx::query(
+            "DELETE FROM targeting_rules
+             WHERE id IN (SELECT rule_id FROM rule_segments WHERE segment_id = $1)",
+        )
+        .bind(segment.id)
+        .execute(&mut *self.tx)
+        .await?;
+        sqlx::query("DELETE FROM segments WHERE id = $1")
+            .bind(segment.id)
+            .execute(&mut *self.tx)
+            .await?;
+        self.mark_project_changed(segment.project_id).await
+    }
+
+    /// Overwrite a segment's fields and constraints. Each constraint is
+    /// `(attribute, operator, values)`.
+    pub async fn replace_segment(
+        &mut self,
+        segment: &SegmentRow,
+        name: &str,
+        description: Option<&str>,
+        match_type: &str,
+        constraints: &[(String, String, Vec<String>)],
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE segments SET name = $2, description = $3, match_type = $4::match_type
+             WHERE id = $1",
+        )
+        .bind(segment.id)
+        .bind(name)
+        .bind(description)
+        .bind(match_type)
+        .execute(&mut *self.tx)
+        .await?;
+
+        sqlx::query("DELETE FROM segment_constraints WHERE segment_id = $1")
+            .bind(segment.id)
+            .execute(&mut *self.tx)
+            .await?;
+        insert_segment_constraints(&mut self.tx, segment.id, constraints).await?;
+
+        self.mark_project_changed(segment.project_id).await
+    }
+
+    /// Overwrite every metadata field of a flag.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn replace_flag(
+        &mut self,
+        flag: &FlagRow,
+        name: &str,
+        description: Option<&str>,
+        tags: &[String],
+        archived: bool,
+        owner: Option<&str>,
+        kind: &str,
+        temporary: bool,
+        expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE flags SET
+                name = $2, description = $3, tags = $4, archived = $5, owner = $6,
+                kind = $7::flag_kind, temporary = $8, expires_at = $9
+             WHERE id = $1",
+        )
+        .bind(flag.id)
+        .bind(name)
+        .bind(description)
+        .bind(tags)
+        .bind(archived)
+        .bind(owner)
+        .bind(kind)
+        .bind(temporary)
+        .bind(expires_at)
+        .execute(&mut *self.tx)
+        .await?;
+        self.mark_project_changed(flag.project_id).await
+    }
+
+    pub async fn update_flag_variant(
+        &mut self,
+        variant_id: Uuid,
+        value: Option<&serde_json::Value>,
+        description: Option<&str>,
+    ) -> Result<FlagVariantRow> {
+        let row = sqlx::query_as::<_, FlagVariantRow>(
+            "UPDATE flag_variants SET
+                value = COALESCE($2, value),
+                description = COALESCE($3, description)
+             WHERE id = $1
+             RETURNING *",
+        )
+        .bind(variant_id)
+        .bind(value)
+        .bind(description)
+        .fetch_one(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    /// Rewrite `sort_order` so variants appear in the given order.
+    pub async fn reorder_flag_variants(
+        &mut self,
+        flag_id: Uuid,
+        variant_ids: &[Uuid],
+    ) -> Result<Vec<FlagVariantRow>> {
+        for (i, variant_id) in variant_ids.iter().enumerate() {
+            sqlx::query("UPDATE flag_variants SET sort_order = $3 WHERE id = $1 AND flag_id = $2")
+                .bind(variant_id)
+                .bind(flag_id)
+                .bind(i as i32)
+                .execute(&mut *self.tx)
+                .await?;
+        }
+        fetch_flag_variants(&mut self.tx, flag_id).await
+    }
+
+    /// Replace a variant's value and description, clearing the description
+    /// when `None`.
+    pub async fn replace_flag_variant(
+        &mut self,
+        variant_id: Uuid,
+        value: &serde_json::Value,
+        description: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query("UPDATE flag_variants SET value = $2, description = $3 WHERE id = $1")
+            .bind(variant_id)
+            .bind(value)
+            .bind(description)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn create_scheduled_change(
+        &mut self,
+        project_id: Uuid,
+        flag_environment_id: Uuid,
+        action: &ScheduledAction,
+        run_at: chrono::DateTime<chrono::Utc>,
+        created_by: Option<Uuid>,
+        created_by_email: Option<&str>,
+    ) -> Result<ScheduledChangeRow> {
+        let id: Uuid = sqlx::query_scalar(
+            "INSERT INTO scheduled_changes
+                (project_id, flag_environment_id, action, payload, run_at, created_by, created_by_email)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             RETURNING id",
+        )
+        .bind(project_id)
+        .bind(flag_environment_id)
+        .bind(action.name())
+        .bind(serde_json::to_value(action)?)
+        .bind(run_at)
+        .bind(created_by)
+        .bind(created_by_email)
+        .fetch_one(&mut *self.tx)
+        .await?;
+
+        fetch_scheduled_change(&mut self.tx, id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("scheduled change {id} vanished after insert"))
+    }
+
+    /// Apply a due scheduled change and mark it succeeded.
+    ///
+    /// A transaction-scoped advisory lock on the change's ID keeps replicas
+    /// from running the same job twice. Returns `None` if another replica
+    /// holds the lock or the change is no longer pending. Fails if the
+    /// environment requires approval, which it may have started to since the
+    /// change was scheduled.
+    pub async fn run_scheduled_change(&mut self, id: Uuid) -> Result<Option<ScheduledChangeRow>> {
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_xact_lock($1)")
+            .bind(advisory_key(id))
+            .fetch_one(&mut *self.tx)
+            .await?;
+        if !locked {
+            return Ok(None);
+        }
+
+        let job: Option<(Uuid, serde_json::Value, bool)> = sqlx::query_as(
+            "SELECT sc.flag_environment_id, sc.payload, e.requires_approval
+             FROM scheduled_changes sc
+             JOIN flag_environments fe ON fe.id = sc.flag_environment_id
+             JOIN environments e ON e.id = fe.environment_id
+             WHERE sc.id = $1 AND sc.status = 'pending' AND sc.run_at <= NOW()",
+        )
+        .bind(id)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        let Some((flag_environment_id, payload, requires_approval)) = job else {
+            return Ok(None);
+        };
+        if requires_approval {
+            anyhow::bail!(
+                "environment requires approval; scheduled changes are not applied there"
+            );
+        }
+
+        let action: ScheduledAction = serde_json::from_value(payload)?;
+        apply_scheduled_action(&mut self.tx, flag_environment_id, &action).await?;
+
+        sqlx::query(
+            "UPDATE scheduled_changes SET status = 'succeeded', executed_at = NOW() WHERE id = $1",
+        )
+        .bind(id)
+        .execute(&mut *self.tx)
+        .await?;
+
+        let job = fetch_scheduled_change(&mut self.tx, id).await?;
+        if let Some(ref job) = job {
+            self.mark_changed(job.environment_id);
+        }
+        Ok(job)
+    }
+
+    pub async fn get_scheduled_change(&mut self, id: Uuid) -> Result<Option<ScheduledChangeRow>> {
+        fetch_scheduled_change(&mut self.tx, id).await
+    }
+
+    pub async fn fail_scheduled_change(&mut self, id: Uuid, error: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE scheduled_changes SET status = 'failed', error = $2, executed_at = NOW()
+             WHERE id = $1 AND status = 'pending'",
+        )
+        .bind(id)
+        .bind(error)
+        .execute(&mut *self.tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Cancel a pending change. Returns `None` if it was no longer pending.
+    pub async fn cancel_scheduled_change(
+        &mut self,
+        id: Uuid,
+    ) -> Result<Option<ScheduledChangeRow>> {
+        let updated = sqlx::query(
+            "UPDATE scheduled_changes SET status = 'cancelled' WHERE id = $1 AND status = 'pending'",
+        )
+        .bind(id)
+        .execute(&mut *self.tx)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Ok(None);
+        }
+        fetch_scheduled_change(&mut self.tx, id).await
+    }
+
+    /// Create a plan and apply its first step to the rule. The rule's
+    /// current variant and distributions are kept for rollback.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_rollout_plan(
+        &mut self,
+        project_id: Uuid,
+        flag_environment_id: Uuid,
+        rule_id: Uuid,
+        variant_id: Uuid,
+        control_variant_id: Option<Uuid>,
+        steps: &[RolloutStep],
+        guardrails: &[Guardrail],
+        created_by: Option<Uuid>,
+        created_by_email: Option<&str>,
+    ) -> Result<RolloutPlanRow> {
+        let first = steps
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("a rollout plan needs at least one step"))?;
+
+        let original_variant_id: Option<Uuid> =
+            sqlx::query_scalar("SELECT variant_id FROM targeting_rules WHERE id = $1")
+                .bind(rule_id)
+                .fetch_one(&mut *self.tx)
+                .await?;
+        let original_distributions: Vec<DistributionSpec> =
+            sqlx::query_as::<_, RuleDistributionRow>(
+                "SELECT * FROM rule_distributions WHERE rule_id = $1 ORDER BY sort_order",
+            )
+            .bind(rule_id)
+            .fetch_all(&mut *self.tx)
+            .await?
+            .into_iter()
+            .map(|d| DistributionSpec {
+                variant_id: d.variant_id,
+                rollout_pct: d.rollout_pct,
+            })
+            .collect();
+
+        let status = if steps.len() == 1 { "completed" } else { "active" };
+        let id: Uuid = sqlx::query_scalar(
+            "INSERT INTO rollout_plans
+                (project_id, flag_environment_id, rule_id, variant_id, control_variant_id, steps,
+                 status, original_variant_id, original_distributions, created_by, created_by_email,
+                 guardrails)
+             VALUES ($1, $2, $3, $4, $5, $6, $7::rollout_status, $8, $9, $10, $11, $12)
+             RETURNING id",
+        )
+        .bind(project_id)
+        .bind(flag_environment_id)
+        .bind(rule_id)
+        .bind(variant_id)
+        .bind(control_variant_id)
+        .bind(serde_json::to_value(steps)?)
+        .bind(status)
+        .bind(original_variant_id)
+        .bind(serde_json::to_value(&original_distributions)?)
+        .bind(created_by)
+        .bind(created_by_email)
+        .bind(serde_json::to_value(guardrails)?)
+        .fetch_one(&mut *self.tx)
+        .await?;
+
+        if steps.len() > 1 {
+            sqlx::query(&format!(
+                "UPDATE rollout_plans SET next_step_at = {ROLLOUT_STEP_END} WHERE id = $1"
+            ))
+            .bind(id)
+            .execute(&mut *self.tx)
+            .await?;
+        }
+
+        let distributions = first.distributions(variant_id, control_variant_id);
+        set_rule_distributions(&mut self.tx, flag_environment_id, rule_id, &distributions).await?;
+
+        let plan = fetch_rollout_plan(&mut self.tx, id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("rollout plan {id} vanished after insert"))?;
+        self.mark_changed(plan.environment_id);
+        Ok(plan)
+    }
+
+    /// Move a due plan to its next step, under the same advisory lock
+    /// scheme as scheduled changes. Returns `None` if another replica holds
+    /// the lock or the plan is not due.
+    pub async fn advance_rollout_plan(&mut self, id: Uuid) -> Result<Option<RolloutPlanRow>> {
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_xact_lock($1)")
+            .bind(advisory_key(id))
+            .fetch_one(&mut *self.tx)
+            .await?;
+        if !locked {
+            return Ok(None);
+        }
+
+        let plan = sqlx::query_as::<_, RolloutPlanRow>(&format!(
+            "SELECT {ROLLOUT_PLAN_COLS} FROM {ROLLOUT_PLAN_FROM}
+             WHERE rp.id = $1 AND rp.status = 'active' AND rp.next_step_at <= NOW()"
+        ))
+        .bind(id)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        let Some(plan) = plan else {
+            return Ok(None);
+        };
+
+        let steps: Vec<RolloutStep> = serde_json::from_value(plan.steps.clone())?;
+        let next = plan.current_step as usize + 1;
+        let step = steps
+            .get(next)
+            .ok_or_else(|| anyhow::anyhow!("rollout plan {id} has no step {next}"))?;
+
+        let distributions = step.distributions(plan.variant_id, plan.control_variant_id);
+        set_rule_distributions(
+            &mut self.tx,
+            plan.flag_environment_id,
+            plan.rule_id,
+            &distributions,
+        )
+        .await?;
+
+        let query = if next + 1 == steps.len() {
+            "UPDATE rollout_plans SET current_step = $2, status = 'completed', next_step_at = NULL
+             WHERE id = $1"
+                .to_string()
+        } else {
+            format!(
+                "UPDATE rollout_plans SET current_step = $2, next_step_at = {ROLLOUT_STEP_END}
+                 WHERE id = $1"
+            )
+        };
+        sqlx::query(&query)
+            .bind(id)
+            .bind(next as i32)
+            .execute(&mut *self.tx)
+            .await?;
+
+        let plan = fetch_rollout_plan(&mut self.tx, id).await?;
+        if let Some(ref plan) = plan {
+            self.mark_changed(plan.environment_id);
+        }
+        Ok(plan)
+    }
+
+    /// Stop advancing a plan. Returns `None` if it was not active.
+    pub async fn pause_rollout_plan(&mut self, id: Uuid) -> Result<Option<RolloutPlanRow>> {
+        let updated = sqlx::query(
+            "UPDATE rollout_plans SET status = 'paused', next_step_at = NULL
+             WHERE id = $1 AND status = 'active'",
+        )
+        .bind(id)
+        .execute(&mut *self.tx)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Ok(None);
+        }
+        fetch_rollout_plan(&mut self.tx, id).await
+    }
+
+    /// Resume a paused plan. The current step's dwell time starts over.
+    /// Returns `None` if it was not paused.
+    pub async fn resume_rollout_plan(&mut self, id: Uuid) -> Result<Option<RolloutPlanRow>> {
+        let updated = sqlx::query(&format!(
+            "UPDATE rollout_plans SET status = 'active', next_step_at = {ROLLOUT_STEP_END}
+             WHERE id = $1 AND status = 'paused'"
+        ))
+        .bind(id)
+        .execute(&mut *self.tx)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Ok(None);
+        }
+        fetch_rollout_plan(&mut self.tx, id).await
+    }
+
+    /// Stop a running plan and restore the rule as it was before the plan
+    /// started. Returns `None` if the plan was not active or paused.
+    pub async fn rollback_rollout_plan(&mut self, id: Uuid) -> Result<Option<RolloutPlanRow>> {
+        let plan = sqlx::query_as::<_, (Uuid, Uuid, Option<Uuid>, serde_json::Value)>(
+            "UPDATE rollout_plans SET status = 'rolled_back', next_step_at = NULL
+             WHERE id = $1 AND status IN ('active', 'paused')
+             RETURNING flag_environment_id, rule_id, original_variant_id, original_distributions",
+        )
+        .bind(id)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        let Some((flag_environment_id, rule_id, original_variant_id, original)) = plan else {
+            return Ok(None);
+        };
+
+        let original: Vec<DistributionSpec> = serde_json::from_value(original)?;
+        let distributions: Vec<(Uuid, i32)> = original
+            .iter()
+            .map(|d| (d.variant_id, d.rollout_pct))
+            .collect();
+        set_rule_distributions(&mut self.tx, flag_environment_id, rule_id, &distributions).await?;
+        sqlx::query("UPDATE targeting_rules SET variant_id = $2 WHERE id = $1")
+            .bind(rule_id)
+            .bind(original_variant_id)
+            .execute(&mut *self.tx)
+            .await?;
+
+        let plan = fetch_rollout_plan(&mut self.tx, id).await?;
+        if let Some(ref plan) = plan {
+            self.mark_changed(plan.environment_id);
+        }
+        Ok(plan)
+    }
+
+    /// Stop a plan whose guardrail was breached: either send all of the
+    /// rule's traffic to `control_variant_id` or turn the flag off. Returns
+    /// `None` if the plan had already stopped.
+    pub async fn halt_rollout_plan(
+        &mut self,
+        id: Uuid,
+        action: GuardrailAction,
+        reason: &serde_json::Value,
+    ) -> Result<Option<RolloutPlanRow>> {
+        let plan = sqlx::query_as::<_, (Uuid, Uuid, Option<Uuid>)>(
+            "UPDATE rollout_plans SET status = 'rolled_back', next_step_at = NULL, halted_reason = $2
+             WHERE id = $1 AND status IN ('active', 'paused')
+             RETURNING flag_environment_id, rule_id, control_variant_id",
+        )
+        .bind(id)
+        .bind(reason)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        let Some((flag_environment_id, rule_id, control_variant_id)) = plan else {
+            return Ok(None);
+        };
+
+        match (action, control_variant_id) {
+            (GuardrailAction::Rollback, Some(control)) => {
+                set_rule_distributions(
+                    &mut self.tx,
+                    flag_environment_id,
+                    rule_id,
+                    &[(control, 10000)],
+                )
+                .await?;
+            }
+            _ => {
+                sqlx::query("UPDATE flag_environments SET enabled = FALSE WHERE id = $1")
+                    .bind(flag_environment_id)
+                    .execute(&mut *self.tx)
+                    .await?;
+            }
+        }
+
+        let plan = fetch_rollout_plan(&mut self.tx, id).await?;
+        if let Some(ref plan) = plan {
+            self.mark_changed(plan.environment_id);
+        }
+        Ok(plan)
+    }
+
+    pub async fn replace_code_references(
+        &mut self,
+        project_id: Uuid,
+        repository: &str,
+        branch: Option<&str>,
+        references: &[(Uuid, String, i32, Option<String>)],
+    ) -> Result<u64> {
+        sqlx::query(
+            "DELETE FROM code_references
+             WHERE project_id = $1 AND repository = $2 AND branch IS NOT DISTINCT FROM $3",
+        )
+        .bind(project_id)
+        .bind(repository)
+        .bind(branch)
+        .execute(&mut *self.tx)
+        .await?;
+
+        for (flag_id, file_path, line_number, line_content) in references {
+            sqlx::query(
+                "INSERT INTO code_references (project_id, flag_id, repository, branch, file_path, line_number, line_content)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            )
+            .bind(project_id)
+            .bind(flag_id)
+            .bind(repository)
+            .bind(branch)
+            .bind(file_path)
+            .bind(line_number)
+            .bind(line_content)
+            .execute(&mut *self.tx)
+            .await?;
+        }
+
+        Ok(references.len() as u64)
+    }
+
+    /// Lock a variant until commit. Writes that reference it wait for the
+    /// lock, so a usage check made after taking it stays true until commit.
+    pub async fn lock_flag_variant(
+        &mut self,
+        flag_id: Uuid,
+        variant_id: Uuid,
+    ) -> Result<Option<FlagVariantRow>> {
+        let row = sqlx::query_as::<_, FlagVariantRow>(
+            "SELECT * FROM flag_variants WHERE id = $1 AND flag_id = $2 FOR UPDATE",
+        )
+        .bind(variant_id)
+        .bind(flag_id)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    pub async fn get_variant_usage(&mut self, variant_id: Uuid) -> Result<VariantUsageRow> {
+        fetch_variant_usage(&mut self.tx, variant_id).await
+    }
+
+    /// Delete a variant, which drops it from every environment's config.
+    pub async fn delete_flag_variant(&mut self, project_id: Uuid, variant_id: Uuid) -> Result<()> {
+        sqlx::query("DELETE FROM flag_variants WHERE id = $1")
+            .bind(variant_id)
+            .execute(&mut *self.tx)
+            .await?;
+        self.mark_project_changed(project_id).await
+    }
+
+    /// A flag's per-environment row as seen inside this unit of work.
+    pub async fn get_flag_environment(
+        &mut self,
+        flag_id: Uuid,
+        environment_id: Uuid,
+    ) -> Result<Option<FlagEnvironmentRow>> {
+        let row = sqlx::query_as::<_, FlagEnvironmentRow>(
+            "SELECT * FROM flag_environments WHERE flag_id = $1 AND environment_id = $2",
+        )
+        .bind(flag_id)
+        .bind(environment_id)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_flag(
+        &mut self,
+        project_id: Uuid,
+        key: &str,
+        name: &str,
+        description: Option<&str>,
+        flag_type: &str,
+        tags: &[String],
+        owner: Option<&str>,
+        kind: &str,
+        temporary: bool,
+        expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<FlagRow> {
+        let row = sqlx::query_as::<_, FlagRow>(
+            &format!("INSERT INTO flags (project_id, key, name, description, flag_type, tags, owner, kind, temporary, expires_at)
+             VALUES ($1, $2, $3, $4, $5::flag_type, $6, $7, $8::flag_kind, $9, $10)
+             RETURNING {FLAG_COLS}"),
+        )
+        .bind(project_id)
+        .bind(key)
+        .bind(name)
+        .bind(description)
+        .bind(flag_type)
+        .bind(tags)
+        .bind(owner)
+        .bind(kind)
+        .bind(temporary)
+        .bind(expires_at)
+        .fetch_one(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    pub async fn create_flag_variant(
+        &mut self,
+        flag_id: Uuid,
+        key: &str,
+        value: &serde_json::Value,
+        description: Option<&str>,
+        sort_order: i32,
+    ) -> Result<FlagVariantRow> {
+        insert_flag_variant(&mut self.tx, flag_id, key, value, description, sort_order).await
+    }
+
+    pub async fn create_flag_environment(
+        &mut self,
+        flag_id: Uuid,
+        environment_id: Uuid,
+        enabled: bool,
+        default_variant_id: Option<Uuid>,
+    ) -> Result<FlagEnvironmentRow> {
+        let row = sqlx::query_as::<_, FlagEnvironmentRow>(
+            "INSERT INTO flag_environments (flag_id, environment_id, enabled, default_variant_id)
+             VALUES ($1, $2, $3, $4) RETURNING *",
+        )
+        .bind(flag_id)
+        .bind(environment_id)
+        .bind(enabled)
+        .bind(default_variant_id)
+        .fetch_one(&mut *self.tx)
+        .await?;
+        self.mark_changed(environment_id);
+        Ok(row)
+    }
+
+    /// Create a new environment whose flag states, default variants, rules and
+    /// overrides are copied from `source_environment_id`.
+    pub async fn clone_environment(
+        &mut self,
+        project_id: Uuid,
+        source_environment_id: Uuid,
+        name: &str,
+        slug: &str,
+        color: Option<&str>,
+    ) -> Result<EnvironmentRow> {
+        let row = insert_environment(&mut self.tx, project_id, name, slug, color).await?;
+
+        let pairs: Vec<(Uuid, Uuid, bool, Option<Uuid>)> = sqlx::query_as(
+            "SELECT src.id, dst.id, src.enabled, src.default_variant_id
+             FROM flag_environments src
+             JOIN flag_environments dst ON dst.flag_id = src.flag_id AND dst.environment_id = $2
+             WHERE src.environment_id = $1",
+        )
+        .bind(source_environment_id)
+        .bind(row.id)
+        .fetch_all(&mut *self.tx)
+        .await?;
+
+        for (source_fe_id, target_fe_id, enabled, default_variant_id) in pairs {
+            sqlx::query(
+                "UPDATE flag_environments SET enabled = $2, default_variant_id = $3 WHERE id = $1",
+            )
+            .bind(target_fe_id)
+            .bind(enabled)
+            .bind(default_variant_id)
+            .execute(&mut *self.tx)
+            .await?;
+
+            replace_flag_environment_contents(&mut self.tx, source_fe_id, target_fe_id).await?;
+        }
+
+        self.mark_changed(row.id);
+        Ok(row)
+    }
+
+    /// Overwrite several flag environments, as a rollback does.
+    pub async fn write_flag_environments(
+        &mut self,
+        specs: &[(Uuid, FlagEnvironmentSpec)],
+    ) -> Result<()> {
+        for (flag_environment_id, spec) in specs {
+            let environment_id =
+                write_flag_environment(&mut self.tx, *flag_environment_id, spec).await?;
+            self.mark_changed(environment_id);
+        }
+        Ok(())
+    }
+
+    /// Open a change request as part of this unit of work, so it is only
+    /// visible if the rest of the operation commits.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_change_request(
+        &mut self,
+        project_id: Uuid,
+        flag_environment_id: Uuid,
+        action: &str,
+        base_state: &serde_json::Value,
+        proposed_state: &serde_json::Value,
+        requested_by: Option<Uuid>,
+        requested_by_email: Option<&str>,
+    ) -> Result<ChangeRequestRow> {
+        insert_change_request(
+            &mut self.tx,
+            project_id,
+            flag_environment_id,
+            action,
+            base_state,
+            proposed_state,
+            requested_by,
+            requested_by_email,
+        )
+        .await
+    }
+
+    /// Reject or cancel a pending change request. Returns `None` if it was
+    /// no longer pending.
+    pub async fn close_change_request(
+        &mut self,
+        id: Uuid,
+        status: &str,
+        reviewed_by: Option<Uuid>,
+        reviewed_by_email: Option<&str>,
+        comment: Option<&str>,
+    ) -> Result<Option<ChangeRequestRow>> {
+        let updated = sqlx::query(
+            "UPDATE change_requests SET
+                status = $2::change_request_status, reviewed_by = $3, reviewed_by_email = $4,
+                review_comment = $5, reviewed_at = NOW()
+             WHERE id = $1 AND status = 'pending'",
+        )
+        .bind(id)
+        .bind(status)
+        .bind(reviewed_by)
+        .bind(reviewed_by_email)
+        .bind(comment)
+        .execute(&mut *self.tx)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Ok(None);
+        }
+        fetch_change_request(&mut self.tx, id).await
+    }
+
+    /// Mark a pending change request applied and write its proposed state.
+    /// Returns `None` if it was no longer pending. The caller locks the flag
+    /// environment and checks the request's base state first.
+    pub async fn apply_change_request(
+        &mut self,
+        id: Uuid,
+        spec: &FlagEnvironmentSpec,
+        reviewed_by: Option<Uuid>,
+        reviewed_by_email: Option<&str>,
+        comment: Option<&str>,
+    ) -> Result<Option<ChangeRequestRow>> {
+        let fe_id: Option<Uuid> = sqlx::query_scalar(
+            "UPDATE change_requests SET
+                status = 'applied', reviewed_by = $2, reviewed_by_email = $3,
+                review_comment = $4, reviewed_at = NOW()
+             WHERE id = $1 AND status = 'pending'
+             RETURNING flag_environment_id",
+        )
+        .bind(id)
+        .bind(reviewed_by)
+        .bind(reviewed_by_email)
+        .bind(comment)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+
+        let Some(fe_id) = fe_id else {
+            return Ok(None);
+        };
+        write_flag_environment(&mut self.tx, fe_id, spec).await?;
+
+        let row = sqlx::query_as::<_, ChangeRequestRow>(&format!(
+            "SELECT {CHANGE_REQUEST_COLS} FROM {CHANGE_REQUEST_FROM} WHERE cr.id = $1"
+        ))
+        .bind(id)
+        .fetch_one(&mut *self.tx)
+        .await?;
+        self.mark_changed(row.environment_id);
+        Ok(Some(row))
+    }
+
+    /// Record an audit entry as part of this unit of work; see
+    /// [`PostgresStore::create_audit_log`].
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_audit_log(
+        &mut self,
+        project_id: Uuid,
+        ctx: &AuditContext,
+        action: &str,
+        entity_type: &str,
+        entity_id: Option<Uuid>,
+        before_state: Option<&serde_json::Value>,
+        after_state: Option<&serde_json::Value>,
+    ) -> Result<AuditLogRow> {
+        insert_audit_log(
+            &mut self.tx,
+            &self.audit_key,
+            project_id,
+            ctx,
+            action,
+            entity_type,
+            entity_id,
+            before_state,
+            after_state,
+        )
+        .await
+    }
+
+    /// Flags selected for a bulk operation, locked until commit. `keys` and
+    /// `tags` match any of their entries; all criteria must hold.
+    pub async fn lock_flags(
+        &mut self,
+        project_id: Uuid,
+        archived: bool,
+        keys: Option<&[String]>,
+        tags: Option<&[String]>,
+        filter: &FlagFilter,
+    ) -> Result<Vec<FlagRow>> {
+        let rows = sqlx::query_as::<_, FlagRow>(&format!(
+            "SELECT {FLAG_COLS} FROM flags
+             WHERE project_id = $1 AND archived = $2
+               AND ($3::TEXT[] IS NULL OR key = ANY($3))
+               AND ($4::TEXT[] IS NULL OR tags && $4)
+               AND ($5::TEXT IS NULL OR owner = $5)
+               AND ($6::TEXT IS NULL OR kind::TEXT = $6)
+               AND ($7::BOOLEAN IS NULL OR temporary = $7)
+               AND ($8::BOOLEAN IS NULL
+                    OR (expires_at IS NOT NULL AND expires_at <= NOW()) = $8)
+             ORDER BY key
+             FOR UPDATE"
+        ))
+        .bind(project_id)
+        .bind(archived)
+        .bind(keys)
+        .bind(tags)
+        .bind(filter.owner.as_deref())
+        .bind(filter.kind.as_deref())
+        .bind(filter.temporary)
+        .bind(filter.expired)
+        .fetch_all(&mut *self.tx)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Lock a flag by key until commit.
+    pub async fn lock_flag(&mut self, project_id: Uuid, key: &str) -> Result<Option<FlagRow>> {
+        let row = sqlx::query_as::<_, FlagRow>(&format!(
+            "SELECT {FLAG_COLS} FROM flags WHERE project_id = $1 AND key = $2 FOR UPDATE"
+        ))
+        .bind(project_id)
+        .bind(key)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    /// Update a flag's metadata. Marking a flag permanent clears its expiry.
+    ///
+    /// With `expected_revision` set, returns `None` when the flag has moved on
+    /// since the caller read it.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_flag(
+        &mut self,
+        flag_id: Uuid,
+        expected_revision: Option<i64>,
+        name: Option<&str>,
+        description: Option<&str>,
+        tags: Option<&[String]>,
+        archived: Option<bool>,
+        owner: Option<Option<&str>>,
+        kind: Option<&str>,
+        temporary: Option<bool>,
+        expires_at: Option<Option<chrono::DateTime<chrono::Utc>>>,
+    ) -> Result<Option<FlagRow>> {
+        // For owner and expires_at, `Some(None)` clears the column.
+        let row = sqlx::query_as::<_, FlagRow>(
+            &format!("UPDATE flags SET
+                name = COALESCE($2, name),
+                description = COALESCE($3, description),
+                tags = COALESCE($4, tags),
+                archived = COALESCE($5, archived),
+                owner = CASE WHEN $6 THEN $7 ELSE owner END,
+                kind = COALESCE($8::flag_kind, kind),
+                temporary = COALESCE($9, temporary),
+                expires_at = CASE WHEN $9 = FALSE THEN NULL WHEN $10 THEN $11 ELSE expires_at END
+             WHERE id = $1 AND ($12::BIGINT IS NULL OR revision = $12)
+             RETURNING {FLAG_COLS}"),
+        )
+        .bind(flag_id)
+        .bind(name)
+        .bind(description)
+        .bind(tags)
+        .bind(archived)
+        .bind(owner.is_some())
+        .bind(owner.flatten())
+        .bind(kind)
+        .bind(temporary)
+        .bind(expires_at.is_some())
+        .bind(expires_at.flatten())
+        .bind(expected_revision)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        if let Some(ref flag) = row {
+            self.mark_project_changed(flag.project_id).await?;
+        }
+        Ok(row)
+    }
+
+    /// Delete a flag, which drops it from every environment's config.
+    pub async fn delete_flag(&mut self, project_id: Uuid, flag_id: Uuid) -> Result<()> {
+        sqlx::query("DELETE FROM flags WHERE id = $1")
+            .bind(flag_id)
+            .execute(&mut *self.tx)
+            .await?;
+        self.mark_project_changed(project_id).await
+    }
+
+    /// Returns `None` when `expected_revision` no longer matches the row.
+    pub async fn toggle_flag(
+        &mut self,
+        flag_id: Uuid,
+        environment_id: Uuid,
+        enabled: bool,
+        expected_revision: Option<i64>,
+    ) -> Result<Option<FlagEnvironmentRow>> {
+        let row = sqlx::query_as::<_, FlagEnvironmentRow>(
+            "UPDATE flag_environments SET enabled = $3
+             WHERE flag_id = $1 AND environment_id = $2 AND ($4::BIGINT IS NULL OR revision = $4)
+             RETURNING *",
+        )
+        .bind(flag_id)
+        .bind(environment_id)
+        .bind(enabled)
+        .bind(expected_revision)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        if row.is_some() {
+            self.mark_changed(environment_id);
+        }
+        Ok(row)
+    }
+
+    pub async fn set_default_variant(
+        &mut self,
+        flag_environment_id: Uuid,
+        variant_id: Uuid,
+    ) -> Result<FlagEnvironmentRow> {
+        let row = sqlx::query_as::<_, FlagEnvironmentRow>(
+            "UPDATE flag_environments SET default_variant_id = $2 WHERE id = $1 RETURNING *",
+        )
+        .bind(flag_environment_id)
+        .bind(variant_id)
+        .fetch_one(&mut *self.tx)
+        .await?;
+        self.mark_changed(row.environment_id);
+        Ok(row)
+    }
+
+    /// Enable or disable a flag in one environment. Returns `None` if it
+    /// already was in that state or is not configured there.
+    pub async fn set_flag_enabled(
+        &mut self,
+        flag_id: Uuid,
+        environment_id: Uuid,
+        enabled: bool,
+    ) -> Result<Option<FlagEnvironmentRow>> {
+        let row = sqlx::query_as::<_, FlagEnvironmentRow>(
+            "UPDATE flag_environments SET enabled = $3
+             WHERE flag_id = $1 AND environment_id = $2 AND enabled <> $3
+             RETURNING *",
+        )
+        .bind(flag_id)
+        .bind(environment_id)
+        .bind(enabled)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        if row.is_some() {
+            self.mark_changed(environment_id);
+        }
+        Ok(row)
+    }
+
+    /// Archive or unarchive a flag. Archived flags drop out of every
+    /// environment's config. Returns `None` if nothing changed.
+    pub async fn set_flag_archived(
+        &mut self,
+        flag_id: Uuid,
+        archived: bool,
+    ) -> Result<Option<FlagRow>> {
+        let row = sqlx::query_as::<_, FlagRow>(&format!(
+            "UPDATE flags SET archived = $2 WHERE id = $1 AND archived <> $2
+             RETURNING {FLAG_COLS}"
+        ))
+        .bind(flag_id)
+        .bind(archived)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        if let Some(ref flag) = row {
+            self.mark_project_changed(flag.project_id).await?;
+        }
+        Ok(row)
+    }
+
+    /// Add the tags a flag does not have yet. Returns `None` if it had all
+    /// of them.
+    pub async fn add_flag_tags(
+        &mut self,
+        flag_id: Uuid,
+        tags: &[String],
+    ) -> Result<Option<FlagRow>> {
+        let row = sqlx::query_as::<_, FlagRow>(&format!(
+            "UPDATE flags
+             SET tags = tags || ARRAY(
+                 SELECT DISTINCT t FROM unnest($2::TEXT[]) t WHERE t <> ALL(tags)
+             )
+             WHERE id = $1 AND NOT tags @> $2
+             RETURNING {FLAG_COLS}"
+        ))
+        .bind(flag_id)
+        .bind(tags)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    /// Remove tags from a flag. Returns `None` if it had none of them.
+    pub async fn remove_flag_tags(
+        &mut self,
+        flag_id: Uuid,
+        tags: &[String],
+    ) -> Result<Option<FlagRow>> {
+        let row = sqlx::query_as::<_, FlagRow>(&format!(
+            "UPDATE flags SET tags = ARRAY(SELECT t FROM unnest(tags) t WHERE t <> ALL($2))
+             WHERE id = $1 AND tags && $2
+             RETURNING {FLAG_COLS}"
+        ))
+        .bind(flag_id)
+        .bind(tags)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    /// Set or clear a flag's owner. Returns `None` if it was already set.
+    pub async fn set_flag_owner(
+        &mut self,
+        flag_id: Uuid,
+        owner: Option<&str>,
+    ) -> Result<Option<FlagRow>> {
+        let row = sqlx::query_as::<_, FlagRow>(&format!(
+            "UPDATE flags SET owner = $2 WHERE id = $1 AND owner IS DISTINCT FROM $2
+             RETURNING {FLAG_COLS}"
+        ))
+        .bind(flag_id)
+        .bind(owner)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        Ok(row)
+    }
+
+    /// Rewrite ranks so rules evaluate in the given order.
+    ///
+    /// Returns `None` (and changes nothing) unless `rule_ids` lists exactly
+    /// the flag environment's current rules, checked under lock.
+    pub async fn reorder_targeting_rules(
+        &mut self,
+        flag_environment_id: Uuid,
+        rule_ids: &[Uuid],
+    ) -> Result<Option<Vec<TargetingRuleRow>>> {
+        let environment_id = lock_flag_environment(&mut self.tx, flag_environment_id).await?;
+
+        let mut current: Vec<Uuid> = sqlx::query_scalar(
+            "SELECT id FROM targeting_rules WHERE flag_environment_id = $1 FOR UPDATE",
+        )
+        .bind(flag_environment_id)
+        .fetch_all(&mut *self.tx)
+        .await?;
+        let mut requested = rule_ids.to_vec();
+        current.sort();
+        requested.sort();
+        if current != requested {
+            return Ok(None);
+        }
+
+        for (rank, rule_id) in rule_ids.iter().enumerate() {
+            sqlx::query(
+                "UPDATE targeting_rules SET rank = $3 WHERE id = $1 AND flag_environment_id = $2",
+            )
+            .bind(rule_id)
+            .bind(flag_environment_id)
+            .bind(rank as i32)
+            .execute(&mut *self.tx)
+            .await?;
+        }
+
+        let rows = sqlx::query_as::<_, TargetingRuleRow>(
+            "SELECT * FROM targeting_rules WHERE flag_environment_id = $1 ORDER BY rank",
+        )
+        .bind(flag_environment_id)
+        .fetch_all(&mut *self.tx)
+        .await?;
+
+        self.mark_changed(environment_id);
+        Ok(Some(rows))
+    }
+
+    pub async fn get_flag_overrides(
+        &mut self,
+        flag_environment_id: Uuid,
+    ) -> Result<Vec<FlagOverrideRow>> {
+        fetch_flag_overrides(&mut self.tx, flag_environment_id).await
+    }
+
+    /// Insert or replace the overrides for the given targeting keys.
+    pub async fn upsert_flag_overrides(
+        &mut self,
+        flag_environment_id: Uuid,
+        overrides: &[(String, Uuid)],
+    ) -> Result<Vec<FlagOverrideRow>> {
+        let environment_id = lock_flag_environment(&mut self.tx, flag_environment_id).await?;
+        let mut rows = Vec::with_capacity(overrides.len());
+
+        for (targeting_key, variant_id) in overrides {
+            let row = sqlx::query_as::<_, FlagOverrideRow>(
+                "INSERT INTO flag_overrides (flag_environment_id, targeting_key, variant_id)
+                 VALUES ($1, $2, $3)
+                 ON CONFLICT (flag_environment_id, targeting_key) DO UPDATE SET variant_id = EXCLUDED.variant_id
+                 RETURNING *",
+            )
+            .bind(flag_environment_id)
+            .bind(targeting_key)
+            .bind(variant_id)
+            .fetch_one(&mut *self.tx)
+            .await?;
+            rows.push(row);
+        }
+
+        self.mark_changed(environment_id);
+        Ok(rows)
+    }
+
+    /// Remove an override. Returns the deleted row, if any.
+    pub async fn delete_flag_override(
+        &mut self,
+        flag_environment_id: Uuid,
+        targeting_key: &str,
+    ) -> Result<Option<FlagOverrideRow>> {
+        let environment_id = lock_flag_environment(&mut self.tx, flag_environment_id).await?;
+        let row = sqlx::query_as::<_, FlagOverrideRow>(
+            "DELETE FROM flag_overrides WHERE flag_environment_id = $1 AND targeting_key = $2 RETURNING *",
+        )
+        .bind(flag_environment_id)
+        .bind(targeting_key)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        if row.is_some() {
+            self.mark_changed(environment_id);
+        }
+        Ok(row)
+    }
+
+    /// Append a rule (with its segments and distributions) after the
+    /// existing rules of a flag environment.
+    pub async fn create_targeting_rule(
+        &mut self,
+        flag_environment_id: Uuid,
+        description: Option<&str>,
+        variant_id: Option<Uuid>,
+        segments: &[(Uuid, bool)],
+        distributions: &[(Uuid, i32)],
+    ) -> Result<TargetingRuleRow> {
+        // Serialize appends (and reorders) on the flag environment so two
+        // new rules cannot both take MAX(rank) + 1.
+        let environment_id = lock_flag_environment(&mut self.tx, flag_environment_id).await?;
+
+        let row = sqlx::query_as::<_, TargetingRuleRow>(
+            "INSERT INTO targeting_rules (flag_environment_id, rank, description, variant_id)
+             VALUES ($1, (SELECT COALESCE(MAX(rank) + 1, 0) FROM targeting_rules WHERE flag_environment_id = $1), $2, $3)
+             RETURNING *",
+        )
+        .bind(flag_environment_id)
+        .bind(description)
+        .bind(variant_id)
+        .fetch_one(&mut *self.tx)
+        .await?;
+
+        insert_rule_children(&mut self.tx, row.id, segments, distributions).await?;
+
+        self.mark_changed(environment_id);
+        Ok(row)
+    }
+
+    /// Replace a rule's description, variant, segments and distributions.
+    ///
+    /// Returns `None` (and changes nothing) when `expected_revision` no longer
+    /// matches the rule.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_targeting_rule(
+        &mut self,
+        flag_environment_id: Uuid,
+        rule_id: Uuid,
+        expected_revision: Option<i64>,
+        description: Option<&str>,
+        variant_id: Option<Uuid>,
+        segments: &[(Uuid, bool)],
+        distributions: &[(Uuid, i32)],
+    ) -> Result<Option<TargetingRuleRow>> {
+        let environment_id = lock_flag_environment(&mut self.tx, flag_environment_id).await?;
+
+        let row = sqlx::query_as::<_, TargetingRuleRow>(
+            "UPDATE targeting_rules SET description = $3, variant_id = $4
+             WHERE id = $1 AND flag_environment_id = $2 AND ($5::BIGINT IS NULL OR revision = $5)
+             RETURNING *",
+        )
+        .bind(rule_id)
+        .bind(flag_environment_id)
+        .bind(description)
+        .bind(variant_id)
+        .bind(expected_revision)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        sqlx::query("DELETE FROM rule_segments WHERE rule_id = $1")
+            .bind(rule_id)
+            .execute(&mut *self.tx)
+            .await?;
+        sqlx::query("DELETE FROM rule_distributions WHERE rule_id = $1")
+            .bind(rule_id)
+            .execute(&mut *self.tx)
+            .await?;
+
+        insert_rule_children(&mut self.tx, rule_id, segments, distributions).await?;
+
+        self.mark_changed(environment_id);
+        Ok(Some(row))
+    }
+
+    /// Returns `false` when `expected_revision` no longer matches the rule.
+    pub async fn delete_targeting_rule(
+        &mut self,
+        flag_environment_id: Uuid,
+        rule_id: Uuid,
+        expected_revision: Option<i64>,
+    ) -> Result<bool> {
+        let environment_id = lock_flag_environment(&mut self.tx, flag_environment_id).await?;
+
+        let result = sqlx::query(
+            "DELETE FROM targeting_rules
+             WHERE id = $1 AND flag_environment_id = $2 AND ($3::BIGINT IS NULL OR revision = $3)",
+        )
+        .bind(rule_id)
+        .bind(flag_environment_id)
+        .bind(expected_revision)
+        .execute(&mut *self.tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        self.mark_changed(environment_id);
+        Ok(true)
+    }
+}
+
+/// Lock a flag environment row until the transaction ends, serializing
+/// writers that renumber or append to its rules.
+async fn fetch_flag_overrides(
+    conn: &mut sqlx::PgConnection,
+    flag_environment_id: Uuid,
+) -> Result<Vec<FlagOverrideRow>> {
+    let rows = sqlx::query_as::<_, FlagOverrideRow>(
+        "SELECT * FROM flag_overrides WHERE flag_environment_id = $1 ORDER BY targeting_key",
+    )
+    .bind(flag_environment_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows)
+}
+
+async fn fetch_scheduled_change(
+    conn: &mut sqlx::PgConnection,
+    id: Uuid,
+) -> Result<Option<ScheduledChangeRow>> {
+    let row = sqlx::query_as::<_, ScheduledChangeRow>(&format!(
+        "SELECT {SCHEDULED_CHANGE_COLS} FROM {SCHEDULED_CHANGE_FROM} WHERE sc.id = $1"
+    ))
+    .bind(id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(row)
+}
+
+async fn fetch_rollout_plan(
+    conn: &mut sqlx::PgConnection,
+    id: Uuid,
+) -> Result<Option<RolloutPlanRow>> {
+    let row = sqlx::query_as::<_, RolloutPlanRow>(&format!(
+        "SELECT {ROLLOUT_PLAN_COLS} FROM {ROLLOUT_PLAN_FROM} WHERE rp.id = $1"
+    ))
+    .bind(id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(row)
+}
+
+/// Lock a flag environment until commit and return its environment.
+async fn lock_flag_environment(
+    conn: &mut sqlx::PgConnection,
+    flag_environment_id: Uuid,
+) -> Result<Uuid> {
+    let environment_id =
+        sqlx::query_scalar("SELECT environment_id FROM flag_environments WHERE id = $1 FOR UPDATE")
+            .bind(flag_environment_id)
+            .fetch_one(&mut *conn)
+            .await?;
+    Ok(environment_id)
+}
+
+async fn fetch_change_request(
+    conn: &mut sqlx::PgConnection,
+    id: Uuid,
+) -> Result<Option<ChangeRequestRow>> {
+    let row = sqlx::query_as::<_, ChangeRequestRow>(&format!(
+        "SELECT {CHANGE_REQUEST_COLS} FROM {CHANGE_REQUEST_FROM} WHERE cr.id = $1"
+    ))
+    .bind(id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(row)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn insert_change_request(
+    conn: &mut sqlx::PgConnection,
+    project_id: Uuid,
+    flag_environment_id: Uuid,
+    action: &str,
+    base_state: &serde_json::Value,
+    proposed_state: &serde_json::Value,
+    requested_by: Option<Uuid>,
+    requested_by_email: Option<&str>,
+) -> Result<ChangeRequestRow> {
+    let id: Uuid = sqlx::query_scalar(
+        "INSERT INTO change_requests
+            (project_id, flag_environment_id, action, base_state, proposed_state, requested_by, requested_by_email)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         RETURNING id",
+    )
+    .bind(project_id)
+    .bind(flag_environment_id)
+    .bind(action)
+    .bind(base_state)
+    .bind(proposed_state)
+    .bind(requested_by)
+    .bind(requested_by_email)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let row = sqlx::query_as::<_, ChangeRequestRow>(&format!(
+        "SELECT {CHANGE_REQUEST_COLS} FROM {CHANGE_REQUEST_FROM} WHERE cr.id = $1"
+    ))
+    .bind(id)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(row)
+}
+
+// Reads shared by `PostgresStore` (on a pooled connection) and `UnitOfWork`
+// (inside its transaction).
+
+async fn fetch_environments(
+    conn: &mut sqlx::PgConnection,
+    project_id: Uuid,
+) -> Result<Vec<EnvironmentRow>> {
+    let rows = sqlx::query_as::<_, EnvironmentRow>(
+        "SELECT * FROM environments WHERE project_id = $1 ORDER BY sort_order",
+    )
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows)
+}
+
+async fn fetch_all_flags(conn: &mut sqlx::PgConnection, project_id: Uuid) -> Result<Vec<FlagRow>> {
+    let rows = sqlx::query_as::<_, FlagRow>(&format!(
+        "SELECT {FLAG_COLS} FROM flags WHERE project_id = $1 ORDER BY key"
+    ))
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows)
+}
+
+async fn fetch_flag_variants(
+    conn: &mut sqlx::PgConnection,
+    flag_id: Uuid,
+) -> Result<Vec<FlagVariantRow>> {
+    let rows = sqlx::query_as::<_, FlagVariantRow>(
+        "SELECT * FROM flag_variants WHERE flag_id = $1 ORDER BY sort_order",
+    )
+    .bind(flag_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows)
+}
+
+async fn fetch_flag_environments(
+    conn: &mut sqlx::PgConnection,
+    flag_id: Uuid,
+) -> Result<Vec<FlagEnvironmentRow>> {
+    let rows = sqlx::query_as::<_, FlagEnvironmentRow>(
+        "SELECT * FROM flag_environments WHERE flag_id = $1",
+    )
+    .bind(flag_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows)
+}
+
+async fn fetch_flag_environment_contents(
+    conn: &mut sqlx::PgConnection,
+    flag_environment_id: Uuid,
+) -> Result<FlagEnvironmentContents> {
+    let rules = sqlx::query_as::<_, TargetingRuleRow>(
+        "SELECT * FROM targeting_rules WHERE flag_environment_id = $1 ORDER BY rank",
+    )
+    .bind(flag_environment_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut contents = Vec::with_capacity(rules.len());
+    for rule in rules {
+        let segments = sqlx::query_as::<_, RuleSegmentRow>(
+            "SELECT * FROM rule_segments WHERE rule_id = $1",
+        )
+        .bind(rule.id)
+        .fetch_all(&mut *conn)
+        .await?;
+        let distributions = sqlx::query_as::<_, RuleDistributionRow>(
+            "SELECT * FROM rule_distributions WHERE rule_id = $1 ORDER BY sort_order",
+        )
+        .bind(rule.id)
+        .fetch_all(&mut *conn)
+        .await?;
+        contents.push(RuleContents {
+            rule,
+            segments,
+            distributions,
+        });
+    }
+
+    let overrides = sqlx::query_as::<_, FlagOverrideRow>(
+        "SELECT * FROM flag_overrides WHERE flag_environment_id = $1 ORDER BY targeting_key",
+    )
+    .bind(flag_environment_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(FlagEnvironmentContents {
+        rules: contents,
+        overrides,
+    })
+}
+
+async fn fetch_segments(conn: &mut sqlx::PgConnection, project_id: Uuid) -> Result<Vec<SegmentRow>> {
+    let rows = sqlx::query_as::<_, SegmentRow>(&format!(
+        "SELECT {SEGMENT_COLS} FROM segments WHERE project_id = $1 ORDER BY name"
+    ))
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows)
+}
+
+async fn fetch_segment_constraints(
+    conn: &mut sqlx::PgConnection,
+    segment_id: Uuid,
+) -> Result<Vec<SegmentConstraintRow>> {
+    let rows = sqlx::query_as::<_, SegmentConstraintRow>(&format!(
+        "SELECT {CONSTRAINT_COLS} FROM segment_constraints WHERE segment_id = $1
+         ORDER BY sort_order"
+    ))
+    .bind(segment_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows)
+}
+
+/// Count references to a variant from defaults, overrides, rules and distributions.
+async fn fetch_variant_usage(
+    conn: &mut sqlx::PgConnection,
+    variant_id: Uuid,
+) -> Result<VariantUsageRow> {
+    let row = sqlx::query_as::<_, VariantUsageRow>(
+        "SELECT
+            (SELECT COUNT(*) FROM flag_environments WHERE default_variant_id = $1) AS default_count,
+            (SELECT COUNT(*) FROM flag_overrides WHERE variant_id = $1) AS override_count,
+            (SELECT COUNT(*) FROM targeting_rules WHERE variant_id = $1) AS rule_count,
+            (SELECT COUNT(*) FROM rule_distributions WHERE variant_id = $1) AS distribution_count",
+    )
+    .bind(variant_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(row)
+}
+
+async fn fetch_segment_usage(
+    conn: &mut sqlx::PgConnection,
+    segment_id: Uuid,
+) -> Result<Vec<SegmentUsageRow>> {
+    let rows = sqlx::query_as::<_, SegmentUsageRow>(
+        "SELECT rs.rule_id, rs.negate, f.id AS flag_id, f.key AS flag_key,
+                e.id AS environment_id, e.name AS environment_name
+         FROM rule_segments rs
+         JOIN targeting_rules tr ON rs.rule_id = tr.id
+         JOIN flag_environments fe ON tr.flag_environment_id = fe.id
+         JOIN flags f ON fe.flag_id = f.id
+         JOIN environments e ON fe.environment_id = e.id
+         WHERE rs.segment_id = $1
+         ORDER BY f.key, e.sort_order, tr.rank",
+    )
+    .bind(segment_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows)
+}
+
+/// Build the evaluation config of an environment from its non-archived
+/// flags and the project's segments.
+async fn fetch_flags_config(
+    conn: &mut sqlx::PgConnection,
+    project_id: Uuid,
+    environment_id: Uuid,
+) -> Result<eval::FlagsConfig> {
+    let flags: Vec<FlagRow> = sqlx::query_as(&format!(
+        "SELECT {FLAG_COLS} FROM flags WHERE project_id = $1 AND archived = FALSE"
+    ))
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    let all_segments = fetch_segments(conn, project_id).await?;
+
+    let mut flag_configs = std::collections::HashMap::new();
+    let mut segment_map = std::collections::HashMap::new();
+
+    // Build segment map
+    for seg in &all_segments {
+        let constraints = fetch_segment_constraints(conn, seg.id).await?;
+        segment_map.insert(
+            seg.id,
+            eval::Segment {
+                id: seg.id,
+                key: seg.key.clone(),
+                name: seg.name.clone(),
+                match_type: match seg.match_type.as_str() {
+                    "any" => eval::MatchType::Any,
+                    _ => eval::MatchType::All,
+                },
+                constraints: constraints
+                    .into_iter()
+                    .map(|c| eval::SegmentConstraint {
+                        attribute: c.attribute,
+                        operator: parse_operator(&c.operator),
+                        values: c.values,
+                    })
+                    .collect(),
+            },
+        );
+    }
+
+    // Build flag configs
+    for flag in &flags {
+        let variants = fetch_flag_variants(conn, flag.id).await?;
+        let flag_env = sqlx::query_as::<_, FlagEnvironmentRow>(
+            "SELECT * FROM flag_environments WHERE flag_id = $1 AND environment_id = $2",
+        )
+        .bind(flag.id)
+        .bind(environment_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let Some(fe) = flag_env else { continue };
+
+        let contents = fetch_flag_environment_contents(conn, fe.id).await?;
+
+        let mut eval_rules = Vec::new();
+        for RuleContents {
+            rule,
+            segments,
+            distributions,
+        } in contents.rules
+        {
+            eval_rules.push(eval::TargetingRule {
+                id: rule.id,
+                rank: rule.rank,
+                description: rule.description,
+                segments: segments
+                    .into_iter()
+                    .map(|rs| eval::RuleSegment {
+                        segment_id: rs.segment_id,
+                        negate: rs.negate,
+                    })
+                    .collect(),
+                distributions: distributions
+                    .into_iter()
+                    .map(|rd| eval::RuleDistribution {
+                        variant_id: rd.variant_id,
+                        rollout_pct: rd.rollout_pct,
+                    })
+                    .collect(),
+                variant_id: rule.variant_id,
+            });
+        }
+
+        let default_variant_id = fe.default_variant_id.unwrap_or_else(|| {
+            variants.first().map(|v| v.id).unwrap_or_default()
+        });
+
+        flag_configs.insert(
+            flag.key.clone(),
+            eval::FlagConfig {
+                key: flag.key.clone(),
+                flag_type: match flag.flag_type.as_str() {
+                    "string" => eval::FlagType::String,
+                    "number" => eval::FlagType::Number,
+                    "json" => eval::FlagType::Json,
+                    _ => eval::FlagType::Boolean,
+                },
+                variants: variants
+                    .into_iter()
+                    .map(|v| eval::Variant {
+                        id: v.id,
+                        key: v.key,
+                        value: v.value,
+                        description: v.description,
+                    })
+                    .collect(),
+                environment: eval::FlagEnvironment {
+                    enabled: fe.enabled,
+                    default_variant_id,
+                    rules: eval_rules,
+                    overrides: contents
+                        .overrides
+                        .into_iter()
+                        .map(|o| eval::FlagOverride {
+                            targeting_key: o.targeting_key,
+                            variant_id: o.variant_id,
+                        })
+                        .collect(),
+                },
+            },
+        );
+    }
+
+    // Get config version
+    let version_row = sqlx::query_as::<_, ConfigVersionRow>(
+        "SELECT * FROM config_versions WHERE environment_id = $1",
+    )
+    .bind(environment_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(eval::FlagsConfig {
+        flags: flag_configs,
+        segments: segment_map,
+        version: version_row.map(|v| v.version).unwrap_or(1),
+    })
+}
+
+/// Record an organization-level change to `user_id` in the audit log of
+/// every project in the organization.
+#[allow(clippy::too_many_arguments)]
+async fn audit_organization(
+    conn: &mut sqlx::PgConnection,
+    audit_key: &[u8],
+    org_id: Uuid,
+    ctx: &AuditContext,
+    action: &str,
+    user_id: Uuid,
+    before_state: Option<&serde_json::Value>,
+    after_state: Option<&serde_json::Value>,
+) -> Result<()> {
+    let project_ids: Vec<Uuid> =
+        sqlx::query_scalar("SELECT id FROM projects WHERE organization_id = $1 ORDER BY id")
+            .bind(org_id)
+            .fetch_all(&mut *conn)
+            .await?;
+    for project_id in project_ids {
+        insert_audit_log(
+            &mut *conn,
+            audit_key,
+            project_id,
+            ctx,
+            action,
+            "user",
+            Some(user_id),
+            before_state,
+            after_state,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Append an audit entry to the project's hash chain and queue its webhook
+/// event. Holds the project's chain lock until the transaction ends.
+#[allow(clippy::too_many_arguments)]
+async fn insert_audit_log(
+    conn: &mut sqlx::PgConnection,
+    audit_key: &[u8],
+    project_id: Uuid,
+    ctx: &AuditContext,
+    action: &str,
+    entity_type: &str,
+    entity_id: Option<Uuid>,
+    before_state: Option<&serde_json::Value>,
+    after_state: Option<&serde_json::Value>,
+) -> Result<AuditLogRow> {
+    let diff = match (before_state, after_state) {
+        (Some(before), Some(after)) => {
+            let mut changes = serde_json::Map::new();
+            json_diff(String::new(), before, after, &mut changes);
+            Some(serde_json::Value::Object(changes))
+        }
+        _ => None,
+    };
+
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(advisory_key(project_id))
+        .execute(&mut *conn)
+        .await?;
+    let last: Option<(i64, Option<String>)> = sqlx::query_as(
+        "SELECT seq, hash FROM audit_log WHERE project_id = $1 ORDER BY seq DESC LIMIT 1",
+    )
+    .bind(project_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let (seq, prev_hash) = match last {
+        Some((seq, hash)) => (seq + 1, hash),
+        None => (1, None),
+    };
+
+    let mut row = sqlx::query_as::<_, AuditLogRow>(
+        "INSERT INTO audit_log
+            (project_id, actor_id, actor_email, action, entity_type, entity_id,
+             before_state, after_state, diff, metadata, environment_id, seq, prev_hash)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) RETURNING *",
+    )
+    .bind(project_id)
+    .bind(ctx.actor_id)
+    .bind(ctx.actor_email.as_deref())
+    .bind(action)
+    .bind(entity_type)
+    .bind(entity_id)
+    .bind(before_state)
+    .bind(after_state)
+    .bind(diff)
+    .bind(ctx.metadata())
+    .bind(ctx.environment_id)
+    .bind(seq)
+    .bind(prev_hash)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    // Hash the row as stored, so verification reproduces it exactly.
+    let hash = audit_hash(audit_key, &row);
+    sqlx::query("UPDATE audit_log SET hash = $2 WHERE id = $1")
+        .bind(row.id)
+        .bind(&hash)
+        .execute(&mut *conn)
+        .await?;
+    row.hash = Some(hash);
+
+    let data = serde_json::json!({
+        "audit_log_id": row.id,
+        "entity_type": row.entity_type,
+        "entity_id": row.entity_id,
+        "environment_id": row.environment_id,
+        "actor": { "id": row.actor_id, "email": row.actor_email },
+        "before": row.before_state,
+        "after": row.after_state,
+        "diff": row.diff,
+    });
+    enqueue_webhook_event(&mut *conn, project_id, action, row.created_at, &data).await?;
+    Ok(row)
+}
+
+/// Queue `event` for every enabled webhook of the project subscribed to it.
+/// Webhooks with no event list receive everything.
+async fn enqueue_webhook_event(
+    conn: &mut sqlx::PgConnection,
+    project_id: Uuid,
+    event: &str,
+    occurred_at: chrono::DateTime<chrono::Utc>,
+    data: &serde_json::Value,
+) -> Result<()> {
+    let payload = serde_json::json!({
+        "event": event,
+        "project_id": project_id,
+        "occurred_at": occurred_at,
+        "data": data,
+    });
+    sqlx::query(
+        "INSERT INTO webhook_deliveries (webhook_id, project_id, event, payload)
+         SELECT id, project_id, $2, $3 FROM webhooks
+         WHERE project_id = $1 AND enabled AND (cardinality(events) = 0 OR $2 = ANY(events))",
+    )
+    .bind(project_id)
+    .bind(event)
+    .bind(payload)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Insert the user or refresh their email. An absent email never clears a
+/// stored one.
+async fn upsert_user(
+    conn: &mut sqlx::PgConnection,
+    external_id: &str,
+    email: Option<&str>,
+) -> Result<UserRow> {
+    let row = sqlx::query_as::<_, UserRow>(
+        "INSERT INTO users (external_id, email) VALUES ($1, $2)
+         ON CONFLICT (external_id) DO UPDATE
+         SET email = COALESCE(EXCLUDED.email, users.email)
+         RETURNING *",
+    )
+    .bind(external_id)
+    .bind(email)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(row)
+}
+
+async fn insert_organization(
+    conn: &mut sqlx::PgConnection,
+    name: &str,
+    slug: &str,
+    external_id: Option<&str>,
+) -> Result<OrganizationRow> {
+    let row = sqlx::query_as::<_, OrganizationRow>(
+        "INSERT INTO organizations (name, slug, external_id) VALUES ($1, $2, $3) RETURNING *",
+    )
+    .bind(name)
+    .bind(slug)
+    .bind(external_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(row)
+}
+
+async fn insert_organization_member(
+    conn: &mut sqlx::PgConnection,
+    org_id: Uuid,
+    user_id: Uuid,
+    role: &str,
+) -> Result<bool> {
+    let result = sqlx::query(
+        "INSERT INTO organization_members (organization_id, user_id, role)
+         VALUES ($1, $2, $3::project_role)
+         ON CONFLICT (organization_id, user_id) DO NOTHING",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .bind(role)
+    .execute(&mut *conn)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+async fn fetch_environment_permissions(
+    conn: &mut sqlx::PgConnection,
+    environment_id: Uuid,
+) -> Result<Vec<EnvironmentPermissionRow>> {
+    let rows = sqlx::query_as::<_, EnvironmentPermissionRow>(
+        "SELECT u.id AS user_id, u.external_id, u.email, ep.role::TEXT AS role, ep.created_at
+         FROM environment_permissions ep
+         JOIN users u ON u.id = ep.user_id
+         WHERE ep.environment_id = $1
+         ORDER BY ep.created_at",
+    )
+    .bind(environment_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows)
+}
+
+async fn upsert_project_member(
+    conn: &mut sqlx::PgConnection,
+    project_id: Uuid,
+    user_id: Uuid,
+    role: &str,
+) -> Result<ProjectMemberRow> {
+    sqlx::query(
+        "INSERT INTO project_members (project_id, user_id, role) VALUES ($1, $2, $3::project_role)
+         ON CONFLICT (project_id, user_id) DO UPDATE SET role = EXCLUDED.role",
+    )
+    .bind(project_id)
+    .bind(user_id)
+    .bind(role)
+    .execute(&mut *conn)
+    .await?;
+
+    let row = sqlx::query_as::<_, ProjectMemberRow>(
+        "SELECT u.id AS user_id, u.external_id, u.email, pm.role::TEXT AS role, pm.created_at, pm.updated_at
+         FROM project_members pm
+         JOIN users u ON u.id = pm.user_id
+         WHERE pm.project_id = $1 AND pm.user_id = $2",
+    )
+    .bind(project_id)
+    .bind(user_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(row)
+}
+
+async fn insert_sdk_key(
+    conn: &mut sqlx::PgConnection,
+    environment_id: Uuid,
+    name: &str,
+    key_type: &str,
+    key_hash: &str,
+    key_prefix: &str,
+) -> Result<SdkKeyRow> {
+    let row = sqlx::query_as::<_, SdkKeyRow>(&format!(
+        "INSERT INTO sdk_keys (environment_id, name, key_type, key_hash, key_prefix)
+         VALUES ($1, $2, $3::sdk_key_type, $4, $5) RETURNING {SDK_KEY_COLS}"
+    ))
+    .bind(environment_id)
+    .bind(name)
+    .bind(key_type)
+    .bind(key_hash)
+    .bind(key_prefix)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(row)
+}
+
+/// Insert an environment after the existing ones, initialise its config
+/// version and give every existing flag a disabled `flag_environments` row
+/// pointing at its first variant.
+async fn insert_environment(
+    conn: &mut sqlx::PgConnection,
+    project_id: Uuid,
+    name: &str,
+    slug: &str,
+    color: Option<&str>,
+) -> Result<EnvironmentRow> {
+    let row = sqlx::query_as::<_, EnvironmentRow>(
+        "INSERT INTO environments (project_id, name, slug, color, sort_order)
+         VALUES ($1, $2, $3, $4, (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM environments WHERE project_id = $1))
+         RETURNING *",
+    )
+    .bind(project_id)
+    .bind(name)
+    .bind(slug)
+    .bind(color)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    // Initialize config version
+    sqlx::query("INSERT INTO config_versions (environment_id) VALUES ($1) ON CONFLICT DO NOTHING")
+        .bind(row.id)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO flag_environments (flag_id, environment_id, enabled, default_variant_id)
+         SELECT f.id, $1, FALSE,
+                (SELECT v.id FROM flag_variants v WHERE v.flag_id = f.id ORDER BY v.sort_order LIMIT 1)
+         FROM flags f
+         WHERE f.project_id = $2
+         ON CONFLICT (flag_id, environment_id) DO NOTHING",
+    )
+    .bind(row.id)
+    .bind(project_id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(row)
+}
+
+async fn insert_flag_variant(
+    conn: &mut sqlx::PgConnection,
+    flag_id: Uuid,
+    key: &str,
+    value: &serde_json::Value,
+    description: Option<&str>,
+    sort_order: i32,
+) -> Result<FlagVariantRow> {
+    let row = sqlx::query_as::<_, FlagVariantRow>(
+        "INSERT INTO flag_variants (flag_id, key, value, description, sort_order)
+         VALUES ($1, $2, $3, $4, $5) RETURNING *",
+    )
+    .bind(flag_id)
+    .bind(key)
+    .bind(value)
+    .bind(description)
+    .bind(sort_order)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(row)
+}
+
+/// Append constraints to a segment in the given order. Each entry is
+/// `(attribute, operator, values)`.
+async fn insert_segment_constraints(
+    conn: &mut sqlx::PgConnection,
+    segment_id: Uuid,
+    constraints: &[(String, String, Vec<String>)],
+) -> Result<Vec<SegmentConstraintRow>> {
+    let mut rows = Vec::with_capacity(constraints.len());
+    for (i, (attribute, operator, values)) in constraints.iter().enumerate() {
+        let row = sqlx::query_as::<_, SegmentConstraintRow>(
+            &format!("INSERT INTO segment_constraints (segment_id, attribute, operator, values, sort_order)
+             VALUES ($1, $2, $3::operator_type, $4, $5) RETURNING {CONSTRAINT_COLS}"),
+        )
+        .bind(segment_id)
+        .bind(attribute)
+        .bind(operator)
+        .bind(values)
+        .bind(i as i32)
+        .fetch_one(&mut *conn)
+        .await?;
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Replace the rules (with their segments and distributions) and overrides of
+/// `target_fe_id` with copies of those of `source_fe_id`.
+async fn replace_flag_environment_contents(
+    conn: &mut sqlx::PgConnection,
+    source_fe_id: Uuid,
+    target_fe_id: Uuid,
+) -> Result<()> {
+    sqlx::query("DELETE FROM targeting_rules WHERE flag_environment_id = $1")
+        .bind(target_fe_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM flag_overrides WHERE flag_environment_id = $1")
+        .bind(target_fe_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let rules = sqlx::query_as::<_, TargetingRuleRow>(
+        "SELECT * FROM targeting_rules WHERE flag_environment_id = $1 ORDER BY rank",
+    )
+    .bind(source_fe_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for rule in rules {
+        let (new_rule_id,): (Uuid,) = sqlx::query_as(
+            "INSERT INTO targeting_rules (flag_environment_id, rank, description, variant_id)
+             VALUES ($1, $2, $3, $4) RETURNING id",
+        )
+        .bind(target_fe_id)
+        .bind(rule.rank)
+        .bind(&rule.description)
+        .bind(rule.variant_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO rule_segments (rule_id, segment_id, negate)
+             SELECT $2, segment_id, negate FROM rule_segments WHERE rule_id = $1",
+        )
+        .bind(rule.id)
+        .bind(new_rule_id)
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO rule_distributions (rule_id, variant_id, rollout_pct, sort_order)
+             SELECT $2, variant_id, rollout_pct, sort_order FROM rule_distributions WHERE rule_id = $1",
+        )
+        .bind(rule.id)
+        .bind(new_rule_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    sqlx::query(
+        "INSERT INTO flag_overrides (flag_environment_id, targeting_key, variant_id)
+         SELECT $2, targeting_key, variant_id FROM flag_overrides WHERE flag_environment_id = $1",
+    )
+    .bind(source_fe_id)
+    .bind(target_fe_id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Overwrite a flag environment's enabled state, default variant, rules and
+/// overrides. Returns the ID of the environment it belongs to.
+async fn write_flag_environment(
+    conn: &mut sqlx::PgConnection,
+    flag_environment_id: Uuid,
+    spec: &FlagEnvironmentSpec,
+) -> Result<Uuid> {
+    let environment_id: Uuid = sqlx::query_scalar(
+        "UPDATE flag_environments SET enabled = $2, default_variant_id = $3 WHERE id = $1
+         RETURNING environment_id",
+    )
+    .bind(flag_environment_id)
+    .bind(spec.enabled)
+    .bind(spec.default_variant_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query("DELETE FROM targeting_rules WHERE flag_environment_id = $1")
+        .bind(flag_environment_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM flag_overrides WHERE flag_environment_id = $1")
+        .bind(flag_environment_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for (rank, rule) in spec.rules.iter().enumerate() {
+        let rule_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO targeting_rules (flag_environment_id, rank, description, variant_id)
+             VALUES ($1, $2, $3, $4) RETURNING id",
+        )
+        .bind(flag_environment_id)
+        .bind(rank as i32)
+        .bind(&rule.description)
+        .bind(rule.variant_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        insert_rule_children(&mut *conn, rule_id, &rule.segments, &rule.distributions).await?;
+    }
+
+    for (targeting_key, variant_id) in &spec.overrides {
+        sqlx::query(
+            "INSERT INTO flag_overrides (flag_environment_id, targeting_key, variant_id) VALUES ($1, $2, $3)",
+        )
+        .bind(flag_environment_id)
+        .bind(targeting_key)
+        .bind(variant_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(environment_id)
+}
+
+/// Collect the leaves that differ between two JSON documents, keyed by path
+/// (`rules[0].variant`). Objects and arrays are compared element-wise; a
+/// field present on one side only is reported against `null`.
+pub(crate) fn json_diff(
+    path: String,
+    before: &serde_json::Value,
+    after: &serde_json::Value,
+    changes: &mut serde_json::Map<String, serde_json::Value>,
+) {
+    use serde_json::Value;
+
+    const NULL: Value = Value::Null;
+    let field = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{path}.{key}")
+        }
+    };
+
+    match (before, after) {
+        (Value::Object(b), Value::Object(a)) => {
+            let keys: std::collections::BTreeSet<&String> = b.keys().chain(a.keys()).collect();
+            for key in keys {
+                json_diff(
+                    field(key),
+                    b.get(key).unwrap_or(&NULL),
+                    a.get(key).unwrap_or(&NULL),
+                    changes,
+                );
+            }
+        }
+        (Value::Array(b), Value::Array(a)) => {
+            for i in 0..b.len().max(a.len()) {
+                json_diff(
+                    format!("{path}[{i}]"),
+                    b.get(i).unwrap_or(&NULL),
+                    a.get(i).unwrap_or(&NULL),
+                    changes,
+                );
+            }
+        }
+        _ if before != after => {
+            changes.insert(
+                path,
+                serde_json::json!({ "before": before, "after": after }),
+            );
+        }
+        _ => {}
+    }
+}
+
+/// Checks a project's audit entries in `seq` order and builds the
+/// [`AuditChainReport`] for [`PostgresStore::verify_audit_chain`].
+struct AuditChainWalk<'a> {
+    key: &'a [u8],
+    report: AuditChainReport,
+    expected_seq: i64,
+    prev_hash: Option<String>,
+    keyed: bool,
+}
+
+impl<'a> AuditChainWalk<'a> {
+    fn new(key: &'a [u8]) -> Self {
+        Self {
+            key,
+            report: AuditChainReport {
+                verified: 0,
+                unchained: 0,
+                unkeyed: 0,
+                first_broken: None,
+                head: None,
+            },
+            expected_seq: 1,
+            prev_hash: None,
+            keyed: false,
+        }
+    }
+
+    /// Check the next entry. Returns false, with `first_broken` set, if it
+    /// breaks the chain.
+    fn push(&mut self, row: &AuditLogRow) -> bool {
+        let report = &mut self.report;
+        let broken = if row.seq != self.expected_seq {
+            Some(format!("entry {} is missing", self.expected_seq))
+        } else if let Some(hash) = &row.hash {
+            if row.prev_hash != self.prev_hash {
+                Some("prev_hash does not match the previous entry".to_string())
+            } else if *hash == audit_hash(self.key, row) {
+                self.keyed = true;
+                report.verified += 1;
+                None
+            } else if !self.keyed && *hash == legacy_audit_hash(row) {
+                report.verified += 1;
+                report.unkeyed += 1;
+                None
+            } else {
+                Some("content does not match its hash".to_string())
+            }
+        } else if report.verified > 0 {
+            Some("hash is missing".to_string())
+        } else {
+            report.unchained += 1;
+            None
+        };
+
+        if let Some(reason) = broken {
+            report.first_broken = Some(AuditChainBreak {
+                seq: row.seq,
+                id: row.id,
+                reason,
+            });
+            return false;
+        }
+        if let Some(hash) = &row.hash {
+            report.head = Some(AuditChainHead {
+                seq: row.seq,
+                hash: hash.clone(),
+                created_at: row.created_at,
+            });
+        }
+        self.prev_hash = row.hash.clone();
+        self.expected_seq += 1;
+        true
+    }
+}
+
+/// HMAC-SHA256, keyed by the server's audit key, over an audit entry's
+/// content and the previous entry's hash.
+fn audit_hash(key: &[u8], row: &AuditLogRow) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(audit_content(row).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Unkeyed SHA-256 of an entry, as written before the chain was keyed.
+fn legacy_audit_hash(row: &AuditLogRow) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(audit_content(row).as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+fn audit_content(row: &AuditLogRow) -> String {
+    serde_json::json!([
+        row.id,
+        row.project_id,
+        row.seq,
+        row.prev_hash,
+        row.actor_id,
+        row.actor_email,
+        row.action,
+        row.entity_type,
+        row.entity_id,
+        row.environment_id,
+        row.before_state,
+        row.after_state,
+        row.diff,
+        row.metadata,
+        row.created_at.timestamp_micros(),
+    ])
+    .to_string()
+}
+
+/// Advisory lock key for a job. Collisions only cost a skipped tick.
+fn advisory_key(id: Uuid) -> i64 {
+    id.as_u64_pair().0 as i64
+}
+
+async fn apply_scheduled_action(
+    conn: &mut sqlx::PgConnection,
+    flag_environment_id: Uuid,
+    action: &ScheduledAction,
+) -> Result<()> {
+    match action {
+        ScheduledAction::Toggle { enabled } => {
+            sqlx::query("UPDATE flag_environments SET enabled = $2 WHERE id = $1")
+                .bind(flag_environment_id)
+                .bind(enabled)
+                .execute(&mut *conn)
+                .await?;
+        }
+        ScheduledAction::DefaultVariant { variant_id } => {
+            sqlx::query("UPDATE flag_environments SET default_variant_id = $2 WHERE id = $1")
+                .bind(flag_environment_id)
+                .bind(variant_id)
+                .execute(&mut *conn)
+                .await?;
+        }
+        ScheduledAction::Rule {
+            rule_id,
+            description,
+            variant_id,
+            segments,
+            distributions,
+        } => {
+            let updated = sqlx::query(
+                "UPDATE targeting_rules SET description = $3, variant_id = $4
+                 WHERE id = $1 AND flag_environment_id = $2",
+            )
+            .bind(rule_id)
+            .bind(flag_environment_id)
+            .bind(description)
+            .bind(variant_id)
+            .execute(&mut *conn)
+            .await?;
+            if updated.rows_affected() == 0 {
+                anyhow::bail!("rule {rule_id} no longer exists");
+            }
+
+            sqlx::query("DELETE FROM rule_segments WHERE rule_id = $1")
+                .bind(rule_id)
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query("DELETE FROM rule_distributions WHERE rule_id = $1")
+                .bind(rule_id)
+                .execute(&mut *conn)
+                .await?;
+
+            let segments: Vec<(Uuid, bool)> =
+                segments.iter().map(|s| (s.segment_id, s.negate)).collect();
+            let distributions: Vec<(Uuid, i32)> = distributions
+                .iter()
+                .map(|d| (d.variant_id, d.rollout_pct))
+                .collect();
+            insert_rule_children(&mut *conn, *rule_id, &segments, &distributions).await?;
+        }
+        ScheduledAction::Rollout {
+            rule_id,
+            distributions,
+        } => {
+            let distributions: Vec<(Uuid, i32)> = distributions
+                .iter()
+                .map(|d| (d.variant_id, d.rollout_pct))
+                .collect();
+            set_rule_distributions(conn, flag_environment_id, *rule_id, &distributions).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace a rule's distributions. Its fixed variant is cleared, since it
+/// would take precedence over them.
+async fn set_rule_distributions(
+    conn: &mut sqlx::PgConnection,
+    flag_environment_id: Uuid,
+    rule_id: Uuid,
+    distributions: &[(Uuid, i32)],
+) -> Result<()> {
+    let updated = sqlx::query(
+        "UPDATE targeting_rules SET variant_id = NULL WHERE id = $1 AND flag_environment_id = $2",
+    )
+    .bind(rule_id)
+    .bind(flag_environment_id)
+    .execute(&mut *conn)
+    .await?;
+    if updated.rows_affected() == 0 {
+        anyhow::bail!("rule {rule_id} no longer exists");
+    }
+
+    sqlx::query("DELETE FROM rule_distributions WHERE rule_id = $1")
+        .bind(rule_id)
+        .execute(&mut *conn)
+        .await?;
+    insert_rule_children(&mut *conn, rule_id, &[], distributions).await
+}
+
+/// Insert the segment references and distribution buckets of a rule.
+async fn insert_rule_children(
+    conn: &mut sqlx::PgConnection,
+    rule_id: Uuid,
+    segments: &[(Uuid, bool)],
+    distributions: &[(Uuid, i32)],
+) -> Result<()> {
+    for (segment_id, negate) in segments {
+        sqlx::query("INSERT INTO rule_segments (rule_id, segment_id, negate) VALUES ($1, $2, $3)")
+            .bind(rule_id)
+            .bind(segment_id)
+            .bind(negate)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    for (i, (variant_id, rollout_pct)) in distributions.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO rule_distributions (rule_id, variant_id, rollout_pct, sort_order)
+             VALUES ($1, $2, $3, $4)",
+        )
+        .bind(rule_id)
+        .bind(variant_id)
+        .bind(rollout_pct)
+        .bind(i as i32)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+fn parse_operator(s: &str) -> eval::Operator {
+    match s {
+        "eq" => eval::Operator::Eq,
+        "neq" => eval::Operator::Neq,
+        "gt" => eval::Operator::Gt,
+        "gte" => eval::Operator::Gte,
+        "lt" => eval::Operator::Lt,
+        "lte" => eval::Operator::Lte,
+        "in" => eval::Operator::In,
+        "not_in" => eval::Operator::NotIn,
+        "contains" => eval::Operator::Contains,
+        "starts_with" => eval::Operator::StartsWith,
+        "ends_with" => eval::Operator::EndsWith,
+        "matches" => eval::Operator::Matches,
+        "semver_eq" => eval::Operator::SemverEq,
+        "semver_gt" => eval::Operator::SemverGt,
+        "semver_lt" => eval::Operator::SemverLt,
+        _ => eval::Operator::Eq,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const KEY: &[u8] = b"test-audit-key";
+
+    fn fixed_row() -> AuditLogRow {
+        AuditLogRow {
+            id: Uuid::from_u128(1),
+            project_id: Uuid::from_u128(2),
+            actor_id: None,
+            actor_email: Some("ops@example.com".to_string()),
+            action: "flag_updated".to_string(),
+            entity_type: "flag".to_string(),
+            entity_id: Some(Uuid::from_u128(3)),
+            before_state: Some(serde_json::json!({ "enabled": false })),
+            after_state: Some(serde_json::json!({ "enabled": true })),
+            metadata: Some(serde_json::json!({ "ip": "203.0.113.7" })),
+            created_at: chrono::Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap()
+                + chrono::Duration::microseconds(678_901),
+            diff: None,
+            environment_id: None,
+            seq: 1,
+            prev_hash: None,
+            hash: None,
+        }
+    }
+
+    /// Append an entry hashed with `hash` and linked to the last one.
+    fn append(rows: &mut Vec<AuditLogRow>, hash: fn(&AuditLogRow) -> String) {
+        let mut row = fixed_row();
+        row.id = Uuid::from_u128(100 + rows.len() as u128);
+        row.seq = rows.len() as i64 + 1;
+        row.prev_hash = rows.last().and_then(|r| r.hash.clone());
+        row.hash = Some(hash(&row));
+        rows.push(row);
+    }
+
+    /// A chain with one entry per hash function, in order.
+    fn chain(hashes: &[fn(&AuditLogRow) -> String]) -> Vec<AuditLogRow> {
+        let mut rows = Vec::new();
+        for hash in hashes {
+            append(&mut rows, *hash);
+        }
+        rows
+    }
+
+    fn keyed(row: &AuditLogRow) -> String {
+        audit_hash(KEY, row)
+    }
+
+    fn walk(rows: &[AuditLogRow]) -> AuditChainReport {
+        let mut walk = AuditChainWalk::new(KEY);
+        for row in rows {
+            if !walk.push(row) {
+                break;
+            }
+        }
+        walk.report
+    }
+
+    fn broken_at(report: &AuditChainReport) -> Option<(i64, &str)> {
+        report
+            .first_broken
+            .as_ref()
+            .map(|b| (b.seq, b.reason.as_str()))
+    }
+
+    #[test]
+    fn test_audit_content_is_pinned() {
+        // Changing the hash input invalidates every existing chain.
+        assert_eq!(
+            audit_content(&fixed_row()),
+            concat!(
+                r#"["00000000-0000-0000-0000-000000000001","00000000-0000-0000-0000-000000000002","#,
+                r#"1,null,null,"ops@example.com","flag_updated","flag","#,
+                r#""00000000-0000-0000-0000-000000000003",null,{"enabled":false},"#,
+                r#"{"enabled":true},null,{"ip":"203.0.113.7"},1704164645678901]"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_audit_hashes_are_pinned() {
+        let row = fixed_row();
+        assert_eq!(
+            audit_hash(KEY, &row),
+            "18b24881e3915256c29a9809e43eb6934e1d8216cf4e0c9ef2872dc6f5c19716"
+        );
+        assert_eq!(
+            legacy_audit_hash(&row),
+            "e46a23c84381a39718228d060020b8b423407dcc030b273643ddfccc7b422e93"
+        );
+        assert_ne!(audit_hash(b"another-key", &row), audit_hash(KEY, &row));
+    }
+
+    #[test]
+    fn test_walk_verifies_keyed_chain() {
+        let rows = chain(&[keyed, keyed, keyed]);
+        let report = walk(&rows);
+        assert_eq!(broken_at(&report), None);
+        assert_eq!(
+            (report.verified, report.unkeyed, report.unchained),
+            (3, 0, 0)
+        );
+        assert_eq!(report.head.map(|h| h.seq), Some(3));
+    }
+
+    #[test]
+    fn test_walk_accepts_legacy_entries_before_first_keyed() {
+        let rows = chain(&[legacy_audit_hash, legacy_audit_hash, keyed, keyed]);
+        let report = walk(&rows);
+        assert_eq!(broken_at(&report), None);
+        assert_eq!((report.verified, report.unkeyed), (4, 2));
+    }
+
+    #[test]
+    fn test_walk_rejects_legacy_entry_after_keyed() {
+        // Without the key, a forger could only append legacy entries.
+        let rows = chain(&[keyed, legacy_audit_hash]);
+        let report = walk(&rows);
+        assert_eq!(
+            broken_at(&report),
+            Some((2, "content does not match its hash"))
+        );
+        assert_eq!(report.verified, 1);
+    }
+
+    #[test]
+    fn test_walk_rejects_wrong_key() {
+        let rows = chain(&[|row| audit_hash(b"another-key", row)]);
+        let report = walk(&rows);
+        assert_eq!(
+            broken_at(&report),
+            Some((1, "content does not match its hash"))
+        );
+    }
+
+    #[test]
+    fn test_walk_rejects_altered_content() {
+        let mut rows = chain(&[keyed, keyed]);
+        rows[1].actor_email = Some("someone-else@example.com".to_string());
+        let report = walk(&rows);
+        assert_eq!(
+            broken_at(&report),
+            Some((2, "content does not match its hash"))
+        );
+    }
+
+    #[test]
+    fn test_walk_rejects_gaps_and_relinked_entries() {
+        let mut rows = chain(&[keyed, keyed, keyed]);
+        rows.remove(1);
+        let report = walk(&rows);
+        assert_eq!(broken_at(&report), Some((3, "entry 2 is missing")));
+
+        rows[1].seq = 2;
+        let report = walk(&rows);
+        assert_eq!(
+            broken_at(&report),
+            Some((2, "prev_hash does not match the previous entry"))
+        );
+    }
+
+    #[test]
+    fn test_walk_counts_unchained_prefix_only() {
+        // Entries from before chaining have no hash.
+        let mut rows = vec![fixed_row()];
+        append(&mut rows, keyed);
+        append(&mut rows, keyed);
+        let report = walk(&rows);
+        assert_eq!(broken_at(&report), None);
+        assert_eq!((report.verified, report.unchained), (2, 1));
+
+        let mut unhashed = fixed_row();
+        unhashed.seq = 4;
+        rows.push(unhashed);
+        let report = walk(&rows);
+        assert_eq!(broken_at(&report), Some((4, "hash is missing")));
     }
 }
@@ -0,0 +1,296 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use sha2::Sha256;
+use tokio::task::JoinSet;
+
+use crate::state::AppState;
+use crate::store::models::PendingWebhookDelivery;
+use crate::store::PostgresStore;
+
+/// How often the worker looks for due deliveries.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Upper bound on deliveries sent per tick.
+const BATCH_SIZE: i64 = 50;
+
+/// How long a receiver has to respond before the attempt counts as failed.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Attempts before a delivery is marked failed.
+const MAX_ATTEMPTS: i32 = 10;
+
+/// Delay before the first retry; doubled on every further attempt.
+const BASE_BACKOFF_SECS: i64 = 30;
+
+/// Longest delay between two attempts.
+const MAX_BACKOFF_SECS: i64 = 6 * 3600;
+
+/// Background worker that sends queued webhook deliveries and retries the
+/// failed ones with exponential backoff.
+///
+/// Every replica runs one; deliveries are leased when claimed, so each
+/// attempt is made by exactly one of them.
+///
+/// Receivers are only reached on public addresses: host names go through
+/// [`PublicResolver`], redirects are not followed and proxies are bypassed,
+/// so a webhook URL can't be pointed at the internal network.
+pub async fn run(state: AppState) {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy()
+        .dns_resolver(Arc::new(PublicResolver))
+        .build();
+    let client = match client {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!("Webhook worker disabled, HTTP client failed to build: {e}");
+            return;
+        }
+    };
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = tick(&state.store, &client).await {
+            tracing::error!("Webhook delivery tick failed: {e}");
+        }
+    }
+}
+
+async fn tick(store: &PostgresStore, client: &reqwest::Client) -> anyhow::Result<()> {
+    let mut sends = JoinSet::new();
+    for delivery in store.claim_webhook_deliveries(BATCH_SIZE).await? {
+        let store = store.clone();
+        let client = client.clone();
+        sends.spawn(async move { deliver(&store, &client, delivery).await });
+    }
+    while sends.join_next().await.is_some() {}
+    Ok(())
+}
+
+async fn deliver(
+    store: &PostgresStore,
+    client: &reqwest::Client,
+    delivery: PendingWebhookDelivery,
+) {
+    let body = delivery.payload.to_string();
+    let timestamp = chrono::Utc::now().timestamp().to_string();
+
+    // IP literals never reach the resolver, so they are checked here.
+    let literal = reqwest::Url::parse(&delivery.url)
+        .ok()
+        .and_then(|url| literal_ip(&url));
+    if let Some(ip) = literal.filter(|ip| !is_public(*ip)) {
+        let error = format!("{ip} is not a public address");
+        finish(store, &delivery, None, Some(error)).await;
+        return;
+    }
+
+    let result = client
+        .post(&delivery.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-FlagForge-Event", &delivery.event)
+        .header("X-FlagForge-Delivery", delivery.id.to_string())
+        .header("X-FlagForge-Timestamp", &timestamp)
+        .header(
+            "X-FlagForge-Signature",
+            signature(&delivery.secret, &timestamp, &body),
+        )
+        .body(body)
+        .send()
+        .await;
+
+    let (status_code, error) = match result {
+        Ok(response) if response.status().is_success() => {
+            (Some(response.status().as_u16() as i32), None)
+        }
+        Ok(response) => (
+            Some(response.status().as_u16() as i32),
+            Some(format!("receiver responded with {}", response.status())),
+        ),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    finish(store, &delivery, status_code, error).await;
+}
+
+/// Records the outcome of an attempt and schedules the retry, if any.
+async fn finish(
+    store: &PostgresStore,
+    delivery: &PendingWebhookDelivery,
+    status_code: Option<i32>,
+    error: Option<String>,
+) {
+    let attempts = delivery.attempts + 1;
+    let retry_in = (error.is_some() && attempts < MAX_ATTEMPTS).then(|| backoff(attempts));
+    if let Some(ref error) = error {
+        tracing::warn!(delivery_id = %delivery.id, attempts, "Webhook delivery failed: {error}");
+    }
+
+    if let Err(e) = store
+        .record_webhook_attempt(delivery.id, status_code, error.as_deref(), retry_in)
+        .await
+    {
+        tracing::error!(delivery_id = %delivery.id, "Failed to record webhook attempt: {e}");
+    }
+}
+
+/// `sha256=` followed by the hex HMAC-SHA256 of `"{timestamp}.{body}"`, keyed
+/// with the webhook's secret. Receivers recompute it to authenticate the
+/// request, and check the timestamp to reject replays.
+fn signature(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Delay before retrying after the given number of failed attempts.
+fn backoff(attempts: i32) -> chrono::Duration {
+    let secs = BASE_BACKOFF_SECS
+        .saturating_mul(1 << (attempts - 1).clamp(0, 20))
+        .min(MAX_BACKOFF_SECS);
+    chrono::Duration::seconds(secs)
+}
+
+/// Resolves receiver hosts and refuses any that point at a loopback, private
+/// or otherwise non-public address.
+///
+/// Checking at connect time rather than only when the webhook is saved
+/// covers DNS records that change after validation.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+                return Err(format!(
+                    "{} resolves to non-public address {}",
+                    name.as_str(),
+                    addr.ip()
+                )
+                .into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// The host of `url` when it is an IP literal rather than a name.
+pub(crate) fn literal_ip(url: &reqwest::Url) -> Option<IpAddr> {
+    let host = url.host_str()?;
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// Whether `ip` is reachable on the public internet, i.e. not loopback,
+/// private, link-local, shared (CGNAT), documentation, multicast or
+/// unspecified.
+pub(crate) fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_known_value() {
+        // HMAC-SHA256("secret", "1700000000.{\"a\":1}"), computed independently.
+        assert_eq!(
+            signature("secret", "1700000000", r#"{"a":1}"#),
+            "sha256=49f24e537407743fa4a0242bb63b94b9a47ee99cbbe071ccd8a22550ae411686"
+        );
+    }
+
+    #[test]
+    fn test_signature_covers_every_input() {
+        let sig = signature("secret", "1700000000", "body");
+        assert_ne!(sig, signature("other", "1700000000", "body"));
+        assert_ne!(sig, signature("secret", "1700000001", "body"));
+        assert_ne!(sig, signature("secret", "1700000000", "bodY"));
+    }
+
+    #[test]
+    fn test_backoff_doubles() {
+        assert_eq!(backoff(1).num_seconds(), 30);
+        assert_eq!(backoff(2).num_seconds(), 60);
+        assert_eq!(backoff(3).num_seconds(), 120);
+        assert_eq!(backoff(9).num_seconds(), 30 * 256);
+    }
+
+    #[test]
+    fn test_backoff_bounds() {
+        assert_eq!(backoff(0).num_seconds(), BASE_BACKOFF_SECS);
+        assert_eq!(backoff(-5).num_seconds(), BASE_BACKOFF_SECS);
+        assert_eq!(backoff(11).num_seconds(), MAX_BACKOFF_SECS);
+        assert_eq!(backoff(i32::MAX).num_seconds(), MAX_BACKOFF_SECS);
+    }
+
+    #[test]
+    fn test_is_public() {
+        for ip in ["8.8.8.8", "1.1.1.1", "2606:4700:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fc00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn test_literal_ip() {
+        let ip = |url: &str| literal_ip(&reqwest::Url::parse(url).unwrap());
+        assert_eq!(
+            ip("http://127.0.0.1:8080/hook"),
+            Some("127.0.0.1".parse().unwrap())
+        );
+        assert_eq!(ip("https://[::1]/hook"), Some("::1".parse().unwrap()));
+        assert_eq!(ip("https://example.com/hook"), None);
+    }
+}
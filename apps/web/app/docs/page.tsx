@@ -189,7 +189,7 @@ cd flagforge`}
         <CodeBlock
           lang="bash"
           code={`cp .env.example .env
-# Edit .env and set your CLERK_DOMAIN
+# Edit .env and set your CLERK_DOMAIN and AUDIT_HMAC_KEY
 # e.g. CLERK_DOMAIN=your-app.clerk.accounts.dev`}
         />
 
@@ -294,9 +294,11 @@ console.log(result.enabled); // false (default)`}
             <tr><td><code>DATABASE_URL</code></td><td>Yes</td><td>PostgreSQL connection string</td></tr>
             <tr><td><code>REDIS_URL</code></td><td>No</td><td>Redis connection string (defaults to localhost:6379)</td></tr>
             <tr><td><code>CLERK_DOMAIN</code></td><td>Yes</td><td>Your Clerk domain for JWT verification</td></tr>
+            <tr><td><code>AUDIT_HMAC_KEY</code></td><td>Yes</td><td>Secret of at least 32 characters keying the audit log hash chain</td></tr>
             <tr><td><code>HOST</code></td><td>No</td><td>Bind address (default: 0.0.0.0)</td></tr>
             <tr><td><code>PORT</code></td><td>No</td><td>Listen port (default: 8080)</td></tr>
             <tr><td><code>LOG_LEVEL</code></td><td>No</td><td>Tracing level (default: info)</td></tr>
+            <tr><td><code>TRUSTED_PROXIES</code></td><td>No</td><td>Comma-separated proxy addresses or CIDR ranges whose <code>X-Forwarded-For</code> is trusted for audit IPs</td></tr>
           </tbody>
         </table>
 
@@ -307,6 +309,7 @@ console.log(result.enabled); // false (default)`}
   -e DATABASE_URL=postgres://user:pass@db:5432/flagforge \\
   -e REDIS_URL=redis://redis:6379 \\
   -e CLERK_DOMAIN=your-app.clerk.accounts.dev \\
+  -e AUDIT_HMAC_KEY=your-stable-secret-of-32-or-more-chars \\
   -p 8080:8080 \\
   ghcr.io/flagforge/flagforge:latest`}
         />
@@ -793,9 +796,11 @@ function Component() {
             <tr><td><code>DATABASE_URL</code></td><td>—</td><td>PostgreSQL connection string (required)</td></tr>
             <tr><td><code>REDIS_URL</code></td><td><code>redis://127.0.0.1:6379</code></td><td>Redis for cache + pub/sub</td></tr>
             <tr><td><code>CLERK_DOMAIN</code></td><td>—</td><td>Clerk domain for management JWT auth (required)</td></tr>
+            <tr><td><code>AUDIT_HMAC_KEY</code></td><td>—</td><td>Secret (32+ characters) keying the audit hash chain (required)</td></tr>
             <tr><td><code>HOST</code></td><td><code>0.0.0.0</code></td><td>Server bind address</td></tr>
             <tr><td><code>PORT</code></td><td><code>8080</code></td><td>Server listen port</td></tr>
             <tr><td><code>LOG_LEVEL</code></td><td><code>info</code></td><td>Tracing filter (debug, info, warn, error)</td></tr>
+            <tr><td><code>TRUSTED_PROXIES</code></td><td>—</td><td>Proxies (addresses or CIDR ranges) whose <code>X-Forwarded-For</code> is trusted; otherwise the peer address is recorded</td></tr>
           </tbody>
         </table>
       </>
@@ -41,6 +41,29 @@ impl Evaluator {
         flag_key: &str,
         context: &EvaluationContext,
         default_value: &serde_json::Value,
+    ) -> EvaluationResult {
+        self.evaluate_inner(flag_key, context, default_value, None)
+    }
+
+    /// Evaluate a single flag and record every step that led to the result,
+    /// for explaining an evaluation after the fact.
+    pub fn evaluate_with_trace(
+        &self,
+        flag_key: &str,
+        context: &EvaluationContext,
+        default_value: &serde_json::Value,
+    ) -> (EvaluationResult, EvaluationTrace) {
+        let mut trace = EvaluationTrace::default();
+        let result = self.evaluate_inner(flag_key, context, default_value, Some(&mut trace));
+        (result, trace)
+    }
+
+    fn evaluate_inner(
+        &self,
+        flag_key: &str,
+        context: &EvaluationContext,
+        default_value: &serde_json::Value,
+        mut trace: Option<&mut EvaluationTrace>,
     ) -> EvaluationResult {
         // 1. Lookup flag
         let Some(flag) = self.flags.get(flag_key) else {
@@ -54,6 +77,10 @@ impl Evaluator {
         };
 
         let env = &flag.environment;
+        if let Some(t) = trace.as_deref_mut() {
+            t.flag_found = true;
+            t.enabled = env.enabled;
+        }
 
         // 2. Flag disabled?
         if !env.enabled {
@@ -73,6 +100,9 @@ impl Evaluator {
         if let Some(targeting_key) = &context.targeting_key {
             for ovr in &env.overrides {
                 if ovr.targeting_key == *targeting_key {
+                    if let Some(t) = trace.as_deref_mut() {
+                        t.matched_override = Some(targeting_key.clone());
+                    }
                     let variant = self.find_variant(&flag.variants, ovr.variant_id);
                     return EvaluationResult {
                         flag_key: flag_key.to_string(),
@@ -92,8 +122,23 @@ impl Evaluator {
         rules.sort_by_key(|r| r.rank);
 
         for rule in &rules {
-            if self.evaluate_rule_segments(rule, context) {
-                return self.resolve_rule_result(flag, rule, context, default_value);
+            let matched = match trace.as_deref_mut() {
+                Some(t) => {
+                    let segments = self.trace_rule_segments(rule, context);
+                    let matched = segments.iter().all(|s| s.matched);
+                    t.rules.push(RuleTrace {
+                        rule_id: rule.id,
+                        rank: rule.rank,
+                        description: rule.description.clone(),
+                        matched,
+                        segments,
+                    });
+                    matched
+                }
+                None => self.evaluate_rule_segments(rule, context),
+            };
+            if matched {
+                return self.resolve_rule_result(flag, rule, context, default_value, trace);
             }
         }
 
@@ -133,20 +178,66 @@ impl Evaluator {
         })
     }
 
+    /// Like [`Self::evaluate_rule_segments`], but evaluates every segment and
+    /// constraint rather than stopping at the first decisive one.
+    fn trace_rule_segments(
+        &self,
+        rule: &TargetingRule,
+        context: &EvaluationContext,
+    ) -> Vec<SegmentTrace> {
+        rule.segments
+            .iter()
+            .map(|rule_seg| {
+                let segment = self.segments.get(&rule_seg.segment_id);
+                let constraints: Vec<ConstraintTrace> = segment
+                    .map(|s| {
+                        s.constraints
+                            .iter()
+                            .map(|c| ConstraintTrace {
+                                attribute: c.attribute.clone(),
+                                operator: c.operator.clone(),
+                                values: c.values.clone(),
+                                actual: self.attribute_value(&c.attribute, context),
+                                matched: self.evaluate_constraint(c, context),
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                let matches = match segment {
+                    None => false,
+                    Some(_) if constraints.is_empty() => true,
+                    Some(s) => match s.match_type {
+                        MatchType::All => constraints.iter().all(|c| c.matched),
+                        MatchType::Any => constraints.iter().any(|c| c.matched),
+                    },
+                };
+
+                SegmentTrace {
+                    segment_id: rule_seg.segment_id,
+                    segment_key: segment.map(|s| s.key.clone()),
+                    negate: rule_seg.negate,
+                    matched: matches != rule_seg.negate,
+                    constraints,
+                }
+            })
+            .collect()
+    }
+
     /// Evaluate a single segment against context.
     fn evaluate_segment(&self, segment: &Segment, context: &EvaluationContext) -> bool {
         if segment.constraints.is_empty() {
             return true;
         }
 
-        let results = segment
+        let mut results = segment
             .constraints
             .iter()
             .map(|constraint| self.evaluate_constraint(constraint, context));
 
         match segment.match_type {
-            MatchType::All => results.fold(true, |acc, r| acc && r),
-            MatchType::Any => results.fold(false, |acc, r| acc || r),
+            MatchType::All => results.all(|r| r),
+            MatchType::Any => results.any(|r| r),
         }
     }
 
@@ -156,21 +247,28 @@ impl Evaluator {
         constraint: &SegmentConstraint,
         context: &EvaluationContext,
     ) -> bool {
+        let Some(value) = self.attribute_value(&constraint.attribute, context) else {
+            return false;
+        };
+
+        evaluate_operator(&constraint.operator, &value, &constraint.values)
+    }
+
+    /// Look up an attribute in the context.
+    fn attribute_value(
+        &self,
+        attribute: &str,
+        context: &EvaluationContext,
+    ) -> Option<serde_json::Value> {
         // Check targeting_key as a special attribute
-        let attr_value = if constraint.attribute == "targetingKey" {
+        if attribute == "targetingKey" {
             context
                 .targeting_key
                 .as_ref()
                 .map(|k| serde_json::Value::String(k.clone()))
         } else {
-            context.attributes.get(&constraint.attribute).cloned()
-        };
-
-        let Some(value) = attr_value else {
-            return false;
-        };
-
-        evaluate_operator(&constraint.operator, &value, &constraint.values)
+            context.attributes.get(attribute).cloned()
+        }
     }
 
     /// Resolve a matched rule to a concrete variant.
@@ -180,6 +278,7 @@ impl Evaluator {
         rule: &TargetingRule,
         context: &EvaluationContext,
         default_value: &serde_json::Value,
+        trace: Option<&mut EvaluationTrace>,
     ) -> EvaluationResult {
         // If a single variant is specified, return it
         if let Some(variant_id) = rule.variant_id {
@@ -203,6 +302,9 @@ impl Evaluator {
                 .unwrap_or("__anonymous__");
 
             let bucket_value = hasher::bucket(&flag.key, targeting_key);
+            if let Some(t) = trace {
+                t.bucket = Some(bucket_value);
+            }
 
             let mut cumulative = 0;
             for dist in &rule.distributions {
@@ -595,4 +697,120 @@ mod tests {
         let result = evaluator.evaluate("non-beta-feature", &ctx_beta, &json!(false));
         assert_eq!(result.reason, EvaluationReason::Default);
     }
+
+    #[test]
+    fn test_trace_records_rule_segments() {
+        let on_variant = make_variant("on", json!(true));
+        let off_variant = make_variant("off", json!(false));
+        let on_id = on_variant.id;
+
+        let segment = Segment {
+            id: Uuid::new_v4(),
+            key: "us-users".to_string(),
+            name: "US Users".to_string(),
+            match_type: MatchType::All,
+            constraints: vec![SegmentConstraint {
+                attribute: "country".to_string(),
+                operator: Operator::Eq,
+                values: vec!["US".to_string()],
+            }],
+        };
+        let rule = TargetingRule {
+            id: Uuid::new_v4(),
+            rank: 1,
+            description: None,
+            segments: vec![RuleSegment {
+                segment_id: segment.id,
+                negate: false,
+            }],
+            distributions: vec![],
+            variant_id: Some(on_id),
+        };
+        let flag = FlagConfig {
+            key: "us-feature".to_string(),
+            flag_type: FlagType::Boolean,
+            variants: vec![on_variant, off_variant.clone()],
+            environment: FlagEnvironment {
+                enabled: true,
+                default_variant_id: off_variant.id,
+                rules: vec![rule.clone()],
+                overrides: vec![],
+            },
+        };
+        let evaluator = make_evaluator(vec![flag], vec![segment]);
+
+        let ctx_uk = EvaluationContext {
+            targeting_key: Some("user-2".to_string()),
+            attributes: HashMap::from([("country".to_string(), json!("UK"))]),
+        };
+        let (result, trace) = evaluator.evaluate_with_trace("us-feature", &ctx_uk, &json!(false));
+        assert_eq!(result.reason, EvaluationReason::Default);
+        assert!(trace.flag_found && trace.enabled);
+        assert_eq!(trace.rules.len(), 1);
+        assert_eq!(trace.rules[0].rule_id, rule.id);
+        assert!(!trace.rules[0].matched);
+        let segment_trace = &trace.rules[0].segments[0];
+        assert_eq!(segment_trace.segment_key.as_deref(), Some("us-users"));
+        assert!(!segment_trace.matched);
+        assert_eq!(segment_trace.constraints[0].actual, Some(json!("UK")));
+        assert!(!segment_trace.constraints[0].matched);
+
+        // The trace never changes the result.
+        let ctx_us = EvaluationContext {
+            targeting_key: Some("user-1".to_string()),
+            attributes: HashMap::from([("country".to_string(), json!("US"))]),
+        };
+        let (result, trace) = evaluator.evaluate_with_trace("us-feature", &ctx_us, &json!(false));
+        let plain = evaluator.evaluate("us-feature", &ctx_us, &json!(false));
+        assert_eq!(result.reason, plain.reason);
+        assert_eq!(result.value, plain.value);
+        assert!(trace.rules[0].matched);
+    }
+
+    #[test]
+    fn test_trace_records_override_and_bucket() {
+        let (mut flag, on_id, off_id) = make_simple_flag("rollout", true);
+        flag.environment.overrides.push(FlagOverride {
+            targeting_key: "vip".to_string(),
+            variant_id: on_id,
+        });
+        flag.environment.rules.push(TargetingRule {
+            id: Uuid::new_v4(),
+            rank: 1,
+            description: None,
+            segments: vec![],
+            distributions: vec![
+                RuleDistribution {
+                    variant_id: on_id,
+                    rollout_pct: 5000,
+                },
+                RuleDistribution {
+                    variant_id: off_id,
+                    rollout_pct: 5000,
+                },
+            ],
+            variant_id: None,
+        });
+        let evaluator = make_evaluator(vec![flag], vec![]);
+
+        let vip = EvaluationContext {
+            targeting_key: Some("vip".to_string()),
+            ..Default::default()
+        };
+        let (result, trace) = evaluator.evaluate_with_trace("rollout", &vip, &json!(false));
+        assert_eq!(result.reason, EvaluationReason::Override);
+        assert_eq!(trace.matched_override.as_deref(), Some("vip"));
+        assert!(trace.rules.is_empty());
+
+        let user = EvaluationContext {
+            targeting_key: Some("user-7".to_string()),
+            ..Default::default()
+        };
+        let (result, trace) = evaluator.evaluate_with_trace("rollout", &user, &json!(false));
+        assert_eq!(result.reason, EvaluationReason::RuleMatch);
+        assert_eq!(trace.bucket, Some(hasher::bucket("rollout", "user-7")));
+
+        let (_, trace) = evaluator.evaluate_with_trace("missing", &user, &json!(false));
+        assert!(!trace.flag_found);
+    }
 }
@@ -57,7 +57,7 @@ fn op_eq(attribute_value: &serde_json::Value, constraint_values: &[String]) -> b
     let Some(attr_str) = to_string(attribute_value) else {
         return false;
     };
-    constraint_values.iter().any(|v| *v == attr_str)
+    constraint_values.contains(&attr_str)
 }
 
 fn op_in(attribute_value: &serde_json::Value, constraint_values: &[String]) -> bool {
@@ -152,6 +152,52 @@ pub struct EvaluationResult {
     pub rule_id: Option<Uuid>,
 }
 
+/// Step-by-step record of how an evaluation reached its result.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EvaluationTrace {
+    pub flag_found: bool,
+    pub enabled: bool,
+    /// Targeting key of the override that matched, if any.
+    pub matched_override: Option<String>,
+    /// Rules in rank order, up to and including the one that matched.
+    pub rules: Vec<RuleTrace>,
+    /// Bucket of the targeting key (0–9999), if a percentage rollout ran.
+    pub bucket: Option<i32>,
+}
+
+/// How one targeting rule was evaluated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleTrace {
+    pub rule_id: Uuid,
+    pub rank: i32,
+    pub description: Option<String>,
+    pub matched: bool,
+    pub segments: Vec<SegmentTrace>,
+}
+
+/// How one segment reference of a rule was evaluated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentTrace {
+    pub segment_id: Uuid,
+    /// `None` if the segment is missing from the config.
+    pub segment_key: Option<String>,
+    pub negate: bool,
+    /// Outcome after applying `negate`.
+    pub matched: bool,
+    pub constraints: Vec<ConstraintTrace>,
+}
+
+/// How one segment constraint was evaluated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstraintTrace {
+    pub attribute: String,
+    pub operator: Operator,
+    pub values: Vec<String>,
+    /// The context's value for the attribute, if it had one.
+    pub actual: Option<serde_json::Value>,
+    pub matched: bool,
+}
+
 /// Full configuration snapshot sent to server SDKs.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FlagsConfig {